@@ -1,10 +1,10 @@
 use std::{
     fs::{DirBuilder, File},
-    io::{Seek, Write, BufWriter},
+    io::{Write, BufWriter},
     path::Path,
 };
 
-use runirip::{files::{BundleFile, SerializedFile}, objects::classes::AssetBundle};
+use runirip::{files::BundleFile, objects::classes::AssetBundle};
 use runirip::config::ExtractionConfig;
 
 fn main() {
@@ -17,22 +17,19 @@ fn main() {
     let mut bundle = BundleFile::from_reader(&mut reader, &config).unwrap();
 
     // iterate over the files in the bundle
-    for directory in &bundle.m_DirectoryInfo {
+    for index in 0..bundle.m_DirectoryInfo.len() {
+        // open the file as its own reader
+        let mut entry = bundle.open_entry(index).unwrap();
         // generate export dir for cab
-        let export_cab_dir = export_dir.join(&directory.path);
-        // seek to the start of the file in the bundle
-        bundle
-            .m_BlockReader
-            .seek(std::io::SeekFrom::Start(directory.offset as u64))
-            .unwrap();
+        let export_cab_dir = export_dir.join(entry.get_entry().get_path());
 
         // try to parse the file as a SerializedFile
-        match SerializedFile::from_reader(&mut bundle.m_BlockReader, &config) {
+        match entry.read_serialized_file(&config) {
             Ok(serialized) => {
                 // iterate over objects
                 for object_info in &serialized.m_Objects {
                     let mut reader =
-                        serialized.get_object_reader(object_info, &mut bundle.m_BlockReader);
+                        serialized.get_object_reader(object_info, &mut entry);
 
                     // read the object
                     let object = reader.read().unwrap();
@@ -78,7 +75,7 @@ fn main() {
                 // TODO - try to filter out resource files
                 println!(
                     "Failed to parse {} as SerializedFile.",
-                    &directory.path.to_string()
+                    entry.get_entry().get_path()
                 );
             }
         }
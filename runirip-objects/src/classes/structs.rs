@@ -1,5 +1,5 @@
 #![allow(non_camel_case_types, non_snake_case)]
-use crate::PPtr;
+use crate::{ByteString, PPtr};
 use serde::{Deserialize, Serialize};
 
 /// AABB is a sub class of the Unity engine since version 3.4.0.
@@ -15967,7 +15967,7 @@ see Text Asset.
 pub struct TextAsset {
     /**The name of the object.*/
     pub m_Name: String,
-    pub m_Script: String,
+    pub m_Script: ByteString,
     /// String: (3.4.0 - 2017.1.0b1)
     pub m_PathName: Option<String>,
 }
@@ -1,4 +1,6 @@
 pub mod classes;
 
+mod byte_string;
 mod pptr;
+pub use byte_string::ByteString;
 pub use pptr::PPtr;
@@ -1,4 +1,4 @@
-use std::{collections::BTreeMap, sync::LazyLock};
+use std::{collections::{BTreeMap, HashMap}, sync::LazyLock};
 
 pub static COMMON_STRINGS: LazyLock<BTreeMap<u32, &'static str>> = LazyLock::new(||
     [
@@ -114,4 +114,11 @@ pub static COMMON_STRINGS: LazyLock<BTreeMap<u32, &'static str>> = LazyLock::new
     .iter()
     .copied()
     .collect()
-);
\ No newline at end of file
+);
+/// Reverse lookup of [`COMMON_STRINGS`], used when writing type trees.
+pub(crate) static COMMON_STRING_OFFSETS: LazyLock<HashMap<&'static str, u32>> = LazyLock::new(||
+    COMMON_STRINGS
+        .iter()
+        .map(|(offset, string)| (*string, *offset))
+        .collect()
+);
@@ -1,13 +1,37 @@
+use crate::files::CompressionType;
+
+/// How the blocks of a bundle are decompressed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlockDecompression {
+    /// Decompress all blocks into memory while opening the bundle.
+    Eager,
+    /// Decompress blocks when they are read, keeping up to `cache_size` of them in memory.
+    Lazy { cache_size: usize },
+}
+
 pub struct ExtractionConfig {
     pub unitycn_key: Option<[u8; 16]>,
+    /// Named keys that are tried on UnityCN bundles that `unitycn_key` doesn't decrypt.
+    pub unitycn_key_ring: Vec<(String, [u8; 16])>,
     pub fallback_unity_version: String,
+    pub block_decompression: BlockDecompression,
+    /// Level that UnityRaw and UnityWeb bundles are opened at, the last one if `None`.
+    /// Lower levels only need the beginning of a streamed bundle.
+    pub streamed_level: Option<usize>,
+    /// Compare the checksums stored in bundles against their data while parsing.
+    /// UnityRaw and UnityWeb bundles with a non-zero `hash` fail, since it can't be checked.
+    pub verify_integrity: bool,
 }
 
 impl ExtractionConfig {
     pub fn new(unitycn_key: Option<[u8; 16]>, fallback_unity_version: String) -> Self {
         Self {
             unitycn_key,
+            unitycn_key_ring: Vec::new(),
             fallback_unity_version,
+            block_decompression: BlockDecompression::Eager,
+            streamed_level: None,
+            verify_integrity: false,
         }
     }
 
@@ -15,13 +39,84 @@ impl ExtractionConfig {
         self.unitycn_key = Some(key);
         self
     }
+
+    pub fn with_unitycn_key_ring(mut self, keys: Vec<(String, [u8; 16])>) -> Self {
+        self.unitycn_key_ring = keys;
+        self
+    }
+
+    pub fn with_block_decompression(mut self, block_decompression: BlockDecompression) -> Self {
+        self.block_decompression = block_decompression;
+        self
+    }
+
+    pub fn with_streamed_level(mut self, level: usize) -> Self {
+        self.streamed_level = Some(level);
+        self
+    }
+
+    pub fn with_verify_integrity(mut self, verify_integrity: bool) -> Self {
+        self.verify_integrity = verify_integrity;
+        self
+    }
 }
 
 impl Default for ExtractionConfig {
     fn default() -> Self {
         Self {
             unitycn_key: None,
+            unitycn_key_ring: Vec::new(),
             fallback_unity_version: "2.5.0f5".to_owned(),
+            block_decompression: BlockDecompression::Eager,
+            streamed_level: None,
+            verify_integrity: false,
+        }
+    }
+}
+
+pub struct BundleWriteConfig {
+    pub compression: CompressionType,
+    pub block_info_compression: CompressionType,
+    pub block_size: u32,
+    pub blocks_info_at_the_end: bool,
+    /// Encrypts the lz4 blocks for UnityCN clients with this key.
+    pub unitycn_key: Option<[u8; 16]>,
+}
+
+impl BundleWriteConfig {
+    pub fn new(compression: CompressionType, blocks_info_at_the_end: bool) -> Self {
+        Self {
+            compression,
+            blocks_info_at_the_end,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_block_info_compression(mut self, compression: CompressionType) -> Self {
+        self.block_info_compression = compression;
+        self
+    }
+
+    pub fn with_block_size(mut self, block_size: u32) -> Self {
+        self.block_size = block_size;
+        self
+    }
+
+    pub fn with_unitycn_key(mut self, key: [u8; 16]) -> Self {
+        self.unitycn_key = Some(key);
+        self
+    }
+}
+
+impl Default for BundleWriteConfig {
+    fn default() -> Self {
+        Self {
+            compression: CompressionType::Lz4hc,
+            block_info_compression: CompressionType::Lz4hc,
+            // same chunk size that Unity uses for chunk based compression
+            block_size: 0x20000,
+            blocks_info_at_the_end: false,
+            unitycn_key: None,
         }
     }
 }
@@ -0,0 +1,568 @@
+use crate::{
+    config::ExtractionConfig,
+    files::{BlockReader, BlockSource, BundleFile, ObjectReader, SerializedFile, WebFile},
+    Error,
+};
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+const BUNDLE_SIGNATURES: [&[u8]; 4] = [b"UnityFS\0", b"UnityWeb\0", b"UnityRaw\0", b"UnityArchive\0"];
+const RESOURCE_EXTENSIONS: [&str; 2] = [".ress", ".resource"];
+
+/// Key that files are indexed by: the lowercased last component of their path,
+/// so `archive:/CAB-x/CAB-x` and `library/unity default resources` match the loaded files.
+fn file_key(path: &str) -> String {
+    path.rsplit(['/', '\\']).next().unwrap_or(path).to_lowercase()
+}
+
+/// A serialized file loaded into an [`Environment`], together with the reader of its data.
+pub struct AssetsFile {
+    path: String,
+    pub file: SerializedFile,
+    reader: Box<dyn BlockSource>,
+}
+
+impl AssetsFile {
+    /// Path the file was loaded from, prefixed with the path of its bundle or web file.
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn get_object_reader(&mut self, path_id: i64) -> Result<ObjectReader<'_, Box<dyn BlockSource>>, Error> {
+        let file = &self.file;
+        let object_info = file
+            .m_Objects
+            .iter()
+            .find(|object_info| object_info.m_PathID == path_id)
+            .ok_or(Error::ObjectNotFound(path_id))?;
+        Ok(file.get_object_reader(object_info, &mut self.reader))
+    }
+}
+
+/// A `.resS` or `.resource` file holding the streamed data of serialized files.
+pub struct ResourceFile {
+    path: String,
+    reader: Box<dyn BlockSource>,
+}
+
+impl ResourceFile {
+    /// Path the file was loaded from, prefixed with the path of its bundle or web file.
+    pub fn get_path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns a reader over the whole file.
+    pub fn get_reader(&mut self) -> &mut Box<dyn BlockSource> {
+        &mut self.reader
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum LoadedFile {
+    Assets(usize),
+    Resource(usize),
+}
+
+/// Loads bundles, serialized files and resource files and resolves references between them.
+///
+/// Every file is indexed by its name, which for the entries of a bundle is the CAB name,
+/// so the `m_Externals` of a serialized file can be followed to the files they refer to.
+/// When two files share a name, the one that was loaded first is kept in the index.
+pub struct Environment {
+    config: ExtractionConfig,
+    assets: Vec<AssetsFile>,
+    resources: Vec<ResourceFile>,
+    names: HashMap<String, LoadedFile>,
+    load_errors: Vec<(PathBuf, Error)>,
+}
+
+impl Environment {
+    pub fn new(config: ExtractionConfig) -> Self {
+        Environment {
+            config,
+            assets: Vec::new(),
+            resources: Vec::new(),
+            names: HashMap::new(),
+            load_errors: Vec::new(),
+        }
+    }
+
+    pub fn get_config(&self) -> &ExtractionConfig {
+        &self.config
+    }
+
+    /// Loads a file, or every file below a folder.
+    ///
+    /// Files of a folder that can't be read or parsed are skipped, and their errors
+    /// are kept in [`Environment::get_load_errors`]. Only errors of the folder itself
+    /// and of a single file passed as `path` are returned.
+    ///
+    /// Files on disk are only opened while they are read, so loading a large
+    /// folder doesn't keep a handle to every file open.
+    pub fn load_path<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let mut paths = std::fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            // keep the load order, and with it the choice between files sharing a name, stable
+            paths.sort();
+            for path in paths {
+                if let Err(e) = self.load_path(&path) {
+                    self.load_errors.push((path, e));
+                }
+            }
+            Ok(())
+        } else {
+            self.load_reader(&path.to_string_lossy(), DiskFileReader::new(path)?)
+        }
+    }
+
+    /// Loads a list of files and folders, keeping the errors of the paths
+    /// that couldn't be loaded in [`Environment::get_load_errors`].
+    pub fn load_paths<P: AsRef<Path>>(&mut self, paths: impl IntoIterator<Item = P>) {
+        for path in paths {
+            let path = path.as_ref();
+            if let Err(e) = self.load_path(path) {
+                self.load_errors.push((path.to_owned(), e));
+            }
+        }
+    }
+
+    /// Errors of the files that [`Environment::load_path`] and [`Environment::load_paths`] skipped.
+    pub fn get_load_errors(&self) -> &[(PathBuf, Error)] {
+        &self.load_errors
+    }
+
+    /// Loads a bundle, web file, serialized file or resource file, depending on its content.
+    ///
+    /// Resource files are recognized by their `.resS` or `.resource` extension,
+    /// any other file that isn't a Unity file fails with [`Error::UnknownSignature`].
+    pub fn load_reader<R: Read + Seek + Send + 'static>(&mut self, path: &str, mut reader: R) -> Result<(), Error> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(0))?;
+        let mut magic = Vec::new();
+        reader.by_ref().take(16).read_to_end(&mut magic)?;
+        reader.seek(SeekFrom::Start(0))?;
+
+        if BUNDLE_SIGNATURES.iter().any(|signature| magic.starts_with(signature)) {
+            let bundle = BundleFile::from_owned_reader(reader, &self.config)?;
+            self.load_bundle(path, bundle)
+        } else if WebFile::is_web_file(&mut reader)? {
+            let web_file = WebFile::from_reader(&mut reader, &self.config)?;
+            self.load_web_file(path, web_file)
+        } else {
+            self.load_file(path, Box::new(reader), length)
+        }
+    }
+
+    /// Loads the entries of a bundle that was already parsed.
+    ///
+    /// Entries that aren't serialized or resource files are skipped.
+    pub fn load_bundle(&mut self, path: &str, mut bundle: BundleFile) -> Result<(), Error> {
+        let data = bundle.take_block_reader();
+        let data_len = data.len();
+        let data = Arc::new(Mutex::new(data));
+        for entry in &bundle.m_DirectoryInfo {
+            if entry.get_end_offset()? > data_len {
+                return Err(Error::EntryNotLoaded(entry.path.clone()));
+            }
+            let reader = SharedEntryReader {
+                data: data.clone(),
+                offset: entry.offset as u64,
+                size: entry.size as u64,
+                position: 0,
+            };
+            skip_unknown(self.load_file(&format!("{path}/{}", entry.path), Box::new(reader), entry.size as u64))?;
+        }
+        Ok(())
+    }
+
+    /// Loads the entries of a web file, which may be bundles themselves.
+    ///
+    /// Entries that aren't Unity files, like the metadata of the player, are skipped.
+    pub fn load_web_file(&mut self, path: &str, web_file: WebFile) -> Result<(), Error> {
+        let data = web_file.m_DataReader.into_inner();
+        for entry in &web_file.m_DirectoryInfo {
+            let entry_data = data
+                .get(entry.offset as usize..entry.get_end_offset()? as usize)
+                .ok_or_else(|| Error::InvalidValue(format!("{} is out of bounds", entry.path)))?;
+            skip_unknown(self.load_reader(&format!("{path}/{}", entry.path), Cursor::new(entry_data.to_vec())))?;
+        }
+        Ok(())
+    }
+
+    fn load_file(&mut self, path: &str, mut reader: Box<dyn BlockSource>, length: u64) -> Result<(), Error> {
+        let key = file_key(path);
+        let loaded = if RESOURCE_EXTENSIONS.iter().any(|extension| key.ends_with(extension)) {
+            self.resources.push(ResourceFile {
+                path: path.to_owned(),
+                reader,
+            });
+            LoadedFile::Resource(self.resources.len() - 1)
+        } else if SerializedFile::is_serialized_file(&mut reader, length, &self.config) {
+            reader.seek(SeekFrom::Start(0))?;
+            let file = SerializedFile::from_reader(&mut reader, &self.config)?;
+            self.assets.push(AssetsFile {
+                path: path.to_owned(),
+                file,
+                reader,
+            });
+            LoadedFile::Assets(self.assets.len() - 1)
+        } else {
+            return Err(Error::UnknownSignature);
+        };
+        self.names.entry(key).or_insert(loaded);
+        Ok(())
+    }
+
+    pub fn get_assets_files(&self) -> &[AssetsFile] {
+        &self.assets
+    }
+
+    pub fn get_resource_files(&self) -> &[ResourceFile] {
+        &self.resources
+    }
+
+    /// Finds a loaded serialized file by its name or any path ending in it.
+    pub fn get_assets_file(&self, path: &str) -> Option<&AssetsFile> {
+        self.assets_index(path).map(|index| &self.assets[index])
+    }
+
+    pub fn get_assets_file_mut(&mut self, path: &str) -> Option<&mut AssetsFile> {
+        self.assets_index(path).map(|index| &mut self.assets[index])
+    }
+
+    /// Finds a loaded resource file by its name or any path ending in it.
+    pub fn get_resource_file_mut(&mut self, path: &str) -> Option<&mut ResourceFile> {
+        match self.names.get(&file_key(path)) {
+            Some(LoadedFile::Resource(index)) => Some(&mut self.resources[*index]),
+            _ => None,
+        }
+    }
+
+    fn assets_index(&self, path: &str) -> Option<usize> {
+        match self.names.get(&file_key(path)) {
+            Some(LoadedFile::Assets(index)) => Some(*index),
+            _ => None,
+        }
+    }
+
+    /// Resolves the `file_id` of a reference in the serialized file `from` to the file it points to.
+    ///
+    /// `0` is `from` itself, any other id is an index into its `m_Externals` offset by one.
+    pub fn resolve_file_id(&self, from: &str, file_id: i64) -> Result<&AssetsFile, Error> {
+        self.resolve_index(from, file_id).map(|index| &self.assets[index])
+    }
+
+    fn resolve_index(&self, from: &str, file_id: i64) -> Result<usize, Error> {
+        let index = self
+            .assets_index(from)
+            .ok_or_else(|| Error::EntryNotFound(from.to_owned()))?;
+        if file_id == 0 {
+            return Ok(index);
+        }
+
+        let external = usize::try_from(file_id - 1)
+            .ok()
+            .and_then(|external| self.assets[index].file.m_Externals.get(external))
+            .ok_or_else(|| Error::InvalidValue(format!("{from} has no external file {file_id}")))?;
+        self.assets_index(external.get_path_name())
+            .ok_or_else(|| Error::EntryNotFound(external.get_path_name().to_owned()))
+    }
+
+    /// Returns the reader of the object that a reference in the serialized file `from` points to.
+    pub fn get_object_reader(
+        &mut self,
+        from: &str,
+        file_id: i64,
+        path_id: i64,
+    ) -> Result<ObjectReader<'_, Box<dyn BlockSource>>, Error> {
+        let index = self.resolve_index(from, file_id)?;
+        self.assets[index].get_object_reader(path_id)
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Environment::new(ExtractionConfig::default())
+    }
+}
+
+/// Ignores the error of a file in a container that isn't a Unity file.
+fn skip_unknown(result: Result<(), Error>) -> Result<(), Error> {
+    match result {
+        Err(Error::UnknownSignature) => Ok(()),
+        result => result,
+    }
+}
+
+/// Moves `position` in a stream of `size` bytes.
+fn seek_position(position: u64, size: u64, pos: SeekFrom) -> std::io::Result<u64> {
+    let position = match pos {
+        SeekFrom::Start(offset) => Some(offset),
+        SeekFrom::End(offset) => size.checked_add_signed(offset),
+        SeekFrom::Current(offset) => position.checked_add_signed(offset),
+    };
+    position.ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "invalid seek to a negative position",
+        )
+    })
+}
+
+/// A bundle entry that shares the uncompressed data of its bundle with the other entries.
+struct SharedEntryReader {
+    data: Arc<Mutex<BlockReader>>,
+    offset: u64,
+    size: u64,
+    position: u64,
+}
+
+impl Read for SharedEntryReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.size.saturating_sub(self.position);
+        let size = (buf.len() as u64).min(remaining) as usize;
+        if size == 0 {
+            return Ok(0);
+        }
+
+        let mut data = self
+            .data
+            .lock()
+            .map_err(|_| std::io::Error::other("bundle data is poisoned"))?;
+        data.seek(SeekFrom::Start(self.offset + self.position))?;
+        let read = data.read(&mut buf[..size])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for SharedEntryReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+const DISK_BUFFER_SIZE: usize = 0x10000;
+
+/// A file on disk that is only opened while its data is read, with a buffer
+/// that is kept across seeks so the small reads of the parsers don't reopen it.
+struct DiskFileReader {
+    path: PathBuf,
+    size: u64,
+    position: u64,
+    buffer: Vec<u8>,
+    buffer_offset: u64,
+}
+
+impl DiskFileReader {
+    fn new(path: &Path) -> Result<Self, Error> {
+        Ok(DiskFileReader {
+            path: path.to_owned(),
+            size: std::fs::metadata(path)?.len(),
+            position: 0,
+            buffer: Vec::new(),
+            buffer_offset: 0,
+        })
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut file = std::fs::File::open(&self.path)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < buf.len() {
+            match file.read(&mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(read)
+    }
+}
+
+impl Read for DiskFileReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() || self.position >= self.size {
+            return Ok(0);
+        }
+        // large reads skip the buffer
+        if buf.len() >= DISK_BUFFER_SIZE {
+            let read = self.read_at(self.position, buf)?;
+            self.position += read as u64;
+            return Ok(read);
+        }
+
+        let buffer_end = self.buffer_offset + self.buffer.len() as u64;
+        if self.position < self.buffer_offset || self.position >= buffer_end {
+            let mut buffer = std::mem::take(&mut self.buffer);
+            buffer.resize(DISK_BUFFER_SIZE, 0);
+            let read = self.read_at(self.position, &mut buffer)?;
+            buffer.truncate(read);
+            self.buffer = buffer;
+            self.buffer_offset = self.position;
+        }
+
+        let start = (self.position - self.buffer_offset) as usize;
+        let read = buf.len().min(self.buffer.len() - start);
+        buf[..read].copy_from_slice(&self.buffer[start..start + read]);
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for DiskFileReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.position = seek_position(self.position, self.size, pos)?;
+        Ok(self.position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::BundleWriteConfig,
+        files::{
+            bundle_file::BundleFileHeader,
+            serialized_file::tests::{objects_data, sample_file},
+            unity_file::FileEntry,
+            CompressionType,
+        },
+        read_ext::ReadUrexExt,
+    };
+
+    const CAB: &str = "CAB-00000000000000000000000000000000";
+
+    fn serialized_data() -> Vec<u8> {
+        let mut data = Cursor::new(Vec::new());
+        sample_file(22, 0).to_writer(&mut data, &objects_data()).unwrap();
+        data.into_inner()
+    }
+
+    fn bundle_data() -> Vec<u8> {
+        let cab = serialized_data();
+        let ress = b"streamed data".to_vec();
+        let entries = vec![
+            FileEntry {
+                offset: 0,
+                size: cab.len() as i64,
+                flags: 4,
+                path: CAB.to_owned(),
+            },
+            FileEntry {
+                offset: cab.len() as i64,
+                size: ress.len() as i64,
+                flags: 0,
+                path: format!("{CAB}.resS"),
+            },
+        ];
+        let mut data = cab;
+        data.extend(&ress);
+
+        let header = BundleFileHeader::new(6, "5.x.x".to_owned(), "2019.4.40f1".to_owned());
+        let config = BundleWriteConfig::new(CompressionType::None, false)
+            .with_block_info_compression(CompressionType::None);
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &config).unwrap();
+        written
+    }
+
+    #[test]
+    fn resolve_across_files() {
+        let mut env = Environment::default();
+        // the standalone file refers to the CAB of the bundle through its externals
+        env.load_reader("Data/level0", Cursor::new(serialized_data())).unwrap();
+        env.load_reader("Data/bundle.unity3d", Cursor::new(bundle_data())).unwrap();
+
+        assert_eq!(env.get_assets_files().len(), 2);
+        assert_eq!(env.get_resource_files().len(), 1);
+        assert_eq!(env.get_resource_files()[0].get_path(), format!("Data/bundle.unity3d/{CAB}.resS"));
+        assert_eq!(env.resolve_file_id("level0", 1).unwrap().get_path(), format!("Data/bundle.unity3d/{CAB}"));
+        assert_eq!(env.resolve_file_id("Data/level0", 0).unwrap().get_path(), "Data/level0");
+
+        let data = env.get_object_reader("level0", 1, 3).unwrap().get_raw_data().unwrap();
+        assert_eq!(data, objects_data()[2]);
+
+        let mut resource = Vec::new();
+        env.get_resource_file_mut(&format!("archive:/{CAB}/{CAB}.resS"))
+            .unwrap()
+            .get_reader()
+            .read_to_end(&mut resource)
+            .unwrap();
+        assert_eq!(resource, b"streamed data");
+
+        assert!(matches!(env.get_object_reader("level0", 1, 99), Err(Error::ObjectNotFound(99))));
+        assert!(matches!(env.get_object_reader("level0", 2, 3), Err(Error::InvalidValue(_))));
+        assert!(matches!(env.get_object_reader("level1", 0, 3), Err(Error::EntryNotFound(_))));
+    }
+
+    #[test]
+    fn missing_external() {
+        let mut env = Environment::default();
+        env.load_reader("level0", Cursor::new(serialized_data())).unwrap();
+        assert!(env.get_object_reader("level0", 0, 1).is_ok());
+        assert!(matches!(env.get_object_reader("level0", 1, 1), Err(Error::EntryNotFound(path)) if path.ends_with(CAB)));
+    }
+
+    #[test]
+    fn load_folder() {
+        let directory = std::env::temp_dir().join(format!("runirip-environment-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("Data")).unwrap();
+        std::fs::write(directory.join("Data/level0"), serialized_data()).unwrap();
+        std::fs::write(directory.join("Data/sharedassets0.resource"), b"0123456789").unwrap();
+        std::fs::write(directory.join("Data/broken.unity3d"), b"UnityFS\0truncated").unwrap();
+        std::fs::write(directory.join("Data/notes.txt"), b"not a unity file").unwrap();
+
+        let mut env = Environment::default();
+        env.load_path(&directory).unwrap();
+        assert_eq!(env.get_assets_files().len(), 1);
+        // only files with a resource extension are kept as resources
+        assert_eq!(env.get_resource_files().len(), 1);
+        assert_eq!(env.get_load_errors().len(), 2);
+        assert_eq!(env.get_load_errors()[0].0, directory.join("Data/broken.unity3d"));
+        assert_eq!(env.get_load_errors()[1].0, directory.join("Data/notes.txt"));
+        assert!(matches!(env.get_load_errors()[1].1, Error::UnknownSignature));
+
+        // files are opened again when they are read
+        let resource = env.get_resource_file_mut("sharedassets0.resource").unwrap();
+        let mut data = Vec::new();
+        resource.get_reader().read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"0123456789");
+        let data = env.get_object_reader("level0", 0, 3).unwrap().get_raw_data().unwrap();
+        assert_eq!(data, objects_data()[2]);
+
+        env.load_paths([directory.join("Data/missing"), directory.join("Data/level0")]);
+        assert_eq!(env.get_load_errors().len(), 3);
+        assert_eq!(env.get_assets_files().len(), 2);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn disk_file_reader() {
+        let path = std::env::temp_dir().join(format!("runirip-disk-file-{}", std::process::id()));
+        let data: Vec<u8> = (0..0x28000u32).map(|i| (i % 251) as u8).collect();
+        std::fs::write(&path, &data).unwrap();
+
+        let mut reader = DiskFileReader::new(&path).unwrap();
+        // small reads across the end of the buffer
+        reader.seek(SeekFrom::Start(0xfff0)).unwrap();
+        assert_eq!(reader.read_bytes_sized(0x20).unwrap(), &data[0xfff0..0x10010]);
+        reader.seek(SeekFrom::Current(-0x30)).unwrap();
+        assert_eq!(reader.read_bytes_sized(8).unwrap(), &data[0xffe0..0xffe8]);
+        // a read larger than the buffer
+        reader.seek(SeekFrom::Start(4)).unwrap();
+        assert_eq!(reader.read_bytes_sized(0x20000).unwrap(), &data[4..0x20004]);
+        reader.seek(SeekFrom::End(-3)).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+        assert_eq!(rest, &data[data.len() - 3..]);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -21,6 +21,15 @@ pub enum Error {
     InvalidValue(String),
     DecompressionError(String),
     NoUnityCNKey,
+    NoMatchingUnityCNKey,
+    EntryNotFound(String),
+    EntryNotLoaded(String),
+    ObjectNotFound(i64),
+    ChecksumMismatch {
+        name: &'static str,
+        expected: u128,
+        actual: u128,
+    },
 
     Message(String)
 }
@@ -43,6 +52,13 @@ impl Display for Error {
             Self::InvalidValue(reason) => write!(f, "Invalid value: {reason}"),
             Self::DecompressionError(reason) => write!(f, "Decompression error: {reason}"),
             Self::NoUnityCNKey => f.write_str("UnityCN decryption key was not provided"),
+            Self::NoMatchingUnityCNKey => f.write_str("None of the UnityCN keys matched"),
+            Self::EntryNotFound(entry) => write!(f, "Entry not found: {entry}"),
+            Self::EntryNotLoaded(entry) => write!(f, "Entry is in a level that wasn't loaded: {entry}"),
+            Self::ObjectNotFound(path_id) => write!(f, "Object not found: {path_id}"),
+            Self::ChecksumMismatch { name, expected, actual } => {
+                write!(f, "{name} mismatch: expected {expected:#x}, got {actual:#x}")
+            }
 
             Self::Message(reason) => f.write_str(&reason)
         }
@@ -94,4 +110,10 @@ impl serde::de::Error for Error {
     fn custom<T: Display>(msg: T) -> Self {
         Error::Message(msg.to_string())
     }
-}
\ No newline at end of file
+}
+#[cfg(feature = "serde")]
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Message(msg.to_string())
+    }
+}
@@ -0,0 +1,163 @@
+// Decoders for the ADPCM codecs of FSB5, to interleaved 16 bit samples
+use crate::Error;
+
+const IMA_INDEX_TABLE: [i32; 8] = [-1, -1, -1, -1, 2, 4, 6, 8];
+
+const IMA_STEP_TABLE: [i32; 89] = [
+    7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73, 80, 88, 97, 107,
+    118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494, 544, 598, 658, 724, 796, 876, 963,
+    1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499, 2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894,
+    6484, 7132, 7845, 8630, 9493, 10442, 11487, 12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794,
+    32767,
+];
+
+/// Bytes of IMA ADPCM per channel and block: a 4 byte header and 64 nibbles.
+const IMA_BLOCK_SIZE: usize = 36;
+/// The header sample and 63 of the nibbles, as the Xbox layout skips the last one.
+const IMA_BLOCK_SAMPLES: usize = 64;
+
+struct ImaChannel {
+    sample: i32,
+    index: i32,
+}
+
+impl ImaChannel {
+    fn from_header(header: &[u8]) -> Self {
+        ImaChannel {
+            sample: i16::from_le_bytes([header[0], header[1]]) as i32,
+            index: (header[2] as i32).clamp(0, 88),
+        }
+    }
+
+    fn expand(&mut self, nibble: u8) -> i16 {
+        let step = IMA_STEP_TABLE[self.index as usize];
+        let mut diff = step >> 3;
+        if nibble & 1 != 0 {
+            diff += step >> 2;
+        }
+        if nibble & 2 != 0 {
+            diff += step >> 1;
+        }
+        if nibble & 4 != 0 {
+            diff += step;
+        }
+        if nibble & 8 != 0 {
+            diff = -diff;
+        }
+        self.sample = (self.sample + diff).clamp(i16::MIN as i32, i16::MAX as i32);
+        self.index = (self.index + IMA_INDEX_TABLE[(nibble & 7) as usize]).clamp(0, 88);
+        self.sample as i16
+    }
+}
+
+/// Decodes IMA ADPCM, which FMOD lays out like Xbox ADPCM for up to 2 channels:
+/// the headers of all channels followed by their nibbles, interleaved every 4 bytes.
+/// With more channels each channel has its own block in turn.
+pub(super) fn decode_ima(data: &[u8], channels: usize, sample_count: usize) -> Result<Vec<i16>, Error> {
+    if channels == 0 {
+        return Err(Error::InvalidValue("IMA ADPCM without channels".to_string()));
+    }
+    let mut samples = vec![0i16; data.len() / (IMA_BLOCK_SIZE * channels) * IMA_BLOCK_SAMPLES * channels];
+    for (block_index, block) in data.chunks_exact(IMA_BLOCK_SIZE * channels).enumerate() {
+        let output = &mut samples[block_index * IMA_BLOCK_SAMPLES * channels..][..IMA_BLOCK_SAMPLES * channels];
+        for channel in 0..channels {
+            let (mut state, nibbles): (ImaChannel, Vec<u8>) = if channels <= 2 {
+                let words = block[channels * 4..].chunks_exact(4).skip(channel).step_by(channels);
+                (ImaChannel::from_header(&block[channel * 4..]), words.flatten().copied().collect())
+            } else {
+                let block = &block[channel * IMA_BLOCK_SIZE..][..IMA_BLOCK_SIZE];
+                (ImaChannel::from_header(block), block[4..].to_vec())
+            };
+            output[channel] = state.sample as i16;
+            let nibbles = nibbles.iter().flat_map(|byte| [byte & 0xf, byte >> 4]);
+            for (i, nibble) in nibbles.take(IMA_BLOCK_SAMPLES - 1).enumerate() {
+                output[(i + 1) * channels + channel] = state.expand(nibble);
+            }
+        }
+    }
+    samples.truncate(sample_count * channels);
+    Ok(samples)
+}
+
+const FADPCM_COEFFICIENTS: [[i32; 2]; 8] = [[0, 0], [60, 0], [122, 60], [115, 52], [98, 55], [0, 0], [0, 0], [0, 0]];
+
+/// Bytes per FADPCM frame: 12 bytes of header and 256 nibbles.
+const FADPCM_FRAME_SIZE: usize = 0x8c;
+const FADPCM_FRAME_SAMPLES: usize = 256;
+
+/// Decodes FMOD's FADPCM, where the frames of the channels take turns.
+///
+/// Each frame starts with 8 coefficient indices and 8 shifts, one for every 32 nibbles, and two history samples.
+pub(super) fn decode_fadpcm(data: &[u8], channels: usize, sample_count: usize) -> Result<Vec<i16>, Error> {
+    if channels == 0 {
+        return Err(Error::InvalidValue("FADPCM without channels".to_string()));
+    }
+    let frame_count = data.len() / FADPCM_FRAME_SIZE / channels;
+    let mut samples = vec![0i16; frame_count * FADPCM_FRAME_SAMPLES * channels];
+    for (frame_index, frame) in data.chunks_exact(FADPCM_FRAME_SIZE).take(frame_count * channels).enumerate() {
+        let channel = frame_index % channels;
+        let start = frame_index / channels * FADPCM_FRAME_SAMPLES;
+        let read_u32 = |offset: usize| u32::from_le_bytes(frame[offset..offset + 4].try_into().unwrap());
+        let (coefficients, shifts) = (read_u32(0), read_u32(4));
+        let mut history = [
+            i16::from_le_bytes([frame[8], frame[9]]) as i32,
+            i16::from_le_bytes([frame[10], frame[11]]) as i32,
+        ];
+
+        for (group, nibbles) in frame[12..].chunks_exact(16).enumerate() {
+            // indices past the table repeat it
+            let [coefficient1, coefficient2] = FADPCM_COEFFICIENTS[((coefficients >> (group * 4)) & 0xf) as usize % 7];
+            let shift = (shifts >> (group * 4)) & 0xf;
+            for (i, byte) in nibbles.iter().enumerate() {
+                for (j, nibble) in [byte & 0xf, byte >> 4].into_iter().enumerate() {
+                    // the nibble is sign extended from the top of 32 bits and scaled by the shift
+                    let value = ((nibble as u32) << 28) as i32 >> (22 - shift);
+                    let value = (value - history[1] * coefficient2 + history[0] * coefficient1) >> 6;
+                    let value = value.clamp(i16::MIN as i32, i16::MAX as i32);
+                    history = [value, history[0]];
+                    samples[(start + group * 32 + i * 2 + j) * channels + channel] = value as i16;
+                }
+            }
+        }
+    }
+    samples.truncate(sample_count * channels);
+    Ok(samples)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ima_stereo() {
+        // both channels start at 100 with step index 0, the left one rises and the right one falls
+        let mut block = vec![100, 0, 0, 0, 100, 0, 0, 0];
+        for _ in 0..8 {
+            block.extend([0x77; 4]);
+            block.extend([0xff; 4]);
+        }
+        let samples = decode_ima(&block, 2, 64).unwrap();
+        assert_eq!(samples.len(), 128);
+        // 7 adds 11 at the first step size and moves up 8 steps
+        assert_eq!(samples[..6], [100, 100, 111, 89, 141, 59]);
+        assert!(samples[126] > 10000 && samples[127] < -10000);
+        assert_eq!(decode_ima(&block, 2, 10).unwrap().len(), 20);
+    }
+
+    #[test]
+    fn fadpcm() {
+        let mut frame = vec![0u8; FADPCM_FRAME_SIZE];
+        // the first group uses coefficients 1 and shift 2, the second index 8 which repeats index 1
+        frame[0] = 0x81;
+        frame[4] = 0x02;
+        frame[8..12].copy_from_slice(&[64, 0, 0, 0]);
+        frame[12] = 0x71;
+        frame[28] = 0x08;
+        let samples = decode_fadpcm(&frame, 1, 256).unwrap();
+        // 60/64 of the history plus 1 << 2, then plus 7 << 2
+        assert_eq!(samples[..2], [64, 88]);
+        // -8 decays by 60/64 from the previous sample
+        assert_eq!(samples[32], ((samples[31] as i32 * 60) >> 6) as i16 - 8);
+        assert_eq!(decode_fadpcm(&frame, 2, 256).unwrap().len(), 0);
+    }
+}
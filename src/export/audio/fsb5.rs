@@ -0,0 +1,218 @@
+// Parsing of FMOD's FSB5 sound banks, which hold the audio of `AudioClip` since Unity 5
+use crate::Error;
+use byteorder::{LittleEndian, ReadBytesExt};
+use num_enum::TryFromPrimitive;
+use std::io::{Read, Seek, SeekFrom};
+
+const MAGIC: &[u8; 4] = b"FSB5";
+
+/// The codec every sample of a bank is stored in.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum Fsb5Codec {
+    None = 0,
+    PCM8 = 1,
+    PCM16 = 2,
+    PCM24 = 3,
+    PCM32 = 4,
+    PCMFloat = 5,
+    GCADPCM = 6,
+    IMAADPCM = 7,
+    VAG = 8,
+    HEVAG = 9,
+    XMA = 10,
+    MPEG = 11,
+    CELT = 12,
+    AT9 = 13,
+    XWMA = 14,
+    Vorbis = 15,
+    FADPCM = 16,
+    Opus = 17,
+}
+
+/// The chunks that can follow a sample header.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u32)]
+enum ChunkType {
+    Channels = 1,
+    Frequency = 2,
+    Loop = 3,
+    VorbisData = 11,
+}
+
+/// The sample rates the 4 bit frequency of a sample header stands for.
+fn frequency(index: u64) -> Result<u32, Error> {
+    Ok(match index {
+        1 => 8000,
+        2 => 11000,
+        3 => 11025,
+        4 => 16000,
+        5 => 22050,
+        6 => 24000,
+        7 => 32000,
+        8 => 44100,
+        9 => 48000,
+        10 => 96000,
+        _ => return Err(Error::InvalidValue(format!("unknown FSB5 frequency {index}"))),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fsb5Sample {
+    pub name: Option<String>,
+    pub frequency: u32,
+    pub channels: u16,
+    /// The length of the sample in samples per channel.
+    pub sample_count: u32,
+    /// The first and last sample of the loop, if the sample loops.
+    pub loop_range: Option<(u32, u32)>,
+    /// Identifies the Vorbis setup header the sample was encoded with, which FSB5 leaves out.
+    pub vorbis_crc32: Option<u32>,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fsb5 {
+    pub version: u32,
+    pub codec: Fsb5Codec,
+    pub samples: Vec<Fsb5Sample>,
+}
+
+impl Fsb5 {
+    pub fn from_reader<T: Read + Seek>(reader: &mut T) -> Result<Self, Error> {
+        let start = reader.stream_position()?;
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(Error::InvalidValue("not an FSB5 bank".to_string()));
+        }
+        let version = reader.read_u32::<LittleEndian>()?;
+        let sample_count = reader.read_u32::<LittleEndian>()?;
+        let sample_headers_size = reader.read_u32::<LittleEndian>()?;
+        let name_table_size = reader.read_u32::<LittleEndian>()?;
+        let data_size = reader.read_u32::<LittleEndian>()?;
+        let codec = reader.read_u32::<LittleEndian>()?;
+        let codec = Fsb5Codec::try_from(codec).map_err(|e| Error::InvalidValue(format!("unknown FSB5 codec {}", e.number)))?;
+        // version 0 has an extra field before the flags and hashes
+        let header_size = if version == 0 { 64 } else { 60 };
+
+        reader.seek(SeekFrom::Start(start + header_size))?;
+        let mut samples = Vec::with_capacity(sample_count as usize);
+        let mut offsets = Vec::with_capacity(sample_count as usize);
+        for _ in 0..sample_count {
+            let bits = reader.read_u64::<LittleEndian>()?;
+            let mut next_chunk = bits & 1 != 0;
+            let mut sample = Fsb5Sample {
+                name: None,
+                frequency: frequency((bits >> 1) & 0xf)?,
+                channels: ((bits >> 5) & 1) as u16 + 1,
+                sample_count: ((bits >> 34) & 0x3fff_ffff) as u32,
+                loop_range: None,
+                vorbis_crc32: None,
+                data: Vec::new(),
+            };
+            offsets.push(((bits >> 6) & 0x0fff_ffff) * 16);
+
+            while next_chunk {
+                let bits = reader.read_u32::<LittleEndian>()?;
+                next_chunk = bits & 1 != 0;
+                let size = ((bits >> 1) & 0xff_ffff) as u64;
+                let chunk_start = reader.stream_position()?;
+                match ChunkType::try_from(bits >> 25) {
+                    Ok(ChunkType::Channels) => sample.channels = reader.read_u8()? as u16,
+                    Ok(ChunkType::Frequency) => sample.frequency = reader.read_u32::<LittleEndian>()?,
+                    Ok(ChunkType::Loop) => {
+                        let loop_start = reader.read_u32::<LittleEndian>()?;
+                        sample.loop_range = Some((loop_start, reader.read_u32::<LittleEndian>()?));
+                    }
+                    Ok(ChunkType::VorbisData) => sample.vorbis_crc32 = Some(reader.read_u32::<LittleEndian>()?),
+                    // the other chunks are only of use to the codecs we don't decode
+                    Err(_) => {}
+                }
+                reader.seek(SeekFrom::Start(chunk_start + size))?;
+            }
+            samples.push(sample);
+        }
+
+        let name_table = start + header_size + sample_headers_size as u64;
+        if name_table_size > 0 {
+            reader.seek(SeekFrom::Start(name_table))?;
+            let name_offsets = (0..sample_count)
+                .map(|_| reader.read_u32::<LittleEndian>())
+                .collect::<Result<Vec<_>, _>>()?;
+            for (sample, offset) in samples.iter_mut().zip(name_offsets) {
+                reader.seek(SeekFrom::Start(name_table + offset as u64))?;
+                let mut name = Vec::new();
+                loop {
+                    match reader.read_u8()? {
+                        0 => break,
+                        byte => name.push(byte),
+                    }
+                }
+                sample.name = Some(String::from_utf8_lossy(&name).into_owned());
+            }
+        }
+
+        let data_start = name_table + name_table_size as u64;
+        for i in 0..samples.len() {
+            let end = offsets.get(i + 1).copied().unwrap_or(data_size as u64);
+            if end < offsets[i] || end > data_size as u64 {
+                return Err(Error::InvalidValue(format!("FSB5 sample {i} is out of bounds")));
+            }
+            reader.seek(SeekFrom::Start(data_start + offsets[i]))?;
+            samples[i].data = vec![0; (end - offsets[i]) as usize];
+            reader.read_exact(&mut samples[i].data)?;
+        }
+
+        Ok(Fsb5 { version, codec, samples })
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use std::io::Cursor;
+
+    /// Builds a bank of one sample, with a loop and a name.
+    pub(in super::super) fn bank(codec: Fsb5Codec, channels: u16, sample_count: u32, data: &[u8]) -> Vec<u8> {
+        let mut headers = Vec::new();
+        let bits = 1 | 8 << 1 | ((channels == 2) as u64) << 5 | (sample_count as u64) << 34;
+        headers.write_u64::<LittleEndian>(bits).unwrap();
+        headers.write_u32::<LittleEndian>(1 | 8 << 1 | 3 << 25).unwrap();
+        headers.write_u32::<LittleEndian>(0).unwrap();
+        headers.write_u32::<LittleEndian>(sample_count - 1).unwrap();
+        if channels > 2 {
+            headers.write_u32::<LittleEndian>(1 << 1 | 1 << 25).unwrap();
+            headers.write_u8(channels as u8).unwrap();
+        }
+        let names = b"\x04\0\0\0clip\0\0\0";
+
+        let mut bank = MAGIC.to_vec();
+        for value in [1, 1, headers.len() as u32, names.len() as u32, data.len() as u32, codec as u32] {
+            bank.write_u32::<LittleEndian>(value).unwrap();
+        }
+        bank.resize(60, 0);
+        bank.extend(headers);
+        bank.extend(names);
+        bank.extend(data);
+        bank
+    }
+
+    #[test]
+    fn headers() {
+        let bank = bank(Fsb5Codec::PCM16, 3, 2, &[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]);
+        let fsb = Fsb5::from_reader(&mut Cursor::new(&bank)).unwrap();
+        assert_eq!(fsb.version, 1);
+        assert_eq!(fsb.codec, Fsb5Codec::PCM16);
+        let sample = &fsb.samples[0];
+        assert_eq!(sample.name.as_deref(), Some("clip"));
+        assert_eq!((sample.frequency, sample.channels, sample.sample_count), (44100, 3, 2));
+        assert_eq!(sample.loop_range, Some((0, 1)));
+        assert_eq!(sample.data.len(), 12);
+
+        assert!(Fsb5::from_reader(&mut Cursor::new(&bank[..bank.len() - 1])).is_err());
+        assert!(Fsb5::from_reader(&mut Cursor::new(b"FSB4")).is_err());
+    }
+}
@@ -0,0 +1,148 @@
+mod adpcm;
+mod fsb5;
+mod vorbis;
+
+use crate::Error;
+pub use fsb5::{Fsb5, Fsb5Codec, Fsb5Sample};
+pub use vorbis::VorbisSetupHeaders;
+
+const WAVE_FORMAT_PCM: u16 = 1;
+const WAVE_FORMAT_IEEE_FLOAT: u16 = 3;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AudioFormat {
+    Wav,
+    Ogg,
+}
+
+impl AudioFormat {
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            AudioFormat::Wav => "wav",
+            AudioFormat::Ogg => "ogg",
+        }
+    }
+}
+
+/// A sample converted to a file that can be played.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AudioFile {
+    pub format: AudioFormat,
+    pub data: Vec<u8>,
+}
+
+fn wav_file(format_tag: u16, channels: u16, frequency: u32, bits_per_sample: u16, data: &[u8]) -> AudioFile {
+    let block_align = channels * bits_per_sample / 8;
+    let mut wav = Vec::with_capacity(44 + data.len() + 1);
+    wav.extend(b"RIFF");
+    wav.extend((36 + data.len().next_multiple_of(2) as u32).to_le_bytes());
+    wav.extend(b"WAVEfmt ");
+    wav.extend(16u32.to_le_bytes());
+    wav.extend(format_tag.to_le_bytes());
+    wav.extend(channels.to_le_bytes());
+    wav.extend(frequency.to_le_bytes());
+    wav.extend((frequency * block_align as u32).to_le_bytes());
+    wav.extend(block_align.to_le_bytes());
+    wav.extend(bits_per_sample.to_le_bytes());
+    wav.extend(b"data");
+    wav.extend((data.len() as u32).to_le_bytes());
+    wav.extend(data);
+    // chunks are padded to an even size
+    if data.len() % 2 == 1 {
+        wav.push(0);
+    }
+    AudioFile { format: AudioFormat::Wav, data: wav }
+}
+
+impl Fsb5Sample {
+    /// Converts the sample to Ogg Vorbis if it's Vorbis, and to WAV if it's PCM or ADPCM.
+    ///
+    /// `setup_headers` are only needed for Vorbis.
+    pub fn export(&self, codec: Fsb5Codec, setup_headers: &VorbisSetupHeaders) -> Result<AudioFile, Error> {
+        let (channels, sample_count) = (self.channels as usize, self.sample_count as usize);
+        let pcm = |format_tag: u16, bits_per_sample: u16| {
+            let size = (sample_count * channels * bits_per_sample as usize / 8).min(self.data.len());
+            wav_file(format_tag, self.channels, self.frequency, bits_per_sample, &self.data[..size])
+        };
+        let pcm16 = |samples: Vec<i16>| {
+            let data: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+            wav_file(WAVE_FORMAT_PCM, self.channels, self.frequency, 16, &data)
+        };
+        Ok(match codec {
+            Fsb5Codec::PCM8 => {
+                // WAV has unsigned 8 bit samples
+                let size = (sample_count * channels).min(self.data.len());
+                let data: Vec<u8> = self.data[..size].iter().map(|sample| sample ^ 0x80).collect();
+                wav_file(WAVE_FORMAT_PCM, self.channels, self.frequency, 8, &data)
+            }
+            Fsb5Codec::PCM16 => pcm(WAVE_FORMAT_PCM, 16),
+            Fsb5Codec::PCM24 => pcm(WAVE_FORMAT_PCM, 24),
+            Fsb5Codec::PCM32 => pcm(WAVE_FORMAT_PCM, 32),
+            Fsb5Codec::PCMFloat => pcm(WAVE_FORMAT_IEEE_FLOAT, 32),
+            Fsb5Codec::IMAADPCM => pcm16(adpcm::decode_ima(&self.data, channels, sample_count)?),
+            Fsb5Codec::FADPCM => pcm16(adpcm::decode_fadpcm(&self.data, channels, sample_count)?),
+            Fsb5Codec::Vorbis => AudioFile { format: AudioFormat::Ogg, data: vorbis::rebuild_ogg(self, setup_headers)? },
+            _ => return Err(Error::Unimplemented("decoding this FSB5 codec")),
+        })
+    }
+}
+
+#[cfg(feature = "objects")]
+pub trait AudioClipExt {
+    /// Where the audio data is stored if it isn't embedded in `m_AudioData`.
+    fn get_audio_data_range(&self) -> Option<crate::resource::ResourceRange>;
+
+    /// Converts the sample of the clip in the FSB5 bank `audio_data`,
+    /// which is `m_AudioData` or the data read from [`AudioClipExt::get_audio_data_range`].
+    ///
+    /// `setup_headers` are only needed for Vorbis.
+    fn decode_audio(&self, audio_data: &[u8], setup_headers: &VorbisSetupHeaders) -> Result<AudioFile, Error>;
+}
+
+#[cfg(feature = "objects")]
+impl AudioClipExt for crate::objects::classes::AudioClip {
+    fn get_audio_data_range(&self) -> Option<crate::resource::ResourceRange> {
+        self.m_Resource
+            .as_ref()
+            .filter(|resource| !resource.m_Source.is_empty())
+            .map(crate::resource::ResourceRange::from)
+    }
+
+    fn decode_audio(&self, audio_data: &[u8], setup_headers: &VorbisSetupHeaders) -> Result<AudioFile, Error> {
+        let fsb = Fsb5::from_reader(&mut std::io::Cursor::new(audio_data))?;
+        let index = self.m_SubsoundIndex.unwrap_or(0).max(0) as usize;
+        let sample = fsb
+            .samples
+            .get(index)
+            .ok_or_else(|| Error::InvalidValue(format!("FSB5 bank has no sample {index}")))?;
+        sample.export(fsb.codec, setup_headers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::fsb5::tests::bank;
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn pcm8() {
+        let fsb = Fsb5::from_reader(&mut Cursor::new(bank(Fsb5Codec::PCM8, 2, 2, &[0, 0x7f, 0x80, 0xff, 1]))).unwrap();
+        let wav = fsb.samples[0].export(fsb.codec, &VorbisSetupHeaders::new()).unwrap();
+        assert_eq!(wav.format.get_extension(), "wav");
+        assert_eq!(&wav.data[..4], b"RIFF");
+        assert_eq!(u32::from_le_bytes(wav.data[4..8].try_into().unwrap()) as usize, wav.data.len() - 8);
+        // stereo, 44100 Hz, 2 bytes per frame
+        assert_eq!(wav.data[22..24], [2, 0]);
+        assert_eq!(u32::from_le_bytes(wav.data[24..28].try_into().unwrap()), 44100);
+        assert_eq!(wav.data[32..34], [2, 0]);
+        // the padding past the last sample is left out
+        assert_eq!(wav.data[40..], [4, 0, 0, 0, 0x80, 0xff, 0, 0x7f]);
+    }
+
+    #[test]
+    fn unsupported() {
+        let fsb = Fsb5::from_reader(&mut Cursor::new(bank(Fsb5Codec::MPEG, 1, 1, &[0; 4]))).unwrap();
+        assert!(fsb.samples[0].export(fsb.codec, &VorbisSetupHeaders::new()).is_err());
+    }
+}
@@ -0,0 +1,583 @@
+// Rebuilding Ogg Vorbis streams out of the bare Vorbis packets of FSB5
+use super::fsb5::Fsb5Sample;
+use crate::Error;
+use std::collections::HashMap;
+
+/// FMOD always encodes with these block sizes, which are left out of FSB5 with the rest of the headers.
+const BLOCK_SIZES: [u32; 2] = [256, 2048];
+
+const VENDOR: &str = "runirip";
+
+/// The Vorbis setup headers samples can refer to by their CRC32.
+///
+/// FSB5 leaves out the setup header, which is one of a fixed set FMOD's encoder uses,
+/// so rebuilding a stream needs a table of them such as the one of `python-fsb5`.
+#[derive(Debug, Clone, Default)]
+pub struct VorbisSetupHeaders {
+    headers: HashMap<u32, Vec<u8>>,
+}
+
+impl VorbisSetupHeaders {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a setup header under the CRC32 that samples refer to it by.
+    pub fn with_header(mut self, crc32: u32, header: Vec<u8>) -> Self {
+        self.insert(crc32, header);
+        self
+    }
+
+    pub fn insert(&mut self, crc32: u32, header: Vec<u8>) {
+        self.headers.insert(crc32, header);
+    }
+
+    pub fn get(&self, crc32: u32) -> Option<&[u8]> {
+        self.headers.get(&crc32).map(Vec::as_slice)
+    }
+}
+
+/// Reads the bits of Vorbis packets, least significant first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, position: 0 }
+    }
+
+    fn read(&mut self, count: u32) -> Result<u32, Error> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self
+                .data
+                .get(self.position / 8)
+                .ok_or_else(|| Error::InvalidValue("Vorbis setup header is truncated".to_string()))?;
+            value |= ((byte >> (self.position % 8)) as u32 & 1) << i;
+            self.position += 1;
+        }
+        Ok(value)
+    }
+
+    fn skip(&mut self, count: u64) -> Result<(), Error> {
+        let position = self.position as u64 + count;
+        if position > self.data.len() as u64 * 8 {
+            return Err(Error::InvalidValue("Vorbis setup header is truncated".to_string()));
+        }
+        self.position = position as usize;
+        Ok(())
+    }
+}
+
+/// The number of bits needed to store `value`.
+fn ilog(value: u32) -> u32 {
+    32 - value.leading_zeros()
+}
+
+/// The largest value whose `dimensions`th power is at most `entries`.
+fn lookup1_values(entries: u32, dimensions: u32) -> u64 {
+    let mut values = (entries as f64).powf(1.0 / dimensions as f64) as u64;
+    while (values + 1).checked_pow(dimensions).is_some_and(|power| power <= entries as u64) {
+        values += 1;
+    }
+    while values > 0 && values.checked_pow(dimensions).is_none_or(|power| power > entries as u64) {
+        values -= 1;
+    }
+    values
+}
+
+fn skip_codebook(bits: &mut BitReader) -> Result<(), Error> {
+    if bits.read(24)? != 0x564342 {
+        return Err(Error::InvalidValue("invalid Vorbis codebook".to_string()));
+    }
+    let dimensions = bits.read(16)?;
+    let entries = bits.read(24)?;
+    if bits.read(1)? == 0 {
+        let sparse = bits.read(1)? != 0;
+        for _ in 0..entries {
+            if !sparse || bits.read(1)? != 0 {
+                bits.read(5)?;
+            }
+        }
+    } else {
+        bits.read(5)?;
+        let mut entry = 0;
+        while entry < entries {
+            entry += bits.read(ilog(entries - entry))?;
+        }
+        if entry > entries {
+            return Err(Error::InvalidValue("invalid Vorbis codebook lengths".to_string()));
+        }
+    }
+    let lookup_type = bits.read(4)?;
+    match lookup_type {
+        0 => {}
+        1 | 2 => {
+            bits.skip(64)?;
+            let value_bits = bits.read(4)? + 1;
+            bits.read(1)?;
+            let values = match lookup_type {
+                1 => lookup1_values(entries, dimensions),
+                _ => entries as u64 * dimensions as u64,
+            };
+            bits.skip(values * value_bits as u64)?;
+        }
+        _ => return Err(Error::InvalidValue(format!("invalid Vorbis lookup type {lookup_type}"))),
+    }
+    Ok(())
+}
+
+fn skip_floor(bits: &mut BitReader) -> Result<(), Error> {
+    match bits.read(16)? {
+        0 => {
+            bits.skip(8 + 16 + 16 + 6 + 8)?;
+            let books = bits.read(4)? + 1;
+            bits.skip(books as u64 * 8)?;
+        }
+        1 => {
+            let partitions = bits.read(5)?;
+            let classes = (0..partitions).map(|_| bits.read(4)).collect::<Result<Vec<_>, _>>()?;
+            let class_count = classes.iter().max().map_or(0, |&max| max + 1);
+            let mut dimensions = Vec::with_capacity(class_count as usize);
+            for _ in 0..class_count {
+                dimensions.push(bits.read(3)? + 1);
+                let subclasses = bits.read(2)?;
+                if subclasses != 0 {
+                    bits.read(8)?;
+                }
+                bits.skip((1u64 << subclasses) * 8)?;
+            }
+            bits.read(2)?;
+            let range_bits = bits.read(4)?;
+            for class in classes {
+                bits.skip(dimensions[class as usize] as u64 * range_bits as u64)?;
+            }
+        }
+        floor_type => return Err(Error::InvalidValue(format!("invalid Vorbis floor type {floor_type}"))),
+    }
+    Ok(())
+}
+
+fn skip_residue(bits: &mut BitReader) -> Result<(), Error> {
+    let residue_type = bits.read(16)?;
+    if residue_type > 2 {
+        return Err(Error::InvalidValue(format!("invalid Vorbis residue type {residue_type}")));
+    }
+    bits.skip(24 * 3)?;
+    let classifications = bits.read(6)? + 1;
+    bits.read(8)?;
+    let mut cascades = Vec::with_capacity(classifications as usize);
+    for _ in 0..classifications {
+        let low = bits.read(3)?;
+        let high = if bits.read(1)? != 0 { bits.read(5)? } else { 0 };
+        cascades.push(high << 3 | low);
+    }
+    for cascade in cascades {
+        bits.skip(cascade.count_ones() as u64 * 8)?;
+    }
+    Ok(())
+}
+
+fn skip_mapping(bits: &mut BitReader, channels: u32) -> Result<(), Error> {
+    let mapping_type = bits.read(16)?;
+    if mapping_type != 0 {
+        return Err(Error::InvalidValue(format!("invalid Vorbis mapping type {mapping_type}")));
+    }
+    let submaps = if bits.read(1)? != 0 { bits.read(4)? + 1 } else { 1 };
+    if bits.read(1)? != 0 {
+        let steps = bits.read(8)? + 1;
+        bits.skip(steps as u64 * 2 * ilog(channels.saturating_sub(1)) as u64)?;
+    }
+    bits.read(2)?;
+    if submaps > 1 {
+        bits.skip(channels as u64 * 4)?;
+    }
+    bits.skip(submaps as u64 * 24)?;
+    Ok(())
+}
+
+/// Reads whether each mode of a setup header uses long blocks, which are the last thing in it.
+fn read_mode_block_flags(setup_header: &[u8], channels: u32) -> Result<Vec<bool>, Error> {
+    let mut bits = BitReader::new(&setup_header[7..]);
+    for _ in 0..bits.read(8)? + 1 {
+        skip_codebook(&mut bits)?;
+    }
+    for _ in 0..bits.read(6)? + 1 {
+        bits.read(16)?;
+    }
+    for _ in 0..bits.read(6)? + 1 {
+        skip_floor(&mut bits)?;
+    }
+    for _ in 0..bits.read(6)? + 1 {
+        skip_residue(&mut bits)?;
+    }
+    for _ in 0..bits.read(6)? + 1 {
+        skip_mapping(&mut bits, channels)?;
+    }
+    let mode_count = bits.read(6)? + 1;
+    let mut block_flags = Vec::with_capacity(mode_count as usize);
+    for _ in 0..mode_count {
+        block_flags.push(bits.read(1)? != 0);
+        bits.skip(16 + 16 + 8)?;
+    }
+    Ok(block_flags)
+}
+
+/// The lookup table of the CRC of Ogg pages, which is unreflected with a polynomial of 0x04c11db7.
+const fn ogg_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = (i as u32) << 24;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04c1_1db7 } else { crc << 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+const OGG_CRC_TABLE: [u32; 256] = ogg_crc_table();
+
+fn ogg_crc(data: &[u8]) -> u32 {
+    data.iter()
+        .fold(0, |crc, &byte| (crc << 8) ^ OGG_CRC_TABLE[((crc >> 24) as u8 ^ byte) as usize])
+}
+
+const MAX_SEGMENTS: usize = 255;
+/// Pages are ended once they hold this much, like libogg does.
+const PAGE_SIZE: usize = 4096;
+
+/// Splits packets into Ogg pages.
+struct OggWriter {
+    output: Vec<u8>,
+    serial: u32,
+    sequence: u32,
+    segments: Vec<u8>,
+    data: Vec<u8>,
+    /// The granule position of the last packet that ends on the current page.
+    granule: Option<i64>,
+    /// Whether the current page starts in the middle of a packet.
+    continued: bool,
+}
+
+impl OggWriter {
+    fn new(serial: u32) -> Self {
+        OggWriter {
+            output: Vec::new(),
+            serial,
+            sequence: 0,
+            segments: Vec::new(),
+            data: Vec::new(),
+            granule: None,
+            continued: false,
+        }
+    }
+
+    fn write_page(&mut self, end_of_stream: bool) {
+        let mut flags = 0;
+        if self.continued {
+            flags |= 1;
+        }
+        if self.sequence == 0 {
+            flags |= 2;
+        }
+        if end_of_stream {
+            flags |= 4;
+        }
+        let start = self.output.len();
+        self.output.extend(b"OggS\0");
+        self.output.push(flags);
+        self.output.extend(self.granule.unwrap_or(-1).to_le_bytes());
+        self.output.extend(self.serial.to_le_bytes());
+        self.output.extend(self.sequence.to_le_bytes());
+        self.output.extend([0; 4]);
+        self.output.push(self.segments.len() as u8);
+        self.output.append(&mut self.segments);
+        self.output.append(&mut self.data);
+        let crc = ogg_crc(&self.output[start..]);
+        self.output[start + 22..start + 26].copy_from_slice(&crc.to_le_bytes());
+        self.sequence += 1;
+        self.granule = None;
+        self.continued = false;
+    }
+
+    fn write_packet(&mut self, packet: &[u8], granule: i64) {
+        if self.data.len() >= PAGE_SIZE {
+            self.write_page(false);
+        }
+        // a packet is laced as runs of 255 bytes, ended by a shorter segment
+        let mut remaining = packet;
+        loop {
+            if self.segments.len() == MAX_SEGMENTS {
+                self.write_page(false);
+                self.continued = true;
+            }
+            let (segment, rest) = remaining.split_at(remaining.len().min(255));
+            self.segments.push(segment.len() as u8);
+            self.data.extend(segment);
+            remaining = rest;
+            if segment.len() < 255 {
+                break;
+            }
+        }
+        self.granule = Some(granule);
+    }
+
+    fn flush(&mut self) {
+        if !self.segments.is_empty() {
+            self.write_page(false);
+        }
+    }
+
+    /// Writes the last page, which ends the stream.
+    fn finish(mut self) -> Vec<u8> {
+        self.write_page(true);
+        self.output
+    }
+}
+
+fn identification_header(channels: u8, frequency: u32) -> Vec<u8> {
+    let mut header = b"\x01vorbis".to_vec();
+    header.extend(0u32.to_le_bytes());
+    header.push(channels);
+    header.extend(frequency.to_le_bytes());
+    // no maximum, nominal or minimum bitrate
+    header.extend([0; 12]);
+    header.push((BLOCK_SIZES[1].trailing_zeros() << 4 | BLOCK_SIZES[0].trailing_zeros()) as u8);
+    header.push(1);
+    header
+}
+
+fn comment_header() -> Vec<u8> {
+    let mut header = b"\x03vorbis".to_vec();
+    header.extend((VENDOR.len() as u32).to_le_bytes());
+    header.extend(VENDOR.as_bytes());
+    header.extend(0u32.to_le_bytes());
+    header.push(1);
+    header
+}
+
+/// Rebuilds the Ogg Vorbis stream of a sample, whose data is a series of packets prefixed with their 16 bit size.
+pub(super) fn rebuild_ogg(sample: &Fsb5Sample, setup_headers: &VorbisSetupHeaders) -> Result<Vec<u8>, Error> {
+    let crc32 = sample
+        .vorbis_crc32
+        .ok_or_else(|| Error::InvalidValue("Vorbis sample has no setup header CRC32".to_string()))?;
+    let setup_header = setup_headers
+        .get(crc32)
+        .ok_or_else(|| Error::InvalidValue(format!("unknown Vorbis setup header CRC32 {crc32:08x}")))?;
+    // tables may have the headers with or without their packet type and signature
+    let setup_header = match setup_header.starts_with(b"\x05vorbis") {
+        true => setup_header.to_vec(),
+        false => [b"\x05vorbis", setup_header].concat(),
+    };
+    let channels = sample.channels as u32;
+    let block_flags = read_mode_block_flags(&setup_header, channels)?;
+    let mode_bits = ilog(block_flags.len() as u32 - 1);
+
+    let mut ogg = OggWriter::new(crc32);
+    ogg.write_packet(&identification_header(sample.channels as u8, sample.frequency), 0);
+    ogg.flush();
+    ogg.write_packet(&comment_header(), 0);
+    ogg.write_packet(&setup_header, 0);
+    ogg.flush();
+
+    let mut data = sample.data.as_slice();
+    let (mut granule, mut previous_block_size) = (0i64, None);
+    while data.len() >= 2 {
+        let size = u16::from_le_bytes([data[0], data[1]]) as usize;
+        if size == 0 {
+            break;
+        }
+        let packet = data
+            .get(2..2 + size)
+            .ok_or_else(|| Error::InvalidValue("Vorbis packet is truncated".to_string()))?;
+        data = &data[2 + size..];
+
+        let mut bits = BitReader::new(packet);
+        if bits.read(1)? != 0 {
+            return Err(Error::InvalidValue("Vorbis packet is not an audio packet".to_string()));
+        }
+        let mode = bits.read(mode_bits)? as usize;
+        let long_block = *block_flags
+            .get(mode)
+            .ok_or_else(|| Error::InvalidValue(format!("Vorbis packet uses missing mode {mode}")))?;
+        let block_size = BLOCK_SIZES[long_block as usize];
+        // each packet completes the overlap of its block and the previous one
+        if let Some(previous_block_size) = previous_block_size {
+            granule += (block_size + previous_block_size) as i64 / 4;
+        }
+        previous_block_size = Some(block_size);
+        ogg.write_packet(packet, granule.min(sample.sample_count as i64));
+    }
+    Ok(ogg.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes bits least significant first, like Vorbis reads them.
+    #[derive(Default)]
+    struct BitWriter {
+        data: Vec<u8>,
+        position: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: u64, count: u32) {
+            for i in 0..count {
+                if self.position.is_multiple_of(8) {
+                    self.data.push(0);
+                }
+                *self.data.last_mut().unwrap() |= (value.checked_shr(i).unwrap_or(0) as u8 & 1) << (self.position % 8);
+                self.position += 1;
+            }
+        }
+    }
+
+    /// A setup header with one of everything, and a short and a long mode.
+    fn setup_header() -> Vec<u8> {
+        let mut bits = BitWriter::default();
+        // one ordered codebook of 4 entries with a lookup of type 1
+        bits.write(0, 8);
+        bits.write(0x564342, 24);
+        bits.write(2, 16);
+        bits.write(4, 24);
+        bits.write(1, 1);
+        bits.write(0, 5);
+        bits.write(4, 3);
+        bits.write(1, 4);
+        bits.write(0, 32);
+        bits.write(0, 32);
+        bits.write(2, 4);
+        bits.write(0, 1);
+        bits.write(0, 2 * 3);
+        // one time domain transform
+        bits.write(0, 6);
+        bits.write(0, 16);
+        // one floor of type 1 with two partitions of class 0
+        bits.write(0, 6);
+        bits.write(1, 16);
+        bits.write(2, 5);
+        bits.write(0, 4);
+        bits.write(0, 4);
+        bits.write(1, 3);
+        bits.write(1, 2);
+        bits.write(0, 8);
+        bits.write(0, 16);
+        bits.write(0, 2);
+        bits.write(7, 4);
+        bits.write(0, 2 * 2 * 7);
+        // one residue with two classifications, the second of which has a book for the fourth pass
+        bits.write(0, 6);
+        bits.write(2, 16);
+        bits.write(0, 24 * 3);
+        bits.write(1, 6);
+        bits.write(0, 8);
+        bits.write(0, 3);
+        bits.write(0, 1);
+        bits.write(0, 3);
+        bits.write(1, 1);
+        bits.write(1, 5);
+        bits.write(0, 8);
+        // one mapping with coupling of the stereo channels
+        bits.write(0, 6);
+        bits.write(0, 16);
+        bits.write(0, 1);
+        bits.write(1, 1);
+        bits.write(0, 8);
+        bits.write(1, 1);
+        bits.write(0, 1);
+        bits.write(0, 2);
+        bits.write(0, 24);
+        // a short and a long mode
+        bits.write(1, 6);
+        bits.write(0, 1);
+        bits.write(0, 40);
+        bits.write(1, 1);
+        bits.write(0, 40);
+        bits.write(1, 1);
+        [b"\x05vorbis".as_slice(), &bits.data].concat()
+    }
+
+    #[test]
+    fn lookup_values() {
+        assert_eq!(lookup1_values(4, 2), 2);
+        assert_eq!(lookup1_values(8, 2), 2);
+        assert_eq!(lookup1_values(9, 2), 3);
+        assert_eq!(lookup1_values(1000, 3), 10);
+    }
+
+    #[test]
+    fn mode_block_flags() {
+        assert_eq!(read_mode_block_flags(&setup_header(), 2).unwrap(), [false, true]);
+        let header = setup_header();
+        assert!(read_mode_block_flags(&header[..header.len() - 6], 2).is_err());
+    }
+
+    #[test]
+    fn crc() {
+        // CRC-32/POSIX without its final inversion
+        assert_eq!(ogg_crc(b"123456789"), 0x89a1897f);
+    }
+
+    fn read_pages(mut ogg: &[u8]) -> Vec<(u8, i64, Vec<u8>)> {
+        let mut pages = Vec::new();
+        while !ogg.is_empty() {
+            assert_eq!(&ogg[..4], b"OggS");
+            let segment_count = ogg[26] as usize;
+            let size: usize = ogg[27..27 + segment_count].iter().map(|&s| s as usize).sum();
+            let page_size = 27 + segment_count + size;
+            let mut page = ogg[..page_size].to_vec();
+            let crc = u32::from_le_bytes(page[22..26].try_into().unwrap());
+            page[22..26].fill(0);
+            assert_eq!(ogg_crc(&page), crc);
+            let granule = i64::from_le_bytes(page[6..14].try_into().unwrap());
+            pages.push((page[5], granule, page[27..27 + segment_count].to_vec()));
+            ogg = &ogg[page_size..];
+        }
+        pages
+    }
+
+    #[test]
+    fn rebuild() {
+        let setup = setup_header();
+        let headers = VorbisSetupHeaders::new().with_header(0x1234, setup[7..].to_vec());
+        // a short, a long and a short block, then a large packet that spans two pages
+        let mut data = Vec::new();
+        for (mode, size) in [(0u8, 3u16), (1, 3), (0, 3), (0, 257 * 255)] {
+            data.extend(size.to_le_bytes());
+            data.push(mode << 1);
+            data.extend(vec![0; size as usize - 1]);
+        }
+        data.extend([0, 0]);
+        let mut sample = Fsb5Sample {
+            name: None,
+            frequency: 44100,
+            channels: 2,
+            sample_count: 2000,
+            loop_range: None,
+            vorbis_crc32: Some(0x1234),
+            data,
+        };
+
+        let pages = read_pages(&rebuild_ogg(&sample, &headers).unwrap());
+        assert_eq!(pages.len(), 4);
+        assert_eq!((pages[0].0, pages[0].1, pages[0].2.as_slice()), (2, 0, [30].as_slice()));
+        assert_eq!(pages[1].2.len(), 2);
+        // 0, then (256 + 2048) / 4 twice, then (256 + 256) / 4 for the packet that ends on the last page
+        assert_eq!((pages[2].0, pages[2].1, pages[2].2.len()), (0, 1152, 255));
+        assert_eq!(pages[2].2[..4], [3, 3, 3, 255]);
+        assert_eq!((pages[3].0, pages[3].1, pages[3].2.len()), (5, 1280, 6));
+        assert_eq!(pages[3].2[5], 0);
+
+        sample.vorbis_crc32 = Some(0x4321);
+        assert!(rebuild_ogg(&sample, &headers).is_err());
+    }
+}
@@ -0,0 +1,177 @@
+// Font export, as the embedded TrueType or OpenType file or the glyph table of a bitmap font
+use crate::objects::classes::Font;
+use crate::Error;
+use serde_json::json;
+
+/// The kind of sfnt file a font is embedded as.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FontFormat {
+    TrueType,
+    OpenType,
+    Collection,
+}
+
+impl FontFormat {
+    /// Detects the format from the sfnt version or collection tag at the start of `data`.
+    pub fn detect(data: &[u8]) -> Option<Self> {
+        match data.get(..4)? {
+            [0, 1, 0, 0] | b"true" => Some(FontFormat::TrueType),
+            b"OTTO" => Some(FontFormat::OpenType),
+            b"ttcf" => Some(FontFormat::Collection),
+            _ => None,
+        }
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            FontFormat::TrueType => "ttf",
+            FontFormat::OpenType => "otf",
+            FontFormat::Collection => "ttc",
+        }
+    }
+}
+
+/// A font file extracted from `m_FontData`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FontFile {
+    pub name: String,
+    pub format: FontFormat,
+    pub data: Vec<u8>,
+}
+
+impl FontFile {
+    /// The name of the font with the extension of its format.
+    pub fn get_file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.get_extension())
+    }
+}
+
+/// `char` arrays are read a byte per `char`, so every `char` maps back to a byte.
+fn font_bytes(data: &[char]) -> Result<Vec<u8>, Error> {
+    data.iter()
+        .map(|&c| u8::try_from(c).map_err(|_| Error::InvalidValue(format!("font data has a char that isn't a byte: {c:?}"))))
+        .collect()
+}
+
+pub trait FontExt {
+    /// Extracts the embedded font file, or returns `None` for bitmap fonts, which have no font data.
+    fn export_font(&self) -> Result<Option<FontFile>, Error>;
+
+    /// Dumps the glyphs of `m_CharacterRects` as JSON, together with the texture they are cut from.
+    fn export_character_table(&self) -> Result<String, Error>;
+}
+
+impl FontExt for Font {
+    fn export_font(&self) -> Result<Option<FontFile>, Error> {
+        if self.m_FontData.is_empty() {
+            return Ok(None);
+        }
+        let data = font_bytes(&self.m_FontData)?;
+        let format = FontFormat::detect(&data)
+            .ok_or_else(|| Error::InvalidValue(format!("font data of {} isn't a TrueType or OpenType font", self.m_Name)))?;
+        Ok(Some(FontFile { name: self.m_Name.clone(), format, data }))
+    }
+
+    fn export_character_table(&self) -> Result<String, Error> {
+        let table = json!({
+            "name": self.m_Name,
+            "texture": self.m_Texture,
+            "fontSize": self.m_FontSize,
+            "lineSpacing": self.m_LineSpacing,
+            "asciiStartOffset": self.m_AsciiStartOffset,
+            "characters": self.m_CharacterRects,
+        });
+        serde_json::to_string_pretty(&table).map_err(|e| Error::Message(format!("character table encoding failed: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats() {
+        assert_eq!(FontFormat::detect(&[0, 1, 0, 0, 0, 0x10]), Some(FontFormat::TrueType));
+        assert_eq!(FontFormat::detect(b"true\0\x01"), Some(FontFormat::TrueType));
+        assert_eq!(FontFormat::detect(b"OTTO\0\x0b"), Some(FontFormat::OpenType));
+        assert_eq!(FontFormat::detect(b"ttcf\0\x01\0\0"), Some(FontFormat::Collection));
+        assert_eq!(FontFormat::detect(b"wOFF"), None);
+        assert_eq!(FontFormat::detect(b"OTT"), None);
+
+        let file = FontFile { name: "Roboto".to_string(), format: FontFormat::OpenType, data: Vec::new() };
+        assert_eq!(file.get_file_name(), "Roboto.otf");
+    }
+
+    #[test]
+    fn bytes_from_chars() {
+        let data: Vec<char> = [0x00, 0x01, 0x80, 0xff].into_iter().map(char::from).collect();
+        assert_eq!(font_bytes(&data).unwrap(), [0x00, 0x01, 0x80, 0xff]);
+        assert!(font_bytes(&['\u{100}']).is_err());
+    }
+
+    #[test]
+    fn character_table() {
+        use crate::objects::classes::{CharacterInfo, Rectf};
+        use crate::objects::PPtr;
+
+        let character = |index, x, advance| CharacterInfo {
+            index,
+            uv: Rectf { x, y: 0.5, width: 0.25, height: 0.5 },
+            vert: Rectf { x: 0.0, y: -1.0, width: 8.0, height: 16.0 },
+            advance: Some(advance),
+            flipped: Some(false),
+            width: None,
+        };
+        let font = Font {
+            m_Ascent: 14.0,
+            m_AsciiStartOffset: 32,
+            m_CharacterRects: vec![character(65, 0.0, 9.0), character(66, 0.25, 10.5)],
+            m_ConvertCase: 0,
+            m_DefaultMaterial: PPtr { m_FileID: 0, m_PathID: 0 },
+            m_DefaultStyle: 0,
+            m_FontData: Vec::new(),
+            m_FontNames: vec!["Pixel".to_string()],
+            m_FontSize: 16.0,
+            m_KerningValues: Vec::new(),
+            m_LineSpacing: 18.0,
+            m_Name: "Pixel".to_string(),
+            m_Texture: PPtr { m_FileID: 0, m_PathID: 42 },
+            m_CharacterPadding: Some(1),
+            m_CharacterSpacing: Some(0),
+            m_Descent: Some(-2.0),
+            m_FallbackFonts: Some(Vec::new()),
+            m_FontCountX: None,
+            m_FontCountY: None,
+            m_FontRenderingMode: Some(0),
+            m_GridFont: None,
+            m_Kerning: None,
+            m_PerCharacterKerning: None,
+            m_PixelScale: Some(1.0),
+            m_ShouldRoundAdvanceValue: Some(true),
+            m_Tracking: Some(1.0),
+            m_UseLegacyBoundsCalculation: Some(false),
+        };
+        assert!(font.export_font().unwrap().is_none());
+
+        let table: serde_json::Value = serde_json::from_str(&font.export_character_table().unwrap()).unwrap();
+        assert_eq!(table["name"], "Pixel");
+        assert_eq!(table["texture"]["m_PathID"], 42);
+        assert_eq!(table["fontSize"], 16.0);
+        assert_eq!(table["asciiStartOffset"], 32);
+
+        let characters = table["characters"].as_array().unwrap();
+        assert_eq!(characters.len(), 2);
+        assert_eq!(characters[0]["index"], 65);
+        assert_eq!(characters[0]["advance"], 9.0);
+        assert_eq!(characters[1]["index"], 66);
+        assert_eq!(characters[1]["advance"], 10.5);
+        assert_eq!(
+            characters[1]["uv"],
+            json!({ "x": 0.25, "y": 0.5, "width": 0.25, "height": 0.5 })
+        );
+        assert_eq!(
+            characters[1]["vert"],
+            json!({ "x": 0.0, "y": -1.0, "width": 8.0, "height": 16.0 })
+        );
+    }
+}
@@ -0,0 +1,235 @@
+// Decompression of `CompressedMesh`, which stores each attribute as a packed bit vector
+use super::packed::{unpack_all_floats, unpack_floats, unpack_ints};
+use super::{BoneWeights, MeshData};
+use crate::objects::classes::{CompressedMesh, PackedBitVector};
+use crate::Error;
+
+/// Bits per UV channel in `m_UVInfo`: 2 for the dimension and 1 for whether the channel exists.
+const UV_INFO_BITS: u32 = 4;
+const UV_DIMENSION_MASK: u32 = 3;
+const UV_CHANNEL_EXISTS: u32 = 4;
+const MAX_UV_CHANNELS: usize = 8;
+
+/// Skin weights are stored in 31ths.
+const WEIGHT_STEPS: u32 = 31;
+
+/// Rebuilds unit vectors from their x and y and the sign of z.
+fn unit_vectors(xy: &[f32], signs: impl Iterator<Item = bool>) -> Vec<[f32; 3]> {
+    xy.chunks_exact(2)
+        .zip(signs)
+        .map(|(xy, positive)| {
+            let (mut x, mut y) = (xy[0], xy[1]);
+            let z_squared = 1.0 - x * x - y * y;
+            let mut z = if z_squared >= 0.0 {
+                z_squared.sqrt()
+            } else {
+                let length = (x * x + y * y).sqrt();
+                (x, y) = (x / length, y / length);
+                0.0
+            };
+            if !positive {
+                z = -z;
+            }
+            [x, y, z]
+        })
+        .collect()
+}
+
+/// Reads 4 bones per vertex, where each vertex lists bones until their weights add up to 1.
+/// The weight of the fourth bone is left out as it's implied by the other three.
+fn read_skin(weights: &[u32], bones: &[u32]) -> Result<Vec<BoneWeights>, Error> {
+    let mut bones = bones.iter().copied();
+    let mut next_bone = || bones.next().ok_or_else(|| Error::InvalidValue("missing bone indices".to_string()));
+    let mut skin = Vec::new();
+    let mut vertex = BoneWeights::default();
+    let (mut count, mut sum) = (0, 0);
+    for &weight in weights {
+        vertex.weights[count] = weight as f32 / WEIGHT_STEPS as f32;
+        vertex.bones[count] = next_bone()?;
+        count += 1;
+        sum += weight;
+
+        if sum >= WEIGHT_STEPS || count == 3 {
+            if sum < WEIGHT_STEPS {
+                vertex.weights[3] = (WEIGHT_STEPS - sum) as f32 / WEIGHT_STEPS as f32;
+                vertex.bones[3] = next_bone()?;
+            }
+            skin.push(std::mem::take(&mut vertex));
+            (count, sum) = (0, 0);
+        }
+    }
+    Ok(skin)
+}
+
+/// Unity 4 packs the 4 channels of each color as separate items.
+fn read_colors(colors: &PackedBitVector) -> Result<Vec<[f32; 4]>, Error> {
+    let channels = PackedBitVector {
+        m_BitSize: colors.m_BitSize / 4,
+        m_Data: colors.m_Data.clone(),
+        m_NumItems: colors.m_NumItems * 4,
+        m_Range: colors.m_Range,
+        m_Start: colors.m_Start,
+    };
+    Ok(unpack_ints(&channels)?
+        .chunks_exact(4)
+        .map(|color| std::array::from_fn(|i| color[i] as f32 / 255.0))
+        .collect())
+}
+
+/// Replaces the attributes of `data` with those that are stored in `mesh`.
+///
+/// Returns the index buffer if the mesh has one.
+pub(super) fn decompress(mesh: &CompressedMesh, data: &mut MeshData) -> Result<Option<Vec<u32>>, Error> {
+    if mesh.m_Vertices.m_NumItems > 0 {
+        data.positions = unpack_all_floats(&mesh.m_Vertices)?
+            .chunks_exact(3)
+            .map(|v| [v[0], v[1], v[2]])
+            .collect();
+    }
+    let vertex_count = data.positions.len();
+
+    if mesh.m_UV.m_NumItems > 0 {
+        let read_uvs = |start: usize, dimension: usize| -> Result<Vec<[f32; 2]>, Error> {
+            Ok(unpack_floats(&mesh.m_UV, start, vertex_count * dimension)?
+                .chunks_exact(dimension)
+                .map(|uv| [uv[0], uv.get(1).copied().unwrap_or_default()])
+                .collect())
+        };
+        data.uvs.clear();
+        match mesh.m_UVInfo.filter(|&info| info != 0) {
+            Some(info) => {
+                let mut start = 0;
+                for channel in 0..MAX_UV_CHANNELS {
+                    let bits = info >> (channel as u32 * UV_INFO_BITS);
+                    data.uvs.push(Vec::new());
+                    if bits & UV_CHANNEL_EXISTS != 0 {
+                        let dimension = 1 + (bits & UV_DIMENSION_MASK) as usize;
+                        data.uvs[channel] = read_uvs(start, dimension)?;
+                        start += vertex_count * dimension;
+                    }
+                }
+            }
+            // before Unity 5 there are one or two channels of two dimensions
+            None => {
+                data.uvs.push(read_uvs(0, 2)?);
+                if mesh.m_UV.m_NumItems as usize >= vertex_count * 4 {
+                    data.uvs.push(read_uvs(vertex_count * 2, 2)?);
+                }
+            }
+        }
+    }
+
+    if let Some(bind_poses) = mesh.m_BindPoses.as_ref().filter(|poses| poses.m_NumItems > 0) {
+        data.bind_poses = unpack_all_floats(bind_poses)?
+            .chunks_exact(16)
+            .map(|m| std::array::from_fn(|row| std::array::from_fn(|column| m[row * 4 + column])))
+            .collect();
+    }
+
+    if mesh.m_Normals.m_NumItems > 0 {
+        let signs = unpack_ints(&mesh.m_NormalSigns)?;
+        data.normals = unit_vectors(&unpack_all_floats(&mesh.m_Normals)?, signs.iter().map(|&sign| sign != 0));
+    }
+
+    if mesh.m_Tangents.m_NumItems > 0 {
+        // each tangent has the sign of z and the sign of w
+        let signs = unpack_ints(&mesh.m_TangentSigns)?;
+        let directions = unit_vectors(&unpack_all_floats(&mesh.m_Tangents)?, signs.iter().step_by(2).map(|&s| s != 0));
+        data.tangents = directions
+            .into_iter()
+            .zip(signs.chunks_exact(2))
+            .map(|([x, y, z], signs)| [x, y, z, if signs[1] > 0 { 1.0 } else { -1.0 }])
+            .collect();
+    }
+
+    if let Some(colors) = mesh.m_FloatColors.as_ref().filter(|colors| colors.m_NumItems > 0) {
+        data.colors = unpack_all_floats(colors)?
+            .chunks_exact(4)
+            .map(|c| [c[0], c[1], c[2], c[3]])
+            .collect();
+    } else if let Some(colors) = mesh.m_Colors.as_ref().filter(|colors| colors.m_NumItems > 0) {
+        data.colors = read_colors(colors)?;
+    }
+
+    if mesh.m_Weights.m_NumItems > 0 {
+        data.skin = read_skin(&unpack_ints(&mesh.m_Weights)?, &unpack_ints(&mesh.m_BoneIndices)?)?;
+    }
+
+    Ok(match mesh.m_Triangles.m_NumItems {
+        0 => None,
+        _ => Some(unpack_ints(&mesh.m_Triangles)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::packed::pack;
+    use super::*;
+
+    fn empty() -> PackedBitVector {
+        pack(&[], 0, 0.0, 0.0)
+    }
+
+    fn compressed_mesh() -> CompressedMesh {
+        CompressedMesh {
+            m_BoneIndices: empty(),
+            m_NormalSigns: empty(),
+            m_Normals: empty(),
+            m_TangentSigns: empty(),
+            m_Tangents: empty(),
+            m_Triangles: empty(),
+            m_UV: empty(),
+            m_Vertices: empty(),
+            m_Weights: empty(),
+            m_BindPoses: None,
+            m_Colors: None,
+            m_FloatColors: None,
+            m_UVInfo: None,
+        }
+    }
+
+    #[test]
+    fn skin_weights() {
+        // a single bone, two bones that add up to 31, and three bones with an implied fourth
+        let skin = read_skin(&[31, 10, 21, 10, 10, 10], &[4, 1, 2, 5, 6, 7, 8]).unwrap();
+        assert_eq!(skin.len(), 3);
+        assert_eq!(skin[0], BoneWeights { bones: [4, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] });
+        assert_eq!(skin[1].bones, [1, 2, 0, 0]);
+        assert_eq!(skin[2].bones, [5, 6, 7, 8]);
+        assert_eq!(skin[2].weights[3], 1.0 / 31.0);
+        assert!(read_skin(&[10, 10, 10], &[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn attributes() {
+        let mut mesh = compressed_mesh();
+        // two vertices with coordinates from -1 to 1 in 1 bit steps
+        mesh.m_Vertices = pack(&[0, 1, 0, 1, 1, 1], 1, 2.0, -1.0);
+        mesh.m_Normals = pack(&[1, 1, 0, 0], 1, 1.0, 0.0);
+        mesh.m_NormalSigns = pack(&[1, 0], 1, 0.0, 0.0);
+        // two UV channels, one of 1 and one of 2 dimensions
+        mesh.m_UVInfo = Some(0b0100 | 0b0101 << 4);
+        mesh.m_UV = pack(&[0, 1, 1, 0, 1, 1], 1, 1.0, 0.0);
+        mesh.m_Triangles = pack(&[0, 1, 1], 2, 0.0, 0.0);
+
+        let mut data = MeshData::default();
+        let indices = decompress(&mesh, &mut data).unwrap();
+        assert_eq!(indices, Some(vec![0, 1, 1]));
+        assert_eq!(data.positions, [[-1.0, 1.0, -1.0], [1.0, 1.0, 1.0]]);
+        // the first normal doesn't have unit length, so it's normalized
+        let half = 1.0 / 2f32.sqrt();
+        assert_eq!(data.normals, [[half, half, 0.0], [0.0, 0.0, -1.0]]);
+        assert_eq!(data.uvs.len(), 8);
+        assert_eq!(data.uvs[0], [[0.0, 0.0], [1.0, 0.0]]);
+        assert_eq!(data.uvs[1], [[1.0, 0.0], [1.0, 1.0]]);
+    }
+
+    #[test]
+    fn legacy_colors() {
+        let mut colors = pack(&[255, 0, 51, 255], 8, 0.0, 0.0);
+        // the bit size and count are those of whole colors
+        colors.m_BitSize = 32;
+        colors.m_NumItems = 1;
+        assert_eq!(read_colors(&colors).unwrap(), [[1.0, 0.0, 0.2, 1.0]]);
+    }
+}
@@ -0,0 +1,328 @@
+// glTF 2.0 export, as .gltf with an embedded buffer or as binary .glb
+use super::{MeshData, Topology};
+use crate::Error;
+use serde_json::{json, Value};
+use std::io::Write;
+
+const GLB_MAGIC: &[u8; 4] = b"glTF";
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: &[u8; 4] = b"JSON";
+const CHUNK_BIN: &[u8; 4] = b"BIN\0";
+
+const UNSIGNED_SHORT: u32 = 5123;
+const UNSIGNED_INT: u32 = 5125;
+const FLOAT: u32 = 5126;
+
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (i, &byte)| value | (byte as u32) << (16 - i * 8));
+        for i in 0..4 {
+            encoded.push(match i <= chunk.len() {
+                true => BASE64_ALPHABET[(value >> (18 - i * 6)) as usize & 63] as char,
+                false => '=',
+            });
+        }
+    }
+    encoded
+}
+
+/// Inverts a matrix, or returns `None` if it's singular.
+fn invert(matrix: [[f32; 4]; 4]) -> Option<[[f32; 4]; 4]> {
+    let mut m = matrix.map(|row| row.map(f64::from));
+    let mut inverse: [[f64; 4]; 4] = std::array::from_fn(|r| std::array::from_fn(|c| (r == c) as u8 as f64));
+    for column in 0..4 {
+        let pivot = (column..4).max_by(|&a, &b| m[a][column].abs().total_cmp(&m[b][column].abs()))?;
+        if m[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(column, pivot);
+        inverse.swap(column, pivot);
+        let scale = m[column][column];
+        for c in 0..4 {
+            m[column][c] /= scale;
+            inverse[column][c] /= scale;
+        }
+        for row in (0..4).filter(|&row| row != column) {
+            let factor = m[row][column];
+            for c in 0..4 {
+                m[row][c] -= factor * m[column][c];
+                inverse[row][c] -= factor * inverse[column][c];
+            }
+        }
+    }
+    Some(inverse.map(|row| row.map(|value| value as f32)))
+}
+
+/// Mirrors a row major matrix on the x axis and flattens it in column major order.
+fn convert_matrix(matrix: [[f32; 4]; 4]) -> [f32; 16] {
+    let sign = |i: usize| if i == 0 { -1.0 } else { 1.0 };
+    std::array::from_fn(|i| {
+        let (row, column) = (i % 4, i / 4);
+        matrix[row][column] * sign(row) * sign(column)
+    })
+}
+
+/// Collects the buffer views and accessors of a single buffer.
+#[derive(Default)]
+struct Buffers {
+    data: Vec<u8>,
+    views: Vec<Value>,
+    accessors: Vec<Value>,
+}
+
+impl Buffers {
+    fn add_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        self.views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.data.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.data.extend_from_slice(bytes);
+        self.data.resize(self.data.len().next_multiple_of(4), 0);
+        self.views.len() - 1
+    }
+
+    fn add_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str, bounds: bool) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|value| value.to_le_bytes()).collect();
+        let view = self.add_view(&bytes, ARRAY_BUFFER);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": FLOAT,
+            "count": values.len(),
+            "type": kind,
+        });
+        if bounds {
+            let fold = |f: fn(f32, f32) -> f32| -> [f32; N] {
+                std::array::from_fn(|i| values.iter().map(|value| value[i]).reduce(f).unwrap_or_default())
+            };
+            accessor["min"] = json!(fold(f32::min).as_slice());
+            accessor["max"] = json!(fold(f32::max).as_slice());
+        }
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    fn add_integers(&mut self, values: &[u32], component_type: u32, kind: &str, target: u32) -> usize {
+        let bytes: Vec<u8> = match component_type {
+            UNSIGNED_SHORT => values.iter().flat_map(|&value| (value as u16).to_le_bytes()).collect(),
+            _ => values.iter().flat_map(|value| value.to_le_bytes()).collect(),
+        };
+        let view = self.add_view(&bytes, target);
+        let components = if kind == "VEC4" { 4 } else { 1 };
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": component_type,
+            "count": values.len() / components,
+            "type": kind,
+        }));
+        self.accessors.len() - 1
+    }
+}
+
+impl MeshData {
+    /// Builds the glTF document and its binary buffer.
+    ///
+    /// The mesh is converted to glTF's right handed coordinates by mirroring it on the x axis.
+    /// Skinned meshes get a joint per bind pose, placed where the bind pose puts the bone.
+    fn build_gltf(&self) -> Result<(Value, Vec<u8>), Error> {
+        if self.positions.is_empty() {
+            return Err(Error::InvalidValue(format!("mesh {} has no vertices", self.name)));
+        }
+        let mut buffers = Buffers::default();
+        let mut attributes = serde_json::Map::new();
+        let positions: Vec<[f32; 3]> = self.positions.iter().map(|&[x, y, z]| [-x, y, z]).collect();
+        attributes.insert("POSITION".into(), buffers.add_floats(&positions, "VEC3", true).into());
+        if !self.normals.is_empty() {
+            let normals: Vec<[f32; 3]> = self.normals.iter().map(|&[x, y, z]| [-x, y, z]).collect();
+            attributes.insert("NORMAL".into(), buffers.add_floats(&normals, "VEC3", false).into());
+        }
+        if !self.tangents.is_empty() {
+            // mirroring and flipping the UVs both flip the bitangent, so w stays the same
+            let tangents: Vec<[f32; 4]> = self.tangents.iter().map(|&[x, y, z, w]| [-x, y, z, w]).collect();
+            attributes.insert("TANGENT".into(), buffers.add_floats(&tangents, "VEC4", false).into());
+        }
+        for (set, uvs) in self.uvs.iter().filter(|uvs| !uvs.is_empty()).enumerate() {
+            // glTF UVs start at the top left, Unity ones at the bottom left
+            let uvs: Vec<[f32; 2]> = uvs.iter().map(|&[u, v]| [u, 1.0 - v]).collect();
+            attributes.insert(format!("TEXCOORD_{set}"), buffers.add_floats(&uvs, "VEC2", false).into());
+        }
+        if !self.colors.is_empty() {
+            attributes.insert("COLOR_0".into(), buffers.add_floats(&self.colors, "VEC4", false).into());
+        }
+
+        let skinned = !self.skin.is_empty() && !self.bind_poses.is_empty();
+        if skinned {
+            let bones: Vec<u32> = self.skin.iter().flat_map(|skin| skin.bones).collect();
+            if let Some(bone) = bones.iter().find(|&&bone| bone as usize >= self.bind_poses.len()) {
+                return Err(Error::InvalidValue(format!("bone {bone} has no bind pose")));
+            }
+            let joints = buffers.add_integers(&bones, UNSIGNED_SHORT, "VEC4", ARRAY_BUFFER);
+            attributes.insert("JOINTS_0".into(), joints.into());
+            let weights: Vec<[f32; 4]> = self.skin.iter().map(|skin| skin.weights).collect();
+            attributes.insert("WEIGHTS_0".into(), buffers.add_floats(&weights, "VEC4", false).into());
+        }
+
+        let mut primitives = Vec::new();
+        for sub_mesh in self.sub_meshes.iter().filter(|sub_mesh| !sub_mesh.indices.is_empty()) {
+            let (mode, indices) = match sub_mesh.topology {
+                // mirroring turns the triangles around, so they are wound the other way
+                Topology::Triangles => (4, sub_mesh.indices.chunks_exact(3).flat_map(|t| [t[0], t[2], t[1]]).collect()),
+                Topology::Lines => (1, sub_mesh.indices.clone()),
+                Topology::Points => (0, sub_mesh.indices.clone()),
+            };
+            let indices = buffers.add_integers(&indices, UNSIGNED_INT, "SCALAR", ELEMENT_ARRAY_BUFFER);
+            primitives.push(json!({ "attributes": attributes, "indices": indices, "mode": mode }));
+        }
+        if primitives.is_empty() {
+            primitives.push(json!({ "attributes": attributes, "mode": 0 }));
+        }
+
+        let mut mesh_node = json!({ "name": self.name, "mesh": 0 });
+        let mut nodes = Vec::new();
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "runirip" },
+            "scene": 0,
+            "meshes": [{ "name": self.name, "primitives": primitives }],
+        });
+        if skinned {
+            let matrices: Vec<[f32; 16]> = self.bind_poses.iter().map(|&pose| convert_matrix(pose)).collect();
+            let inverse_bind_matrices: Vec<u8> = matrices.iter().flatten().flat_map(|v| v.to_le_bytes()).collect();
+            let view = buffers.views.len();
+            buffers.add_view(&inverse_bind_matrices, ARRAY_BUFFER);
+            // inverse bind matrices aren't vertex attributes, so their view has no target
+            buffers.views[view].as_object_mut().unwrap().remove("target");
+            buffers.accessors.push(json!({
+                "bufferView": view,
+                "componentType": FLOAT,
+                "count": matrices.len(),
+                "type": "MAT4",
+            }));
+            for (bone, pose) in self.bind_poses.iter().enumerate() {
+                let transform = invert(*pose).unwrap_or(*pose);
+                nodes.push(json!({ "name": format!("bone_{bone}"), "matrix": convert_matrix(transform) }));
+            }
+            mesh_node["skin"] = json!(0);
+            document["skins"] = json!([{
+                "inverseBindMatrices": buffers.accessors.len() - 1,
+                "joints": (1..=nodes.len()).collect::<Vec<_>>(),
+            }]);
+        }
+        nodes.insert(0, mesh_node);
+        document["scenes"] = json!([{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }]);
+        document["nodes"] = json!(nodes);
+        document["bufferViews"] = json!(buffers.views);
+        document["accessors"] = json!(buffers.accessors);
+        document["buffers"] = json!([{ "byteLength": buffers.data.len() }]);
+        Ok((document, buffers.data))
+    }
+
+    /// Writes the mesh as a .gltf file, with its buffer embedded as a data URI.
+    pub fn to_gltf<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (mut document, buffer) = self.build_gltf()?;
+        document["buffers"][0]["uri"] = json!(format!("data:application/octet-stream;base64,{}", base64(&buffer)));
+        serde_json::to_writer(&mut writer, &document).map_err(|e| Error::Message(format!("glTF encoding failed: {e}")))
+    }
+
+    /// Writes the mesh as a binary .glb file.
+    pub fn to_glb<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let (document, buffer) = self.build_gltf()?;
+        let mut json = serde_json::to_vec(&document).map_err(|e| Error::Message(format!("glTF encoding failed: {e}")))?;
+        json.resize(json.len().next_multiple_of(4), b' ');
+        let length = 12 + 8 + json.len() + 8 + buffer.len();
+
+        writer.write_all(GLB_MAGIC)?;
+        writer.write_all(&GLB_VERSION.to_le_bytes())?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+        for (kind, data) in [(CHUNK_JSON, &json), (CHUNK_BIN, &buffer)] {
+            writer.write_all(&(data.len() as u32).to_le_bytes())?;
+            writer.write_all(kind)?;
+            writer.write_all(data)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{BoneWeights, SubMeshData};
+    use super::*;
+
+    fn triangle() -> MeshData {
+        MeshData {
+            name: "triangle".to_string(),
+            positions: vec![[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 0.0, 0.0]],
+            uvs: vec![Vec::new(), vec![[0.0, 0.0], [0.0, 1.0], [1.0, 0.0]]],
+            sub_meshes: vec![SubMeshData { topology: Topology::Triangles, indices: vec![0, 1, 2] }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn encoding() {
+        assert_eq!(base64(b"runirip"), "cnVuaXJpcA==");
+        assert_eq!(base64(b"mesh"), "bWVzaA==");
+        assert_eq!(base64(b"abc"), "YWJj");
+
+        let matrix = [[2.0, 0.0, 0.0, 1.0], [0.0, 4.0, 0.0, 2.0], [0.0, 0.0, 1.0, 3.0], [0.0, 0.0, 0.0, 1.0]];
+        let inverse = invert(matrix).unwrap();
+        assert_eq!(inverse[0], [0.5, 0.0, 0.0, -0.5]);
+        assert_eq!(inverse[1], [0.0, 0.25, 0.0, -0.5]);
+        assert!(invert([[0.0; 4]; 4]).is_none());
+        // translation ends up in the last column, with x mirrored
+        assert_eq!(convert_matrix(matrix)[12..], [-1.0, 2.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn gltf_document() {
+        let (document, buffer) = triangle().build_gltf().unwrap();
+        let primitive = &document["meshes"][0]["primitives"][0];
+        assert_eq!(primitive["attributes"]["POSITION"], 0);
+        // UV sets are numbered without gaps
+        assert_eq!(primitive["attributes"]["TEXCOORD_0"], 1);
+        assert_eq!(document["accessors"][0]["min"], json!([-1.0, 0.0, 0.0]));
+        assert_eq!(document["buffers"][0]["byteLength"], buffer.len());
+
+        // mirrored positions and flipped UVs, then the indices wound the other way
+        let floats: Vec<f32> = buffer[..60].chunks_exact(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect();
+        assert_eq!(floats[6..9], [-1.0, 0.0, 0.0]);
+        assert_eq!(floats[9..15], [0.0, 1.0, 0.0, 0.0, 1.0, 1.0]);
+        let indices: Vec<u32> = buffer[60..].chunks_exact(4).map(|b| u32::from_le_bytes(b.try_into().unwrap())).collect();
+        assert_eq!(indices, [0, 2, 1]);
+
+        let mut gltf = Vec::new();
+        triangle().to_gltf(&mut gltf).unwrap();
+        let parsed: Value = serde_json::from_slice(&gltf).unwrap();
+        assert!(parsed["buffers"][0]["uri"].as_str().unwrap().starts_with("data:application/octet-stream;base64,"));
+        assert!(MeshData::default().to_gltf(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn skinned_glb() {
+        let mut mesh = triangle();
+        mesh.skin = vec![BoneWeights { bones: [1, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] }; 3];
+        let identity: [[f32; 4]; 4] = std::array::from_fn(|r| std::array::from_fn(|c| (r == c) as u8 as f32));
+        mesh.bind_poses = vec![identity; 2];
+
+        let mut glb = Vec::new();
+        mesh.to_glb(&mut glb).unwrap();
+        assert_eq!(&glb[..4], GLB_MAGIC);
+        assert_eq!(u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize, glb.len());
+        let json_length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        assert_eq!(&glb[16..20], CHUNK_JSON);
+        let document: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+        assert_eq!(document["skins"][0]["joints"], json!([1, 2]));
+        assert_eq!(document["nodes"][0]["skin"], 0);
+        assert_eq!(document["scenes"][0]["nodes"], json!([0, 1, 2]));
+        assert_eq!(&glb[24 + json_length..28 + json_length], CHUNK_BIN);
+
+        mesh.skin[0].bones[0] = 2;
+        assert!(mesh.to_glb(Vec::new()).is_err());
+    }
+}
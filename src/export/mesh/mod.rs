@@ -0,0 +1,402 @@
+mod compressed;
+mod gltf;
+mod obj;
+mod packed;
+mod vertex_data;
+
+use crate::objects::classes::{Enum_BoneInfluence__BoneWeights4, Matrix4x4f, Mesh, SpriteRenderData, SubMesh};
+use crate::Error;
+use vertex_data::{Attribute, VertexChannel};
+
+/// The bones that influence a vertex and their weights, which add up to 1.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BoneWeights {
+    pub bones: [u32; 4],
+    pub weights: [f32; 4],
+}
+
+/// How the indices of a sub mesh are grouped. Strips and quads are converted to these.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Topology {
+    Triangles,
+    Lines,
+    Points,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubMeshData {
+    pub topology: Topology,
+    /// Vertex indices, with the sub mesh's base vertex already added.
+    pub indices: Vec<u32>,
+}
+
+/// The decoded vertices and sub meshes of a `Mesh`, in Unity's left handed coordinate system.
+///
+/// Every vertex attribute is either empty or has one entry per position.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MeshData {
+    pub name: String,
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub tangents: Vec<[f32; 4]>,
+    pub colors: Vec<[f32; 4]>,
+    /// The texture coordinates of each UV channel, empty for the channels the mesh doesn't use.
+    pub uvs: Vec<Vec<[f32; 2]>>,
+    pub skin: Vec<BoneWeights>,
+    /// The inverse of each bone's transform when bound to the mesh, as row major matrices.
+    pub bind_poses: Vec<[[f32; 4]; 4]>,
+    pub sub_meshes: Vec<SubMeshData>,
+}
+
+/// Splits channel values into vertices of `N` components, filling missing components from `defaults`.
+fn components<const N: usize>(channel: &VertexChannel, defaults: [f32; N]) -> Vec<[f32; N]> {
+    channel
+        .values
+        .chunks_exact(channel.dimension)
+        .map(|vertex| std::array::from_fn(|i| vertex.get(i).copied().unwrap_or(defaults[i])))
+        .collect()
+}
+
+fn matrix_rows(matrix: &Matrix4x4f) -> [[f32; 4]; 4] {
+    let m = matrix;
+    [
+        [m.e00, m.e01, m.e02, m.e03],
+        [m.e10, m.e11, m.e12, m.e13],
+        [m.e20, m.e21, m.e22, m.e23],
+        [m.e30, m.e31, m.e32, m.e33],
+    ]
+}
+
+/// Reads an index buffer of 16 or 32 bit indices.
+fn read_index_buffer(data: &[u8], index_size: usize, big_endian: bool) -> Vec<u32> {
+    data.chunks_exact(index_size)
+        .map(|bytes| match (index_size, big_endian) {
+            (2, false) => u16::from_le_bytes([bytes[0], bytes[1]]) as u32,
+            (2, true) => u16::from_be_bytes([bytes[0], bytes[1]]) as u32,
+            (_, false) => u32::from_le_bytes(bytes.try_into().unwrap()),
+            (_, true) => u32::from_be_bytes(bytes.try_into().unwrap()),
+        })
+        .collect()
+}
+
+/// Converts the indices of `sub_mesh` to triangles, lines or points.
+fn convert_sub_mesh(sub_mesh: &SubMesh, indices: &[u32], index_size: usize) -> Result<SubMeshData, Error> {
+    let first = sub_mesh.firstByte as usize / index_size;
+    let base = sub_mesh.baseVertex.unwrap_or(0);
+    let indices: Vec<u32> = indices
+        .get(first..first + sub_mesh.indexCount as usize)
+        .ok_or_else(|| Error::InvalidValue(format!("sub mesh indices {first}.. are out of bounds")))?
+        .iter()
+        .map(|&index| {
+            index.checked_add(base).ok_or_else(|| {
+                Error::InvalidValue(format!("vertex index {index} + base vertex {base} is out of bounds"))
+            })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    // Unity 3 only has triangle lists and strips
+    let topology = match (sub_mesh.topology, sub_mesh.isTriStrip) {
+        (Some(topology), _) => topology,
+        (None, Some(strip)) if strip != 0 => 1,
+        _ => 0,
+    };
+    let (topology, indices) = match topology {
+        0 => (Topology::Triangles, indices),
+        1 => {
+            // every other triangle of a strip is wound the other way, degenerate ones are left out
+            let mut triangles = Vec::new();
+            for (i, window) in indices.windows(3).enumerate() {
+                let [a, b, c] = [window[0], window[1], window[2]];
+                if a != b && b != c && a != c {
+                    triangles.extend(if i % 2 == 0 { [a, b, c] } else { [b, a, c] });
+                }
+            }
+            (Topology::Triangles, triangles)
+        }
+        2 => {
+            let triangles = indices.chunks_exact(4).flat_map(|q| [q[0], q[1], q[2], q[0], q[2], q[3]]).collect();
+            (Topology::Triangles, triangles)
+        }
+        3 => (Topology::Lines, indices),
+        4 => (Topology::Lines, indices.windows(2).flatten().copied().collect()),
+        5 => (Topology::Points, indices),
+        topology => return Err(Error::InvalidValue(format!("unknown mesh topology {topology}"))),
+    };
+    Ok(SubMeshData { topology, indices })
+}
+
+impl MeshData {
+    fn set_channels(&mut self, channels: Vec<VertexChannel>) {
+        let (mut weights, mut bones) = (None, None);
+        for channel in channels {
+            match channel.attribute {
+                Attribute::Position => self.positions = components(&channel, [0.0; 3]),
+                Attribute::Normal => self.normals = components(&channel, [0.0; 3]),
+                Attribute::Tangent => self.tangents = components(&channel, [0.0, 0.0, 0.0, 1.0]),
+                Attribute::Color => self.colors = components(&channel, [0.0, 0.0, 0.0, 1.0]),
+                Attribute::TexCoord(index) => {
+                    if self.uvs.len() <= index {
+                        self.uvs.resize(index + 1, Vec::new());
+                    }
+                    self.uvs[index] = components(&channel, [0.0; 2]);
+                }
+                Attribute::BlendWeight => weights = Some(channel),
+                Attribute::BlendIndices => bones = Some(channel),
+            }
+        }
+
+        // the weight of the last bone is left out when it can be derived from the others
+        let Some(bones) = bones else { return };
+        let weight_count = weights.as_ref().map_or(0, |weights| weights.dimension.min(4));
+        self.skin = bones
+            .values
+            .chunks_exact(bones.dimension)
+            .enumerate()
+            .map(|(vertex, indices)| {
+                let mut skin = BoneWeights::default();
+                for (bone, &index) in skin.bones.iter_mut().zip(indices) {
+                    *bone = index as u32;
+                }
+                let start = vertex * weights.as_ref().map_or(0, |weights| weights.dimension);
+                if let Some(values) = weights.as_ref().and_then(|w| w.values.get(start..start + weight_count)) {
+                    skin.weights[..weight_count].copy_from_slice(values);
+                }
+                if weight_count < bones.dimension.min(4) {
+                    skin.weights[weight_count] = 1.0 - skin.weights.iter().sum::<f32>();
+                }
+                skin
+            })
+            .collect();
+    }
+
+    /// Unity 3.4 stores the vertex attributes as arrays.
+    fn set_arrays(&mut self, mesh: &Mesh) {
+        if let Some(vertices) = &mesh.m_Vertices {
+            self.positions = vertices.iter().map(|v| [v.x, v.y, v.z]).collect();
+        }
+        if let Some(normals) = &mesh.m_Normals {
+            self.normals = normals.iter().map(|v| [v.x, v.y, v.z]).collect();
+        }
+        if let Some(tangents) = &mesh.m_Tangents {
+            self.tangents = tangents.iter().map(|v| [v.x, v.y, v.z, v.w]).collect();
+        }
+        if let Some(colors) = &mesh.m_Colors {
+            self.colors = colors
+                .iter()
+                .map(|color| match color.rgba {
+                    Some(rgba) => rgba.to_le_bytes().map(|c| c as f32 / 255.0),
+                    None => [color.r, color.g, color.b, color.a].map(Option::unwrap_or_default),
+                })
+                .collect();
+        }
+        for (index, uvs) in [&mesh.m_UV, &mesh.m_UV1].into_iter().enumerate() {
+            if let Some(uvs) = uvs {
+                self.uvs.resize(self.uvs.len().max(index + 1), Vec::new());
+                self.uvs[index] = uvs.iter().map(|v| [v.x, v.y]).collect();
+            }
+        }
+        if let Some(skin) = mesh.m_Skin.as_ref().filter(|skin| !skin.is_empty()) {
+            self.skin = skin
+                .iter()
+                .map(|influence| {
+                    let (bones, weights) = match influence {
+                        Enum_BoneInfluence__BoneWeights4::BoneInfluence(i) => (
+                            [i.boneIndex_0_, i.boneIndex_1_, i.boneIndex_2_, i.boneIndex_3_],
+                            [i.weight_0_, i.weight_1_, i.weight_2_, i.weight_3_],
+                        ),
+                        Enum_BoneInfluence__BoneWeights4::BoneWeights4(w) => (
+                            [w.boneIndex_0_, w.boneIndex_1_, w.boneIndex_2_, w.boneIndex_3_],
+                            [w.weight_0_, w.weight_1_, w.weight_2_, w.weight_3_],
+                        ),
+                    };
+                    BoneWeights {
+                        bones: bones.map(|bone| bone.unwrap_or_default().max(0) as u32),
+                        weights: weights.map(Option::unwrap_or_default),
+                    }
+                })
+                .collect();
+        }
+    }
+
+    /// Drops attributes that don't have one entry per vertex and checks the indices.
+    fn validate(&mut self) -> Result<(), Error> {
+        let count = self.positions.len();
+        fn keep<T>(values: &mut Vec<T>, count: usize) {
+            if values.len() != count {
+                values.clear();
+            }
+        }
+        keep(&mut self.normals, count);
+        keep(&mut self.tangents, count);
+        keep(&mut self.colors, count);
+        keep(&mut self.skin, count);
+        self.uvs.iter_mut().for_each(|uvs| keep(uvs, count));
+        while self.uvs.last().is_some_and(Vec::is_empty) {
+            self.uvs.pop();
+        }
+
+        let indices = self.sub_meshes.iter().flat_map(|sub_mesh| &sub_mesh.indices);
+        if let Some(index) = indices.copied().find(|&index| index as usize >= count) {
+            return Err(Error::InvalidValue(format!("vertex index {index} is out of bounds of {count} vertices")));
+        }
+        Ok(())
+    }
+}
+
+pub trait MeshExt {
+    /// Where the vertex data is stored if it isn't embedded in `m_VertexData`.
+    fn get_vertex_data_range(&self) -> Option<crate::resource::ResourceRange>;
+
+    /// Decodes the mesh from the data of its `m_VertexData`
+    /// or the data read from [`MeshExt::get_vertex_data_range`].
+    ///
+    /// `file` is the file the mesh was read from, whose Unity version selects the vertex layout.
+    fn decode_mesh(&self, vertex_data: &[u8], file: &crate::files::SerializedFile) -> Result<MeshData, Error>;
+}
+
+impl MeshExt for Mesh {
+    fn get_vertex_data_range(&self) -> Option<crate::resource::ResourceRange> {
+        self.m_StreamData
+            .as_ref()
+            .filter(|stream_data| !stream_data.path.is_empty())
+            .map(crate::resource::ResourceRange::from)
+    }
+
+    fn decode_mesh(&self, vertex_data: &[u8], file: &crate::files::SerializedFile) -> Result<MeshData, Error> {
+        let unity_version = file.m_UnityVersion.as_deref().unwrap_or_default();
+        let version = super::parse_unity_version(unity_version)
+            .ok_or_else(|| Error::InvalidRevision(unity_version.to_string()))?;
+        let big_endian = file.m_Header.get_endianness() == 1;
+
+        let mut data = MeshData {
+            name: self.m_Name.clone(),
+            bind_poses: self.m_BindPose.iter().map(matrix_rows).collect(),
+            ..Default::default()
+        };
+        if let Some(info) = &self.m_VertexData {
+            data.set_channels(vertex_data::read_channels(info, vertex_data, version, big_endian)?);
+        }
+        data.set_arrays(self);
+        let compressed_indices = compressed::decompress(&self.m_CompressedMesh, &mut data)?;
+
+        let index_size = match (self.m_IndexFormat, self.m_Use16BitIndices) {
+            (Some(1), _) | (None, Some(0)) => 4,
+            _ => 2,
+        };
+        let indices =
+            compressed_indices.unwrap_or_else(|| read_index_buffer(&self.m_IndexBuffer, index_size, big_endian));
+        data.sub_meshes = self
+            .m_SubMeshes
+            .iter()
+            .map(|sub_mesh| convert_sub_mesh(sub_mesh, &indices, index_size))
+            .collect::<Result<_, _>>()?;
+        data.validate()?;
+        Ok(data)
+    }
+}
+
+/// Decodes the mesh Unity generates for a sprite, which is kept in its render data.
+pub(crate) fn decode_sprite_mesh(
+    render_data: &SpriteRenderData,
+    file: &crate::files::SerializedFile,
+) -> Result<MeshData, Error> {
+    let unity_version = file.m_UnityVersion.as_deref().unwrap_or_default();
+    let version =
+        super::parse_unity_version(unity_version).ok_or_else(|| Error::InvalidRevision(unity_version.to_string()))?;
+    let big_endian = file.m_Header.get_endianness() == 1;
+
+    let mut data = MeshData::default();
+    if let Some(vertices) = &render_data.vertices {
+        // before Unity 5.6 sprites have an array of vertices and a single list of triangles
+        data.positions = vertices.iter().map(|v| [v.pos.x, v.pos.y, v.pos.z]).collect();
+        let indices = render_data.indices.iter().flatten().map(|&index| index as u32).collect();
+        data.sub_meshes.push(SubMeshData { topology: Topology::Triangles, indices });
+    } else if let Some(info) = &render_data.m_VertexData {
+        data.set_channels(vertex_data::read_channels(info, &info.m_DataSize, version, big_endian)?);
+        let indices = read_index_buffer(render_data.m_IndexBuffer.as_deref().unwrap_or_default(), 2, big_endian);
+        data.sub_meshes = render_data
+            .m_SubMeshes
+            .iter()
+            .flatten()
+            .map(|sub_mesh| convert_sub_mesh(sub_mesh, &indices, 2))
+            .collect::<Result<_, _>>()?;
+    }
+    data.validate()?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::classes::{Vector3f, AABB};
+
+    fn sub_mesh(first_byte: u32, index_count: u32, topology: i32) -> SubMesh {
+        let zero = || Vector3f { x: 0.0, y: 0.0, z: 0.0 };
+        SubMesh {
+            firstByte: first_byte,
+            firstVertex: 0,
+            indexCount: index_count,
+            localAABB: AABB { m_Center: zero(), m_Extent: zero() },
+            vertexCount: 0,
+            baseVertex: Some(1),
+            isTriStrip: None,
+            topology: Some(topology),
+            triangleCount: None,
+        }
+    }
+
+    #[test]
+    fn topologies() {
+        let indices = [0, 1, 2, 3, 3, 4];
+        let convert = |topology, count| convert_sub_mesh(&sub_mesh(0, count, topology), &indices, 2).unwrap();
+        assert_eq!(convert(0, 3).indices, [1, 2, 3]);
+        // the degenerate triangles of a strip are dropped
+        assert_eq!(convert(1, 6).indices, [1, 2, 3, 3, 2, 4]);
+        assert_eq!(convert(2, 4).indices, [1, 2, 3, 1, 3, 4]);
+        let lines = convert(4, 3);
+        assert_eq!((lines.topology, lines.indices), (Topology::Lines, vec![1, 2, 2, 3]));
+        assert!(convert_sub_mesh(&sub_mesh(4, 6, 0), &indices, 2).is_err());
+        assert!(convert_sub_mesh(&sub_mesh(0, 3, 6), &indices, 2).is_err());
+
+        let overflowing = SubMesh { baseVertex: Some(u32::MAX), ..sub_mesh(0, 3, 0) };
+        assert!(matches!(convert_sub_mesh(&overflowing, &indices, 2), Err(Error::InvalidValue(_))));
+    }
+
+    #[test]
+    fn index_buffers() {
+        assert_eq!(read_index_buffer(&[1, 0, 0, 1], 2, false), [1, 256]);
+        assert_eq!(read_index_buffer(&[1, 0, 0, 1], 2, true), [256, 1]);
+        assert_eq!(read_index_buffer(&[1, 0, 0, 1, 2], 4, false), [0x0100_0001]);
+    }
+
+    #[test]
+    fn blend_channels() {
+        let mut data = MeshData::default();
+        data.set_channels(vec![
+            VertexChannel { attribute: Attribute::BlendWeight, dimension: 2, values: vec![0.5, 0.25] },
+            VertexChannel { attribute: Attribute::BlendIndices, dimension: 3, values: vec![3.0, 1.0, 2.0] },
+        ]);
+        assert_eq!(data.skin, [BoneWeights { bones: [3, 1, 2, 0], weights: [0.5, 0.25, 0.25, 0.0] }]);
+
+        // with a single bone there are no weights
+        data.set_channels(vec![VertexChannel { attribute: Attribute::BlendIndices, dimension: 1, values: vec![7.0] }]);
+        assert_eq!(data.skin, [BoneWeights { bones: [7, 0, 0, 0], weights: [1.0, 0.0, 0.0, 0.0] }]);
+    }
+
+    #[test]
+    fn validation() {
+        let mut data = MeshData {
+            positions: vec![[0.0; 3]; 2],
+            normals: vec![[0.0; 3]],
+            uvs: vec![vec![[0.0; 2]; 2], vec![[0.0; 2]; 3]],
+            sub_meshes: vec![SubMeshData { topology: Topology::Points, indices: vec![0, 1] }],
+            ..Default::default()
+        };
+        data.validate().unwrap();
+        assert!(data.normals.is_empty());
+        assert_eq!(data.uvs.len(), 1);
+
+        data.sub_meshes[0].indices.push(2);
+        assert!(data.validate().is_err());
+    }
+}
@@ -0,0 +1,92 @@
+// Wavefront OBJ export
+use super::{MeshData, Topology};
+use crate::Error;
+use std::io::Write;
+
+impl MeshData {
+    /// Writes the mesh as an OBJ file, with a group per sub mesh.
+    ///
+    /// Only positions, normals, the first UV channel and vertex colors are written, as OBJ has nothing else.
+    /// Like for glTF, the mesh is mirrored on the x axis.
+    pub fn to_obj<W: Write>(&self, writer: W) -> Result<(), Error> {
+        let mut writer = std::io::BufWriter::new(writer);
+        writeln!(writer, "o {}", self.name)?;
+        for (i, [x, y, z]) in self.positions.iter().enumerate() {
+            match self.colors.get(i) {
+                // vertex colors are a common extension of the v statement
+                Some([r, g, b, _]) => writeln!(writer, "v {} {y} {z} {r} {g} {b}", -x)?,
+                None => writeln!(writer, "v {} {y} {z}", -x)?,
+            }
+        }
+        let uvs = self.uvs.iter().find(|uvs| !uvs.is_empty());
+        for [u, v] in uvs.into_iter().flatten() {
+            writeln!(writer, "vt {u} {v}")?;
+        }
+        for [x, y, z] in &self.normals {
+            writeln!(writer, "vn {} {y} {z}", -x)?;
+        }
+
+        let vertex = |index: u32| {
+            let index = index + 1;
+            match (uvs.is_some(), !self.normals.is_empty()) {
+                (true, true) => format!("{index}/{index}/{index}"),
+                (true, false) => format!("{index}/{index}"),
+                (false, true) => format!("{index}//{index}"),
+                (false, false) => index.to_string(),
+            }
+        };
+        for (i, sub_mesh) in self.sub_meshes.iter().enumerate() {
+            writeln!(writer, "g {}_{i}", self.name)?;
+            match sub_mesh.topology {
+                // mirroring turns the triangles around, so they are wound the other way
+                Topology::Triangles => {
+                    for triangle in sub_mesh.indices.chunks_exact(3) {
+                        let [a, b, c] = [triangle[0], triangle[2], triangle[1]].map(vertex);
+                        writeln!(writer, "f {a} {b} {c}")?;
+                    }
+                }
+                Topology::Lines => {
+                    for line in sub_mesh.indices.chunks_exact(2) {
+                        writeln!(writer, "l {} {}", line[0] + 1, line[1] + 1)?;
+                    }
+                }
+                Topology::Points => {
+                    for &point in &sub_mesh.indices {
+                        writeln!(writer, "p {}", point + 1)?;
+                    }
+                }
+            }
+        }
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::SubMeshData;
+    use super::*;
+
+    #[test]
+    fn obj() {
+        let mesh = MeshData {
+            name: "quad".to_string(),
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.5]],
+            normals: vec![[0.0, 0.0, -1.0]; 4],
+            sub_meshes: vec![
+                SubMeshData { topology: Topology::Triangles, indices: vec![0, 1, 2, 0, 2, 3] },
+                SubMeshData { topology: Topology::Lines, indices: vec![3, 0] },
+            ],
+            ..Default::default()
+        };
+        let mut obj = Vec::new();
+        mesh.to_obj(&mut obj).unwrap();
+        let obj = String::from_utf8(obj).unwrap();
+        let lines: Vec<&str> = obj.lines().collect();
+        assert_eq!(lines[0], "o quad");
+        assert_eq!(lines[2], "v -1 0 0");
+        assert_eq!(lines[4], "v -0 1 0.5");
+        assert_eq!(lines[5], "vn -0 0 -1");
+        assert_eq!(lines[9..], ["g quad_0", "f 1//1 3//3 2//2", "f 1//1 4//4 3//3", "g quad_1", "l 4 1"]);
+    }
+}
@@ -0,0 +1,107 @@
+// Unpacking of the bit packed arrays that compressed meshes are stored in
+use crate::objects::classes::PackedBitVector;
+use crate::Error;
+
+/// Reads `count` values of `m_BitSize` bits from item `start` on, least significant bit first.
+fn unpack_bits(vector: &PackedBitVector, start: usize, count: usize) -> Result<Vec<u32>, Error> {
+    let bit_size = vector.m_BitSize as usize;
+    if bit_size > 32 {
+        return Err(Error::InvalidValue(format!("packed bit vector with {bit_size} bit items")));
+    }
+    if (start + count) * bit_size > vector.m_Data.len() * 8 {
+        return Err(Error::InvalidValue(format!(
+            "packed bit vector of {} bytes is too short for {} items",
+            vector.m_Data.len(),
+            start + count
+        )));
+    }
+
+    let mut position = start * bit_size;
+    let mut values = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut value = 0u64;
+        let mut bits = 0;
+        while bits < bit_size {
+            let (byte, shift) = (vector.m_Data[position / 8] as u64, position % 8);
+            let read = (bit_size - bits).min(8 - shift);
+            value |= ((byte >> shift) & ((1 << read) - 1)) << bits;
+            position += read;
+            bits += read;
+        }
+        values.push(value as u32);
+    }
+    Ok(values)
+}
+
+/// All items of the vector as integers.
+pub(super) fn unpack_ints(vector: &PackedBitVector) -> Result<Vec<u32>, Error> {
+    unpack_bits(vector, 0, vector.m_NumItems as usize)
+}
+
+/// `count` items from item `start` on, scaled to `m_Start` to `m_Start + m_Range`.
+pub(super) fn unpack_floats(vector: &PackedBitVector, start: usize, count: usize) -> Result<Vec<f32>, Error> {
+    // unpack_bits rejects bit sizes above 32 before they are used as a shift
+    let values = unpack_bits(vector, start, count)?;
+    let max = ((1u64 << vector.m_BitSize) - 1) as f32;
+    Ok(values
+        .into_iter()
+        .map(|value| match vector.m_BitSize {
+            0 => vector.m_Start,
+            _ => value as f32 / max * vector.m_Range + vector.m_Start,
+        })
+        .collect())
+}
+
+/// All items of the vector as floats.
+pub(super) fn unpack_all_floats(vector: &PackedBitVector) -> Result<Vec<f32>, Error> {
+    unpack_floats(vector, 0, vector.m_NumItems as usize)
+}
+
+#[cfg(test)]
+pub(super) fn pack(values: &[u32], bit_size: u8, range: f32, start: f32) -> PackedBitVector {
+    let mut data = vec![0u8; (values.len() * bit_size as usize).div_ceil(8)];
+    for (i, &value) in values.iter().enumerate() {
+        for bit in 0..bit_size as usize {
+            let position = i * bit_size as usize + bit;
+            data[position / 8] |= (((value >> bit) & 1) as u8) << (position % 8);
+        }
+    }
+    PackedBitVector {
+        m_BitSize: bit_size,
+        m_Data: data,
+        m_NumItems: values.len() as u32,
+        m_Range: range,
+        m_Start: start,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ints() {
+        let values = [0, 1, 5, 31, 17, 8];
+        assert_eq!(unpack_ints(&pack(&values, 5, 0.0, 0.0)).unwrap(), values);
+        let values = [u32::MAX, 0, 0x1234_5678];
+        assert_eq!(unpack_ints(&pack(&values, 32, 0.0, 0.0)).unwrap(), values);
+
+        let mut vector = pack(&values, 32, 0.0, 0.0);
+        vector.m_NumItems = 4;
+        assert!(unpack_ints(&vector).is_err());
+    }
+
+    #[test]
+    fn floats() {
+        let close = |values: Vec<f32>, expected: &[f32]| {
+            values.len() == expected.len() && values.iter().zip(expected).all(|(a, b)| (a - b).abs() < 1e-5)
+        };
+        let vector = pack(&[0, 2, 3, 1], 2, 6.0, -3.0);
+        assert!(close(unpack_all_floats(&vector).unwrap(), &[-3.0, 1.0, 3.0, -1.0]));
+        assert!(close(unpack_floats(&vector, 1, 2).unwrap(), &[1.0, 3.0]));
+
+        let mut vector = pack(&[1], 8, 1.0, 0.0);
+        vector.m_BitSize = 64;
+        assert!(matches!(unpack_all_floats(&vector), Err(Error::InvalidValue(_))));
+    }
+}
@@ -0,0 +1,329 @@
+// Unpacking of the streams and channels of `VertexData` for each layout Unity has used
+use crate::export::half_to_f32;
+use crate::objects::classes::{StreamInfo, VertexData};
+use crate::Error;
+
+/// What a channel of `VertexData` holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Attribute {
+    Position,
+    Normal,
+    Tangent,
+    Color,
+    TexCoord(usize),
+    BlendWeight,
+    BlendIndices,
+}
+
+/// The attribute of channel `index`, as the number of channels grew over time.
+fn channel_attribute(index: usize, version: (u32, u32)) -> Option<Attribute> {
+    use Attribute::*;
+    let attributes: &[Attribute] = if version.0 < 5 {
+        &[Position, Normal, Color, TexCoord(0), TexCoord(1), Tangent]
+    } else if version.0 < 2018 {
+        &[Position, Normal, Color, TexCoord(0), TexCoord(1), TexCoord(2), TexCoord(3), Tangent]
+    } else {
+        &[
+            Position,
+            Normal,
+            Tangent,
+            Color,
+            TexCoord(0),
+            TexCoord(1),
+            TexCoord(2),
+            TexCoord(3),
+            TexCoord(4),
+            TexCoord(5),
+            TexCoord(6),
+            TexCoord(7),
+            BlendWeight,
+            BlendIndices,
+        ]
+    };
+    attributes.get(index).copied()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ComponentFormat {
+    Float,
+    Half,
+    UNorm8,
+    SNorm8,
+    UNorm16,
+    SNorm16,
+    UInt8,
+    SInt8,
+    UInt16,
+    SInt16,
+    UInt32,
+    SInt32,
+}
+
+impl ComponentFormat {
+    /// Maps the `format` of a channel, whose values changed in 2017 and again in 2019.
+    fn from_channel(format: u8, version: (u32, u32)) -> Result<Self, Error> {
+        use ComponentFormat::*;
+        let formats: &[ComponentFormat] = if version.0 < 2017 {
+            &[Float, Half, UNorm8, UInt8, UInt32]
+        } else if version.0 < 2019 {
+            &[Float, Half, UNorm8, UNorm8, SNorm8, UNorm16, SNorm16, UInt8, SInt8, UInt16, SInt16, UInt32, SInt32]
+        } else {
+            &[Float, Half, UNorm8, SNorm8, UNorm16, SNorm16, UInt8, SInt8, UInt16, SInt16, UInt32, SInt32]
+        };
+        formats
+            .get(format as usize)
+            .copied()
+            .ok_or_else(|| Error::InvalidValue(format!("unknown vertex format {format}")))
+    }
+
+    fn size(&self) -> usize {
+        use ComponentFormat::*;
+        match self {
+            UNorm8 | SNorm8 | UInt8 | SInt8 => 1,
+            Half | UNorm16 | SNorm16 | UInt16 | SInt16 => 2,
+            Float | UInt32 | SInt32 => 4,
+        }
+    }
+
+    fn read(&self, bytes: &[u8], big_endian: bool) -> f32 {
+        use ComponentFormat::*;
+        let mut buffer = [0u8; 4];
+        let size = self.size();
+        buffer[..size].copy_from_slice(&bytes[..size]);
+        if big_endian {
+            buffer[..size].reverse();
+        }
+        let u16_value = u16::from_le_bytes([buffer[0], buffer[1]]);
+        let u32_value = u32::from_le_bytes(buffer);
+        match self {
+            Float => f32::from_bits(u32_value),
+            Half => half_to_f32(u16_value),
+            UNorm8 => buffer[0] as f32 / 255.0,
+            SNorm8 => (buffer[0] as i8 as f32 / 127.0).max(-1.0),
+            UNorm16 => u16_value as f32 / 65535.0,
+            SNorm16 => (u16_value as i16 as f32 / 32767.0).max(-1.0),
+            UInt8 => buffer[0] as f32,
+            SInt8 => buffer[0] as i8 as f32,
+            UInt16 => u16_value as f32,
+            SInt16 => u16_value as i16 as f32,
+            UInt32 => u32_value as f32,
+            SInt32 => u32_value as i32 as f32,
+        }
+    }
+}
+
+struct Channel {
+    attribute: Attribute,
+    stream: usize,
+    offset: usize,
+    format: ComponentFormat,
+    dimension: usize,
+}
+
+struct Stream {
+    offset: usize,
+    stride: usize,
+}
+
+/// The values of one channel, `dimension` per vertex.
+pub(super) struct VertexChannel {
+    pub attribute: Attribute,
+    pub dimension: usize,
+    pub values: Vec<f32>,
+}
+
+/// Unity 3.5 only stores which channels each stream has, which are always in the same formats.
+fn legacy_channels(streams: &[&StreamInfo]) -> Vec<Channel> {
+    use ComponentFormat::*;
+    let mut channels = Vec::new();
+    for (stream, info) in streams.iter().enumerate() {
+        let mut offset = 0;
+        for index in (0..6).filter(|index| info.channelMask & (1 << index) != 0) {
+            let (format, dimension) = match index {
+                0 | 1 => (Float, 3),
+                2 => (UNorm8, 4),
+                3 | 4 => (Float, 2),
+                _ => (Float, 4),
+            };
+            channels.push(Channel {
+                attribute: channel_attribute(index, (3, 5)).unwrap(),
+                stream,
+                offset,
+                format,
+                dimension,
+            });
+            offset += format.size() * dimension;
+        }
+    }
+    channels
+}
+
+/// Since Unity 5 the streams follow each other, each aligned to 16 bytes.
+fn packed_streams(channels: &[Channel], vertex_count: usize) -> Vec<Stream> {
+    let stream_count = channels.iter().map(|channel| channel.stream + 1).max().unwrap_or(0);
+    let mut offset = 0;
+    (0..stream_count)
+        .map(|stream| {
+            let stride = channels
+                .iter()
+                .filter(|channel| channel.stream == stream)
+                .map(|channel| channel.format.size() * channel.dimension)
+                .sum();
+            let info = Stream { offset, stride };
+            offset = (offset + vertex_count * stride).next_multiple_of(16);
+            info
+        })
+        .collect()
+}
+
+/// Reads every channel of `vertex_data` from `data`, which is its `m_DataSize` or the streamed data.
+pub(super) fn read_channels(
+    vertex_data: &VertexData,
+    data: &[u8],
+    version: (u32, u32),
+    big_endian: bool,
+) -> Result<Vec<VertexChannel>, Error> {
+    let vertex_count = vertex_data.m_VertexCount as usize;
+    let (channels, streams) = match &vertex_data.m_Channels {
+        Some(infos) => {
+            let mut channels = Vec::new();
+            for (index, info) in infos.iter().enumerate() {
+                // newer versions keep flags in the upper bits
+                let dimension = (info.dimension & 0xf) as usize;
+                let Some(attribute) = channel_attribute(index, version).filter(|_| dimension > 0) else {
+                    continue;
+                };
+                channels.push(Channel {
+                    attribute,
+                    stream: info.stream as usize,
+                    offset: info.offset as usize,
+                    format: ComponentFormat::from_channel(info.format, version)?,
+                    dimension,
+                });
+            }
+            let streams = match &vertex_data.m_Streams {
+                Some(infos) => infos
+                    .iter()
+                    .map(|info| Stream { offset: info.offset as usize, stride: info.stride as usize })
+                    .collect(),
+                None => packed_streams(&channels, vertex_count),
+            };
+            (channels, streams)
+        }
+        None => {
+            let infos: Vec<&StreamInfo> = [
+                &vertex_data.m_Streams_0_,
+                &vertex_data.m_Streams_1_,
+                &vertex_data.m_Streams_2_,
+                &vertex_data.m_Streams_3_,
+            ]
+            .into_iter()
+            .map_while(Option::as_ref)
+            .collect();
+            let streams = infos
+                .iter()
+                .map(|info| Stream { offset: info.offset as usize, stride: info.stride as usize })
+                .collect();
+            (legacy_channels(&infos), streams)
+        }
+    };
+
+    let mut result = Vec::with_capacity(channels.len());
+    for channel in channels {
+        let stream = streams
+            .get(channel.stream)
+            .ok_or_else(|| Error::InvalidValue(format!("vertex channel uses missing stream {}", channel.stream)))?;
+        let size = channel.format.size();
+        let start = stream.offset + channel.offset;
+        if vertex_count > 0 && start + (vertex_count - 1) * stream.stride + channel.dimension * size > data.len() {
+            return Err(Error::InvalidValue(format!(
+                "vertex data is {} bytes long, too short for {vertex_count} vertices",
+                data.len()
+            )));
+        }
+
+        let mut values = Vec::with_capacity(vertex_count * channel.dimension);
+        for vertex in 0..vertex_count {
+            let offset = start + vertex * stream.stride;
+            values.extend((0..channel.dimension).map(|i| channel.format.read(&data[offset + i * size..], big_endian)));
+        }
+        result.push(VertexChannel { attribute: channel.attribute, dimension: channel.dimension, values });
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::classes::ChannelInfo;
+
+    fn vertex_data(vertex_count: u32, channels: &[(u8, u8, u8, u8)]) -> VertexData {
+        VertexData {
+            m_DataSize: Vec::new(),
+            m_VertexCount: vertex_count,
+            m_Channels: Some(
+                channels
+                    .iter()
+                    .map(|&(stream, offset, format, dimension)| ChannelInfo { dimension, format, offset, stream })
+                    .collect(),
+            ),
+            m_CurrentChannels: None,
+            m_Streams: None,
+            m_Streams_0_: None,
+            m_Streams_1_: None,
+            m_Streams_2_: None,
+            m_Streams_3_: None,
+        }
+    }
+
+    #[test]
+    fn packed_streams() {
+        // float3 positions in stream 0, 2019 UNorm8 colors in stream 1, which starts 16 byte aligned
+        let mut channels = vec![(0, 0, 0, 3), (0, 0, 0, 0), (0, 0, 0, 0), (1, 0, 2, 4)];
+        channels.resize(14, (0, 0, 0, 0));
+        let vertex_data = vertex_data(2, &channels);
+        let mut data: Vec<u8> = [1.0f32, 2.0, 3.0, -1.0, 0.5, 0.0].iter().flat_map(|v| v.to_le_bytes()).collect();
+        data.extend([0; 8]);
+        data.extend([255, 0, 0, 255, 0, 255, 0, 51]);
+
+        let channels = read_channels(&vertex_data, &data, (2019, 4), false).unwrap();
+        assert_eq!(channels.len(), 2);
+        assert_eq!(channels[0].attribute, Attribute::Position);
+        assert_eq!(channels[0].values, [1.0, 2.0, 3.0, -1.0, 0.5, 0.0]);
+        assert_eq!(channels[1].attribute, Attribute::Color);
+        assert_eq!(channels[1].values, [1.0, 0.0, 0.0, 1.0, 0.0, 1.0, 0.0, 0.2]);
+        assert!(read_channels(&vertex_data, &data[..data.len() - 1], (2019, 4), false).is_err());
+    }
+
+    #[test]
+    fn interleaved_formats() {
+        // half UVs after big endian float positions, in the channel layout of Unity 5
+        let vertex_data = vertex_data(1, &[(0, 0, 0, 3), (0, 0, 0, 0), (0, 0, 0, 0), (0, 12, 1, 2)]);
+        let mut data: Vec<u8> = [1.0f32, 2.0, 3.0].iter().flat_map(|v| v.to_be_bytes()).collect();
+        data.extend([0x38, 0x00, 0x3c, 0x00]);
+
+        let channels = read_channels(&vertex_data, &data, (5, 6), true).unwrap();
+        assert_eq!(channels[0].values, [1.0, 2.0, 3.0]);
+        assert_eq!(channels[1].attribute, Attribute::TexCoord(0));
+        assert_eq!(channels[1].values, [0.5, 1.0]);
+    }
+
+    #[test]
+    fn legacy_streams() {
+        let mut vertex_data = vertex_data(1, &[]);
+        vertex_data.m_Channels = None;
+        vertex_data.m_Streams_0_ = Some(StreamInfo {
+            channelMask: 0b1001,
+            offset: 0,
+            stride: 20,
+            align: Some(0),
+            dividerOp: None,
+            frequency: None,
+        });
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 0.25, 0.75].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+        let channels = read_channels(&vertex_data, &data, (3, 5), false).unwrap();
+        assert_eq!(channels[1].attribute, Attribute::TexCoord(0));
+        assert_eq!(channels[1].values, [0.25, 0.75]);
+    }
+}
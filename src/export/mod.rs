@@ -0,0 +1,38 @@
+#[cfg(feature = "audio")]
+pub mod audio;
+#[cfg(feature = "font")]
+pub mod font;
+#[cfg(feature = "mesh")]
+pub mod mesh;
+#[cfg(feature = "shader")]
+pub mod shader;
+#[cfg(feature = "sprite")]
+pub mod sprite;
+#[cfg(feature = "text")]
+pub mod text;
+#[cfg(feature = "texture")]
+pub mod texture;
+
+/// Parses the major and minor version out of a Unity version like `2019.4.40f1`.
+pub(crate) fn parse_unity_version(unity_version: &str) -> Option<(u32, u32)> {
+    let mut parts = unity_version.split('.').map(|part| part.parse::<u32>().ok());
+    Some((parts.next()??, parts.next()??))
+}
+
+/// Converts an IEEE 754 half precision float.
+pub(crate) fn half_to_f32(half: u16) -> f32 {
+    let sign = ((half >> 15) as u32) << 31;
+    let exponent = ((half >> 10) & 0x1f) as u32;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        0 if mantissa == 0 => sign,
+        0 => {
+            // subnormal halfs are normal floats
+            let shift = mantissa.leading_zeros() - 21;
+            sign | ((113 - shift) << 23) | (((mantissa << shift) & 0x3ff) << 13)
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent + 112) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
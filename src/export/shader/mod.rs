@@ -0,0 +1,270 @@
+mod program;
+mod shaderlab;
+
+use crate::objects::classes::{Enum_u32__Vec_u32, Shader};
+use crate::Error;
+use num_enum::TryFromPrimitive;
+pub use program::{ShaderProgram, ShaderSubProgram};
+
+/// The `platforms` a `Shader` has programs for.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
+#[repr(u32)]
+pub enum ShaderCompilerPlatform {
+    GL = 0,
+    D3D9 = 1,
+    Xbox360 = 2,
+    PS3 = 3,
+    D3D11 = 4,
+    GLES20 = 5,
+    NaCl = 6,
+    Flash = 7,
+    D3D11_9x = 8,
+    GLES3Plus = 9,
+    PSP2 = 10,
+    PS4 = 11,
+    XboxOne = 12,
+    PSM = 13,
+    Metal = 14,
+    OpenGLCore = 15,
+    N3DS = 16,
+    WiiU = 17,
+    Vulkan = 18,
+    Switch = 19,
+    XboxOneD3D12 = 20,
+    GameCoreXboxOne = 21,
+    GameCoreScarlett = 22,
+    PS5 = 23,
+    PS5NGGC = 24,
+}
+
+impl ShaderCompilerPlatform {
+    /// The name ShaderLab uses for the platform.
+    pub fn get_name(&self) -> &'static str {
+        use ShaderCompilerPlatform::*;
+        match self {
+            GL => "openGL",
+            D3D9 => "d3d9",
+            Xbox360 => "xbox360",
+            PS3 => "ps3",
+            D3D11 => "d3d11",
+            GLES20 => "gles",
+            NaCl => "glesdesktop",
+            Flash => "flash",
+            D3D11_9x => "d3d11_9x",
+            GLES3Plus => "gles3",
+            PSP2 => "psp2",
+            PS4 => "ps4",
+            XboxOne | GameCoreXboxOne => "xboxone",
+            PSM => "psm",
+            Metal => "metal",
+            OpenGLCore => "glcore",
+            N3DS => "n3ds",
+            WiiU => "wiiu",
+            Vulkan => "vulkan",
+            Switch => "switch",
+            XboxOneD3D12 => "xboxone_d3d12",
+            GameCoreScarlett => "xbox_scarlett",
+            PS5 => "ps5",
+            PS5NGGC => "ps5_nggc",
+        }
+    }
+
+    /// Whether the platform runs programs of `program_type`.
+    pub fn supports(&self, program_type: ShaderGpuProgramType) -> bool {
+        use ShaderCompilerPlatform::*;
+        use ShaderGpuProgramType as Type;
+        match self {
+            GL => program_type == Type::GLLegacy,
+            D3D9 => matches!(program_type, Type::DX9VertexSM20 | Type::DX9VertexSM30 | Type::DX9PixelSM20 | Type::DX9PixelSM30),
+            D3D11 | D3D11_9x => matches!(
+                program_type,
+                Type::DX10Level9Vertex
+                    | Type::DX10Level9Pixel
+                    | Type::DX11VertexSM40
+                    | Type::DX11VertexSM50
+                    | Type::DX11PixelSM40
+                    | Type::DX11PixelSM50
+                    | Type::DX11GeometrySM40
+                    | Type::DX11GeometrySM50
+                    | Type::DX11HullSM50
+                    | Type::DX11DomainSM50
+            ),
+            GLES20 | NaCl => program_type == Type::GLES,
+            GLES3Plus => matches!(program_type, Type::GLES31AEP | Type::GLES31 | Type::GLES3),
+            Metal => matches!(program_type, Type::MetalVS | Type::MetalFS),
+            OpenGLCore => matches!(program_type, Type::GLCore32 | Type::GLCore41 | Type::GLCore43),
+            Vulkan => program_type == Type::SPIRV,
+            PS5NGGC => program_type == Type::PS5NGGC,
+            Flash => false,
+            _ => matches!(
+                program_type,
+                Type::ConsoleVS | Type::ConsoleFS | Type::ConsoleHS | Type::ConsoleDS | Type::ConsoleGS
+            ),
+        }
+    }
+}
+
+/// The `m_GpuProgramType` of sub programs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
+#[repr(i32)]
+pub enum ShaderGpuProgramType {
+    Unknown = 0,
+    GLLegacy = 1,
+    GLES31AEP = 2,
+    GLES31 = 3,
+    GLES3 = 4,
+    GLES = 5,
+    GLCore32 = 6,
+    GLCore41 = 7,
+    GLCore43 = 8,
+    DX9VertexSM20 = 9,
+    DX9VertexSM30 = 10,
+    DX9PixelSM20 = 11,
+    DX9PixelSM30 = 12,
+    DX10Level9Vertex = 13,
+    DX10Level9Pixel = 14,
+    DX11VertexSM40 = 15,
+    DX11VertexSM50 = 16,
+    DX11PixelSM40 = 17,
+    DX11PixelSM50 = 18,
+    DX11GeometrySM40 = 19,
+    DX11GeometrySM50 = 20,
+    DX11HullSM50 = 21,
+    DX11DomainSM50 = 22,
+    MetalVS = 23,
+    MetalFS = 24,
+    SPIRV = 25,
+    ConsoleVS = 26,
+    ConsoleFS = 27,
+    ConsoleHS = 28,
+    ConsoleDS = 29,
+    ConsoleGS = 30,
+    RayTracing = 31,
+    PS5NGGC = 32,
+}
+
+fn decompress_lz4(data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    #[cfg(feature = "lz4")]
+    {
+        let mut output = vec![0; size];
+        lz4_flex::block::decompress_into(data, &mut output)?;
+        Ok(output)
+    }
+
+    #[cfg(not(feature = "lz4"))]
+    Err(Error::FeatureDisabled("lz4"))
+}
+
+/// Since Unity 2019.3 each platform has a list of segments, before that a single one.
+fn segment_values(values: Option<&Enum_u32__Vec_u32>) -> Vec<u32> {
+    match values {
+        Some(Enum_u32__Vec_u32::u32(value)) => vec![*value],
+        Some(Enum_u32__Vec_u32::Vec(values)) => values.clone(),
+        None => Vec::new(),
+    }
+}
+
+/// Replaces each `GpuProgramIndex n` of a Unity 5.3 or 5.4 script with the source of that sub program.
+fn insert_programs(script: &str, program: &ShaderProgram) -> String {
+    const MARKER: &str = "GpuProgramIndex ";
+    let mut output = String::with_capacity(script.len());
+    let mut rest = script;
+    while let Some(start) = rest.find(MARKER) {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + MARKER.len()..];
+        let digits = after.len() - after.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        match after[..digits].parse::<usize>().ok().and_then(|index| program.sub_programs.get(index)) {
+            Some(sub_program) => output.push_str(&sub_program.get_source()),
+            None => output.push_str(&rest[start..start + MARKER.len() + digits]),
+        }
+        rest = &after[digits..];
+    }
+    output.push_str(rest);
+    output
+}
+
+pub trait ShaderExt {
+    /// Decompresses and parses the programs of each of the shader's `platforms`.
+    fn read_programs(&self) -> Result<Vec<(u32, ShaderProgram)>, Error>;
+
+    /// Renders the shader as ShaderLab-like text, with the source of its programs embedded.
+    ///
+    /// Before Unity 5.5 this is the shader's script. After that it's generated from `m_ParsedForm`,
+    /// with the programs of every platform listed in each pass.
+    fn export_shader(&self) -> Result<String, Error>;
+}
+
+impl ShaderExt for Shader {
+    fn read_programs(&self) -> Result<Vec<(u32, ShaderProgram)>, Error> {
+        let (Some(blob), Some(platforms)) = (&self.compressedBlob, &self.platforms) else {
+            return Ok(Vec::new());
+        };
+        let mut programs = Vec::with_capacity(platforms.len());
+        for (i, &platform) in platforms.iter().enumerate() {
+            let entry = |values: &Option<Vec<Enum_u32__Vec_u32>>| segment_values(values.as_ref().and_then(|v| v.get(i)));
+            let (offsets, compressed, decompressed) =
+                (entry(&self.offsets), entry(&self.compressedLengths), entry(&self.decompressedLengths));
+            let has_segment_indices = matches!(
+                self.offsets.as_ref().and_then(|offsets| offsets.get(i)),
+                Some(Enum_u32__Vec_u32::Vec(_))
+            );
+
+            let mut segments = Vec::with_capacity(offsets.len());
+            for ((offset, compressed), decompressed) in offsets.into_iter().zip(compressed).zip(decompressed) {
+                let data = blob
+                    .get(offset as usize..offset as usize + compressed as usize)
+                    .ok_or_else(|| Error::InvalidValue(format!("shader blob of platform {platform} is out of bounds")))?;
+                segments.push(decompress_lz4(data, decompressed as usize)?);
+            }
+            programs.push((platform, ShaderProgram::from_segments(&segments, has_segment_indices)?));
+        }
+        Ok(programs)
+    }
+
+    fn export_shader(&self) -> Result<String, Error> {
+        if let Some(parsed) = &self.m_ParsedForm {
+            let programs = self.read_programs()?;
+            return Ok(shaderlab::ShaderLab { shader: parsed, programs: &programs }.to_string());
+        }
+
+        let script = self.m_Script.as_deref().unwrap_or_default();
+        match (&self.m_SubProgramBlob, self.decompressedSize) {
+            (Some(blob), Some(size)) if !blob.is_empty() => {
+                let program = ShaderProgram::from_segments(&[decompress_lz4(blob, size as usize)?], false)?;
+                Ok(insert_programs(script, &program))
+            }
+            _ => Ok(script.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::program::tests::{segment, sub_program};
+    use super::*;
+
+    #[test]
+    fn platforms() {
+        let platform = ShaderCompilerPlatform::try_from(9).unwrap();
+        assert_eq!(platform.get_name(), "gles3");
+        assert!(platform.supports(ShaderGpuProgramType::GLES3));
+        assert!(!platform.supports(ShaderGpuProgramType::GLES));
+        assert!(ShaderCompilerPlatform::PS4.supports(ShaderGpuProgramType::ConsoleFS));
+    }
+
+    #[test]
+    fn legacy_script() {
+        let programs = [sub_program(201510240, ShaderGpuProgramType::GLES, &[], b"void main() {}")];
+        let mut data = segment(&programs);
+        // Unity 5.4 has no segment indices
+        data.drain(12..16);
+        data[4..8].copy_from_slice(&12u32.to_le_bytes());
+        let program = ShaderProgram::from_segments(&[data], false).unwrap();
+        let script = "Program \"vp\" {\nSubProgram \"gles \" {\nGpuProgramIndex 0\n}\nGpuProgramIndex 7\n}";
+        assert_eq!(
+            insert_programs(script, &program),
+            "Program \"vp\" {\nSubProgram \"gles \" {\nvoid main() {}\n}\nGpuProgramIndex 7\n}"
+        );
+    }
+}
@@ -0,0 +1,221 @@
+// The containers of compiled shader programs that `Shader` keeps per platform
+use super::ShaderGpuProgramType;
+use crate::Error;
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// The first version of the sub program format that has a field before the keywords.
+const VERSION_5_5: i32 = 201608170;
+/// Local keywords were listed separately from 2019.1 until 2021.1.
+const VERSION_2019_1: i32 = 201806140;
+const VERSION_2021_1: i32 = 202012090;
+
+/// A compiled variant of a shader program for one platform.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderSubProgram {
+    /// The date of the format, like `201806140`.
+    pub version: i32,
+    pub program_type: i32,
+    pub keywords: Vec<String>,
+    pub local_keywords: Vec<String>,
+    pub code: Vec<u8>,
+}
+
+fn read_aligned_string<R: Read + Seek>(reader: &mut R) -> Result<String, Error> {
+    let bytes = read_aligned_bytes(reader)?;
+    Ok(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn read_aligned_bytes<R: Read + Seek>(reader: &mut R) -> Result<Vec<u8>, Error> {
+    let length = reader.read_i32::<LittleEndian>()?;
+    let length = usize::try_from(length).map_err(|_| Error::InvalidValue(format!("invalid length {length}")))?;
+    // the length of a corrupt blob isn't allocated before it is known to fit
+    let position = reader.stream_position()?;
+    let remaining = reader.seek(SeekFrom::End(0))?.saturating_sub(position);
+    reader.seek(SeekFrom::Start(position))?;
+    if length as u64 > remaining {
+        return Err(Error::InvalidValue(format!("length {length} exceeds the {remaining} remaining bytes")));
+    }
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+    let padding = (4 - length % 4) % 4;
+    reader.seek(SeekFrom::Current(padding as i64))?;
+    Ok(bytes)
+}
+
+impl ShaderSubProgram {
+    fn from_reader<R: Read + Seek>(reader: &mut R) -> Result<Self, Error> {
+        let version = reader.read_i32::<LittleEndian>()?;
+        let program_type = reader.read_i32::<LittleEndian>()?;
+        reader.seek(SeekFrom::Current(if version >= VERSION_5_5 { 16 } else { 12 }))?;
+        let keyword_count = reader.read_i32::<LittleEndian>()?;
+        let keywords = (0..keyword_count)
+            .map(|_| read_aligned_string(reader))
+            .collect::<Result<_, _>>()?;
+        let local_keywords = if (VERSION_2019_1..VERSION_2021_1).contains(&version) {
+            let count = reader.read_i32::<LittleEndian>()?;
+            (0..count).map(|_| read_aligned_string(reader)).collect::<Result<_, _>>()?
+        } else {
+            Vec::new()
+        };
+        let code = read_aligned_bytes(reader)?;
+        Ok(ShaderSubProgram { version, program_type, keywords, local_keywords, code })
+    }
+
+    /// The source of the program: GLSL and Metal sources as they are and anything else as a hex dump.
+    pub fn get_source(&self) -> String {
+        use ShaderGpuProgramType::*;
+        match ShaderGpuProgramType::try_from(self.program_type) {
+            Ok(GLLegacy | GLES31AEP | GLES31 | GLES3 | GLES | GLCore32 | GLCore41 | GLCore43) => {
+                String::from_utf8_lossy(&self.code).into_owned()
+            }
+            Ok(MetalVS | MetalFS) => {
+                // the source may be preceded by a header with its offset, and is preceded by the entry point's name
+                let mut start = 0;
+                if self.code.starts_with(&0xf00dcafeu32.to_le_bytes()) && self.code.len() >= 8 {
+                    start = (u32::from_le_bytes(self.code[4..8].try_into().unwrap()) as usize).min(self.code.len());
+                }
+                let source = &self.code[start..];
+                let source = source.iter().position(|&byte| byte == 0).map_or(source, |end| &source[end + 1..]);
+                String::from_utf8_lossy(source).into_owned()
+            }
+            program_type => {
+                let name = program_type.map_or_else(|_| self.program_type.to_string(), |t| format!("{t:?}"));
+                let mut source = format!("// {} bytes of {name} program data\n", self.code.len());
+                for line in self.code.chunks(32) {
+                    source.extend(line.iter().map(|byte| format!("{byte:02x}")));
+                    source.push('\n');
+                }
+                source
+            }
+        }
+    }
+}
+
+/// The sub programs of a shader for one platform, indexed by the `m_BlobIndex` of `SerializedSubProgram`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShaderProgram {
+    pub sub_programs: Vec<ShaderSubProgram>,
+}
+
+impl ShaderProgram {
+    /// Reads the sub programs of a platform from its decompressed segments.
+    ///
+    /// The first segment starts with the offset and length of each sub program,
+    /// and since Unity 2019.3 also the segment it is in.
+    pub fn from_segments(segments: &[Vec<u8>], has_segment_indices: bool) -> Result<Self, Error> {
+        let Some(first) = segments.first() else {
+            return Ok(ShaderProgram::default());
+        };
+        let mut reader = Cursor::new(first);
+        let count = reader.read_i32::<LittleEndian>()?;
+        let count = usize::try_from(count).map_err(|_| Error::InvalidValue(format!("invalid sub program count {count}")))?;
+        let mut entries = Vec::with_capacity(count.min(first.len() / 8));
+        for _ in 0..count {
+            let offset = reader.read_u32::<LittleEndian>()?;
+            let _length = reader.read_u32::<LittleEndian>()?;
+            let segment = if has_segment_indices { reader.read_u32::<LittleEndian>()? } else { 0 };
+            entries.push((offset, segment));
+        }
+
+        let mut sub_programs = Vec::with_capacity(entries.len());
+        for (offset, segment) in entries {
+            let data = segments
+                .get(segment as usize)
+                .ok_or_else(|| Error::InvalidValue(format!("shader sub program is in missing segment {segment}")))?;
+            let mut reader = Cursor::new(data);
+            reader.seek(SeekFrom::Start(offset as u64))?;
+            sub_programs.push(ShaderSubProgram::from_reader(&mut reader)?);
+        }
+        Ok(ShaderProgram { sub_programs })
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+
+    fn write_aligned(data: &mut Vec<u8>, bytes: &[u8]) {
+        data.write_i32::<LittleEndian>(bytes.len() as i32).unwrap();
+        data.extend(bytes);
+        data.resize(data.len().next_multiple_of(4), 0);
+    }
+
+    pub(in super::super) fn sub_program(version: i32, program_type: ShaderGpuProgramType, keywords: &[&str], code: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_i32::<LittleEndian>(version).unwrap();
+        data.write_i32::<LittleEndian>(program_type as i32).unwrap();
+        data.resize(data.len() + if version >= VERSION_5_5 { 16 } else { 12 }, 0);
+        data.write_i32::<LittleEndian>(keywords.len() as i32).unwrap();
+        for keyword in keywords {
+            write_aligned(&mut data, keyword.as_bytes());
+        }
+        if (VERSION_2019_1..VERSION_2021_1).contains(&version) {
+            data.write_i32::<LittleEndian>(1).unwrap();
+            write_aligned(&mut data, b"LOCAL");
+        }
+        write_aligned(&mut data, code);
+        data
+    }
+
+    /// A segment with a table of `programs` in the layout of 2019.3, all in this segment.
+    pub(in super::super) fn segment(programs: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.write_i32::<LittleEndian>(programs.len() as i32).unwrap();
+        let mut offset = 4 + programs.len() * 12;
+        for program in programs {
+            for value in [offset, program.len(), 0] {
+                data.write_u32::<LittleEndian>(value as u32).unwrap();
+            }
+            offset += program.len();
+        }
+        programs.iter().for_each(|program| data.extend(program));
+        data
+    }
+
+    #[test]
+    fn sub_programs() {
+        let programs = [
+            sub_program(201806140, ShaderGpuProgramType::GLES3, &["FOG_LINEAR"], b"void main() {}"),
+            sub_program(202012090, ShaderGpuProgramType::DX11PixelSM50, &[], &[0xde, 0xad]),
+        ];
+        let program = ShaderProgram::from_segments(&[segment(&programs)], true).unwrap();
+        let gles = &program.sub_programs[0];
+        assert_eq!(gles.keywords, ["FOG_LINEAR"]);
+        assert_eq!(gles.local_keywords, ["LOCAL"]);
+        assert_eq!(gles.get_source(), "void main() {}");
+        let dx11 = &program.sub_programs[1];
+        assert!(dx11.local_keywords.is_empty());
+        assert_eq!(dx11.get_source(), "// 2 bytes of DX11PixelSM50 program data\ndead\n");
+
+        let mut missing = segment(&programs);
+        missing[12] = 1;
+        assert!(ShaderProgram::from_segments(&[missing], true).is_err());
+    }
+
+    #[test]
+    fn oversized_length() {
+        let mut data = Vec::new();
+        data.write_i32::<LittleEndian>(i32::MAX).unwrap();
+        data.extend(b"short");
+        let result = read_aligned_bytes(&mut std::io::Cursor::new(data));
+        assert!(matches!(result, Err(Error::InvalidValue(_))));
+    }
+
+    #[test]
+    fn metal_source() {
+        let mut code = 0xf00dcafeu32.to_le_bytes().to_vec();
+        code.extend(12u32.to_le_bytes());
+        code.extend([0; 4]);
+        code.extend(b"xlatMtlMain\0#include <metal_stdlib>");
+        let program = ShaderSubProgram {
+            version: VERSION_2021_1,
+            program_type: ShaderGpuProgramType::MetalFS as i32,
+            keywords: Vec::new(),
+            local_keywords: Vec::new(),
+            code,
+        };
+        assert_eq!(program.get_source(), "#include <metal_stdlib>");
+    }
+}
@@ -0,0 +1,415 @@
+// Rendering `SerializedShader` as ShaderLab
+use super::{ShaderCompilerPlatform, ShaderGpuProgramType, ShaderProgram};
+use crate::objects::classes::{
+    Enum_FastPropertyName__String, SerializedPass, SerializedProgram, SerializedProperty, SerializedShader,
+    SerializedShaderFloatValue, SerializedShaderRTBlendState, SerializedShaderState, SerializedStencilOp,
+    SerializedSubProgram, SerializedTagMap,
+};
+use std::fmt::{self, Display, Formatter};
+
+const COMPARE_FUNCTIONS: &[&str] = &["Disabled", "Never", "Less", "Equal", "LEqual", "Greater", "NotEqual", "GEqual", "Always"];
+const STENCIL_OPERATIONS: &[&str] = &["Keep", "Zero", "Replace", "IncrSat", "DecrSat", "Invert", "IncrWrap", "DecrWrap"];
+const BLEND_FACTORS: &[&str] = &[
+    "Zero",
+    "One",
+    "DstColor",
+    "SrcColor",
+    "OneMinusDstColor",
+    "SrcAlpha",
+    "OneMinusSrcColor",
+    "DstAlpha",
+    "OneMinusDstAlpha",
+    "SrcAlphaSaturate",
+    "OneMinusSrcAlpha",
+];
+const BLEND_OPERATIONS: &[&str] = &["Add", "Sub", "RevSub", "Min", "Max"];
+const CULL_MODES: &[&str] = &["Off", "Front", "Back"];
+const FOG_MODES: &[&str] = &["Off", "Linear", "Exp", "Exp2"];
+
+/// The defaults of the depth test, culling and stencil comparison, which aren't written out.
+const ALWAYS: f32 = 8.0;
+const L_EQUAL: f32 = 4.0;
+const CULL_BACK: f32 = 2.0;
+
+fn property_name(name: &Enum_FastPropertyName__String) -> &str {
+    match name {
+        Enum_FastPropertyName__String::FastPropertyName(name) => &name.name,
+        Enum_FastPropertyName__String::String(name) => name,
+    }
+}
+
+/// A state value is either a property in brackets or a number, shown as one of `names` if there are any.
+fn value(value: &SerializedShaderFloatValue, names: &[&str]) -> String {
+    let name = property_name(&value.name);
+    if !name.is_empty() {
+        return format!("[{name}]");
+    }
+    match names.get(value.val as usize).filter(|_| value.val >= 0.0 && value.val.fract() == 0.0) {
+        Some(name) => name.to_string(),
+        None => value.val.to_string(),
+    }
+}
+
+/// Whether a state is left at its default, in which case it isn't written out.
+fn is(state: &SerializedShaderFloatValue, default: f32) -> bool {
+    property_name(&state.name).is_empty() && state.val == default
+}
+
+fn write_tags(f: &mut Formatter, tags: &SerializedTagMap, indent: &str) -> fmt::Result {
+    if tags.tags.is_empty() {
+        return Ok(());
+    }
+    write!(f, "{indent}Tags {{")?;
+    for (key, value) in &tags.tags {
+        write!(f, " \"{key}\"=\"{value}\"")?;
+    }
+    writeln!(f, " }}")
+}
+
+fn write_property(f: &mut Formatter, property: &SerializedProperty) -> fmt::Result {
+    const FLAGS: &[(u32, &str)] = &[
+        (1 << 0, "HideInInspector"),
+        (1 << 1, "PerRendererData"),
+        (1 << 2, "NoScaleOffset"),
+        (1 << 3, "Normal"),
+        (1 << 4, "HDR"),
+        (1 << 5, "Gamma"),
+        (1 << 6, "NonModifiableTextureData"),
+        (1 << 7, "MainTexture"),
+        (1 << 8, "MainColor"),
+    ];
+    write!(f, " ")?;
+    for attribute in &property.m_Attributes {
+        write!(f, "[{attribute}] ")?;
+    }
+    for (_, flag) in FLAGS.iter().filter(|(bit, _)| property.m_Flags & bit != 0) {
+        write!(f, "[{flag}] ")?;
+    }
+    write!(f, "{} (\"{}\", ", property.m_Name, property.m_Description)?;
+
+    let [x, y, z, w] = [property.m_DefValue_0_, property.m_DefValue_1_, property.m_DefValue_2_, property.m_DefValue_3_]
+        .map(Option::unwrap_or_default);
+    match property.m_Type {
+        0 => writeln!(f, "Color) = ({x},{y},{z},{w})"),
+        1 => writeln!(f, "Vector) = ({x},{y},{z},{w})"),
+        2 => writeln!(f, "Float) = {x}"),
+        3 => writeln!(f, "Range({y}, {z})) = {x}"),
+        4 => {
+            let dimension = match property.m_DefTexture.m_TexDim {
+                1 => "Any",
+                2 => "2D",
+                3 => "3D",
+                4 => "Cube",
+                5 => "2DArray",
+                6 => "CubeArray",
+                _ => "2D",
+            };
+            writeln!(f, "{dimension}) = \"{}\" {{}}", property.m_DefTexture.m_DefaultName)
+        }
+        5 => writeln!(f, "Int) = {x}"),
+        property_type => writeln!(f, "Unknown{property_type}) = {x}"),
+    }
+}
+
+fn write_stencil_op(f: &mut Formatter, op: &SerializedStencilOp, suffix: &str) -> fmt::Result {
+    writeln!(f, "   Comp{suffix} {}", value(&op.comp, COMPARE_FUNCTIONS))?;
+    writeln!(f, "   Pass{suffix} {}", value(&op.pass, STENCIL_OPERATIONS))?;
+    writeln!(f, "   Fail{suffix} {}", value(&op.fail, STENCIL_OPERATIONS))?;
+    writeln!(f, "   ZFail{suffix} {}", value(&op.zFail, STENCIL_OPERATIONS))
+}
+
+fn is_default_stencil_op(op: &SerializedStencilOp) -> bool {
+    is(&op.comp, ALWAYS) && is(&op.pass, 0.0) && is(&op.fail, 0.0) && is(&op.zFail, 0.0)
+}
+
+fn write_blend(f: &mut Formatter, index: usize, blend: &SerializedShaderRTBlendState, separate: bool) -> fmt::Result {
+    // render targets other than the first are only listed when they are blended separately
+    let target = if separate { format!("{index} ") } else { String::new() };
+    if !(is(&blend.srcBlend, 1.0) && is(&blend.destBlend, 0.0) && is(&blend.srcBlendAlpha, 1.0) && is(&blend.destBlendAlpha, 0.0)) {
+        write!(f, "  Blend {target}{} {}", value(&blend.srcBlend, BLEND_FACTORS), value(&blend.destBlend, BLEND_FACTORS))?;
+        if !(is(&blend.srcBlendAlpha, 1.0) && is(&blend.destBlendAlpha, 0.0)) {
+            let (source, destination) = (&blend.srcBlendAlpha, &blend.destBlendAlpha);
+            write!(f, ", {} {}", value(source, BLEND_FACTORS), value(destination, BLEND_FACTORS))?;
+        }
+        writeln!(f)?;
+    }
+    if !(is(&blend.blendOp, 0.0) && is(&blend.blendOpAlpha, 0.0)) {
+        write!(f, "  BlendOp {target}{}", value(&blend.blendOp, BLEND_OPERATIONS))?;
+        if !is(&blend.blendOpAlpha, 0.0) {
+            write!(f, ", {}", value(&blend.blendOpAlpha, BLEND_OPERATIONS))?;
+        }
+        writeln!(f)?;
+    }
+    if !is(&blend.colMask, 15.0) {
+        let mask = match property_name(&blend.colMask.name) {
+            "" => {
+                let bits = blend.colMask.val as u32;
+                let channels: String = [(8, 'R'), (4, 'G'), (2, 'B'), (1, 'A')]
+                    .into_iter()
+                    .filter(|(bit, _)| bits & bit != 0)
+                    .map(|(_, channel)| channel)
+                    .collect();
+                if channels.is_empty() { "0".to_string() } else { channels }
+            }
+            name => format!("[{name}]"),
+        };
+        writeln!(f, "  ColorMask {target}{mask}")?;
+    }
+    Ok(())
+}
+
+fn write_state(f: &mut Formatter, state: &SerializedShaderState) -> fmt::Result {
+    if !state.m_Name.is_empty() {
+        writeln!(f, "  Name \"{}\"", state.m_Name)?;
+    }
+    if state.m_LOD != 0 {
+        writeln!(f, "  LOD {}", state.m_LOD)?;
+    }
+    write_tags(f, &state.m_Tags, "  ")?;
+
+    let blends = [
+        &state.rtBlend0,
+        &state.rtBlend1,
+        &state.rtBlend2,
+        &state.rtBlend3,
+        &state.rtBlend4,
+        &state.rtBlend5,
+        &state.rtBlend6,
+        &state.rtBlend7,
+    ];
+    let target_count = if state.rtSeparateBlend { blends.len() } else { 1 };
+    for (index, blend) in blends.into_iter().take(target_count).enumerate() {
+        write_blend(f, index, blend, state.rtSeparateBlend)?;
+    }
+
+    if !is(&state.alphaToMask, 0.0) {
+        writeln!(f, "  AlphaToMask {}", value(&state.alphaToMask, &["Off", "On"]))?;
+    }
+    if let Some(z_clip) = state.zClip.as_ref().filter(|z_clip| !is(z_clip, 1.0)) {
+        writeln!(f, "  ZClip {}", value(z_clip, &["False", "True"]))?;
+    }
+    if !is(&state.zTest, L_EQUAL) {
+        writeln!(f, "  ZTest {}", value(&state.zTest, COMPARE_FUNCTIONS))?;
+    }
+    if !is(&state.zWrite, 1.0) {
+        writeln!(f, "  ZWrite {}", value(&state.zWrite, &["Off", "On"]))?;
+    }
+    if !is(&state.culling, CULL_BACK) {
+        writeln!(f, "  Cull {}", value(&state.culling, CULL_MODES))?;
+    }
+    if let Some(conservative) = state.conservative.as_ref().filter(|conservative| !is(conservative, 0.0)) {
+        writeln!(f, "  Conservative {}", value(conservative, &["False", "True"]))?;
+    }
+    if !(is(&state.offsetFactor, 0.0) && is(&state.offsetUnits, 0.0)) {
+        writeln!(f, "  Offset {}, {}", value(&state.offsetFactor, &[]), value(&state.offsetUnits, &[]))?;
+    }
+
+    let stencil_ops = [(&state.stencilOp, ""), (&state.stencilOpFront, "Front"), (&state.stencilOpBack, "Back")];
+    if !(is(&state.stencilRef, 0.0)
+        && is(&state.stencilReadMask, 255.0)
+        && is(&state.stencilWriteMask, 255.0)
+        && stencil_ops.iter().all(|(op, _)| is_default_stencil_op(op)))
+    {
+        writeln!(f, "  Stencil {{")?;
+        if !is(&state.stencilRef, 0.0) {
+            writeln!(f, "   Ref {}", value(&state.stencilRef, &[]))?;
+        }
+        if !is(&state.stencilReadMask, 255.0) {
+            writeln!(f, "   ReadMask {}", value(&state.stencilReadMask, &[]))?;
+        }
+        if !is(&state.stencilWriteMask, 255.0) {
+            writeln!(f, "   WriteMask {}", value(&state.stencilWriteMask, &[]))?;
+        }
+        for (op, suffix) in stencil_ops.into_iter().filter(|(op, _)| !is_default_stencil_op(op)) {
+            write_stencil_op(f, op, suffix)?;
+        }
+        writeln!(f, "  }}")?;
+    }
+
+    let fog_color = [&state.fogColor.x, &state.fogColor.y, &state.fogColor.z, &state.fogColor.w];
+    let fog_range = [&state.fogDensity, &state.fogStart, &state.fogEnd];
+    // -1 is an unknown fog mode, which means the pass doesn't set it
+    if state.fogMode != -1 || !fog_color.iter().chain(&fog_range).all(|value| is(value, 0.0)) {
+        writeln!(f, "  Fog {{")?;
+        if let Some(mode) = usize::try_from(state.fogMode).ok().and_then(|mode| FOG_MODES.get(mode)) {
+            writeln!(f, "   Mode {mode}")?;
+        }
+        if !fog_color.iter().all(|value| is(value, 0.0)) {
+            let [r, g, b, a] = fog_color.map(|component| value(component, &[]));
+            writeln!(f, "   Color ({r},{g},{b},{a})")?;
+        }
+        if !is(&state.fogDensity, 0.0) {
+            writeln!(f, "   Density {}", value(&state.fogDensity, &[]))?;
+        }
+        if !(is(&state.fogStart, 0.0) && is(&state.fogEnd, 0.0)) {
+            writeln!(f, "   Range {}, {}", value(&state.fogStart, &[]), value(&state.fogEnd, &[]))?;
+        }
+        writeln!(f, "  }}")?;
+    }
+
+    if state.lighting {
+        writeln!(f, "  Lighting On")?;
+    }
+    writeln!(f, "  GpuProgramID {}", state.gpuProgramID)
+}
+
+/// `SerializedShader` as ShaderLab, with the sources of the sub programs of each platform.
+pub(super) struct ShaderLab<'a> {
+    pub shader: &'a SerializedShader,
+    pub programs: &'a [(u32, ShaderProgram)],
+}
+
+impl ShaderLab<'_> {
+    /// The names of the keywords a sub program was compiled with.
+    fn keywords(&self, pass: &SerializedPass, sub_program: &SerializedSubProgram, blob_keywords: &[String]) -> Vec<String> {
+        let indices: Vec<u16> = match &sub_program.m_KeywordIndices {
+            Some(indices) => indices.clone(),
+            None => [&sub_program.m_GlobalKeywordIndices, &sub_program.m_LocalKeywordIndices]
+                .into_iter()
+                .flatten()
+                .flatten()
+                .copied()
+                .collect(),
+        };
+        if indices.is_empty() {
+            return blob_keywords.to_vec();
+        }
+        // since 2021.2 the names are kept by the shader, before that by each pass
+        let name = |index: u16| match &self.shader.m_KeywordNames {
+            Some(names) => names.get(index as usize).cloned(),
+            None => pass.m_NameIndices.iter().find(|(_, i)| *i == index as i32).map(|(name, _)| name.clone()),
+        };
+        indices.into_iter().filter_map(name).collect()
+    }
+
+    fn write_program(&self, f: &mut Formatter, pass: &SerializedPass, program: &SerializedProgram, name: &str) -> fmt::Result {
+        if program.m_SubPrograms.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "Program \"{name}\" {{")?;
+        // sub programs for several hardware tiers share their blob index and program type
+        for sub_program in &program.m_SubPrograms {
+            let tiered = program.m_SubPrograms.iter().any(|other| {
+                !std::ptr::eq(other, sub_program)
+                    && other.m_BlobIndex == sub_program.m_BlobIndex
+                    && other.m_GpuProgramType == sub_program.m_GpuProgramType
+            });
+            let program_type = ShaderGpuProgramType::try_from(sub_program.m_GpuProgramType as i32).ok();
+            let platform = self.programs.iter().find(|(platform, _)| {
+                let platform = ShaderCompilerPlatform::try_from(*platform).ok();
+                platform.zip(program_type).is_some_and(|(platform, program_type)| platform.supports(program_type))
+            });
+            let Some((platform, platform_program)) = platform else {
+                continue;
+            };
+            let platform_name = ShaderCompilerPlatform::try_from(*platform).map_or("unknown", |p| p.get_name());
+            let compiled = platform_program.sub_programs.get(sub_program.m_BlobIndex as usize);
+
+            write!(f, "SubProgram \"{platform_name} ")?;
+            if tiered {
+                write!(f, "hw_tier{:02} ", sub_program.m_ShaderHardwareTier)?;
+            }
+            writeln!(f, "\" {{")?;
+            let blob_keywords: Vec<String> = compiled
+                .map(|compiled| compiled.keywords.iter().chain(&compiled.local_keywords).cloned().collect())
+                .unwrap_or_default();
+            let keywords = self.keywords(pass, sub_program, &blob_keywords);
+            if !keywords.is_empty() {
+                write!(f, "Keywords {{")?;
+                for keyword in keywords {
+                    write!(f, " \"{keyword}\"")?;
+                }
+                writeln!(f, " }}")?;
+            }
+            match compiled {
+                Some(compiled) => writeln!(f, "\"{}\"", compiled.get_source())?,
+                None => writeln!(f, "// sub program {} is missing", sub_program.m_BlobIndex)?,
+            }
+            writeln!(f, "}}")?;
+        }
+        writeln!(f, "}}")
+    }
+
+    fn write_pass(&self, f: &mut Formatter, pass: &SerializedPass) -> fmt::Result {
+        match pass.m_Type {
+            1 => return writeln!(f, " UsePass \"{}\"", pass.m_UseName),
+            2 => {
+                writeln!(f, " GrabPass {{")?;
+                if !pass.m_TextureName.is_empty() {
+                    writeln!(f, "  \"{}\"", pass.m_TextureName)?;
+                }
+            }
+            _ => {
+                writeln!(f, " Pass {{")?;
+                write_tags(f, &pass.m_Tags, "  ")?;
+                write_state(f, &pass.m_State)?;
+                let mut programs = vec![
+                    (&pass.progVertex, "vp"),
+                    (&pass.progFragment, "fp"),
+                    (&pass.progGeometry, "gp"),
+                    (&pass.progHull, "hp"),
+                    (&pass.progDomain, "dp"),
+                ];
+                if let Some(ray_tracing) = &pass.progRayTracing {
+                    programs.push((ray_tracing, "rtp"));
+                }
+                for (program, name) in programs {
+                    self.write_program(f, pass, program, name)?;
+                }
+            }
+        }
+        writeln!(f, " }}")
+    }
+}
+
+impl Display for ShaderLab<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let shader = self.shader;
+        writeln!(f, "Shader \"{}\" {{", shader.m_Name)?;
+        if !shader.m_PropInfo.m_Props.is_empty() {
+            writeln!(f, "Properties {{")?;
+            for property in &shader.m_PropInfo.m_Props {
+                write_property(f, property)?;
+            }
+            writeln!(f, "}}")?;
+        }
+        for sub_shader in &shader.m_SubShaders {
+            writeln!(f, "SubShader {{")?;
+            if sub_shader.m_LOD != 0 {
+                writeln!(f, " LOD {}", sub_shader.m_LOD)?;
+            }
+            write_tags(f, &sub_shader.m_Tags, " ")?;
+            for pass in &sub_shader.m_Passes {
+                self.write_pass(f, pass)?;
+            }
+            writeln!(f, "}}")?;
+        }
+        if !shader.m_FallbackName.is_empty() {
+            writeln!(f, "Fallback \"{}\"", shader.m_FallbackName)?;
+        }
+        if !shader.m_CustomEditorName.is_empty() {
+            writeln!(f, "CustomEditor \"{}\"", shader.m_CustomEditorName)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn float(val: f32, name: &str) -> SerializedShaderFloatValue {
+        SerializedShaderFloatValue { name: Enum_FastPropertyName__String::String(name.to_string()), val }
+    }
+
+    #[test]
+    fn state_values() {
+        assert_eq!(value(&float(4.0, ""), COMPARE_FUNCTIONS), "LEqual");
+        assert_eq!(value(&float(1.5, ""), COMPARE_FUNCTIONS), "1.5");
+        assert_eq!(value(&float(20.0, ""), BLEND_FACTORS), "20");
+        assert_eq!(value(&float(4.0, "_ZTest"), COMPARE_FUNCTIONS), "[_ZTest]");
+        assert!(is(&float(4.0, ""), L_EQUAL));
+        // a state set by a property is written out even if its value is the default
+        assert!(!is(&float(4.0, "_ZTest"), L_EQUAL));
+    }
+}
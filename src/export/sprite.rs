@@ -0,0 +1,295 @@
+// Cutting sprites out of their texture or the texture of their sprite atlas
+use super::texture::{RgbaImage, Texture2DExt};
+use crate::environment::Environment;
+use crate::objects::classes::{Rectf, Sprite, SpriteAtlas, SpriteAtlasData, Texture2D, Vector2f, GUID};
+use crate::objects::PPtr;
+use crate::Error;
+
+/// How a sprite was packed, which `settingsRaw` keeps as bit fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpriteSettings {
+    pub packed: bool,
+    pub packing_mode: SpritePackingMode,
+    pub packing_rotation: SpritePackingRotation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpritePackingMode {
+    /// The sprite was packed by the shape of its mesh.
+    Tight,
+    Rectangle,
+}
+
+/// How a sprite was turned when it was packed, which is undone when cutting it out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpritePackingRotation {
+    None,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate180,
+    Rotate90,
+}
+
+impl From<u32> for SpriteSettings {
+    fn from(settings_raw: u32) -> Self {
+        SpriteSettings {
+            packed: settings_raw & 1 != 0,
+            packing_mode: match (settings_raw >> 1) & 1 {
+                0 => SpritePackingMode::Tight,
+                _ => SpritePackingMode::Rectangle,
+            },
+            packing_rotation: match (settings_raw >> 2) & 0xf {
+                1 => SpritePackingRotation::FlipHorizontal,
+                2 => SpritePackingRotation::FlipVertical,
+                3 => SpritePackingRotation::Rotate180,
+                4 => SpritePackingRotation::Rotate90,
+                _ => SpritePackingRotation::None,
+            },
+        }
+    }
+}
+
+/// Where a sprite is in its texture, from its own render data or that of its atlas.
+#[derive(Debug, Clone, Copy)]
+pub struct SpriteTextureInfo<'a> {
+    pub texture: &'a PPtr,
+    /// The area of the sprite in the texture, from the bottom left.
+    pub texture_rect: &'a Rectf,
+    pub texture_rect_offset: &'a Vector2f,
+    pub settings: SpriteSettings,
+}
+
+/// Copies the pixels of `rect`, which counts from the bottom left, rounded out to whole pixels.
+fn crop(image: &RgbaImage, rect: &Rectf) -> RgbaImage {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let left = (rect.x.floor().max(0.0) as usize).min(width);
+    let bottom = (rect.y.floor().max(0.0) as usize).min(height);
+    let right = ((rect.x + rect.width).ceil().max(0.0) as usize).clamp(left, width);
+    let top = ((rect.y + rect.height).ceil().max(0.0) as usize).clamp(bottom, height);
+
+    let mut data = Vec::with_capacity((right - left) * (top - bottom) * 4);
+    for row in height - top..height - bottom {
+        data.extend_from_slice(&image.data[(row * width + left) * 4..(row * width + right) * 4]);
+    }
+    RgbaImage { width: (right - left) as u32, height: (top - bottom) as u32, data }
+}
+
+/// Undoes the rotation or flip the sprite was packed with.
+fn unrotate(image: RgbaImage, rotation: SpritePackingRotation) -> RgbaImage {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let (new_width, new_height) = match rotation {
+        SpritePackingRotation::None => return image,
+        SpritePackingRotation::Rotate90 => (height, width),
+        _ => (width, height),
+    };
+    let mut data = Vec::with_capacity(image.data.len());
+    for y in 0..new_height {
+        for x in 0..new_width {
+            let (x, y) = match rotation {
+                SpritePackingRotation::None => (x, y),
+                SpritePackingRotation::FlipHorizontal => (width - 1 - x, y),
+                SpritePackingRotation::FlipVertical => (x, height - 1 - y),
+                SpritePackingRotation::Rotate180 => (width - 1 - x, height - 1 - y),
+                // turned a quarter counterclockwise, so it's turned back clockwise
+                SpritePackingRotation::Rotate90 => (y, height - 1 - x),
+            };
+            data.extend_from_slice(&image.data[(y * width + x) * 4..][..4]);
+        }
+    }
+    RgbaImage { width: new_width as u32, height: new_height as u32, data }
+}
+
+fn in_triangle(point: [f32; 2], [a, b, c]: [[f32; 2]; 3]) -> bool {
+    let side = |p: [f32; 2], q: [f32; 2]| (q[0] - p[0]) * (point[1] - p[1]) - (q[1] - p[1]) * (point[0] - p[0]);
+    let sides = [side(a, b), side(b, c), side(c, a)];
+    sides.iter().all(|&s| s >= 0.0) || sides.iter().all(|&s| s <= 0.0)
+}
+
+/// Clears the pixels whose center is outside every triangle, which are in pixels from the bottom left.
+fn mask(image: &mut RgbaImage, triangles: &[[[f32; 2]; 3]]) {
+    let (width, height) = (image.width as usize, image.height as usize);
+    for (i, pixel) in image.data.chunks_exact_mut(4).enumerate() {
+        let center = [(i % width) as f32 + 0.5, (height - i / width) as f32 - 0.5];
+        if !triangles.iter().any(|&triangle| in_triangle(center, triangle)) {
+            pixel.fill(0);
+        }
+    }
+}
+
+fn same_guid(a: &GUID, b: &GUID) -> bool {
+    [a.data_0_, a.data_1_, a.data_2_, a.data_3_] == [b.data_0_, b.data_1_, b.data_2_, b.data_3_]
+}
+
+/// Reads and decodes the texture `pointer` refers to from the serialized file `from`.
+fn decode_texture(env: &mut Environment, from: &str, pointer: &PPtr) -> Result<RgbaImage, Error> {
+    let path = env.resolve_file_id(from, pointer.m_FileID)?.get_path().to_owned();
+    let texture: Texture2D = env.get_object_reader(from, pointer.m_FileID, pointer.m_PathID)?.read()?.parse()?;
+    let image_data = match texture.get_image_data_range() {
+        Some(range) => range.read_from_environment(env)?,
+        None => texture.image_data.clone().unwrap_or_default(),
+    };
+    texture.decode_image(&image_data, &env.resolve_file_id(&path, 0)?.file)
+}
+
+pub trait SpriteExt {
+    /// Where the sprite is in its texture. Sprites packed into `atlas` are looked up by their `m_RenderDataKey`.
+    fn get_texture_info<'a>(&'a self, atlas: Option<&'a SpriteAtlas>) -> SpriteTextureInfo<'a>;
+
+    /// Cuts the sprite out of `texture`, the decoded texture of [`SpriteExt::get_texture_info`].
+    ///
+    /// With `tight`, sprites that were packed by their shape have the pixels outside of their mesh cleared.
+    /// `file` is the file the sprite was read from, whose Unity version selects the layout of the mesh.
+    fn crop_sprite(
+        &self,
+        texture: &RgbaImage,
+        atlas: Option<&SpriteAtlas>,
+        file: &crate::files::SerializedFile,
+        tight: bool,
+    ) -> Result<RgbaImage, Error>;
+
+    /// Looks up the atlas and texture of a sprite read from the serialized file `from`, and cuts it out of the texture.
+    fn decode_sprite(&self, env: &mut Environment, from: &str, tight: bool) -> Result<RgbaImage, Error>;
+}
+
+/// The render data of `sprite` in `atlas`, which is keyed by its `m_RenderDataKey`.
+fn find_atlas_data<'a>(sprite: &Sprite, atlas: &'a SpriteAtlas) -> Option<&'a SpriteAtlasData> {
+    let (guid, id) = sprite.m_RenderDataKey.as_ref()?;
+    atlas
+        .m_RenderDataMap
+        .iter()
+        .find(|((key_guid, key_id), _)| key_id == id && same_guid(key_guid, guid))
+        .map(|(_, data)| data)
+}
+
+impl SpriteExt for Sprite {
+    fn get_texture_info<'a>(&'a self, atlas: Option<&'a SpriteAtlas>) -> SpriteTextureInfo<'a> {
+        let atlas_data = atlas.and_then(|atlas| find_atlas_data(self, atlas));
+        match atlas_data {
+            Some(data) => SpriteTextureInfo {
+                texture: &data.texture,
+                texture_rect: &data.textureRect,
+                texture_rect_offset: &data.textureRectOffset,
+                settings: data.settingsRaw.into(),
+            },
+            None => SpriteTextureInfo {
+                texture: &self.m_RD.texture,
+                texture_rect: &self.m_RD.textureRect,
+                texture_rect_offset: &self.m_RD.textureRectOffset,
+                settings: self.m_RD.settingsRaw.into(),
+            },
+        }
+    }
+
+    fn crop_sprite(
+        &self,
+        texture: &RgbaImage,
+        atlas: Option<&SpriteAtlas>,
+        file: &crate::files::SerializedFile,
+        tight: bool,
+    ) -> Result<RgbaImage, Error> {
+        let info = self.get_texture_info(atlas);
+        let mut image = crop(texture, info.texture_rect);
+        if info.settings.packed {
+            image = unrotate(image, info.settings.packing_rotation);
+        }
+
+        if tight && info.settings.packing_mode == SpritePackingMode::Tight {
+            let mesh = super::mesh::decode_sprite_mesh(&self.m_RD, file)?;
+            // the mesh is in units around the pivot
+            let pivot = self.m_Pivot.as_ref().map_or([0.5, 0.5], |pivot| [pivot.x, pivot.y]);
+            let origin = [
+                self.m_Rect.width * pivot[0] - info.texture_rect_offset.x,
+                self.m_Rect.height * pivot[1] - info.texture_rect_offset.y,
+            ];
+            let to_pixels = |index: u32| {
+                let [x, y, _] = mesh.positions[index as usize];
+                [x * self.m_PixelsToUnits + origin[0], y * self.m_PixelsToUnits + origin[1]]
+            };
+            let triangles: Vec<[[f32; 2]; 3]> = mesh
+                .sub_meshes
+                .iter()
+                .flat_map(|sub_mesh| sub_mesh.indices.chunks_exact(3))
+                .map(|triangle| [to_pixels(triangle[0]), to_pixels(triangle[1]), to_pixels(triangle[2])])
+                .collect();
+            if !triangles.is_empty() {
+                mask(&mut image, &triangles);
+            }
+        }
+        Ok(image)
+    }
+
+    fn decode_sprite(&self, env: &mut Environment, from: &str, tight: bool) -> Result<RgbaImage, Error> {
+        let atlas = match self.m_SpriteAtlas.as_ref().filter(|atlas| atlas.m_PathID != 0) {
+            Some(pointer) => {
+                let path = env.resolve_file_id(from, pointer.m_FileID)?.get_path().to_owned();
+                let atlas: SpriteAtlas = env.get_object_reader(from, pointer.m_FileID, pointer.m_PathID)?.read()?.parse()?;
+                Some((path, atlas))
+            }
+            None => None,
+        };
+        // the texture of an atlas is referred to from the file of the atlas
+        let atlas_ref = atlas.as_ref().map(|(_, atlas)| atlas);
+        let texture_from = match &atlas {
+            Some((path, atlas)) if find_atlas_data(self, atlas).is_some() => path.as_str(),
+            _ => from,
+        };
+        let texture = decode_texture(env, texture_from, self.get_texture_info(atlas_ref).texture)?;
+        self.crop_sprite(&texture, atlas_ref, &env.resolve_file_id(from, 0)?.file, tight)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image whose pixels are numbered in their red channel, from the top left.
+    fn numbered(width: u32, height: u32) -> RgbaImage {
+        let data = (0..width * height).flat_map(|i| [i as u8, 0, 0, 255]).collect();
+        RgbaImage { width, height, data }
+    }
+
+    fn reds(image: &RgbaImage) -> Vec<u8> {
+        image.data.chunks_exact(4).map(|pixel| pixel[0]).collect()
+    }
+
+    #[test]
+    fn settings() {
+        let settings = SpriteSettings::from(1 | 1 << 1 | 4 << 2 | 1 << 6);
+        assert!(settings.packed);
+        assert_eq!(settings.packing_mode, SpritePackingMode::Rectangle);
+        assert_eq!(settings.packing_rotation, SpritePackingRotation::Rotate90);
+        assert_eq!(SpriteSettings::from(0).packing_mode, SpritePackingMode::Tight);
+    }
+
+    #[test]
+    fn crop_from_bottom() {
+        // the bottom right 2x2 pixels, with the rect rounded out
+        let rect = Rectf { x: 2.5, y: 0.0, width: 1.0, height: 1.5 };
+        let image = crop(&numbered(4, 3), &rect);
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(reds(&image), [6, 7, 10, 11]);
+        let outside = Rectf { x: 3.0, y: 2.0, width: 5.0, height: 5.0 };
+        assert_eq!(reds(&crop(&numbered(4, 3), &outside)), [3]);
+    }
+
+    #[test]
+    fn rotations() {
+        use SpritePackingRotation::*;
+        assert_eq!(reds(&unrotate(numbered(3, 2), FlipHorizontal)), [2, 1, 0, 5, 4, 3]);
+        assert_eq!(reds(&unrotate(numbered(3, 2), FlipVertical)), [3, 4, 5, 0, 1, 2]);
+        assert_eq!(reds(&unrotate(numbered(3, 2), Rotate180)), [5, 4, 3, 2, 1, 0]);
+        let rotated = unrotate(numbered(3, 2), Rotate90);
+        assert_eq!((rotated.width, rotated.height), (2, 3));
+        assert_eq!(reds(&rotated), [3, 0, 4, 1, 5, 2]);
+    }
+
+    #[test]
+    fn tight_mask() {
+        // a triangle over the bottom left half of a 2x2 image
+        let mut image = numbered(2, 2);
+        mask(&mut image, &[[[0.0, 0.0], [2.0, 0.0], [0.0, 2.0]]]);
+        assert_eq!(reds(&image), [0, 0, 2, 3]);
+        assert_eq!(image.data[4..8], [0, 0, 0, 0]);
+        assert_eq!(image.data[8..12], [2, 0, 0, 255]);
+    }
+}
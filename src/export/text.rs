@@ -0,0 +1,155 @@
+// TextAsset export with the extension guessed from the content, and MonoScript export as C# stubs
+use crate::objects::classes::{MonoScript, TextAsset};
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// What a `TextAsset` holds, guessed from its content.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TextAssetFormat {
+    Text,
+    Json,
+    Xml,
+    LuaBytecode,
+    /// Binary data, which Unity imports from `.bytes` files.
+    Bytes,
+}
+
+impl TextAssetFormat {
+    pub fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"\x1bLua") {
+            return TextAssetFormat::LuaBytecode;
+        }
+        let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+        let Ok(text) = std::str::from_utf8(data) else {
+            return TextAssetFormat::Bytes;
+        };
+        // text that is mostly control characters is binary data that happens to be valid UTF-8
+        let control = text.chars().filter(|c| c.is_control() && !c.is_whitespace()).count();
+        if control * 10 > text.chars().count() {
+            return TextAssetFormat::Bytes;
+        }
+        let text = text.trim_start();
+        if text.starts_with('{') || text.starts_with('[') {
+            TextAssetFormat::Json
+        } else if text.starts_with("<?xml") || (text.starts_with('<') && text.trim_end().ends_with('>')) {
+            TextAssetFormat::Xml
+        } else {
+            TextAssetFormat::Text
+        }
+    }
+
+    pub fn get_extension(&self) -> &'static str {
+        match self {
+            TextAssetFormat::Text => "txt",
+            TextAssetFormat::Json => "json",
+            TextAssetFormat::Xml => "xml",
+            TextAssetFormat::LuaBytecode => "luac",
+            TextAssetFormat::Bytes => "bytes",
+        }
+    }
+}
+
+/// The script of a `TextAsset` as a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextAssetFile {
+    pub name: String,
+    pub format: TextAssetFormat,
+    pub data: Vec<u8>,
+}
+
+impl TextAssetFile {
+    /// The name of the asset with the extension of its format.
+    pub fn get_file_name(&self) -> String {
+        format!("{}.{}", self.name, self.format.get_extension())
+    }
+}
+
+pub trait TextAssetExt {
+    /// Exports the script as it's stored, with the format guessed from its content.
+    fn export_text(&self) -> TextAssetFile;
+}
+
+impl TextAssetExt for TextAsset {
+    fn export_text(&self) -> TextAssetFile {
+        let data = self.m_Script.as_bytes().to_vec();
+        TextAssetFile { name: self.m_Name.clone(), format: TextAssetFormat::detect(&data), data }
+    }
+}
+
+pub trait MonoScriptExt {
+    /// The full name of the class, as `namespace.class`.
+    fn get_full_name(&self) -> String;
+
+    /// Renders a C# stub declaring the class in its namespace, named by [`MonoScriptExt::get_full_name`].
+    fn export_stub(&self) -> String;
+}
+
+impl MonoScriptExt for MonoScript {
+    fn get_full_name(&self) -> String {
+        match self.m_Namespace.as_str() {
+            "" => self.m_ClassName.clone(),
+            namespace => format!("{namespace}.{}", self.m_ClassName),
+        }
+    }
+
+    fn export_stub(&self) -> String {
+        let mut stub = format!("// {}\n", self.m_AssemblyName);
+        let indent = if self.m_Namespace.is_empty() { "" } else { "    " };
+        if !self.m_Namespace.is_empty() {
+            stub.push_str(&format!("namespace {}\n{{\n", self.m_Namespace));
+        }
+        stub.push_str(&format!("{indent}public class {}\n{indent}{{\n{indent}}}\n", self.m_ClassName));
+        if !self.m_Namespace.is_empty() {
+            stub.push_str("}\n");
+        }
+        stub
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objects::classes::Enum_u32__Hash128;
+
+    #[test]
+    fn formats() {
+        assert_eq!(TextAssetFormat::detect(b"hello\r\nworld"), TextAssetFormat::Text);
+        assert_eq!(TextAssetFormat::detect(b"\xef\xbb\xbf  {\"a\": 1}"), TextAssetFormat::Json);
+        assert_eq!(TextAssetFormat::detect(b"<?xml version=\"1.0\"?><a/>"), TextAssetFormat::Xml);
+        assert_eq!(TextAssetFormat::detect(b"<a>\n</a>\n"), TextAssetFormat::Xml);
+        assert_eq!(TextAssetFormat::detect(b"\x1bLuaS\x00"), TextAssetFormat::LuaBytecode);
+        assert_eq!(TextAssetFormat::detect(b"\x08\x96\x01\xff"), TextAssetFormat::Bytes);
+        assert_eq!(TextAssetFormat::detect(b"\x08\x01\x12\x02ab"), TextAssetFormat::Bytes);
+        assert_eq!(TextAssetFormat::detect(b""), TextAssetFormat::Text);
+    }
+
+    #[test]
+    fn binary_script() {
+        let text_asset = TextAsset { m_Name: "table".to_owned(), m_Script: vec![0x08, 0xff].into(), m_PathName: None };
+        let file = text_asset.export_text();
+        assert_eq!(file.get_file_name(), "table.bytes");
+        assert_eq!(file.data, [0x08, 0xff]);
+    }
+
+    #[test]
+    fn stubs() {
+        let mut script = MonoScript {
+            m_AssemblyName: "Assembly-CSharp.dll".to_owned(),
+            m_ClassName: "Player".to_owned(),
+            m_ExecutionOrder: 0,
+            m_Name: "Player".to_owned(),
+            m_Namespace: "Game.Actors".to_owned(),
+            m_PropertiesHash: Enum_u32__Hash128::u32(0),
+            m_IsEditorScript: None,
+        };
+        assert_eq!(script.get_full_name(), "Game.Actors.Player");
+        assert_eq!(
+            script.export_stub(),
+            "// Assembly-CSharp.dll\nnamespace Game.Actors\n{\n    public class Player\n    {\n    }\n}\n"
+        );
+
+        script.m_Namespace.clear();
+        assert_eq!(script.get_full_name(), "Player");
+        assert_eq!(script.export_stub(), "// Assembly-CSharp.dll\npublic class Player\n{\n}\n");
+    }
+}
@@ -0,0 +1,648 @@
+// ASTC block decoder for 2D blocks, following the Khronos data format specification
+use super::{decode_blocks, f32_to_u8, half_to_f32};
+use crate::Error;
+
+/// The color the specification mandates for blocks that can't be decoded.
+const ERROR_COLOR: [u8; 4] = [255, 0, 255, 255];
+
+/// Quantization levels of the weights, indexed by the 4 bit weight range.
+const WEIGHT_LEVELS: [u32; 16] = [0, 0, 2, 3, 4, 5, 6, 8, 0, 0, 10, 12, 16, 20, 24, 32];
+
+/// Quantization levels of the endpoints, from the finest to the coarsest.
+const ENDPOINT_LEVELS: [u32; 17] = [
+    256, 192, 160, 128, 96, 80, 64, 48, 40, 32, 24, 20, 16, 12, 10, 8, 6,
+];
+
+/// How values with `levels` quantization levels are encoded, as (bits, trits, quints).
+fn encoding(levels: u32) -> (u32, bool, bool) {
+    if levels.is_multiple_of(3) {
+        ((levels / 3).trailing_zeros(), true, false)
+    } else if levels.is_multiple_of(5) {
+        ((levels / 5).trailing_zeros(), false, true)
+    } else {
+        (levels.trailing_zeros(), false, false)
+    }
+}
+
+/// Number of bits `count` values with `levels` quantization levels take.
+fn sequence_bits(count: u32, levels: u32) -> u32 {
+    match encoding(levels) {
+        (bits, true, _) => count * bits + (count * 8).div_ceil(5),
+        (bits, _, true) => count * bits + (count * 7).div_ceil(3),
+        (bits, _, _) => count * bits,
+    }
+}
+
+fn get_bits(block: u128, start: u32, count: u32) -> u32 {
+    if count == 0 || start >= 128 {
+        0
+    } else {
+        ((block >> start) & ((1u128 << count) - 1)) as u32
+    }
+}
+
+fn decode_trits(t: u32) -> [u32; 5] {
+    let bit = |value: u32, i: u32| (value >> i) & 1;
+    let (c, t4, t3);
+    if (t >> 2) & 7 == 7 {
+        c = (((t >> 5) & 7) << 2) | (t & 3);
+        t4 = 2;
+        t3 = 2;
+    } else {
+        c = t & 0x1f;
+        if (t >> 5) & 3 == 3 {
+            t4 = 2;
+            t3 = bit(t, 7);
+        } else {
+            t4 = bit(t, 7);
+            t3 = (t >> 5) & 3;
+        }
+    }
+    let (t2, t1, t0);
+    if c & 3 == 3 {
+        t2 = 2;
+        t1 = bit(c, 4);
+        t0 = (bit(c, 3) << 1) | (bit(c, 2) & !bit(c, 3) & 1);
+    } else if (c >> 2) & 3 == 3 {
+        t2 = 2;
+        t1 = 2;
+        t0 = c & 3;
+    } else {
+        t2 = bit(c, 4);
+        t1 = (c >> 2) & 3;
+        t0 = (bit(c, 1) << 1) | (bit(c, 0) & !bit(c, 1) & 1);
+    }
+    [t0, t1, t2, t3, t4]
+}
+
+fn decode_quints(q: u32) -> [u32; 3] {
+    let bit = |value: u32, i: u32| (value >> i) & 1;
+    if (q >> 1) & 3 == 3 && (q >> 5) & 3 == 0 {
+        let q2 = (bit(q, 0) << 2) | ((bit(q, 4) & !bit(q, 0) & 1) << 1) | (bit(q, 3) & !bit(q, 0) & 1);
+        return [4, 4, q2];
+    }
+    let (q2, c) = if (q >> 1) & 3 == 3 {
+        (4, (((q >> 3) & 3) << 3) | ((!(q >> 5) & 3) << 1) | (q & 1))
+    } else {
+        ((q >> 5) & 3, q & 0x1f)
+    };
+    if c & 7 == 5 {
+        [(c >> 3) & 3, 4, q2]
+    } else {
+        [c & 7, (c >> 3) & 3, q2]
+    }
+}
+
+/// Reads `count` integer sequence encoded values as (trit or quint, low bits) pairs.
+fn decode_sequence(block: u128, start: u32, count: usize, levels: u32) -> Vec<(u32, u32)> {
+    let (bits, trits, quints) = encoding(levels);
+    let mut values = Vec::with_capacity(count + 4);
+    let mut position = start;
+    let mut read = |count: u32| {
+        let value = get_bits(block, position, count);
+        position += count;
+        value
+    };
+
+    while values.len() < count {
+        if trits {
+            // each of the 5 values is followed by some bits of the packed trits
+            let mut low = [0; 5];
+            let mut packed = 0;
+            for (i, shift) in [(0, 0), (1, 2), (2, 4), (3, 5), (4, 7)] {
+                low[i] = read(bits);
+                let size = [2, 2, 1, 2, 1][i];
+                packed |= read(size) << shift;
+            }
+            values.extend(decode_trits(packed).into_iter().zip(low));
+        } else if quints {
+            let mut low = [0; 3];
+            let mut packed = 0;
+            for (i, shift) in [(0, 0), (1, 3), (2, 5)] {
+                low[i] = read(bits);
+                let size = [3, 2, 2][i];
+                packed |= read(size) << shift;
+            }
+            values.extend(decode_quints(packed).into_iter().zip(low));
+        } else {
+            values.push((0, read(bits)));
+        }
+    }
+    values.truncate(count);
+    values
+}
+
+/// Unquantizes a color endpoint value to 8 bits.
+fn unquantize_endpoint((d, low): (u32, u32), levels: u32) -> u32 {
+    let (bits, trits, quints) = encoding(levels);
+    if !trits && !quints {
+        // replicate the bits to fill 8 bits
+        let mut value = 0;
+        let mut filled = 0;
+        while filled < 8 {
+            value = (value << bits) | low;
+            filled += bits;
+        }
+        return value >> (filled - 8);
+    }
+
+    let bit = |i: u32| (low >> i) & 1;
+    let a = if low & 1 == 1 { 0x1ff } else { 0 };
+    let (b, c) = match (trits, bits) {
+        (true, 1) => (0, 204),
+        (true, 2) => (bit(1) * 0x116, 93),
+        (true, 3) => (bit(2) * 0x10a + bit(1) * 0x85, 44),
+        (true, 4) => (bit(3) * 0x104 + bit(2) * 0x82 + bit(1) * 0x41, 22),
+        (true, 5) => (bit(4) * 0x102 + bit(3) * 0x81 + bit(2) * 0x40 + bit(1) * 0x20, 11),
+        (true, _) => (bit(5) * 0x101 + bit(4) * 0x80 + bit(3) * 0x40 + bit(2) * 0x20 + bit(1) * 0x10, 5),
+        (_, 1) => (0, 113),
+        (_, 2) => (bit(1) * 0x10c, 54),
+        (_, 3) => (bit(2) * 0x105 + bit(1) * 0x82, 26),
+        (_, 4) => (bit(3) * 0x102 + bit(2) * 0x81 + bit(1) * 0x40, 13),
+        (_, _) => (bit(4) * 0x101 + bit(3) * 0x80 + bit(2) * 0x40 + bit(1) * 0x20, 6),
+    };
+    let t = (d * c + b) ^ a;
+    (a & 0x80) | (t >> 2)
+}
+
+/// Unquantizes a weight to the range 0..=64.
+fn unquantize_weight((d, low): (u32, u32), levels: u32) -> u32 {
+    let (bits, trits, quints) = encoding(levels);
+    let value = if !trits && !quints {
+        let mut value = 0;
+        let mut filled = 0;
+        while filled < 6 {
+            value = (value << bits) | low;
+            filled += bits;
+        }
+        value >> (filled - 6)
+    } else if bits == 0 {
+        if trits {
+            [0, 32, 63][d as usize]
+        } else {
+            [0, 16, 32, 47, 63][d as usize]
+        }
+    } else {
+        let bit = |i: u32| (low >> i) & 1;
+        let a = if low & 1 == 1 { 0x7f } else { 0 };
+        let (b, c) = match (trits, bits) {
+            (true, 1) => (0, 50),
+            (true, 2) => (bit(1) * 0x45, 23),
+            (true, _) => (bit(2) * 0x42 + bit(1) * 0x21, 11),
+            (_, 1) => (0, 28),
+            (_, _) => (bit(1) * 0x43, 13),
+        };
+        let t = (d * c + b) ^ a;
+        (a & 0x20) | (t >> 2)
+    };
+    if value > 32 {
+        value + 1
+    } else {
+        value
+    }
+}
+
+fn hash52(mut p: u32) -> u32 {
+    p ^= p >> 15;
+    p = p.wrapping_sub(p << 17);
+    p = p.wrapping_add(p << 7);
+    p = p.wrapping_add(p << 4);
+    p ^= p >> 5;
+    p = p.wrapping_add(p << 16);
+    p ^= p >> 7;
+    p ^= p >> 3;
+    p ^= p << 6;
+    p ^= p >> 17;
+    p
+}
+
+/// The partition a texel of a block belongs to.
+fn select_partition(seed: u32, x: u32, y: u32, partitions: u32, small_block: bool) -> usize {
+    let (x, y) = if small_block { (x << 1, y << 1) } else { (x, y) };
+    let seed = seed + (partitions - 1) * 1024;
+    let rnum = hash52(seed);
+    let mut seeds = [0u32; 8];
+    for (i, value) in seeds.iter_mut().enumerate() {
+        let nibble = (rnum >> (i * 4)) & 0xf;
+        *value = nibble * nibble;
+    }
+    let (sh1, sh2) = if seed & 1 == 1 {
+        (if seed & 2 == 2 { 4 } else { 5 }, if partitions == 3 { 6 } else { 5 })
+    } else {
+        (if partitions == 3 { 6 } else { 5 }, if seed & 2 == 2 { 4 } else { 5 })
+    };
+    for (i, value) in seeds.iter_mut().enumerate() {
+        *value >>= if i % 2 == 0 { sh1 } else { sh2 };
+    }
+
+    // z is always 0 for 2D blocks, so seeds 9 to 12 don't contribute
+    let a = (seeds[0] * x + seeds[1] * y + (rnum >> 14)) & 0x3f;
+    let b = (seeds[2] * x + seeds[3] * y + (rnum >> 10)) & 0x3f;
+    let c = if partitions >= 3 { (seeds[4] * x + seeds[5] * y + (rnum >> 6)) & 0x3f } else { 0 };
+    let d = if partitions >= 4 { (seeds[6] * x + seeds[7] * y + (rnum >> 2)) & 0x3f } else { 0 };
+
+    if a >= b && a >= c && a >= d {
+        0
+    } else if b >= c && b >= d {
+        1
+    } else if c >= d {
+        2
+    } else {
+        3
+    }
+}
+
+fn bit_transfer_signed(a: &mut i32, b: &mut i32) {
+    *b = (*b >> 1) | (*a & 0x80);
+    *a = (*a >> 1) & 0x3f;
+    if *a & 0x20 != 0 {
+        *a -= 0x40;
+    }
+}
+
+fn blue_contract(r: i32, g: i32, b: i32, a: i32) -> [i32; 4] {
+    [(r + b) >> 1, (g + b) >> 1, b, a]
+}
+
+/// Decodes the two LDR endpoints of a color endpoint mode, or `None` for HDR modes.
+fn decode_endpoints(mode: u32, v: &[i32]) -> Option<[[u8; 4]; 2]> {
+    let mut v = v.to_vec();
+    let (e0, e1) = match mode {
+        0 => ([v[0], v[0], v[0], 255], [v[1], v[1], v[1], 255]),
+        1 => {
+            let l0 = (v[0] >> 2) | (v[1] & 0xc0);
+            let l1 = (l0 + (v[1] & 0x3f)).min(255);
+            ([l0, l0, l0, 255], [l1, l1, l1, 255])
+        }
+        4 => ([v[0], v[0], v[0], v[2]], [v[1], v[1], v[1], v[3]]),
+        5 => {
+            let (mut v0, mut v1, mut v2, mut v3) = (v[0], v[1], v[2], v[3]);
+            bit_transfer_signed(&mut v1, &mut v0);
+            bit_transfer_signed(&mut v3, &mut v2);
+            let l1 = v0 + v1;
+            ([v0, v0, v0, v2], [l1, l1, l1, v2 + v3])
+        }
+        6 => (
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, 255],
+            [v[0], v[1], v[2], 255],
+        ),
+        8 | 12 => {
+            let (a0, a1) = if mode == 12 { (v[6], v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= v[0] + v[2] + v[4] {
+                ([v[0], v[2], v[4], a0], [v[1], v[3], v[5], a1])
+            } else {
+                (blue_contract(v[1], v[3], v[5], a1), blue_contract(v[0], v[2], v[4], a0))
+            }
+        }
+        9 | 13 => {
+            let pairs = if mode == 13 { 4 } else { 3 };
+            for i in 0..pairs {
+                let (low, high) = v.split_at_mut(i * 2 + 1);
+                bit_transfer_signed(&mut high[0], &mut low[i * 2]);
+            }
+            let (a0, a1) = if mode == 13 { (v[6], v[6] + v[7]) } else { (255, 255) };
+            if v[1] + v[3] + v[5] >= 0 {
+                ([v[0], v[2], v[4], a0], [v[0] + v[1], v[2] + v[3], v[4] + v[5], a1])
+            } else {
+                (
+                    blue_contract(v[0] + v[1], v[2] + v[3], v[4] + v[5], a1),
+                    blue_contract(v[0], v[2], v[4], a0),
+                )
+            }
+        }
+        10 => (
+            [(v[0] * v[3]) >> 8, (v[1] * v[3]) >> 8, (v[2] * v[3]) >> 8, v[4]],
+            [v[0], v[1], v[2], v[5]],
+        ),
+        _ => return None,
+    };
+    let clamp = |e: [i32; 4]| e.map(|c| c.clamp(0, 255) as u8);
+    Some([clamp(e0), clamp(e1)])
+}
+
+/// Decodes a void extent block, which has a single color.
+fn decode_void_extent(block: u128) -> [u8; 4] {
+    let hdr = (block >> 9) & 1 == 1;
+    let channel = |i: u32| {
+        let value = get_bits(block, 64 + i * 16, 16) as u16;
+        if hdr {
+            f32_to_u8(half_to_f32(value))
+        } else {
+            (value >> 8) as u8
+        }
+    };
+    [channel(0), channel(1), channel(2), channel(3)]
+}
+
+struct BlockMode {
+    grid_width: u32,
+    grid_height: u32,
+    dual_plane: bool,
+    weight_levels: u32,
+}
+
+fn decode_block_mode(block: u128) -> Option<BlockMode> {
+    let mode = get_bits(block, 0, 11);
+    let bit = |i: u32| (mode >> i) & 1;
+    let a = (mode >> 5) & 3;
+    let b = (mode >> 7) & 3;
+    let mut dual_plane = bit(10) == 1;
+    let mut high_precision = bit(9);
+
+    let (range, grid_width, grid_height) = if mode & 3 != 0 {
+        let range = (bit(4)) | ((mode & 3) << 1);
+        let (width, height) = match (mode >> 2) & 3 {
+            0 => (b + 4, a + 2),
+            1 => (b + 8, a + 2),
+            2 => (a + 2, b + 8),
+            _ if bit(8) == 1 => (bit(7) + 2, a + 2),
+            _ => (a + 2, bit(7) + 6),
+        };
+        (range, width, height)
+    } else {
+        let range = (bit(4)) | (((mode >> 2) & 3) << 1);
+        if mode & 0xf == 0 {
+            return None;
+        }
+        let (width, height) = match b {
+            0 => (12, a + 2),
+            1 => (a + 2, 12),
+            2 => {
+                dual_plane = false;
+                high_precision = 0;
+                (a + 6, ((mode >> 9) & 3) + 6)
+            }
+            _ if bit(5) == 1 => (10, 6),
+            _ => (6, 10),
+        };
+        (range, width, height)
+    };
+
+    let weight_levels = WEIGHT_LEVELS[(range | (high_precision << 3)) as usize];
+    if weight_levels == 0 {
+        return None;
+    }
+    Some(BlockMode {
+        grid_width,
+        grid_height,
+        dual_plane,
+        weight_levels,
+    })
+}
+
+fn decode_block(data: &[u8], block_width: usize, block_height: usize, pixels: &mut [[u8; 4]]) {
+    let block = u128::from_le_bytes(data[..16].try_into().unwrap());
+    if get_bits(block, 0, 9) == 0x1fc {
+        pixels.fill(decode_void_extent(block));
+        return;
+    }
+    if decode_normal_block(block, block_width, block_height, pixels).is_none() {
+        pixels.fill(ERROR_COLOR);
+    }
+}
+
+fn decode_normal_block(block: u128, block_width: usize, block_height: usize, pixels: &mut [[u8; 4]]) -> Option<()> {
+    let mode = decode_block_mode(block)?;
+    if mode.grid_width as usize > block_width || mode.grid_height as usize > block_height {
+        return None;
+    }
+    let planes = if mode.dual_plane { 2 } else { 1 };
+    let weight_count = mode.grid_width * mode.grid_height * planes;
+    let weight_bits = sequence_bits(weight_count, mode.weight_levels);
+    if weight_count > 64 || !(24..=96).contains(&weight_bits) {
+        return None;
+    }
+
+    let partitions = get_bits(block, 11, 2) + 1;
+    if mode.dual_plane && partitions == 4 {
+        return None;
+    }
+
+    // color endpoint modes, and where the data below the weights begins
+    let mut modes = [0u32; 4];
+    let mut below_weights = 128 - weight_bits;
+    let endpoints_start = if partitions == 1 {
+        modes[0] = get_bits(block, 13, 4);
+        17
+    } else {
+        let selector = get_bits(block, 23, 2);
+        if selector == 0 {
+            modes.fill(get_bits(block, 25, 4));
+        } else {
+            let extra = 3 * partitions - 4;
+            below_weights -= extra;
+            let encoded = get_bits(block, 25, 4) | (get_bits(block, below_weights, extra) << 4);
+            for (i, mode) in modes.iter_mut().take(partitions as usize).enumerate() {
+                let class = (encoded >> i) & 1;
+                let m = (encoded >> (partitions as usize + 2 * i)) & 3;
+                *mode = ((selector - 1 + class) << 2) | m;
+            }
+        }
+        29
+    };
+    let plane_channel = if mode.dual_plane {
+        below_weights -= 2;
+        Some(get_bits(block, below_weights, 2) as usize)
+    } else {
+        None
+    };
+
+    let value_count: u32 = modes.iter().take(partitions as usize).map(|mode| ((mode >> 2) + 1) * 2).sum();
+    if value_count > 18 || below_weights < endpoints_start {
+        return None;
+    }
+    let available = below_weights - endpoints_start;
+    let endpoint_levels = *ENDPOINT_LEVELS
+        .iter()
+        .find(|&&levels| sequence_bits(value_count, levels) <= available)?;
+    let values: Vec<i32> = decode_sequence(block, endpoints_start, value_count as usize, endpoint_levels)
+        .into_iter()
+        .map(|value| unquantize_endpoint(value, endpoint_levels) as i32)
+        .collect();
+
+    let mut endpoints = Vec::with_capacity(partitions as usize);
+    let mut offset = 0;
+    for &mode in modes.iter().take(partitions as usize) {
+        let count = (((mode >> 2) + 1) * 2) as usize;
+        endpoints.push(decode_endpoints(mode, &values[offset..offset + count])?);
+        offset += count;
+    }
+
+    // weights are stored from the top of the block downwards
+    let weights: Vec<u32> = decode_sequence(block.reverse_bits(), 0, weight_count as usize, mode.weight_levels)
+        .into_iter()
+        .map(|value| unquantize_weight(value, mode.weight_levels))
+        .collect();
+
+    let seed = get_bits(block, 13, 10);
+    let small_block = block_width * block_height < 31;
+    let (grid_width, grid_height) = (mode.grid_width as usize, mode.grid_height as usize);
+    let scale_x = (1024 + block_width / 2) / (block_width - 1).max(1);
+    let scale_y = (1024 + block_height / 2) / (block_height - 1).max(1);
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (i % block_width, i / block_width);
+        let partition = if partitions == 1 {
+            0
+        } else {
+            select_partition(seed, x as u32, y as u32, partitions, small_block)
+        };
+
+        // bilinear infill of the weight grid
+        let gx = (scale_x * x * (grid_width - 1) + 32) >> 6;
+        let gy = (scale_y * y * (grid_height - 1) + 32) >> 6;
+        let (jx, fx) = (gx >> 4, gx & 0xf);
+        let (jy, fy) = (gy >> 4, gy & 0xf);
+        let w11 = (fx * fy + 8) >> 4;
+        let w10 = fy - w11;
+        let w01 = fx - w11;
+        let w00 = 16 + w11 - fx - fy;
+        let weight = |plane: usize| {
+            let at = |gx: usize, gy: usize| {
+                if gx < grid_width && gy < grid_height {
+                    weights[(gy * grid_width + gx) * planes as usize + plane] as usize
+                } else {
+                    0
+                }
+            };
+            ((at(jx, jy) * w00 + at(jx + 1, jy) * w01 + at(jx, jy + 1) * w10 + at(jx + 1, jy + 1) * w11 + 8)
+                >> 4) as u32
+        };
+        let weights = [weight(0), if plane_channel.is_some() { weight(1) } else { 0 }];
+
+        let [e0, e1] = endpoints[partition];
+        *pixel = std::array::from_fn(|c| {
+            let w = if plane_channel == Some(c) { weights[1] } else { weights[0] };
+            let c0 = e0[c] as u32 * 257;
+            let c1 = e1[c] as u32 * 257;
+            (((c0 * (64 - w) + c1 * w + 32) >> 6) >> 8) as u8
+        });
+    }
+    Some(())
+}
+
+pub(super) fn decode_astc(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, block_width, block_height, 16, |block, pixels| {
+        decode_block(block, block_width, block_height, pixels)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode(block: u128, block_size: usize) -> Vec<[u8; 4]> {
+        let mut pixels = vec![[0u8; 4]; block_size * block_size];
+        decode_block(&block.to_le_bytes(), block_size, block_size, &mut pixels);
+        pixels
+    }
+
+    #[test]
+    fn void_extent() {
+        let mut block = 0x1fcu128 | (0b11 << 10) | (0x1fff << 12) | (0x1fff << 25) | (0x1fff << 38) | (0x1fff << 51);
+        // blue, at bit 96, stays zero
+        block |= (0xff00u128 << 64) | (0x8000u128 << 80) | (0xffffu128 << 112);
+        assert!(decode(block, 4).iter().all(|&pixel| pixel == [255, 128, 0, 255]));
+
+        // HDR void extents store half floats
+        let header = (block & u64::MAX as u128) | (1 << 9);
+        let hdr = header | (0x3c00u128 << 64) | (0x3800u128 << 80) | (0x3c00u128 << 112);
+        assert_eq!(decode(hdr, 4)[0], [255, 128, 0, 255]);
+    }
+
+    #[test]
+    fn integer_sequences() {
+        // every trit and quint packing decodes to the values it was built from
+        let mut trits = std::collections::HashSet::new();
+        for t in 0..256 {
+            let values = decode_trits(t);
+            assert!(values.iter().all(|&v| v < 3));
+            trits.insert(values);
+        }
+        assert_eq!(trits.len(), 243);
+        let mut quints = std::collections::HashSet::new();
+        for q in 0..128 {
+            let values = decode_quints(q);
+            assert!(values.iter().all(|&v| v < 5));
+            quints.insert(values);
+        }
+        assert_eq!(quints.len(), 125);
+
+        assert_eq!(sequence_bits(16, 3), 26);
+        assert_eq!(sequence_bits(6, 12), 6 * 2 + 10);
+        assert_eq!(sequence_bits(6, 40), 6 * 3 + 14);
+    }
+
+    #[test]
+    fn unquantization() {
+        for levels in ENDPOINT_LEVELS {
+            let (bits, trits, quints) = encoding(levels);
+            let high = if trits { 3 } else if quints { 5 } else { 1 };
+            let mut values: Vec<u32> = (0..high)
+                .flat_map(|d| (0..1 << bits).map(move |low| unquantize_endpoint((d, low), levels)))
+                .collect();
+            values.sort();
+            assert_eq!(values.len() as u32, levels);
+            assert_eq!((values[0], values[values.len() - 1]), (0, 255), "{levels} levels");
+        }
+        for levels in [2, 3, 4, 5, 6, 8, 10, 12, 16, 20, 24, 32] {
+            let (bits, trits, quints) = encoding(levels);
+            let high = if trits { 3 } else if quints { 5 } else { 1 };
+            let mut values: Vec<u32> = (0..high)
+                .flat_map(|d| (0..1 << bits).map(move |low| unquantize_weight((d, low), levels)))
+                .collect();
+            values.sort();
+            values.dedup();
+            assert_eq!(values.len() as u32, levels);
+            assert_eq!((values[0], values[values.len() - 1]), (0, 64), "{levels} levels");
+        }
+    }
+
+    #[test]
+    fn single_partition() {
+        // a 4x4 grid of 2 bit weights (weight range 4, A = 2) with direct RGBA endpoints
+        let mut block = 0b10u128 | (2 << 5);
+        block |= 12 << 13;
+        let endpoints = [0u128, 255, 0, 255, 0, 255, 255, 255];
+        for (i, value) in endpoints.iter().enumerate() {
+            block |= value << (17 + i * 8);
+        }
+        // the left column uses the first endpoint, weights are stored bit reversed from the top
+        for i in 0..16 {
+            let weight = if i % 4 == 0 { 0u128 } else { 3 };
+            block |= (weight & 1) << (127 - i * 2);
+            block |= (weight >> 1) << (126 - i * 2);
+        }
+        let pixels = decode(block, 4);
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[4], [0, 0, 0, 255]);
+        assert_eq!(pixels[1], [255, 255, 255, 255]);
+        assert_eq!(pixels[15], [255, 255, 255, 255]);
+
+        // the same weights on a 6x6 block are interpolated
+        let pixels = decode(block, 6);
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert!(pixels[1][0] > 0 && pixels[1][0] < 255);
+        assert_eq!(pixels[5], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn partitions() {
+        // both partitions are used by a block, and 1 partition blocks only use the first
+        let used: std::collections::HashSet<usize> =
+            (0..16).map(|i| select_partition(3, i % 4, i / 4, 2, true)).collect();
+        assert_eq!(used.len(), 2);
+        assert!((0..64).all(|i| select_partition(5, i % 8, i / 8, 3, false) < 3));
+    }
+
+    #[test]
+    fn reserved_blocks() {
+        assert!(decode(0, 4).iter().all(|&pixel| pixel == ERROR_COLOR));
+    }
+}
@@ -0,0 +1,835 @@
+// BC1-BC7 block decoders, following the D3D11 block compression specification
+use super::{decode_blocks, f32_to_u8, half_to_f32};
+use crate::Error;
+
+fn rgb565(value: u16) -> [u8; 3] {
+    let (r, g, b) = ((value >> 11) as u8, (value >> 5) as u8 & 0x3f, value as u8 & 0x1f);
+    [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2)]
+}
+
+/// Decodes the color half of a BC1-BC3 block. Only BC1 has the three color mode with transparent black.
+fn decode_color_block(block: &[u8], pixels: &mut [[u8; 4]], allow_transparent: bool) {
+    let c0 = u16::from_le_bytes([block[0], block[1]]);
+    let c1 = u16::from_le_bytes([block[2], block[3]]);
+    let (e0, e1) = (rgb565(c0), rgb565(c1));
+    let mix = |a: u32, b: u32, c: u32| -> [u8; 4] {
+        let channel = |i: usize| ((e0[i] as u32 * a + e1[i] as u32 * b) / c) as u8;
+        [channel(0), channel(1), channel(2), 255]
+    };
+
+    let colors = if c0 > c1 || !allow_transparent {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(2, 1, 3), mix(1, 2, 3)]
+    } else {
+        [mix(1, 0, 1), mix(0, 1, 1), mix(1, 1, 2), [0, 0, 0, 0]]
+    };
+    let indices = u32::from_le_bytes([block[4], block[5], block[6], block[7]]);
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        *pixel = colors[(indices >> (i * 2)) as usize & 3];
+    }
+}
+
+/// Decodes a BC4 block, which is also the alpha half of BC3 and each channel of BC5.
+fn decode_channel_block(block: &[u8]) -> [u8; 16] {
+    let (a0, a1) = (block[0] as u32, block[1] as u32);
+    let mut values = [0u8; 8];
+    values[0] = a0 as u8;
+    values[1] = a1 as u8;
+    if a0 > a1 {
+        for i in 1..7 {
+            values[i + 1] = ((a0 * (7 - i as u32) + a1 * i as u32) / 7) as u8;
+        }
+    } else {
+        for i in 1..5 {
+            values[i + 1] = ((a0 * (5 - i as u32) + a1 * i as u32) / 5) as u8;
+        }
+        values[6] = 0;
+        values[7] = 255;
+    }
+
+    let mut bits = [0u8; 8];
+    bits[..6].copy_from_slice(&block[2..8]);
+    let indices = u64::from_le_bytes(bits);
+    std::array::from_fn(|i| values[(indices >> (i * 3)) as usize & 7])
+}
+
+pub(super) fn decode_bc1(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 8, |block, pixels| {
+        decode_color_block(block, pixels, true)
+    })
+}
+
+pub(super) fn decode_bc2(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        decode_color_block(&block[8..], pixels, false);
+        let alpha = u64::from_le_bytes(block[..8].try_into().unwrap());
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            pixel[3] = ((alpha >> (i * 4)) as u8 & 0xf) * 0x11;
+        }
+    })
+}
+
+pub(super) fn decode_bc3(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        decode_color_block(&block[8..], pixels, false);
+        for (pixel, alpha) in pixels.iter_mut().zip(decode_channel_block(block)) {
+            pixel[3] = alpha;
+        }
+    })
+}
+
+pub(super) fn decode_bc4(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 8, |block, pixels| {
+        for (pixel, red) in pixels.iter_mut().zip(decode_channel_block(block)) {
+            *pixel = [red, 0, 0, 255];
+        }
+    })
+}
+
+pub(super) fn decode_bc5(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        let red = decode_channel_block(block);
+        let green = decode_channel_block(&block[8..]);
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = [red[i], green[i], 0, 255];
+        }
+    })
+}
+
+/// Reads the bits of a 128 bit block starting at the least significant one.
+struct BitReader {
+    bits: u128,
+    position: u32,
+}
+
+impl BitReader {
+    fn new(block: &[u8]) -> Self {
+        BitReader {
+            bits: u128::from_le_bytes(block[..16].try_into().unwrap()),
+            position: 0,
+        }
+    }
+
+    fn read(&mut self, count: u32) -> u32 {
+        if count == 0 || self.position >= 128 {
+            return 0;
+        }
+        let value = (self.bits >> self.position) as u32 & ((1u64 << count) - 1) as u32;
+        self.position += count;
+        value
+    }
+}
+
+const WEIGHTS2: [u32; 4] = [0, 21, 43, 64];
+const WEIGHTS3: [u32; 8] = [0, 9, 18, 27, 37, 46, 55, 64];
+const WEIGHTS4: [u32; 16] = [0, 4, 9, 13, 17, 21, 26, 30, 34, 38, 43, 47, 51, 55, 60, 64];
+
+fn weights(index_bits: u32) -> &'static [u32] {
+    match index_bits {
+        2 => &WEIGHTS2,
+        3 => &WEIGHTS3,
+        _ => &WEIGHTS4,
+    }
+}
+
+fn interpolate(e0: u32, e1: u32, weight: u32) -> u32 {
+    (e0 * (64 - weight) + e1 * weight + 32) >> 6
+}
+
+/// Subset 1 pixels of the two subset partitions, one bit per pixel.
+const PARTITIONS2: [u16; 64] = [
+    0xcccc, 0x8888, 0xeeee, 0xecc8, 0xc880, 0xfeec, 0xfec8, 0xec80,
+    0xc800, 0xffec, 0xfe80, 0xe800, 0xffe8, 0xff00, 0xfff0, 0xf000,
+    0xf710, 0x008e, 0x7100, 0x08ce, 0x008c, 0x7310, 0x3100, 0x8cce,
+    0x088c, 0x3110, 0x6666, 0x366c, 0x17e8, 0x0ff0, 0x718e, 0x399c,
+    0xaaaa, 0xf0f0, 0x5a5a, 0x33cc, 0x3c3c, 0x55aa, 0x9696, 0xa55a,
+    0x73ce, 0x13c8, 0x324c, 0x3bdc, 0x6996, 0xc33c, 0x9966, 0x0660,
+    0x0272, 0x04e4, 0x4e40, 0x2720, 0xc936, 0x936c, 0x39c6, 0x639c,
+    0x9336, 0x9cc6, 0x817e, 0xe718, 0xccf0, 0x0fcc, 0x7744, 0xee22,
+];
+
+const PARTITIONS3: [[u8; 16]; 64] = [
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 1, 2, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 2, 0, 0, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 1, 0, 1, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1, 1, 1, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1],
+    [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2],
+    [0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2, 0, 1, 1, 2],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0, 2, 2, 2, 0],
+    [0, 0, 0, 1, 0, 0, 1, 1, 0, 1, 1, 2, 1, 1, 2, 2],
+    [0, 1, 1, 1, 0, 0, 1, 1, 2, 0, 0, 1, 2, 2, 0, 0],
+    [0, 0, 0, 0, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 2, 2, 0, 0, 2, 2, 1, 1, 1, 1],
+    [0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2, 0, 2, 2, 2],
+    [0, 0, 0, 1, 0, 0, 0, 1, 2, 2, 2, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2],
+    [0, 0, 0, 0, 1, 1, 0, 0, 2, 2, 1, 0, 2, 2, 1, 0],
+    [0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1, 0, 0, 0, 0],
+    [0, 0, 1, 2, 0, 0, 1, 2, 1, 1, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1, 0, 1, 1, 0],
+    [0, 0, 0, 0, 0, 1, 1, 0, 1, 2, 2, 1, 1, 2, 2, 1],
+    [0, 0, 2, 2, 1, 1, 0, 2, 1, 1, 0, 2, 0, 0, 2, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 0, 0, 2, 2, 2, 2, 2],
+    [0, 0, 1, 1, 0, 1, 2, 2, 0, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 0, 0, 2, 0, 0, 0, 2, 2, 1, 1, 2, 2, 2, 1],
+    [0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 2, 2, 2],
+    [0, 2, 2, 2, 0, 0, 2, 2, 0, 0, 1, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 0, 0, 1, 2, 0, 0, 2, 2, 0, 2, 2, 2],
+    [0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0, 0, 1, 2, 0],
+    [0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0],
+    [0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0, 1, 2, 0],
+    [0, 1, 2, 0, 2, 0, 1, 2, 1, 2, 0, 1, 0, 1, 2, 0],
+    [0, 0, 1, 1, 2, 2, 0, 0, 1, 1, 2, 2, 0, 0, 1, 1],
+    [0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 0, 0, 0, 0, 1, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 0, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2, 1, 1, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 2, 2, 0, 0, 1, 1],
+    [0, 2, 2, 0, 1, 2, 2, 1, 0, 2, 2, 0, 1, 2, 2, 1],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 0, 1, 0, 1],
+    [0, 0, 0, 0, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1, 2, 1],
+    [0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 0, 1, 2, 2, 2, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 2, 2, 2, 0, 1, 1, 1],
+    [0, 0, 0, 2, 1, 1, 1, 2, 0, 0, 0, 2, 1, 1, 1, 2],
+    [0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 2, 2, 2, 0, 1, 1, 1, 0, 1, 1, 1, 0, 2, 2, 2],
+    [0, 0, 0, 2, 1, 1, 1, 2, 1, 1, 1, 2, 0, 0, 0, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2, 2, 1, 1, 2],
+    [0, 1, 1, 0, 0, 1, 1, 0, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 0, 2, 2, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 2, 2],
+    [0, 0, 2, 2, 1, 1, 2, 2, 1, 1, 2, 2, 0, 0, 2, 2],
+    [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 1, 1, 2],
+    [0, 0, 0, 2, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0, 1],
+    [0, 2, 2, 2, 1, 2, 2, 2, 0, 2, 2, 2, 1, 2, 2, 2],
+    [0, 1, 0, 1, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2],
+    [0, 1, 1, 1, 2, 0, 1, 1, 2, 2, 0, 1, 2, 2, 2, 0],
+];
+
+/// Anchor pixel of subset 1 in the two subset partitions.
+const ANCHORS2: [u8; 64] = [
+    15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15,
+    15, 2, 8, 2, 2, 8, 8, 15, 2, 8, 2, 2, 8, 8, 2, 2,
+    15, 15, 6, 8, 2, 8, 15, 15, 2, 8, 2, 2, 2, 15, 15, 6,
+    6, 2, 6, 8, 15, 15, 2, 2, 15, 15, 15, 15, 15, 2, 2, 15,
+];
+
+/// Anchor pixels of subsets 1 and 2 in the three subset partitions.
+const ANCHORS3: [[u8; 64]; 2] = [
+    [
+        3, 3, 15, 15, 8, 3, 15, 15, 8, 8, 6, 6, 6, 5, 3, 3,
+        3, 3, 8, 15, 3, 3, 6, 10, 5, 8, 8, 6, 8, 5, 15, 15,
+        8, 15, 3, 5, 6, 10, 8, 15, 15, 3, 15, 5, 15, 15, 15, 15,
+        3, 15, 5, 5, 5, 8, 5, 10, 5, 10, 8, 13, 15, 12, 3, 3,
+    ],
+    [
+        15, 8, 8, 3, 15, 15, 3, 8, 15, 15, 15, 15, 15, 15, 15, 8,
+        15, 8, 15, 3, 15, 8, 15, 8, 3, 15, 6, 10, 15, 15, 10, 8,
+        15, 3, 15, 10, 10, 8, 9, 10, 6, 15, 8, 15, 3, 6, 6, 8,
+        15, 3, 15, 15, 15, 15, 15, 15, 15, 15, 15, 15, 3, 15, 15, 8,
+    ],
+];
+
+fn subset_of(subsets: usize, partition: usize, pixel: usize) -> usize {
+    match subsets {
+        1 => 0,
+        2 => (PARTITIONS2[partition] >> pixel) as usize & 1,
+        _ => PARTITIONS3[partition][pixel] as usize,
+    }
+}
+
+/// Anchor pixels store their index with the most significant bit left out.
+fn is_anchor(subsets: usize, partition: usize, pixel: usize) -> bool {
+    pixel == 0
+        || match subsets {
+            2 => ANCHORS2[partition] as usize == pixel,
+            3 => ANCHORS3[0][partition] as usize == pixel || ANCHORS3[1][partition] as usize == pixel,
+            _ => false,
+        }
+}
+
+struct Bc7Mode {
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    /// One p-bit per endpoint.
+    endpoint_pbits: bool,
+    /// One p-bit per subset, shared by both of its endpoints.
+    shared_pbits: bool,
+    index_bits: u32,
+    index_bits2: u32,
+}
+
+#[allow(clippy::too_many_arguments)]
+const fn bc7_mode(
+    subsets: usize,
+    partition_bits: u32,
+    rotation_bits: u32,
+    index_selection_bits: u32,
+    color_bits: u32,
+    alpha_bits: u32,
+    endpoint_pbits: bool,
+    shared_pbits: bool,
+    index_bits: u32,
+    index_bits2: u32,
+) -> Bc7Mode {
+    Bc7Mode {
+        subsets,
+        partition_bits,
+        rotation_bits,
+        index_selection_bits,
+        color_bits,
+        alpha_bits,
+        endpoint_pbits,
+        shared_pbits,
+        index_bits,
+        index_bits2,
+    }
+}
+
+const BC7_MODES: [Bc7Mode; 8] = [
+    bc7_mode(3, 4, 0, 0, 4, 0, true, false, 3, 0),
+    bc7_mode(2, 6, 0, 0, 6, 0, false, true, 3, 0),
+    bc7_mode(3, 6, 0, 0, 5, 0, false, false, 2, 0),
+    bc7_mode(2, 6, 0, 0, 7, 0, true, false, 2, 0),
+    bc7_mode(1, 0, 2, 1, 5, 6, false, false, 2, 3),
+    bc7_mode(1, 0, 2, 0, 7, 8, false, false, 2, 2),
+    bc7_mode(1, 0, 0, 0, 7, 7, true, false, 4, 0),
+    bc7_mode(2, 6, 0, 0, 5, 5, true, false, 2, 0),
+];
+
+fn expand_bits(value: u32, bits: u32) -> u32 {
+    let value = value << (8 - bits);
+    value | (value >> bits)
+}
+
+fn decode_bc7_block(block: &[u8], pixels: &mut [[u8; 4]]) {
+    let Some(mode_index) = (block[0] != 0).then(|| block[0].trailing_zeros()) else {
+        // reserved mode
+        pixels.fill([0; 4]);
+        return;
+    };
+    let mode = &BC7_MODES[mode_index as usize];
+    let mut reader = BitReader::new(block);
+    reader.read(mode_index + 1);
+
+    let partition = reader.read(mode.partition_bits) as usize;
+    let rotation = reader.read(mode.rotation_bits);
+    let index_selection = reader.read(mode.index_selection_bits);
+
+    let endpoint_count = mode.subsets * 2;
+    let mut endpoints = [[0u32; 4]; 6];
+    for channel in 0..3 {
+        for endpoint in &mut endpoints[..endpoint_count] {
+            endpoint[channel] = reader.read(mode.color_bits);
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        endpoint[3] = reader.read(mode.alpha_bits);
+    }
+
+    let (mut color_bits, mut alpha_bits) = (mode.color_bits, mode.alpha_bits);
+    if mode.endpoint_pbits || mode.shared_pbits {
+        let mut pbits = [0u32; 6];
+        if mode.endpoint_pbits {
+            for pbit in &mut pbits[..endpoint_count] {
+                *pbit = reader.read(1);
+            }
+        } else {
+            for subset in 0..mode.subsets {
+                let pbit = reader.read(1);
+                pbits[subset * 2] = pbit;
+                pbits[subset * 2 + 1] = pbit;
+            }
+        }
+        for (endpoint, pbit) in endpoints[..endpoint_count].iter_mut().zip(pbits) {
+            for channel in endpoint.iter_mut().take(if alpha_bits > 0 { 4 } else { 3 }) {
+                *channel = (*channel << 1) | pbit;
+            }
+        }
+        color_bits += 1;
+        if alpha_bits > 0 {
+            alpha_bits += 1;
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for channel in &mut endpoint[..3] {
+            *channel = expand_bits(*channel, color_bits);
+        }
+        endpoint[3] = if alpha_bits > 0 { expand_bits(endpoint[3], alpha_bits) } else { 255 };
+    }
+
+    let mut indices = [0u32; 16];
+    for (pixel, index) in indices.iter_mut().enumerate() {
+        let anchor = is_anchor(mode.subsets, partition, pixel);
+        *index = reader.read(mode.index_bits - anchor as u32);
+    }
+    let mut indices2 = [0u32; 16];
+    if mode.index_bits2 > 0 {
+        for (pixel, index) in indices2.iter_mut().enumerate() {
+            *index = reader.read(mode.index_bits2 - (pixel == 0) as u32);
+        }
+    }
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let subset = subset_of(mode.subsets, partition, i);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let (color_weight, alpha_weight) = if mode.index_bits2 == 0 {
+            let weight = weights(mode.index_bits)[indices[i] as usize];
+            (weight, weight)
+        } else if index_selection == 0 {
+            (weights(mode.index_bits)[indices[i] as usize], weights(mode.index_bits2)[indices2[i] as usize])
+        } else {
+            (weights(mode.index_bits2)[indices2[i] as usize], weights(mode.index_bits)[indices[i] as usize])
+        };
+
+        let mut color = [0u8; 4];
+        for channel in 0..3 {
+            color[channel] = interpolate(e0[channel], e1[channel], color_weight) as u8;
+        }
+        color[3] = interpolate(e0[3], e1[3], alpha_weight) as u8;
+        if rotation > 0 {
+            color.swap(3, rotation as usize - 1);
+        }
+        *pixel = color;
+    }
+}
+
+pub(super) fn decode_bc7(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, decode_bc7_block)
+}
+
+// BC6H header fields, endpoints w and x belong to subset 0, y and z to subset 1
+const RW: u8 = 0;
+const GW: u8 = 1;
+const BW: u8 = 2;
+const RX: u8 = 3;
+const GX: u8 = 4;
+const BX: u8 = 5;
+const RY: u8 = 6;
+const GY: u8 = 7;
+const BY: u8 = 8;
+const RZ: u8 = 9;
+const GZ: u8 = 10;
+const BZ: u8 = 11;
+const D: u8 = 12;
+
+struct Bc6hMode {
+    mode: u32,
+    transformed: bool,
+    partitioned: bool,
+    endpoint_bits: u32,
+    delta_bits: [u32; 3],
+    /// Fields in the order they are stored as (field, last bit, first bit),
+    /// the first bit read goes to the first bit, so reversed fields have a smaller last bit.
+    layout: &'static [(u8, u8, u8)],
+}
+
+const BC6H_MODES: [Bc6hMode; 14] = [
+    Bc6hMode {
+        mode: 0x00,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 10,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (GY, 4, 4), (BY, 4, 4), (BZ, 4, 4), (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0),
+            (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+            (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x01,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 7,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (GY, 5, 5), (GZ, 4, 4), (GZ, 5, 5), (RW, 6, 0), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4),
+            (GW, 6, 0), (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 6, 0), (BZ, 3, 3), (BZ, 5, 5),
+            (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0),
+            (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x02,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [5, 4, 4],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 4, 0), (RW, 10, 10), (GY, 3, 0), (GX, 3, 0),
+            (GW, 10, 10), (BZ, 0, 0), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0),
+            (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x06,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 5, 4],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (GZ, 4, 4), (GY, 3, 0),
+            (GX, 4, 0), (GW, 10, 10), (GZ, 3, 0), (BX, 3, 0), (BW, 10, 10), (BZ, 1, 1), (BY, 3, 0),
+            (RY, 3, 0), (BZ, 0, 0), (BZ, 2, 2), (RZ, 3, 0), (GY, 4, 4), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x0a,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 11,
+        delta_bits: [4, 4, 5],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 10), (BY, 4, 4), (GY, 3, 0),
+            (GX, 3, 0), (GW, 10, 10), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BW, 10, 10), (BY, 3, 0),
+            (RY, 3, 0), (BZ, 1, 1), (BZ, 2, 2), (RZ, 3, 0), (BZ, 4, 4), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x0e,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 9,
+        delta_bits: [5, 5, 5],
+        layout: &[
+            (RW, 8, 0), (BY, 4, 4), (GW, 8, 0), (GY, 4, 4), (BW, 8, 0), (BZ, 4, 4), (RX, 4, 0),
+            (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0), (BX, 4, 0), (BZ, 1, 1),
+            (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x12,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [6, 5, 5],
+        layout: &[
+            (RW, 7, 0), (GZ, 4, 4), (BY, 4, 4), (GW, 7, 0), (BZ, 2, 2), (GY, 4, 4), (BW, 7, 0),
+            (BZ, 3, 3), (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0), (GZ, 3, 0),
+            (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x16,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 6, 5],
+        layout: &[
+            (RW, 7, 0), (BZ, 0, 0), (BY, 4, 4), (GW, 7, 0), (GY, 5, 5), (GY, 4, 4), (BW, 7, 0),
+            (GZ, 5, 5), (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0),
+            (BX, 4, 0), (BZ, 1, 1), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3),
+            (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x1a,
+        transformed: true,
+        partitioned: true,
+        endpoint_bits: 8,
+        delta_bits: [5, 5, 6],
+        layout: &[
+            (RW, 7, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 7, 0), (BY, 5, 5), (GY, 4, 4), (BW, 7, 0),
+            (BZ, 5, 5), (BZ, 4, 4), (RX, 4, 0), (GZ, 4, 4), (GY, 3, 0), (GX, 4, 0), (BZ, 0, 0),
+            (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0), (RY, 4, 0), (BZ, 2, 2), (RZ, 4, 0), (BZ, 3, 3),
+            (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x1e,
+        transformed: false,
+        partitioned: true,
+        endpoint_bits: 6,
+        delta_bits: [6, 6, 6],
+        layout: &[
+            (RW, 5, 0), (GZ, 4, 4), (BZ, 0, 0), (BZ, 1, 1), (BY, 4, 4), (GW, 5, 0), (GY, 5, 5),
+            (BY, 5, 5), (BZ, 2, 2), (GY, 4, 4), (BW, 5, 0), (GZ, 5, 5), (BZ, 3, 3), (BZ, 5, 5),
+            (BZ, 4, 4), (RX, 5, 0), (GY, 3, 0), (GX, 5, 0), (GZ, 3, 0), (BX, 5, 0), (BY, 3, 0),
+            (RY, 5, 0), (RZ, 5, 0), (D, 4, 0),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x03,
+        transformed: false,
+        partitioned: false,
+        endpoint_bits: 10,
+        delta_bits: [10, 10, 10],
+        layout: &[(RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 9, 0), (GX, 9, 0), (BX, 9, 0)],
+    },
+    Bc6hMode {
+        mode: 0x07,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 11,
+        delta_bits: [9, 9, 9],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 8, 0), (RW, 10, 10), (GX, 8, 0), (GW, 10, 10),
+            (BX, 8, 0), (BW, 10, 10),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x0b,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 12,
+        delta_bits: [8, 8, 8],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 7, 0), (RW, 10, 11), (GX, 7, 0), (GW, 10, 11),
+            (BX, 7, 0), (BW, 10, 11),
+        ],
+    },
+    Bc6hMode {
+        mode: 0x0f,
+        transformed: true,
+        partitioned: false,
+        endpoint_bits: 16,
+        delta_bits: [4, 4, 4],
+        layout: &[
+            (RW, 9, 0), (GW, 9, 0), (BW, 9, 0), (RX, 3, 0), (RW, 10, 15), (GX, 3, 0), (GW, 10, 15),
+            (BX, 3, 0), (BW, 10, 15),
+        ],
+    },
+];
+
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// Scales an unsigned endpoint to 16 bits.
+fn unquantize_bc6h(value: i32, bits: u32) -> i32 {
+    if bits >= 15 {
+        value
+    } else if value == 0 {
+        0
+    } else if value == (1 << bits) - 1 {
+        0xffff
+    } else {
+        ((value << 16) + 0x8000) >> bits
+    }
+}
+
+fn decode_bc6h_block(block: &[u8], pixels: &mut [[u8; 4]]) {
+    let mut reader = BitReader::new(block);
+    let mut mode_bits = reader.read(2);
+    if mode_bits > 1 {
+        mode_bits |= reader.read(3) << 2;
+    }
+    let Some(mode) = BC6H_MODES.iter().find(|mode| mode.mode == mode_bits) else {
+        // reserved modes decode to black
+        pixels.fill([0, 0, 0, 255]);
+        return;
+    };
+
+    let mut fields = [0u32; 13];
+    for &(field, last, first) in mode.layout {
+        if last >= first {
+            for bit in first..=last {
+                fields[field as usize] |= reader.read(1) << bit;
+            }
+        } else {
+            for bit in (last..=first).rev() {
+                fields[field as usize] |= reader.read(1) << bit;
+            }
+        }
+    }
+
+    let endpoint_count = if mode.partitioned { 4 } else { 2 };
+    let mask = (1i32 << mode.endpoint_bits) - 1;
+    let mut endpoints = [[0i32; 3]; 4];
+    for channel in 0..3 {
+        let base = fields[channel] as i32;
+        endpoints[0][channel] = base;
+        for endpoint in 1..endpoint_count {
+            let value = fields[endpoint * 3 + channel];
+            endpoints[endpoint][channel] = if mode.transformed {
+                (base + sign_extend(value, mode.delta_bits[channel])) & mask
+            } else {
+                value as i32
+            };
+        }
+    }
+    for endpoint in &mut endpoints[..endpoint_count] {
+        for channel in endpoint.iter_mut() {
+            *channel = unquantize_bc6h(*channel, mode.endpoint_bits);
+        }
+    }
+
+    let partition = fields[D as usize] as usize;
+    let (subsets, index_bits) = if mode.partitioned { (2, 3) } else { (1, 4) };
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let anchor = is_anchor(subsets, partition, i);
+        let weight = weights(index_bits)[reader.read(index_bits - anchor as u32) as usize] as i32;
+        let subset = subset_of(subsets, partition, i);
+        let (e0, e1) = (endpoints[subset * 2], endpoints[subset * 2 + 1]);
+        let channel = |c: usize| {
+            let value = (e0[c] * (64 - weight) + e1[c] * weight + 32) >> 6;
+            // scale to the largest finite half, whose bits are 0x7bff
+            f32_to_u8(half_to_f32(((value * 31) >> 6) as u16))
+        };
+        *pixel = [channel(0), channel(1), channel(2), 255];
+    }
+}
+
+pub(super) fn decode_bc6h(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, decode_bc6h_block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs values into a 128 bit block starting at the least significant bit.
+    fn pack_bits(fields: &[(u32, u32)]) -> [u8; 16] {
+        let mut bits = 0u128;
+        let mut position = 0;
+        for &(value, count) in fields {
+            bits |= (value as u128) << position;
+            position += count;
+        }
+        assert!(position <= 128);
+        bits.to_le_bytes()
+    }
+
+    type BlockDecoder = fn(&[u8], usize, usize) -> Result<Vec<u8>, Error>;
+
+    fn decode_block(decode: BlockDecoder, block: &[u8]) -> Vec<[u8; 4]> {
+        decode(block, 4, 4)
+            .unwrap()
+            .chunks_exact(4)
+            .map(|pixel| pixel.try_into().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn bc1() {
+        // red and blue endpoints, pixels use indices 0, 1, 2, 3 in turn
+        let block = [0x00, 0xf8, 0x1f, 0x00, 0xe4, 0xe4, 0xe4, 0xe4];
+        let pixels = decode_block(decode_bc1, &block);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [0, 0, 255, 255]);
+        assert_eq!(pixels[2], [170, 0, 85, 255]);
+        assert_eq!(pixels[3], [85, 0, 170, 255]);
+
+        // swapped endpoints select the three color mode with transparent black
+        let block = [0x1f, 0x00, 0x00, 0xf8, 0xe4, 0xe4, 0xe4, 0xe4];
+        let pixels = decode_block(decode_bc1, &block);
+        assert_eq!(pixels[2], [127, 0, 127, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn bc3_and_bc4() {
+        // alpha from 255 to 0 in eight steps, pixels use indices 0 to 7 and then 0
+        let alpha = [255, 0, 0b10001000, 0b11000110, 0b11111010, 0b10001000, 0b11000110, 0b11111010];
+        let mut block = alpha.to_vec();
+        block.extend([0xff, 0xff, 0xff, 0xff, 0, 0, 0, 0]);
+        let pixels = decode_block(decode_bc3, &block);
+        let expected_alpha = [255, 0, 218, 182, 145, 109, 72, 36];
+        for (i, pixel) in pixels.iter().enumerate() {
+            assert_eq!(pixel[..3], [255, 255, 255]);
+            assert_eq!(pixel[3], expected_alpha[i % 8]);
+        }
+
+        let pixels = decode_block(decode_bc4, &alpha);
+        assert_eq!(pixels[2], [218, 0, 0, 255]);
+        let pixels = decode_block(decode_bc5, &[alpha, alpha].concat());
+        assert_eq!(pixels[3], [182, 182, 0, 255]);
+    }
+
+    #[test]
+    fn bc7_mode6() {
+        // one subset with 7 bit endpoints and p-bits, black to opaque white
+        let mut fields = vec![(1 << 6, 7)];
+        fields.extend([(0, 7), (127, 7), (0, 7), (127, 7), (0, 7), (127, 7), (0, 7), (127, 7)]);
+        fields.extend([(0, 1), (1, 1)]);
+        // pixel 0 has a 3 bit anchor index, the others count up from 1 to 15
+        fields.push((0, 3));
+        fields.extend((1..16).map(|index| (index, 4)));
+        let pixels = decode_block(decode_bc7, &pack_bits(&fields));
+        assert_eq!(pixels[0], [0, 0, 0, 0]);
+        assert_eq!(pixels[15], [255, 255, 255, 255]);
+        // weight 34 of 64
+        assert_eq!(pixels[8], [135, 135, 135, 135]);
+    }
+
+    #[test]
+    fn bc7_mode5_rotation() {
+        // 7 bit color from black to white, 8 bit alpha fixed at 0x40, red and alpha swapped
+        let mut fields = vec![(1 << 5, 6), (1, 2)];
+        fields.extend([(0, 7), (127, 7), (0, 7), (127, 7), (0, 7), (127, 7), (0x40, 8), (0x40, 8)]);
+        fields.push((0, 1));
+        fields.extend((1..16).map(|_| (3, 2)));
+        fields.extend((0..16).map(|i| (0, if i == 0 { 1 } else { 2 })));
+        let pixels = decode_block(decode_bc7, &pack_bits(&fields));
+        assert_eq!(pixels[0], [0x40, 0, 0, 0]);
+        assert_eq!(pixels[1], [0x40, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bc7_mode1_partition() {
+        // partition 13 puts the top two rows in subset 0 (black) and the bottom two in subset 1 (white)
+        let mut fields = vec![(1 << 1, 2), (13, 6)];
+        for _ in 0..3 {
+            fields.extend([(0, 6), (0, 6), (63, 6), (63, 6)]);
+        }
+        fields.extend([(0, 1), (1, 1)]);
+        // anchors are pixels 0 and 15, every pixel uses the second endpoint of its subset
+        fields.extend((0..16).map(|i| if i == 0 || i == 15 { (3, 2) } else { (7, 3) }));
+        let pixels = decode_block(decode_bc7, &pack_bits(&fields));
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[7], [0, 0, 0, 255]);
+        assert_eq!(pixels[8], [255, 255, 255, 255]);
+        assert_eq!(pixels[15], [255, 255, 255, 255]);
+    }
+
+    #[test]
+    fn bc6h_mode11() {
+        // 10 bit endpoints from black to the largest value, which clamps to white
+        let mut fields = vec![(0x03, 5)];
+        fields.extend([(0, 10), (0, 10), (0, 10), (0x3ff, 10), (0x3ff, 10), (0x3ff, 10)]);
+        fields.push((0, 3));
+        fields.extend((1..16).map(|index| (index, 4)));
+        let pixels = decode_block(decode_bc6h, &pack_bits(&fields));
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[15], [255, 255, 255, 255]);
+        // the endpoints are half float bits, so a quarter of the range is still dark
+        assert_eq!(pixels[4], [2, 2, 2, 255]);
+    }
+
+    #[test]
+    fn bc6h_mode14_delta() {
+        // 16 bit base endpoint of 1.0 and a delta of -1, reversed high bits
+        let one = 0x3c00u32 * 64 / 31;
+        let mut fields = vec![(0x0f, 5)];
+        fields.extend([(one & 0x3ff, 10), (0, 10), (0, 10)]);
+        let reverse6 = |value: u32| (0..6).fold(0, |acc, bit| acc | (((value >> bit) & 1) << (5 - bit)));
+        fields.extend([(0xf, 4), (reverse6(one >> 10), 6), (0, 4), (0, 6), (0, 4), (0, 6)]);
+        fields.extend((0..16).map(|i| (0, if i == 0 { 3 } else { 4 })));
+        let pixels = decode_block(decode_bc6h, &pack_bits(&fields));
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn partition_anchors() {
+        // every anchor must be a pixel of the subset it anchors
+        for partition in 0..64 {
+            assert_eq!(subset_of(2, partition, ANCHORS2[partition] as usize), 1);
+            assert_eq!(subset_of(3, partition, ANCHORS3[0][partition] as usize), 1);
+            assert_eq!(subset_of(3, partition, ANCHORS3[1][partition] as usize), 2);
+        }
+    }
+}
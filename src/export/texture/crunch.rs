@@ -0,0 +1,868 @@
+// Crunch (.crn) unpacker for the original format and the variant Unity uses since 2017.3
+use crate::Error;
+
+const SIGNATURE: usize = 0x4878;
+const HEADER_SIZE: usize = 74;
+
+const FORMAT_DXT1: u8 = 0;
+const FORMAT_DXT5: u8 = 2;
+/// The DXT5 variants that swizzle channels, which are stored like DXT5.
+const FORMAT_DXT5_AGBR: u8 = 6;
+const FORMAT_ETC1: u8 = 10;
+const FORMAT_ETC2A: u8 = 12;
+
+const MAX_CODE_SIZE: usize = 16;
+const MAX_SYMBOLS_BITS: u32 = 14;
+/// The order in which the code sizes of the code size alphabet are sent.
+const CODE_SIZE_ORDER: [usize; 21] = [17, 18, 19, 20, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15, 16];
+
+/// How many endpoints the 2x2 blocks of a chunk share, and which block uses which.
+const CHUNK_TILE_COUNTS: [usize; 8] = [1, 2, 2, 3, 3, 3, 3, 4];
+const CHUNK_TILES: [[usize; 4]; 8] = [
+    [0, 0, 0, 0],
+    [0, 0, 1, 1],
+    [0, 1, 0, 1],
+    [0, 0, 1, 2],
+    [1, 2, 0, 0],
+    [0, 1, 0, 2],
+    [1, 0, 2, 0],
+    [0, 1, 2, 3],
+];
+
+/// Selectors are sent ordered by their position between the endpoints.
+const DXT1_FROM_LINEAR: [u32; 4] = [0, 2, 3, 1];
+const DXT5_FROM_LINEAR: [u64; 8] = [0, 2, 3, 4, 5, 6, 7, 1];
+
+fn invalid(message: &str) -> Error {
+    Error::InvalidValue(format!("invalid crunch data: {message}"))
+}
+
+fn read_be(data: &[u8], offset: usize, size: usize) -> usize {
+    data[offset..offset + size].iter().fold(0, |value, &byte| (value << 8) | byte as usize)
+}
+
+/// Whether `unity_version` writes the crunch variant that Unity changed in 2017.3.
+pub(super) fn uses_unity_format(unity_version: &str) -> bool {
+    crate::export::parse_unity_version(unity_version).is_some_and(|version| version >= (2017, 3))
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Palette {
+    offset: usize,
+    size: usize,
+    count: usize,
+}
+
+struct Header {
+    width: usize,
+    height: usize,
+    format: u8,
+    color_endpoints: Palette,
+    color_selectors: Palette,
+    alpha_endpoints: Palette,
+    alpha_selectors: Palette,
+    tables: Palette,
+    level: Palette,
+}
+
+impl Header {
+    fn read(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_SIZE || read_be(data, 0, 2) != SIGNATURE {
+            return Err(invalid("missing header"));
+        }
+        let header_size = read_be(data, 2, 2);
+        let data_size = read_be(data, 6, 4);
+        let levels = data[16] as usize;
+        if levels == 0 || header_size < 70 + levels * 4 || data.len() < header_size || data.len() < data_size {
+            return Err(invalid("truncated header"));
+        }
+        let palette = |offset: usize| Palette {
+            offset: read_be(data, offset, 3),
+            size: read_be(data, offset + 3, 3),
+            count: read_be(data, offset + 6, 2),
+        };
+        let level_offset = read_be(data, 70, 4);
+        let level_end = if levels > 1 { read_be(data, 74, 4) } else { data_size };
+        Ok(Header {
+            width: read_be(data, 12, 2),
+            height: read_be(data, 14, 2),
+            format: data[18],
+            color_endpoints: palette(33),
+            color_selectors: palette(41),
+            alpha_endpoints: palette(49),
+            alpha_selectors: palette(57),
+            tables: Palette {
+                offset: read_be(data, 67, 3),
+                size: read_be(data, 65, 2),
+                count: 0,
+            },
+            level: Palette {
+                offset: level_offset,
+                size: level_end.saturating_sub(level_offset),
+                count: 0,
+            },
+        })
+    }
+}
+
+/// Reads bits most significant first, with zeros past the end like the reference decoder.
+struct BitReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8], palette: Palette) -> Result<Self, Error> {
+        let data = palette
+            .offset
+            .checked_add(palette.size)
+            .and_then(|end| data.get(palette.offset..end))
+            .ok_or_else(|| invalid("section out of bounds"))?;
+        Ok(BitReader { data, position: 0 })
+    }
+
+    fn read_bit(&mut self) -> usize {
+        let byte = self.data.get(self.position / 8).copied().unwrap_or(0);
+        self.position += 1;
+        ((byte >> (7 - (self.position - 1) % 8)) & 1) as usize
+    }
+
+    fn read_bits(&mut self, count: u32) -> usize {
+        (0..count).fold(0, |value, _| (value << 1) | self.read_bit())
+    }
+}
+
+/// A canonical Huffman code.
+struct Huffman {
+    counts: [usize; MAX_CODE_SIZE + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(code_sizes: &[u8]) -> Result<Self, Error> {
+        let mut counts = [0; MAX_CODE_SIZE + 1];
+        for &size in code_sizes {
+            *counts.get_mut(size as usize).ok_or_else(|| invalid("code size too large"))? += 1;
+        }
+        counts[0] = 0;
+        let mut symbols: Vec<u16> = (0..code_sizes.len() as u16).filter(|&s| code_sizes[s as usize] != 0).collect();
+        symbols.sort_by_key(|&symbol| code_sizes[symbol as usize]);
+        Ok(Huffman { counts, symbols })
+    }
+
+    /// Reads a code size table, run length encoded with a Huffman code of its own.
+    fn read(reader: &mut BitReader) -> Result<Self, Error> {
+        let total = reader.read_bits(MAX_SYMBOLS_BITS);
+        if total == 0 {
+            return Huffman::new(&[]);
+        }
+        let sent = reader.read_bits(5);
+        if !(1..=CODE_SIZE_ORDER.len()).contains(&sent) {
+            return Err(invalid("bad code size count"));
+        }
+        let mut size_code_sizes = [0u8; 21];
+        for &symbol in &CODE_SIZE_ORDER[..sent] {
+            size_code_sizes[symbol] = reader.read_bits(3) as u8;
+        }
+        let size_code = Huffman::new(&size_code_sizes)?;
+
+        let mut sizes = vec![0u8; total];
+        let mut offset = 0;
+        while offset < total {
+            let remaining = total - offset;
+            let (length, repeat) = match size_code.decode(reader)? {
+                code @ 0..=16 => {
+                    sizes[offset] = code as u8;
+                    (1, false)
+                }
+                17 => (reader.read_bits(3) + 3, false),
+                18 => (reader.read_bits(7) + 11, false),
+                19 => (reader.read_bits(2) + 3, true),
+                20 => (reader.read_bits(6) + 7, true),
+                _ => return Err(invalid("bad code size")),
+            };
+            if length > remaining {
+                return Err(invalid("code sizes overflow"));
+            }
+            if repeat {
+                let previous = offset.checked_sub(1).map(|i| sizes[i]).unwrap_or(0);
+                if previous == 0 {
+                    return Err(invalid("repeated code size without a previous one"));
+                }
+                sizes[offset..offset + length].fill(previous);
+            }
+            offset += length;
+        }
+        Huffman::new(&sizes)
+    }
+
+    fn decode(&self, reader: &mut BitReader) -> Result<usize, Error> {
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= reader.read_bit();
+            if code < first + count {
+                return Ok(self.symbols[index + code - first] as usize);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("bad Huffman code"))
+    }
+}
+
+/// Adds a delta to a palette index, wrapping around the palette like the encoder does.
+fn advance(index: usize, delta: usize, count: usize) -> Result<usize, Error> {
+    let index = index + delta;
+    let index = if index >= count { index - count } else { index };
+    if index < count {
+        Ok(index)
+    } else {
+        Err(invalid("palette index out of bounds"))
+    }
+}
+
+fn entry<T: Copy>(palette: &[T], index: usize) -> Result<T, Error> {
+    palette.get(index).copied().ok_or_else(|| invalid("palette index out of bounds"))
+}
+
+struct Crunch<'a> {
+    data: &'a [u8],
+    header: Header,
+    unity: bool,
+    etc: bool,
+    /// The Huffman code of the chunk encodings, or of the block references in the Unity variant.
+    reference_code: Huffman,
+    endpoint_codes: [Option<Huffman>; 2],
+    selector_codes: [Option<Huffman>; 2],
+    color_endpoints: Vec<[u8; 4]>,
+    /// ETC selectors are stored twice, once transposed for blocks that aren't flipped.
+    color_selectors: Vec<[u8; 4]>,
+    alpha_endpoints: Vec<[u8; 2]>,
+    alpha_selectors: Vec<[u8; 6]>,
+}
+
+impl<'a> Crunch<'a> {
+    fn new(data: &'a [u8], unity: bool) -> Result<Self, Error> {
+        let header = Header::read(data)?;
+        let etc = matches!(header.format, FORMAT_ETC1 | FORMAT_ETC2A);
+        if etc && !unity {
+            return Err(invalid("ETC data needs the Unity crunch format"));
+        }
+
+        let mut reader = BitReader::new(data, header.tables)?;
+        let reference_code = Huffman::read(&mut reader)?;
+        let mut endpoint_codes = [None, None];
+        let mut selector_codes = [None, None];
+        for (i, palette) in [header.color_endpoints, header.alpha_endpoints].iter().enumerate() {
+            if palette.count > 0 {
+                endpoint_codes[i] = Some(Huffman::read(&mut reader)?);
+                selector_codes[i] = Some(Huffman::read(&mut reader)?);
+            }
+        }
+
+        let mut crunch = Crunch {
+            data,
+            header,
+            unity,
+            etc,
+            reference_code,
+            endpoint_codes,
+            selector_codes,
+            color_endpoints: Vec::new(),
+            color_selectors: Vec::new(),
+            alpha_endpoints: Vec::new(),
+            alpha_selectors: Vec::new(),
+        };
+        if crunch.header.color_endpoints.count > 0 {
+            crunch.read_color_endpoints()?;
+            crunch.read_color_selectors()?;
+        }
+        if crunch.header.alpha_endpoints.count > 0 {
+            crunch.read_alpha_endpoints()?;
+            crunch.read_alpha_selectors()?;
+        }
+        Ok(crunch)
+    }
+
+    fn read_color_endpoints(&mut self) -> Result<(), Error> {
+        let mut reader = BitReader::new(self.data, self.header.color_endpoints)?;
+        let code = Huffman::read(&mut reader)?;
+        if self.etc {
+            // 5 bit RGB and the intensity table, as bytes that are each delta coded
+            let mut value = 0u32;
+            for _ in 0..self.header.color_endpoints.count {
+                for shift in (0..32).step_by(8) {
+                    value = value.wrapping_add((code.decode(&mut reader)? as u32) << shift);
+                }
+                value &= 0x1f1f_1f1f;
+                self.color_endpoints.push(value.to_le_bytes());
+            }
+            return Ok(());
+        }
+
+        // two RGB565 colors with delta coded channels
+        let green_code = Huffman::read(&mut reader)?;
+        let mut channels = [0usize; 6];
+        for _ in 0..self.header.color_endpoints.count {
+            for (i, channel) in channels.iter_mut().enumerate() {
+                let (code, mask) = if i % 3 == 1 { (&green_code, 63) } else { (&code, 31) };
+                *channel = (*channel + code.decode(&mut reader)?) & mask;
+            }
+            let [r0, g0, b0, r1, g1, b1] = channels.map(|c| c as u32);
+            let value = b0 | (g0 << 5) | (r0 << 11) | (b1 << 16) | (g1 << 21) | (r1 << 27);
+            self.color_endpoints.push(value.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    fn read_color_selectors(&mut self) -> Result<(), Error> {
+        let mut reader = BitReader::new(self.data, self.header.color_selectors)?;
+        let code = Huffman::read(&mut reader)?;
+        let mut linear = 0u32;
+        let mut pixels = [0usize; 16];
+        for _ in 0..self.header.color_selectors.count {
+            if !self.unity {
+                // each symbol holds the deltas of two pixels, from -3 to 3
+                for pair in pixels.chunks_exact_mut(2) {
+                    let symbol = code.decode(&mut reader)?;
+                    pair[0] = (pair[0] + symbol % 7 + 1) & 3;
+                    pair[1] = (pair[1] + symbol / 7 + 1) & 3;
+                }
+                let value = pixels
+                    .iter()
+                    .enumerate()
+                    .fold(0, |value, (i, &pixel)| value | (DXT1_FROM_LINEAR[pixel] << (i * 2)));
+                self.color_selectors.push(value.to_le_bytes());
+                continue;
+            }
+
+            // the Unity variant xors each selector with the previous one
+            for shift in (0..32).step_by(4) {
+                linear ^= (code.decode(&mut reader)? as u32) << shift;
+            }
+            if !self.etc {
+                let value = ((linear ^ (linear << 1)) & 0xaaaa_aaaa) | ((linear >> 1) & 0x5555_5555);
+                self.color_selectors.push(value.to_le_bytes());
+                continue;
+            }
+            let indices = (!linear & 0xaaaa_aaaa) | (!(linear ^ (linear >> 1)) & 0x5555_5555);
+            for transposed in [true, false] {
+                let mut value = 0u32;
+                for row in 0..4 {
+                    for column in 0..4 {
+                        let pixel = if transposed { column * 4 + row } else { row * 4 + column };
+                        let index = (indices >> (pixel * 2)) & 3;
+                        let bit = column * 4 + row;
+                        value |= ((index >> 1) << (bit + 16)) | ((index & 1) << bit);
+                    }
+                }
+                self.color_selectors.push(value.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn read_alpha_endpoints(&mut self) -> Result<(), Error> {
+        let mut reader = BitReader::new(self.data, self.header.alpha_endpoints)?;
+        let code = Huffman::read(&mut reader)?;
+        let (mut a, mut b) = (0usize, 0usize);
+        for _ in 0..self.header.alpha_endpoints.count {
+            a = (a + code.decode(&mut reader)?) & 0xff;
+            b = (b + code.decode(&mut reader)?) & 0xff;
+            self.alpha_endpoints.push([a as u8, b as u8]);
+        }
+        Ok(())
+    }
+
+    fn read_alpha_selectors(&mut self) -> Result<(), Error> {
+        let mut reader = BitReader::new(self.data, self.header.alpha_selectors)?;
+        let code = Huffman::read(&mut reader)?;
+        let mut pixels = [0usize; 16];
+        let mut linear = [0usize; 8];
+        for _ in 0..self.header.alpha_selectors.count {
+            if !self.unity {
+                // each symbol holds the deltas of two pixels, from -7 to 7
+                for pair in pixels.chunks_exact_mut(2) {
+                    let symbol = code.decode(&mut reader)?;
+                    pair[0] = (pair[0] + symbol % 15 + 1) & 7;
+                    pair[1] = (pair[1] + symbol / 15 + 1) & 7;
+                }
+            } else {
+                // the Unity variant xors each pair of pixels with the previous selector's
+                for (i, pair) in linear.iter_mut().enumerate() {
+                    *pair ^= code.decode(&mut reader)?;
+                    pixels[i * 2] = *pair & 7;
+                    pixels[i * 2 + 1] = (*pair >> 3) & 7;
+                }
+            }
+
+            if !self.etc {
+                let value = pixels
+                    .iter()
+                    .enumerate()
+                    .fold(0u64, |value, (i, &pixel)| value | (DXT5_FROM_LINEAR[pixel] << (i * 3)));
+                self.alpha_selectors.push(value.to_le_bytes()[..6].try_into().unwrap());
+                continue;
+            }
+            for transposed in [true, false] {
+                let mut value = 0u64;
+                for (pixel, &selector) in pixels.iter().enumerate() {
+                    let (row, column) = (pixel / 4, pixel % 4);
+                    let index = if transposed { row * 4 + column } else { column * 4 + row };
+                    let selector = if selector <= 3 { 3 - selector } else { selector } as u64;
+                    value |= selector << (45 - 3 * index);
+                }
+                self.alpha_selectors.push(value.to_be_bytes()[2..].try_into().unwrap());
+            }
+        }
+        Ok(())
+    }
+
+    fn code(codes: &[Option<Huffman>; 2], index: usize) -> Result<&Huffman, Error> {
+        codes[index].as_ref().ok_or_else(|| invalid("missing palette"))
+    }
+
+    /// Unpacks the first mip level to blocks.
+    fn unpack(&self) -> Result<Vec<u8>, Error> {
+        let alpha = match self.header.format {
+            FORMAT_DXT1 | FORMAT_ETC1 => false,
+            FORMAT_DXT5..=FORMAT_DXT5_AGBR | FORMAT_ETC2A => true,
+            format => return Err(Error::Message(format!("crunch format {format} is not supported"))),
+        };
+        let blocks_x = self.header.width.div_ceil(4).max(1);
+        let blocks_y = self.header.height.div_ceil(4).max(1);
+        let block_size = if alpha { 16 } else { 8 };
+        let mut blocks = vec![0u8; blocks_x * blocks_y * block_size];
+        let mut reader = BitReader::new(self.data, self.header.level)?;
+
+        let mut write = |x: usize, y: usize, parts: &[&[u8]]| {
+            if x < blocks_x && y < blocks_y {
+                let mut offset = (y * blocks_x + x) * block_size;
+                for part in parts {
+                    blocks[offset..offset + part.len()].copy_from_slice(part);
+                    offset += part.len();
+                }
+            }
+        };
+        match (self.unity, self.etc) {
+            (false, _) => self.unpack_chunks(&mut reader, blocks_x, blocks_y, alpha, &mut write)?,
+            (true, false) => self.unpack_dxt(&mut reader, blocks_x, blocks_y, alpha, &mut write)?,
+            (true, true) => self.unpack_etc(&mut reader, blocks_x, blocks_y, alpha, &mut write)?,
+        }
+        Ok(blocks)
+    }
+
+    /// The original format, which shares endpoints between the blocks of 2x2 chunks.
+    fn unpack_chunks(
+        &self,
+        reader: &mut BitReader,
+        blocks_x: usize,
+        blocks_y: usize,
+        alpha: bool,
+        write: &mut impl FnMut(usize, usize, &[&[u8]]),
+    ) -> Result<(), Error> {
+        let (chunks_x, chunks_y) = (blocks_x.div_ceil(2), blocks_y.div_ceil(2));
+        let mut encodings = 1;
+        let (mut color_endpoint, mut color_selector, mut alpha_endpoint, mut alpha_selector) = (0, 0, 0, 0);
+        for chunk_y in 0..chunks_y {
+            for i in 0..chunks_x {
+                // rows of chunks go back and forth
+                let chunk_x = if chunk_y % 2 == 1 { chunks_x - 1 - i } else { i };
+                if encodings == 1 {
+                    encodings = self.reference_code.decode(reader)? | 512;
+                }
+                let encoding = encodings & 7;
+                encodings >>= 3;
+
+                let tiles = CHUNK_TILE_COUNTS[encoding];
+                let mut colors = [[0u8; 4]; 4];
+                for color in colors.iter_mut().take(tiles) {
+                    let delta = Self::code(&self.endpoint_codes, 0)?.decode(reader)?;
+                    color_endpoint = advance(color_endpoint, delta, self.color_endpoints.len())?;
+                    *color = self.color_endpoints[color_endpoint];
+                }
+                let mut alphas = [[0u8; 2]; 4];
+                if alpha {
+                    for value in alphas.iter_mut().take(tiles) {
+                        let delta = Self::code(&self.endpoint_codes, 1)?.decode(reader)?;
+                        alpha_endpoint = advance(alpha_endpoint, delta, self.alpha_endpoints.len())?;
+                        *value = self.alpha_endpoints[alpha_endpoint];
+                    }
+                }
+
+                for (block, &tile) in CHUNK_TILES[encoding].iter().enumerate() {
+                    let (x, y) = (chunk_x * 2 + block % 2, chunk_y * 2 + block / 2);
+                    let delta = Self::code(&self.selector_codes, 0)?.decode(reader)?;
+                    color_selector = advance(color_selector, delta, self.color_selectors.len())?;
+                    let selector = self.color_selectors[color_selector];
+                    if alpha {
+                        let delta = Self::code(&self.selector_codes, 1)?.decode(reader)?;
+                        alpha_selector = advance(alpha_selector, delta, self.alpha_selectors.len())?;
+                        let alpha_selectors = self.alpha_selectors[alpha_selector];
+                        write(x, y, &[&alphas[tile], &alpha_selectors, &colors[tile], &selector]);
+                    } else {
+                        write(x, y, &[&colors[tile], &selector]);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the 2 bit references of the block and of the block below it in the Unity variant.
+    ///
+    /// 0 means a new endpoint, 1 the endpoint of the block to the left and 2 the one above.
+    fn read_references(&self, reader: &mut BitReader, group: &mut usize, x: usize) -> Result<[usize; 2], Error> {
+        if x.is_multiple_of(2) {
+            *group = self.reference_code.decode(reader)?;
+        }
+        let references = [*group & 3, (*group >> 2) & 3];
+        *group >>= 4;
+        Ok(references)
+    }
+
+    fn unpack_dxt(
+        &self,
+        reader: &mut BitReader,
+        blocks_x: usize,
+        blocks_y: usize,
+        alpha: bool,
+        write: &mut impl FnMut(usize, usize, &[&[u8]]),
+    ) -> Result<(), Error> {
+        // the stream always covers an even number of blocks
+        let (width, height) = (blocks_x.next_multiple_of(2), blocks_y.next_multiple_of(2));
+        // the reference, color endpoint and alpha endpoint of the block above
+        let mut above = vec![(0, 0, 0); width];
+        let (mut color_endpoint, mut alpha_endpoint) = (0, 0);
+        let mut group = 0;
+        for y in 0..height {
+            for (x, above) in above.iter_mut().enumerate() {
+                let reference = if y % 2 == 1 {
+                    above.0
+                } else {
+                    let [reference, below] = self.read_references(reader, &mut group, x)?;
+                    above.0 = below;
+                    reference
+                };
+                match reference {
+                    0 => {
+                        let delta = Self::code(&self.endpoint_codes, 0)?.decode(reader)?;
+                        color_endpoint = advance(color_endpoint, delta, self.color_endpoints.len())?;
+                        if alpha {
+                            let delta = Self::code(&self.endpoint_codes, 1)?.decode(reader)?;
+                            alpha_endpoint = advance(alpha_endpoint, delta, self.alpha_endpoints.len())?;
+                        }
+                        (above.1, above.2) = (color_endpoint, alpha_endpoint);
+                    }
+                    1 => (above.1, above.2) = (color_endpoint, alpha_endpoint),
+                    _ => (color_endpoint, alpha_endpoint) = (above.1, above.2),
+                }
+
+                let color_selector = Self::code(&self.selector_codes, 0)?.decode(reader)?;
+                let colors = [entry(&self.color_endpoints, color_endpoint)?, entry(&self.color_selectors, color_selector)?];
+                if alpha {
+                    let alpha_selector = Self::code(&self.selector_codes, 1)?.decode(reader)?;
+                    let alpha_endpoint = entry(&self.alpha_endpoints, alpha_endpoint)?;
+                    let alpha_selector = entry(&self.alpha_selectors, alpha_selector)?;
+                    write(x, y, &[&alpha_endpoint, &alpha_selector, &colors[0], &colors[1]]);
+                } else {
+                    write(x, y, &[&colors[0], &colors[1]]);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// ETC blocks can use a second endpoint for their second subblock,
+    /// and can also reference the endpoint of the block diagonally above.
+    fn unpack_etc(
+        &self,
+        reader: &mut BitReader,
+        blocks_x: usize,
+        blocks_y: usize,
+        alpha: bool,
+        write: &mut impl FnMut(usize, usize, &[&[u8]]),
+    ) -> Result<(), Error> {
+        let (width, height) = (blocks_x.next_multiple_of(2), blocks_y.next_multiple_of(2));
+        // the reference and endpoints of both subblocks of the block above
+        let mut above = vec![(0, [0usize; 2], [0usize; 2]); width];
+        let (mut color_endpoint, mut alpha_endpoint) = (0, 0);
+        let (mut diagonal_color, mut diagonal_alpha) = (0, 0);
+        for y in 0..height {
+            for (x, above) in above.iter_mut().enumerate() {
+                let mut reference = if y % 2 == 1 {
+                    above.0
+                } else {
+                    let group = self.reference_code.decode(reader)?;
+                    above.0 = ((group >> 2) & 3) | ((group >> 4) & 12);
+                    (group & 3) | ((group >> 2) & 12)
+                };
+                match reference & 3 {
+                    0 => {
+                        let delta = Self::code(&self.endpoint_codes, 0)?.decode(reader)?;
+                        color_endpoint = advance(color_endpoint, delta, self.color_endpoints.len())?;
+                        if alpha {
+                            let delta = Self::code(&self.endpoint_codes, 1)?.decode(reader)?;
+                            alpha_endpoint = advance(alpha_endpoint, delta, self.alpha_endpoints.len())?;
+                        }
+                        (above.1[0], above.2[0]) = (color_endpoint, alpha_endpoint);
+                    }
+                    1 => (above.1[0], above.2[0]) = (color_endpoint, alpha_endpoint),
+                    3 => {
+                        (color_endpoint, alpha_endpoint) = (diagonal_color, diagonal_alpha);
+                        (above.1[0], above.2[0]) = (color_endpoint, alpha_endpoint);
+                    }
+                    _ => (color_endpoint, alpha_endpoint) = (above.1[0], above.2[0]),
+                }
+                reference >>= 2;
+
+                let e0 = entry(&self.color_endpoints, color_endpoint)?;
+                let color_selector = Self::code(&self.selector_codes, 0)?.decode(reader)?;
+                let alpha_selector = if alpha {
+                    Self::code(&self.selector_codes, 1)?.decode(reader)?
+                } else {
+                    0
+                };
+                if reference != 0 {
+                    let delta = Self::code(&self.endpoint_codes, 0)?.decode(reader)?;
+                    color_endpoint = advance(color_endpoint, delta, self.color_endpoints.len())?;
+                }
+                let e1 = entry(&self.color_endpoints, color_endpoint)?;
+                (diagonal_color, diagonal_alpha) = (above.1[1], above.2[1]);
+                (above.1[1], above.2[1]) = (color_endpoint, alpha_endpoint);
+
+                // the differential mode is used when the second color fits in a 3 bit delta
+                let flip = ((reference >> 1) ^ 1) as u8;
+                let differential = (0..3).all(|c| e0[c] + 3 >= e1[c] && e1[c] + 4 >= e0[c]);
+                let mut block = [0u8; 4];
+                for c in 0..3 {
+                    block[c] = if differential {
+                        (e0[c] << 3) | (e1[c].wrapping_sub(e0[c]) & 7)
+                    } else {
+                        ((e0[c] << 3) & 0xf0) | (e1[c] >> 1)
+                    };
+                }
+                block[3] = (e0[3] << 5) | (e1[3] << 2) | ((differential as u8) << 1) | flip;
+                let selector = entry(&self.color_selectors, color_selector * 2 + flip as usize)?;
+                if alpha {
+                    let alpha_endpoint = entry(&self.alpha_endpoints, alpha_endpoint)?;
+                    let alpha_selector = entry(&self.alpha_selectors, alpha_selector * 2 + flip as usize)?;
+                    write(x, y, &[&alpha_endpoint, &alpha_selector, &block, &selector]);
+                } else {
+                    write(x, y, &[&block, &selector]);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Unpacks the first mip level of crunched data to DXT or ETC blocks.
+///
+/// `unity` selects the variant that Unity writes since 2017.3, which all ETC data uses.
+pub(super) fn unpack(data: &[u8], unity: bool) -> Result<Vec<u8>, Error> {
+    Crunch::new(data, unity)?.unpack()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes bits most significant first.
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        bits: usize,
+    }
+
+    impl BitWriter {
+        fn write(&mut self, value: usize, count: usize) {
+            for i in (0..count).rev() {
+                if self.bits.is_multiple_of(8) {
+                    self.bytes.push(0);
+                }
+                self.bytes[self.bits / 8] |= (((value >> i) & 1) as u8) << (7 - self.bits % 8);
+                self.bits += 1;
+            }
+        }
+
+        /// Writes a code where all `count` symbols have the same size, which it returns.
+        fn code(&mut self, count: usize) -> usize {
+            let size = (count.next_power_of_two().trailing_zeros() as usize).max(1);
+            self.write(count, MAX_SYMBOLS_BITS as usize);
+            self.write(CODE_SIZE_ORDER.len(), 5);
+            for symbol in CODE_SIZE_ORDER {
+                self.write((symbol == size) as usize, 3);
+            }
+            // the only code size has the 1 bit code 0
+            for _ in 0..count {
+                self.write(0, 1);
+            }
+            size
+        }
+    }
+
+    fn file(format: u8, width: usize, sections: [&BitWriter; 6], counts: [usize; 4]) -> Vec<u8> {
+        let mut data = vec![0u8; HEADER_SIZE];
+        let mut offsets = Vec::new();
+        for section in sections {
+            offsets.push((data.len(), section.bytes.len()));
+            data.extend(&section.bytes);
+        }
+        let mut put = |offset: usize, size: usize, value: usize| {
+            data[offset..offset + size].copy_from_slice(&value.to_be_bytes()[8 - size..]);
+        };
+        put(0, 2, SIGNATURE);
+        put(2, 2, HEADER_SIZE);
+        put(6, 4, HEADER_SIZE + sections.iter().map(|s| s.bytes.len()).sum::<usize>());
+        put(12, 2, width);
+        put(14, 2, width);
+        put(16, 1, 1);
+        put(17, 1, 1);
+        put(18, 1, format as usize);
+        // the tables, the palettes and the level, in that order
+        for (i, count) in counts.iter().enumerate() {
+            put(33 + i * 8, 3, offsets[i + 1].0);
+            put(36 + i * 8, 3, offsets[i + 1].1);
+            put(39 + i * 8, 2, *count);
+        }
+        put(65, 2, offsets[0].1);
+        put(67, 3, offsets[0].0);
+        put(70, 4, offsets[5].0);
+        data
+    }
+
+    /// Two RGB565 endpoint pairs: red and black, then green and black.
+    fn dxt_endpoints() -> BitWriter {
+        let mut endpoints = BitWriter::default();
+        let (size5, size6) = (endpoints.code(32), endpoints.code(64));
+        for deltas in [[31, 0, 0, 0, 0, 0], [1, 63, 0, 0, 0, 0]] {
+            for (i, delta) in deltas.into_iter().enumerate() {
+                endpoints.write(delta, if i % 3 == 1 { size6 } else { size5 });
+            }
+        }
+        endpoints
+    }
+
+    const RED: [u8; 8] = [0x00, 0xf8, 0, 0, 0, 0, 0, 0];
+    const GREEN: [u8; 8] = [0xe0, 0x07, 0, 0, 0, 0, 0, 0];
+
+    #[test]
+    fn huffman_codes() {
+        let code = Huffman::new(&[2, 1, 3, 3]).unwrap();
+        let mut data = BitWriter::default();
+        // canonical codes: 1 -> 0, 0 -> 10, 2 -> 110, 3 -> 111, written back to back
+        data.write(0b0_1011_0111, 9);
+        let mut reader = BitReader { data: &data.bytes, position: 0 };
+        let symbols: Vec<usize> = (0..4).map(|_| code.decode(&mut reader).unwrap()).collect();
+        assert_eq!(symbols, [1, 0, 2, 3]);
+
+        let mut data = BitWriter::default();
+        data.code(5);
+        data.write(0b011, 3);
+        let mut reader = BitReader { data: &data.bytes, position: 0 };
+        let code = Huffman::read(&mut reader).unwrap();
+        assert_eq!(code.decode(&mut reader).unwrap(), 3);
+    }
+
+    #[test]
+    fn legacy_dxt1() {
+        let mut tables = BitWriter::default();
+        let encoding_size = tables.code(512);
+        let (endpoint_size, selector_size) = (tables.code(2), tables.code(1));
+
+        // one selector whose pixels all stay at the first endpoint
+        let mut selectors = BitWriter::default();
+        let size = selectors.code(49);
+        for _ in 0..8 {
+            selectors.write(24, size);
+        }
+
+        // a single chunk with one endpoint for the top and one for the bottom row
+        let mut level = BitWriter::default();
+        level.write(1, encoding_size);
+        level.write(0, endpoint_size);
+        level.write(1, endpoint_size);
+        for _ in 0..4 {
+            level.write(0, selector_size);
+        }
+
+        let empty = BitWriter::default();
+        let data = file(FORMAT_DXT1, 8, [&tables, &dxt_endpoints(), &selectors, &empty, &empty, &level], [2, 1, 0, 0]);
+        let blocks = unpack(&data, false).unwrap();
+        assert_eq!(blocks, [RED, RED, GREEN, GREEN].concat());
+        assert!(unpack(&data[..HEADER_SIZE - 1], false).is_err());
+    }
+
+    #[test]
+    fn unity_dxt1() {
+        let mut tables = BitWriter::default();
+        let reference_size = tables.code(256);
+        let (endpoint_size, selector_size) = (tables.code(2), tables.code(1));
+
+        let mut selectors = BitWriter::default();
+        let size = selectors.code(16);
+        for _ in 0..8 {
+            selectors.write(0, size);
+        }
+
+        // new, left, above and new endpoint references
+        let mut level = BitWriter::default();
+        level.write(2 << 2 | 1 << 4, reference_size);
+        level.write(0, endpoint_size);
+        level.write(0, selector_size);
+        level.write(0, selector_size);
+        level.write(0, selector_size);
+        level.write(1, endpoint_size);
+        level.write(0, selector_size);
+
+        let empty = BitWriter::default();
+        let data = file(FORMAT_DXT1, 8, [&tables, &dxt_endpoints(), &selectors, &empty, &empty, &level], [2, 1, 0, 0]);
+        assert_eq!(unpack(&data, true).unwrap(), [RED, RED, RED, GREEN].concat());
+    }
+
+    #[test]
+    fn unity_etc1() {
+        let mut tables = BitWriter::default();
+        let reference_size = tables.code(256);
+        let (endpoint_size, selector_size) = (tables.code(1), tables.code(1));
+
+        // 5 bit red with intensity table 0
+        let mut endpoints = BitWriter::default();
+        let size = endpoints.code(32);
+        for delta in [31, 0, 0, 0] {
+            endpoints.write(delta, size);
+        }
+        // the first linear selector is the most negative modifier
+        let mut selectors = BitWriter::default();
+        let size = selectors.code(16);
+        for _ in 0..8 {
+            selectors.write(0, size);
+        }
+
+        let mut level = BitWriter::default();
+        level.write(2 << 2, reference_size);
+        level.write(0, endpoint_size);
+        level.write(0, selector_size);
+        level.write(1 | 2 << 2, reference_size);
+        for _ in 0..3 {
+            level.write(0, selector_size);
+        }
+
+        let empty = BitWriter::default();
+        let data = file(FORMAT_ETC1, 4, [&tables, &endpoints, &selectors, &empty, &empty, &level], [1, 1, 0, 0]);
+        let blocks = unpack(&data, true).unwrap();
+        assert_eq!(blocks, [0xf8, 0, 0, 0b11, 0xff, 0xff, 0xff, 0xff]);
+        assert!(unpack(&data, false).is_err());
+    }
+
+    #[test]
+    fn unity_versions() {
+        assert!(uses_unity_format("2017.3.0f3"));
+        assert!(uses_unity_format("2019.4.40f1"));
+        assert!(!uses_unity_format("2017.2.5f1"));
+        assert!(!uses_unity_format("5.6.7f1"));
+        assert!(!uses_unity_format(""));
+    }
+}
@@ -0,0 +1,349 @@
+// ETC1, ETC2 and EAC block decoders, following the Khronos data format specification
+use super::decode_blocks;
+use crate::Error;
+
+const ETC1_MODIFIERS: [[i32; 2]; 8] = [
+    [2, 8],
+    [5, 17],
+    [9, 29],
+    [13, 42],
+    [18, 60],
+    [24, 80],
+    [33, 106],
+    [47, 183],
+];
+
+const ETC2_DISTANCES: [i32; 8] = [3, 6, 11, 16, 23, 32, 41, 64];
+
+const EAC_MODIFIERS: [[i32; 8]; 16] = [
+    [-3, -6, -9, -15, 2, 5, 8, 14],
+    [-3, -7, -10, -13, 2, 6, 9, 12],
+    [-2, -5, -8, -13, 1, 4, 7, 12],
+    [-2, -4, -6, -13, 1, 3, 5, 12],
+    [-3, -6, -8, -12, 2, 5, 7, 11],
+    [-3, -7, -9, -11, 2, 6, 8, 10],
+    [-4, -7, -8, -11, 3, 6, 7, 10],
+    [-3, -5, -8, -11, 2, 4, 7, 10],
+    [-2, -6, -8, -10, 1, 5, 7, 9],
+    [-2, -5, -8, -10, 1, 4, 7, 9],
+    [-2, -4, -8, -10, 1, 3, 7, 9],
+    [-2, -5, -7, -10, 1, 4, 6, 9],
+    [-3, -4, -7, -10, 2, 3, 6, 9],
+    [-1, -2, -3, -10, 0, 1, 2, 9],
+    [-4, -6, -8, -9, 3, 5, 7, 8],
+    [-3, -5, -7, -9, 2, 4, 6, 8],
+];
+
+/// Bits `last..=first` of a big endian block, counted from the least significant bit.
+fn bits(block: u64, last: u32, first: u32) -> i32 {
+    ((block >> first) & ((1 << (last - first + 1)) - 1)) as i32
+}
+
+fn extend4(value: i32) -> i32 {
+    (value << 4) | value
+}
+
+fn extend5(value: i32) -> i32 {
+    (value << 3) | (value >> 2)
+}
+
+fn extend6(value: i32) -> i32 {
+    (value << 2) | (value >> 4)
+}
+
+fn extend7(value: i32) -> i32 {
+    (value << 1) | (value >> 6)
+}
+
+fn clamp(value: i32) -> u8 {
+    value.clamp(0, 255) as u8
+}
+
+fn offset(color: [i32; 3], offset: i32) -> [u8; 4] {
+    [clamp(color[0] + offset), clamp(color[1] + offset), clamp(color[2] + offset), 255]
+}
+
+/// ETC pixels are stored column by column, this maps a row major pixel to its bit.
+fn pixel_bit(pixel: usize) -> usize {
+    (pixel % 4) * 4 + pixel / 4
+}
+
+/// The 2 bit index of a pixel, split into a most significant half and a least significant half.
+fn pixel_index(block: u64, pixel: usize) -> usize {
+    let bit = pixel_bit(pixel);
+    ((((block >> (bit + 16)) & 1) << 1) | ((block >> bit) & 1)) as usize
+}
+
+/// Decodes an ETC2 RGB block, which ETC1 is a subset of.
+///
+/// With `punchthrough` the differential bit is the opaque flag of ETC2 RGBA1 instead.
+fn decode_etc2_block(block: &[u8], pixels: &mut [[u8; 4]], punchthrough: bool) {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let differential = (block >> 33) & 1 == 1;
+    let flip = (block >> 32) & 1 == 1;
+    let opaque = !punchthrough || differential;
+
+    if !punchthrough && !differential {
+        let base = [
+            [extend4(bits(block, 63, 60)), extend4(bits(block, 55, 52)), extend4(bits(block, 47, 44))],
+            [extend4(bits(block, 59, 56)), extend4(bits(block, 51, 48)), extend4(bits(block, 43, 40))],
+        ];
+        return decode_subblocks(block, pixels, base, flip, true);
+    }
+
+    let r = bits(block, 63, 59);
+    let g = bits(block, 55, 51);
+    let b = bits(block, 47, 43);
+    let sign_extend = |value: i32| (value << 29) >> 29;
+    let (r2, g2, b2) = (
+        r + sign_extend(bits(block, 58, 56)),
+        g + sign_extend(bits(block, 50, 48)),
+        b + sign_extend(bits(block, 42, 40)),
+    );
+
+    if !(0..32).contains(&r2) {
+        decode_t_block(block, pixels, opaque);
+    } else if !(0..32).contains(&g2) {
+        decode_h_block(block, pixels, opaque);
+    } else if !(0..32).contains(&b2) {
+        decode_planar_block(block, pixels);
+    } else {
+        let base = [[extend5(r), extend5(g), extend5(b)], [extend5(r2), extend5(g2), extend5(b2)]];
+        decode_subblocks(block, pixels, base, flip, opaque);
+    }
+}
+
+fn decode_subblocks(block: u64, pixels: &mut [[u8; 4]], base: [[i32; 3]; 2], flip: bool, opaque: bool) {
+    let tables = [bits(block, 39, 37) as usize, bits(block, 36, 34) as usize];
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (i % 4, i / 4);
+        let subblock = if flip { y / 2 } else { x / 2 };
+        let [small, large] = ETC1_MODIFIERS[tables[subblock]];
+        let index = pixel_index(block, i);
+        *pixel = match index {
+            // punch-through blocks replace the small modifiers with transparency and the base color
+            2 if !opaque => [0, 0, 0, 0],
+            0 if !opaque => offset(base[subblock], 0),
+            0 => offset(base[subblock], small),
+            1 => offset(base[subblock], large),
+            2 => offset(base[subblock], -small),
+            _ => offset(base[subblock], -large),
+        };
+    }
+}
+
+fn paint_pixels(block: u64, pixels: &mut [[u8; 4]], paint: [[u8; 4]; 4], opaque: bool) {
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let index = pixel_index(block, i);
+        *pixel = if index == 2 && !opaque { [0, 0, 0, 0] } else { paint[index] };
+    }
+}
+
+fn decode_t_block(block: u64, pixels: &mut [[u8; 4]], opaque: bool) {
+    let c1 = [
+        extend4((bits(block, 60, 59) << 2) | bits(block, 57, 56)),
+        extend4(bits(block, 55, 52)),
+        extend4(bits(block, 51, 48)),
+    ];
+    let c2 = [extend4(bits(block, 47, 44)), extend4(bits(block, 43, 40)), extend4(bits(block, 39, 36))];
+    let distance = ETC2_DISTANCES[((bits(block, 35, 34) << 1) | bits(block, 32, 32)) as usize];
+    let paint = [offset(c1, 0), offset(c2, distance), offset(c2, 0), offset(c2, -distance)];
+    paint_pixels(block, pixels, paint, opaque);
+}
+
+fn decode_h_block(block: u64, pixels: &mut [[u8; 4]], opaque: bool) {
+    let (r1, g1, b1) = (
+        bits(block, 62, 59),
+        (bits(block, 58, 56) << 1) | bits(block, 52, 52),
+        (bits(block, 51, 51) << 3) | bits(block, 49, 47),
+    );
+    let (r2, g2, b2) = (bits(block, 46, 43), bits(block, 42, 39), bits(block, 38, 35));
+    // the order of the colors stores the lowest bit of the distance
+    let order = ((r1 << 8) | (g1 << 4) | b1) >= ((r2 << 8) | (g2 << 4) | b2);
+    let index = (bits(block, 34, 34) << 2) | (bits(block, 32, 32) << 1) | order as i32;
+    let distance = ETC2_DISTANCES[index as usize];
+
+    let c1 = [extend4(r1), extend4(g1), extend4(b1)];
+    let c2 = [extend4(r2), extend4(g2), extend4(b2)];
+    let paint = [offset(c1, distance), offset(c1, -distance), offset(c2, distance), offset(c2, -distance)];
+    paint_pixels(block, pixels, paint, opaque);
+}
+
+fn decode_planar_block(block: u64, pixels: &mut [[u8; 4]]) {
+    let origin = [
+        extend6(bits(block, 62, 57)),
+        extend7((bits(block, 56, 56) << 6) | bits(block, 54, 49)),
+        extend6((bits(block, 48, 48) << 5) | (bits(block, 44, 43) << 3) | bits(block, 41, 39)),
+    ];
+    let horizontal = [
+        extend6((bits(block, 38, 34) << 1) | bits(block, 32, 32)),
+        extend7(bits(block, 31, 25)),
+        extend6(bits(block, 24, 19)),
+    ];
+    let vertical = [extend6(bits(block, 18, 13)), extend7(bits(block, 12, 6)), extend6(bits(block, 5, 0))];
+
+    for (i, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = ((i % 4) as i32, (i / 4) as i32);
+        let channel = |c: usize| {
+            clamp((x * (horizontal[c] - origin[c]) + y * (vertical[c] - origin[c]) + 4 * origin[c] + 2) >> 2)
+        };
+        *pixel = [channel(0), channel(1), channel(2), 255];
+    }
+}
+
+/// Decodes an 8 bit EAC block, the alpha channel of ETC2 RGBA8.
+fn decode_eac_block(block: &[u8]) -> [u8; 16] {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let base = bits(block, 63, 56);
+    let multiplier = bits(block, 55, 52);
+    let modifiers = EAC_MODIFIERS[bits(block, 51, 48) as usize];
+    std::array::from_fn(|i| {
+        let index = (block >> (45 - 3 * pixel_bit(i))) as usize & 7;
+        clamp(base + modifiers[index] * multiplier)
+    })
+}
+
+/// Decodes an 11 bit EAC block and scales it to 8 bits.
+fn decode_eac11_block(block: &[u8], signed: bool) -> [u8; 16] {
+    let block = u64::from_be_bytes(block[..8].try_into().unwrap());
+    let multiplier = bits(block, 55, 52);
+    let modifiers = EAC_MODIFIERS[bits(block, 51, 48) as usize];
+    std::array::from_fn(|i| {
+        let index = (block >> (45 - 3 * pixel_bit(i))) as usize & 7;
+        // a multiplier of 0 scales the modifiers by 1/8 instead
+        let modifier = if multiplier == 0 { modifiers[index] } else { modifiers[index] * multiplier * 8 };
+        if signed {
+            let base = (bits(block, 63, 56) as u8 as i8).max(-127) as i32;
+            let value = (base * 8 + modifier).clamp(-1023, 1023);
+            (((value + 1023) * 255 + 1023) / 2046) as u8
+        } else {
+            let value = (bits(block, 63, 56) * 8 + 4 + modifier).clamp(0, 2047);
+            ((value * 255 + 1023) / 2047) as u8
+        }
+    })
+}
+
+pub(super) fn decode_etc1(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 8, |block, pixels| {
+        decode_etc2_block(block, pixels, false)
+    })
+}
+
+pub(super) fn decode_etc2_rgb(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_etc1(data, width, height)
+}
+
+pub(super) fn decode_etc2_rgba1(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 8, |block, pixels| {
+        decode_etc2_block(block, pixels, true)
+    })
+}
+
+pub(super) fn decode_etc2_rgba8(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        decode_etc2_block(&block[8..], pixels, false);
+        for (pixel, alpha) in pixels.iter_mut().zip(decode_eac_block(block)) {
+            pixel[3] = alpha;
+        }
+    })
+}
+
+pub(super) fn decode_eac_r(data: &[u8], width: usize, height: usize, signed: bool) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 8, |block, pixels| {
+        for (pixel, red) in pixels.iter_mut().zip(decode_eac11_block(block, signed)) {
+            *pixel = [red, 0, 0, 255];
+        }
+    })
+}
+
+pub(super) fn decode_eac_rg(data: &[u8], width: usize, height: usize, signed: bool) -> Result<Vec<u8>, Error> {
+    decode_blocks(data, width, height, 4, 4, 16, |block, pixels| {
+        let red = decode_eac11_block(block, signed);
+        let green = decode_eac11_block(&block[8..], signed);
+        for (i, pixel) in pixels.iter_mut().enumerate() {
+            *pixel = [red[i], green[i], 0, 255];
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_block(block: u64, punchthrough: bool) -> [[u8; 4]; 16] {
+        let mut pixels = [[0u8; 4]; 16];
+        decode_etc2_block(&block.to_be_bytes(), &mut pixels, punchthrough);
+        pixels
+    }
+
+    /// Sets the index of every pixel, given row by row.
+    fn with_indices(mut block: u64, indices: [u64; 16]) -> u64 {
+        for (pixel, index) in indices.into_iter().enumerate() {
+            let bit = pixel_bit(pixel);
+            block |= ((index >> 1) << (bit + 16)) | ((index & 1) << bit);
+        }
+        block
+    }
+
+    #[test]
+    fn individual_mode() {
+        // left subblock 0x88 gray with table 0, right subblock 0x44 gray with table 7
+        let block = (0x8u64 << 60) | (0x4 << 56) | (0x8 << 52) | (0x4 << 48) | (0x8 << 44) | (0x4 << 40) | (7 << 34);
+        let block = with_indices(block, [0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3, 0, 1, 2, 3]);
+        let pixels = decode_block(block, false);
+        assert_eq!(pixels[0], [0x8a, 0x8a, 0x8a, 255]);
+        assert_eq!(pixels[1], [0x90, 0x90, 0x90, 255]);
+        assert_eq!(pixels[2], [0x44 - 47, 0x44 - 47, 0x44 - 47, 255]);
+        assert_eq!(pixels[3], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn differential_flipped() {
+        // top subblock red 31, bottom subblock red 31 - 4, table 0 in both, all indices 0
+        let block = (31u64 << 59) | (0b100 << 56) | (1 << 33) | (1 << 32);
+        let pixels = decode_block(block, false);
+        assert_eq!(pixels[0], [255, 2, 2, 255]);
+        assert_eq!(pixels[15], [extend5(27) as u8 + 2, 2, 2, 255]);
+    }
+
+    #[test]
+    fn t_mode() {
+        // red 31 with a delta of 3 overflows into T mode, c1 = 0xff0000, c2 = 0x00ff00, distance 64
+        let block = (0b11111u64 << 59) | (0b11 << 56) | (0xf << 40) | (0b11 << 34) | (1 << 33) | (1 << 32);
+        let block = with_indices(block, [0, 1, 2, 3, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let pixels = decode_block(block, false);
+        assert_eq!(pixels[0], [255, 0, 0, 255]);
+        assert_eq!(pixels[1], [64, 255, 64, 255]);
+        assert_eq!(pixels[2], [0, 255, 0, 255]);
+        assert_eq!(pixels[3], [0, 191, 0, 255]);
+
+        // without the opaque flag index 2 is transparent
+        let pixels = decode_block(block & !(1 << 33), true);
+        assert_eq!(pixels[2], [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn planar_mode() {
+        // blue 0 with a delta of -4 overflows into planar mode, a horizontal gradient of red
+        let block = (0b100u64 << 40) | (1 << 33) | (0x3f << 33) | (1 << 32);
+        let pixels = decode_block(block, false);
+        assert_eq!(pixels[0], [0, 0, 0, 255]);
+        assert_eq!(pixels[1], [64, 0, 0, 255]);
+        assert_eq!(pixels[3], [191, 0, 0, 255]);
+        assert_eq!(pixels[4], [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn eac() {
+        // base 128, multiplier 1, table 0 and every pixel uses index 7 (+14)
+        let mut block = (128u64 << 56) | (1 << 52);
+        for i in 0..16 {
+            block |= 7 << (45 - 3 * i);
+        }
+        assert_eq!(decode_eac_block(&block.to_be_bytes()), [142; 16]);
+        // 128 * 8 + 4 + 14 * 8 = 1140 of 2047
+        assert_eq!(decode_eac11_block(&block.to_be_bytes(), false), [142; 16]);
+        // -128 clamps to -127, -127 * 8 + 112 = -904
+        assert_eq!(decode_eac11_block(&block.to_be_bytes(), true), [15; 16]);
+    }
+}
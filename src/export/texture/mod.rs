@@ -0,0 +1,399 @@
+mod astc;
+mod bcn;
+mod crunch;
+mod etc;
+mod pvrtc;
+mod swizzle;
+mod uncompressed;
+
+pub(crate) use super::half_to_f32;
+use crate::Error;
+use num_enum::TryFromPrimitive;
+
+/// The `m_TextureFormat` values of `Texture2D`.
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
+#[repr(i32)]
+pub enum TextureFormat {
+    Alpha8 = 1,
+    ARGB4444 = 2,
+    RGB24 = 3,
+    RGBA32 = 4,
+    ARGB32 = 5,
+    ARGBFloat = 6,
+    RGB565 = 7,
+    BGR24 = 8,
+    R16 = 9,
+    DXT1 = 10,
+    DXT3 = 11,
+    DXT5 = 12,
+    RGBA4444 = 13,
+    BGRA32 = 14,
+    RHalf = 15,
+    RGHalf = 16,
+    RGBAHalf = 17,
+    RFloat = 18,
+    RGFloat = 19,
+    RGBAFloat = 20,
+    YUY2 = 21,
+    RGB9e5Float = 22,
+    RGBFloat = 23,
+    BC6H = 24,
+    BC7 = 25,
+    BC4 = 26,
+    BC5 = 27,
+    DXT1Crunched = 28,
+    DXT5Crunched = 29,
+    PVRTC_RGB2 = 30,
+    PVRTC_RGBA2 = 31,
+    PVRTC_RGB4 = 32,
+    PVRTC_RGBA4 = 33,
+    ETC_RGB4 = 34,
+    ATC_RGB4 = 35,
+    ATC_RGBA8 = 36,
+    EAC_R = 41,
+    EAC_R_SIGNED = 42,
+    EAC_RG = 43,
+    EAC_RG_SIGNED = 44,
+    ETC2_RGB = 45,
+    ETC2_RGBA1 = 46,
+    ETC2_RGBA8 = 47,
+    ASTC_RGB_4x4 = 48,
+    ASTC_RGB_5x5 = 49,
+    ASTC_RGB_6x6 = 50,
+    ASTC_RGB_8x8 = 51,
+    ASTC_RGB_10x10 = 52,
+    ASTC_RGB_12x12 = 53,
+    ASTC_RGBA_4x4 = 54,
+    ASTC_RGBA_5x5 = 55,
+    ASTC_RGBA_6x6 = 56,
+    ASTC_RGBA_8x8 = 57,
+    ASTC_RGBA_10x10 = 58,
+    ASTC_RGBA_12x12 = 59,
+    ETC_RGB4_3DS = 60,
+    ETC_RGBA8_3DS = 61,
+    RG16 = 62,
+    R8 = 63,
+    ETC_RGB4Crunched = 64,
+    ETC2_RGBA8Crunched = 65,
+    ASTC_HDR_4x4 = 66,
+    ASTC_HDR_5x5 = 67,
+    ASTC_HDR_6x6 = 68,
+    ASTC_HDR_8x8 = 69,
+    ASTC_HDR_10x10 = 70,
+    ASTC_HDR_12x12 = 71,
+    RG32 = 72,
+    RGB48 = 73,
+    RGBA64 = 74,
+    R8_SIGNED = 75,
+    RG16_SIGNED = 76,
+    RGB24_SIGNED = 77,
+    RGBA32_SIGNED = 78,
+    R16_SIGNED = 79,
+    RG32_SIGNED = 80,
+    RGB48_SIGNED = 81,
+    RGBA64_SIGNED = 82,
+}
+
+impl TextureFormat {
+    /// The format of the blocks that a crunched format unpacks to.
+    pub fn get_uncrunched_format(&self) -> Option<TextureFormat> {
+        use TextureFormat::*;
+        match self {
+            DXT1Crunched => Some(DXT1),
+            DXT5Crunched => Some(DXT5),
+            ETC_RGB4Crunched => Some(ETC_RGB4),
+            ETC2_RGBA8Crunched => Some(ETC2_RGBA8),
+            _ => None,
+        }
+    }
+
+    /// The block size in pixels of ASTC formats.
+    pub fn get_astc_block_size(&self) -> Option<usize> {
+        use TextureFormat::*;
+        match self {
+            ASTC_RGB_4x4 | ASTC_RGBA_4x4 | ASTC_HDR_4x4 => Some(4),
+            ASTC_RGB_5x5 | ASTC_RGBA_5x5 | ASTC_HDR_5x5 => Some(5),
+            ASTC_RGB_6x6 | ASTC_RGBA_6x6 | ASTC_HDR_6x6 => Some(6),
+            ASTC_RGB_8x8 | ASTC_RGBA_8x8 | ASTC_HDR_8x8 => Some(8),
+            ASTC_RGB_10x10 | ASTC_RGBA_10x10 | ASTC_HDR_10x10 => Some(10),
+            ASTC_RGB_12x12 | ASTC_RGBA_12x12 | ASTC_HDR_12x12 => Some(12),
+            _ => None,
+        }
+    }
+
+    /// The width and height in pixels and the size in bytes of the blocks the format is stored in.
+    ///
+    /// Uncompressed formats use blocks of one pixel. PVRTC and crunched data has no such layout.
+    pub fn get_block_layout(&self) -> Option<(usize, usize, usize)> {
+        use TextureFormat::*;
+        if let Some(block_size) = self.get_astc_block_size() {
+            return Some((block_size, block_size, 16));
+        }
+        match self {
+            DXT1 | BC4 | ETC_RGB4 | ETC_RGB4_3DS | ETC2_RGB | ETC2_RGBA1 | EAC_R | EAC_R_SIGNED | ATC_RGB4 => {
+                Some((4, 4, 8))
+            }
+            DXT3 | DXT5 | BC5 | BC6H | BC7 | ETC_RGBA8_3DS | ETC2_RGBA8 | EAC_RG | EAC_RG_SIGNED | ATC_RGBA8 => {
+                Some((4, 4, 16))
+            }
+            YUY2 => Some((2, 1, 4)),
+            PVRTC_RGB2 | PVRTC_RGBA2 | PVRTC_RGB4 | PVRTC_RGBA4 => None,
+            DXT1Crunched | DXT5Crunched | ETC_RGB4Crunched | ETC2_RGBA8Crunched => None,
+            format => uncompressed::pixel_size(*format).map(|size| (1, 1, size)),
+        }
+    }
+}
+
+/// Decodes the first `width` x `height` image in `data` to RGBA8.
+///
+/// Rows are returned in the order they are stored, which for Unity textures is bottom to top.
+/// Channels that a format doesn't have are 0, except for alpha, which is 255.
+/// ASTC blocks with HDR endpoints decode to magenta, as the ASTC specification asks for.
+pub fn decode_texture(data: &[u8], width: usize, height: usize, format: TextureFormat) -> Result<Vec<u8>, Error> {
+    use TextureFormat::*;
+    if let Some(block_size) = format.get_astc_block_size() {
+        return astc::decode_astc(data, width, height, block_size, block_size);
+    }
+    match format {
+        DXT1 => bcn::decode_bc1(data, width, height),
+        DXT3 => bcn::decode_bc2(data, width, height),
+        DXT5 => bcn::decode_bc3(data, width, height),
+        BC4 => bcn::decode_bc4(data, width, height),
+        BC5 => bcn::decode_bc5(data, width, height),
+        BC6H => bcn::decode_bc6h(data, width, height),
+        BC7 => bcn::decode_bc7(data, width, height),
+        ETC_RGB4 => etc::decode_etc1(data, width, height),
+        ETC2_RGB => etc::decode_etc2_rgb(data, width, height),
+        ETC2_RGBA1 => etc::decode_etc2_rgba1(data, width, height),
+        ETC2_RGBA8 => etc::decode_etc2_rgba8(data, width, height),
+        EAC_R => etc::decode_eac_r(data, width, height, false),
+        EAC_R_SIGNED => etc::decode_eac_r(data, width, height, true),
+        EAC_RG => etc::decode_eac_rg(data, width, height, false),
+        EAC_RG_SIGNED => etc::decode_eac_rg(data, width, height, true),
+        PVRTC_RGB2 | PVRTC_RGBA2 => pvrtc::decode_pvrtc(data, width, height, true),
+        PVRTC_RGB4 | PVRTC_RGBA4 => pvrtc::decode_pvrtc(data, width, height, false),
+        DXT1Crunched | DXT5Crunched | ETC_RGB4Crunched | ETC2_RGBA8Crunched => Err(Error::Message(format!(
+            "{format:?} data has to be unpacked with decode_crunched_texture"
+        ))),
+        format => uncompressed::decode(data, width, height, format),
+    }
+}
+
+/// Converts texture data from the tiled layout of a console platform to the layout [`decode_texture`] expects.
+///
+/// `target_platform` is the `m_TargetPlatform` of the file and `platform_blob` the `m_PlatformBlob` of the texture.
+/// Switch and PS4 data is deswizzled, anything else is returned as is.
+/// The returned format is the one the data is actually stored in, as the Switch pads RGB24 and BGR24 to 4 bytes.
+pub fn deswizzle_texture<'a>(
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    format: TextureFormat,
+    target_platform: i32,
+    platform_blob: &[u8],
+) -> Result<(std::borrow::Cow<'a, [u8]>, TextureFormat), Error> {
+    swizzle::deswizzle(data, width, height, format, target_platform, platform_blob)
+}
+
+/// Unpacks crunched `data` and decodes its first image to RGBA8, like [`decode_texture`].
+///
+/// `unity_version` is the version that wrote the texture, as Unity changed the crunch format in 2017.3.
+pub fn decode_crunched_texture(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    format: TextureFormat,
+    unity_version: &str,
+) -> Result<Vec<u8>, Error> {
+    let block_format = format
+        .get_uncrunched_format()
+        .ok_or_else(|| Error::InvalidValue(format!("{format:?} is not a crunched format")))?;
+    // Unity only added crunched ETC formats after it changed the format
+    let unity = matches!(block_format, TextureFormat::ETC_RGB4 | TextureFormat::ETC2_RGBA8)
+        || crunch::uses_unity_format(unity_version);
+    let blocks = crunch::unpack(data, unity)?;
+    decode_texture(&blocks, width, height, block_format)
+}
+
+/// Decodes `data` as blocks of `block_width` x `block_height` pixels that take `block_size` bytes each.
+///
+/// `decode_block` writes the pixels of one block, row by row, as RGBA8.
+pub(crate) fn decode_blocks<F>(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    block_width: usize,
+    block_height: usize,
+    block_size: usize,
+    mut decode_block: F,
+) -> Result<Vec<u8>, Error>
+where
+    F: FnMut(&[u8], &mut [[u8; 4]]),
+{
+    let blocks_x = width.div_ceil(block_width);
+    let blocks_y = height.div_ceil(block_height);
+    check_size(data, blocks_x * blocks_y * block_size)?;
+
+    let mut image = vec![0u8; width * height * 4];
+    let mut pixels = vec![[0u8; 4]; block_width * block_height];
+    for (index, block) in data.chunks_exact(block_size).take(blocks_x * blocks_y).enumerate() {
+        decode_block(block, &mut pixels);
+
+        // blocks at the right and bottom edges can extend past the image
+        let x = (index % blocks_x) * block_width;
+        let y = (index / blocks_x) * block_height;
+        let copy_width = block_width.min(width - x);
+        for row in 0..block_height.min(height - y) {
+            let start = ((y + row) * width + x) * 4;
+            for (target, pixel) in image[start..start + copy_width * 4]
+                .chunks_exact_mut(4)
+                .zip(&pixels[row * block_width..])
+            {
+                target.copy_from_slice(pixel);
+            }
+        }
+    }
+    Ok(image)
+}
+
+pub(crate) fn check_size(data: &[u8], expected: usize) -> Result<(), Error> {
+    if data.len() < expected {
+        Err(Error::InvalidValue(format!(
+            "texture data is {} bytes long, expected at least {expected}",
+            data.len()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// Clamps a float channel to [0, 1] and scales it to 8 bits.
+pub(crate) fn f32_to_u8(value: f32) -> u8 {
+    (value.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+/// A decoded RGBA8 image with the top row first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Wraps RGBA8 data whose rows are stored bottom to top, as Unity does.
+    pub fn from_bottom_up(width: u32, height: u32, mut data: Vec<u8>) -> Self {
+        let stride = width as usize * 4;
+        let rows = height as usize;
+        for row in 0..rows / 2 {
+            let (top, bottom) = data.split_at_mut((rows - row - 1) * stride);
+            top[row * stride..(row + 1) * stride].swap_with_slice(&mut bottom[..stride]);
+        }
+        RgbaImage { width, height, data }
+    }
+
+    /// Encodes the image as a PNG.
+    pub fn to_png<W: std::io::Write>(&self, writer: W) -> Result<(), Error> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.data))
+            .map_err(|e| Error::Message(format!("PNG encoding failed: {e}")))
+    }
+}
+
+#[cfg(feature = "objects")]
+pub trait Texture2DExt {
+    /// Where the image data is stored if it isn't embedded in `image_data`.
+    fn get_image_data_range(&self) -> Option<crate::resource::ResourceRange>;
+
+    /// Decodes the first mip level of the texture from its `image_data`
+    /// or the data read from [`Texture2DExt::get_image_data_range`].
+    ///
+    /// `file` is the file the texture was read from. Its target platform selects how the data is deswizzled
+    /// and its Unity version selects the crunch format.
+    fn decode_image(&self, image_data: &[u8], file: &crate::files::SerializedFile) -> Result<RgbaImage, Error>;
+}
+
+#[cfg(feature = "objects")]
+impl Texture2DExt for crate::objects::classes::Texture2D {
+    fn get_image_data_range(&self) -> Option<crate::resource::ResourceRange> {
+        self.m_StreamData
+            .as_ref()
+            .filter(|stream_data| !stream_data.path.is_empty())
+            .map(crate::resource::ResourceRange::from)
+    }
+
+    fn decode_image(&self, image_data: &[u8], file: &crate::files::SerializedFile) -> Result<RgbaImage, Error> {
+        let format = TextureFormat::try_from(self.m_TextureFormat)
+            .map_err(|e| Error::InvalidValue(format!("unknown texture format {}", e.number)))?;
+        let (width, height) = (self.m_Width.max(0) as usize, self.m_Height.max(0) as usize);
+        let (image_data, format) = deswizzle_texture(
+            image_data,
+            width,
+            height,
+            format,
+            file.m_TargetPlatform.unwrap_or_default(),
+            self.m_PlatformBlob.as_deref().unwrap_or_default(),
+        )?;
+        let data = if format.get_uncrunched_format().is_some() {
+            let unity_version = file.m_UnityVersion.as_deref().unwrap_or_default();
+            decode_crunched_texture(&image_data, width, height, format, unity_version)?
+        } else {
+            decode_texture(&image_data, width, height, format)?
+        };
+        Ok(RgbaImage::from_bottom_up(width as u32, height as u32, data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn half_floats() {
+        assert_eq!(half_to_f32(0x0000), 0.0);
+        assert_eq!(half_to_f32(0x3c00), 1.0);
+        assert_eq!(half_to_f32(0xc000), -2.0);
+        assert_eq!(half_to_f32(0x3800), 0.5);
+        assert_eq!(half_to_f32(0x7bff), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x0200), 2f32.powi(-15));
+        assert!(half_to_f32(0x7c00).is_infinite());
+    }
+
+    #[test]
+    fn blocks_are_clipped() {
+        // a 5x3 image needs 2x1 blocks of 4x4
+        let data = [1u8, 2];
+        let image = decode_blocks(&data, 5, 3, 4, 4, 1, |block, pixels| {
+            pixels.fill([block[0], 0, 0, 255]);
+        })
+        .unwrap();
+        assert_eq!(image.len(), 5 * 3 * 4);
+        for row in image.chunks_exact(5 * 4) {
+            assert_eq!(row[0], 1);
+            assert_eq!(row[3 * 4], 1);
+            assert_eq!(row[4 * 4], 2);
+        }
+        assert!(matches!(decode_blocks(&data, 9, 3, 4, 4, 1, |_, _| {}), Err(Error::InvalidValue(_))));
+    }
+
+    #[test]
+    fn flip_and_png() {
+        let image = RgbaImage::from_bottom_up(1, 3, vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3]);
+        assert_eq!(image.data, vec![3, 3, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1]);
+
+        let mut png = Vec::new();
+        image.to_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+        let decoder = png::Decoder::new(png.as_slice());
+        let mut reader = decoder.read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut decoded).unwrap();
+        assert_eq!(decoded, image.data);
+    }
+}
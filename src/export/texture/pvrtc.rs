@@ -0,0 +1,258 @@
+// PVRTC1 decoder for the 2 and 4 bits per pixel variants
+use super::check_size;
+use crate::Error;
+
+/// Modulation weights out of 8 for the standard 2 bit values.
+const WEIGHTS: [u32; 4] = [0, 3, 5, 8];
+
+/// The two colors of a block, as 5 bit RGB and 4 bit alpha.
+struct Block {
+    a: [u32; 4],
+    b: [u32; 4],
+    modulation: u32,
+    mode: bool,
+}
+
+impl Block {
+    fn read(data: &[u8]) -> Self {
+        let modulation = u32::from_le_bytes(data[..4].try_into().unwrap());
+        let a = u16::from_le_bytes([data[4], data[5]]) as u32;
+        let b = u16::from_le_bytes([data[6], data[7]]) as u32;
+        let a = if a & 0x8000 != 0 {
+            [(a >> 10) & 0x1f, (a >> 5) & 0x1f, (a & 0x1e) | ((a >> 4) & 1), 0xf]
+        } else {
+            [
+                ((a >> 7) & 0x1e) | ((a >> 11) & 1),
+                ((a >> 3) & 0x1e) | ((a >> 7) & 1),
+                ((a << 1) & 0x1c) | ((a >> 2) & 3),
+                (a >> 11) & 0xe,
+            ]
+        };
+        let b = if b & 0x8000 != 0 {
+            [(b >> 10) & 0x1f, (b >> 5) & 0x1f, b & 0x1f, 0xf]
+        } else {
+            [
+                ((b >> 7) & 0x1e) | ((b >> 11) & 1),
+                ((b >> 3) & 0x1e) | ((b >> 7) & 1),
+                ((b << 1) & 0x1e) | ((b >> 3) & 1),
+                (b >> 11) & 0xe,
+            ]
+        };
+        Block {
+            a,
+            b,
+            modulation,
+            mode: data[4] & 1 == 1,
+        }
+    }
+}
+
+/// Blocks are stored in Morton order, with the bits of `y` below those of `x`.
+fn twiddle(x: usize, y: usize, blocks_x: usize, blocks_y: usize) -> usize {
+    let min = blocks_x.min(blocks_y);
+    let mut result = 0;
+    let mut bit = 1;
+    let mut shift = 0;
+    while bit < min {
+        if y & bit != 0 {
+            result |= 1 << (shift * 2);
+        }
+        if x & bit != 0 {
+            result |= 1 << (shift * 2 + 1);
+        }
+        bit <<= 1;
+        shift += 1;
+    }
+    let rest = if blocks_y < blocks_x { x } else { y };
+    result | ((rest >> shift) << (shift * 2))
+}
+
+/// The modulation weight of a pixel, or how 2 bpp blocks interpolate it from their neighbours.
+#[derive(Clone, Copy, PartialEq)]
+enum Modulation {
+    Weight(u32),
+    Punchthrough,
+    Average,
+    Horizontal,
+    Vertical,
+}
+
+fn block_modulation(block: &Block, two_bpp: bool) -> Vec<Modulation> {
+    let mut bits = block.modulation;
+    if !two_bpp {
+        return (0..16)
+            .map(|i| {
+                let value = ((bits >> (i * 2)) & 3) as usize;
+                match (block.mode, value) {
+                    (true, 1) => Modulation::Weight(4),
+                    (true, 2) => Modulation::Punchthrough,
+                    (true, 3) => Modulation::Weight(8),
+                    _ => Modulation::Weight(WEIGHTS[value]),
+                }
+            })
+            .collect();
+    }
+    if !block.mode {
+        return (0..32).map(|i| Modulation::Weight(((bits >> i) & 1) * 8)).collect();
+    }
+
+    // half of the pixels store 2 bit values in a checkerboard, the rest are interpolated
+    let interpolation = if bits & 1 == 0 {
+        Modulation::Average
+    } else if bits & (1 << 20) != 0 {
+        Modulation::Vertical
+    } else {
+        Modulation::Horizontal
+    };
+    // the low bits of the first and the center pixel are taken by the mode, repeat their high bits
+    if bits & 1 != 0 {
+        bits = (bits & !(1 << 20)) | (((bits >> 21) & 1) << 20);
+    }
+    bits = (bits & !1) | ((bits >> 1) & 1);
+
+    let mut modulation = Vec::with_capacity(32);
+    for y in 0..4 {
+        for x in 0..8 {
+            if (x ^ y) & 1 == 0 {
+                modulation.push(Modulation::Weight(WEIGHTS[(bits & 3) as usize]));
+                bits >>= 2;
+            } else {
+                modulation.push(interpolation);
+            }
+        }
+    }
+    modulation
+}
+
+pub(super) fn decode_pvrtc(data: &[u8], width: usize, height: usize, two_bpp: bool) -> Result<Vec<u8>, Error> {
+    let (block_width, block_height) = if two_bpp { (8, 4) } else { (4, 4) };
+    let blocks_x = width.div_ceil(block_width).max(2);
+    let blocks_y = height.div_ceil(block_height).max(2);
+    check_size(data, blocks_x * blocks_y * 8)?;
+
+    let mut blocks = Vec::with_capacity(blocks_x * blocks_y);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let offset = twiddle(x, y, blocks_x, blocks_y) * 8;
+            blocks.push(Block::read(&data[offset..offset + 8]));
+        }
+    }
+
+    // the modulation of the whole padded image, as interpolation looks into neighbouring blocks
+    let (full_width, full_height) = (blocks_x * block_width, blocks_y * block_height);
+    let mut modulation = vec![Modulation::Weight(0); full_width * full_height];
+    for (index, block) in blocks.iter().enumerate() {
+        let (bx, by) = ((index % blocks_x) * block_width, (index / blocks_x) * block_height);
+        for (i, value) in block_modulation(block, two_bpp).into_iter().enumerate() {
+            modulation[(by + i / block_width) * full_width + bx + i % block_width] = value;
+        }
+    }
+    let weight_at = |x: usize, y: usize| match modulation[(y % full_height) * full_width + x % full_width] {
+        Modulation::Weight(weight) => weight,
+        _ => 0,
+    };
+
+    let mut image = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let (left, right) = ((x + full_width - 1) % full_width, x + 1);
+            let (up, down) = ((y + full_height - 1) % full_height, y + 1);
+            let (weight, punchthrough) = match modulation[y * full_width + x] {
+                Modulation::Weight(weight) => (weight, false),
+                Modulation::Punchthrough => (4, true),
+                Modulation::Average => {
+                    let sum = weight_at(left, y) + weight_at(right, y) + weight_at(x, up) + weight_at(x, down);
+                    ((sum + 2) / 4, false)
+                }
+                Modulation::Horizontal => ((weight_at(left, y) + weight_at(right, y)).div_ceil(2), false),
+                Modulation::Vertical => ((weight_at(x, up) + weight_at(x, down)).div_ceil(2), false),
+            };
+
+            // the colors are bilinearly interpolated between the centers of the 4 nearest blocks
+            let sx = x as isize - block_width as isize / 2;
+            let sy = y as isize - block_height as isize / 2;
+            let (x0, fx) = (sx.div_euclid(block_width as isize), sx.rem_euclid(block_width as isize) as u32);
+            let (y0, fy) = (sy.div_euclid(block_height as isize), sy.rem_euclid(block_height as isize) as u32);
+            let block_at = |dx: isize, dy: isize| {
+                let bx = (x0 + dx).rem_euclid(blocks_x as isize) as usize;
+                let by = (y0 + dy).rem_euclid(blocks_y as isize) as usize;
+                &blocks[by * blocks_x + bx]
+            };
+            let corners = [
+                (block_at(0, 0), (block_width as u32 - fx) * (block_height as u32 - fy)),
+                (block_at(1, 0), fx * (block_height as u32 - fy)),
+                (block_at(0, 1), (block_width as u32 - fx) * fy),
+                (block_at(1, 1), fx * fy),
+            ];
+            // sums are scaled to a total weight of 16, then 5 bit colors and 4 bit alpha are expanded to 8 bits
+            let total = (block_width * block_height) as u32;
+            let interpolate = |color: fn(&Block) -> [u32; 4]| -> [u32; 4] {
+                std::array::from_fn(|c| {
+                    let sum = corners.iter().map(|(block, w)| color(block)[c] * w).sum::<u32>() * 16 / total;
+                    if c == 3 {
+                        sum + (sum >> 4)
+                    } else {
+                        (sum >> 1) + (sum >> 6)
+                    }
+                })
+            };
+            let a = interpolate(|block| block.a);
+            let b = interpolate(|block| block.b);
+
+            let mut pixel: [u8; 4] = std::array::from_fn(|c| ((a[c] * (8 - weight) + b[c] * weight) / 8) as u8);
+            if punchthrough {
+                pixel[3] = 0;
+            }
+            image.extend(pixel);
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn uniform(modulation: u32, a: u16, b: u16, blocks: usize) -> Vec<u8> {
+        let mut block = modulation.to_le_bytes().to_vec();
+        block.extend(a.to_le_bytes());
+        block.extend(b.to_le_bytes());
+        block.repeat(blocks)
+    }
+
+    const RED: u16 = 0x8000 | (31 << 10);
+    const GREEN: u16 = 0x8000 | (31 << 5);
+
+    #[test]
+    fn twiddled_blocks() {
+        assert_eq!(twiddle(0, 1, 4, 4), 1);
+        assert_eq!(twiddle(1, 0, 4, 4), 2);
+        assert_eq!(twiddle(3, 3, 4, 4), 15);
+        // the remaining bits of the larger side are above the interleaved ones
+        assert_eq!(twiddle(2, 1, 4, 2), 5);
+        assert_eq!(twiddle(3, 0, 4, 2), 6);
+    }
+
+    #[test]
+    fn four_bpp() {
+        let image = decode_pvrtc(&uniform(0, RED, GREEN, 4), 8, 8, false).unwrap();
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [255, 0, 0, 255]));
+        let image = decode_pvrtc(&uniform(u32::MAX, RED, GREEN, 4), 8, 8, false).unwrap();
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+
+        // the punch-through mode makes value 2 transparent
+        let image = decode_pvrtc(&uniform(0xaaaa_aaaa, RED | 1, GREEN, 4), 8, 8, false).unwrap();
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [127, 127, 0, 0]));
+        assert!(decode_pvrtc(&[0; 24], 8, 8, false).is_err());
+    }
+
+    #[test]
+    fn two_bpp() {
+        let image = decode_pvrtc(&uniform(u32::MAX, RED, GREEN, 4), 16, 8, true).unwrap();
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+
+        // interpolated pixels average their stored neighbours, which here are all 8 / 8
+        let image = decode_pvrtc(&uniform(0xffff_fffe, RED | 1, GREEN, 4), 16, 8, true).unwrap();
+        assert!(image.chunks_exact(4).all(|pixel| pixel == [0, 255, 0, 255]));
+    }
+}
@@ -0,0 +1,173 @@
+// Deswizzling of the tiled texture layouts used by console platforms
+use super::{check_size, TextureFormat};
+use crate::Error;
+use std::borrow::Cow;
+
+/// The `m_TargetPlatform` values of the platforms that tile textures.
+const PS4: i32 = 31;
+const SWITCH: i32 = 38;
+
+/// The Tegra X1 stores textures as blocks of GOBs (groups of bytes) of 64 bytes by 8 rows.
+const GOB_WIDTH: usize = 64;
+const GOB_HEIGHT: usize = 8;
+const GOB_SIZE: usize = GOB_WIDTH * GOB_HEIGHT;
+
+/// PS4 textures are stored as tiles of 8x8 blocks.
+const PS4_TILE_SIZE: usize = 8;
+
+/// Converts the first image in `data` from the layout of `target_platform` to rows of blocks.
+///
+/// Returns the format the data is stored in, which on the Switch differs from `format` for 3 byte formats.
+/// Formats without a block layout are returned unchanged.
+pub(super) fn deswizzle<'a>(
+    data: &'a [u8],
+    width: usize,
+    height: usize,
+    format: TextureFormat,
+    target_platform: i32,
+    platform_blob: &[u8],
+) -> Result<(Cow<'a, [u8]>, TextureFormat), Error> {
+    match target_platform {
+        // older versions don't write the blob and don't swizzle either
+        SWITCH if !platform_blob.is_empty() => {
+            let block_height = platform_blob
+                .get(8..12)
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .filter(|&log2| log2 <= 5)
+                .ok_or_else(|| Error::InvalidValue("invalid Switch texture platform blob".to_string()))?;
+            // the Switch has no 3 byte formats, Unity pads them to 4 bytes
+            let format = match format {
+                TextureFormat::RGB24 => TextureFormat::RGBA32,
+                TextureFormat::BGR24 => TextureFormat::BGRA32,
+                format => format,
+            };
+            let data = match format.get_block_layout() {
+                Some(layout) => Cow::Owned(deswizzle_block_linear(data, width, height, layout, 1 << block_height)?),
+                None => Cow::Borrowed(data),
+            };
+            Ok((data, format))
+        }
+        PS4 => {
+            let data = match format.get_block_layout() {
+                Some(layout) => Cow::Owned(deswizzle_ps4(data, width, height, layout)?),
+                None => Cow::Borrowed(data),
+            };
+            Ok((data, format))
+        }
+        _ => Ok((Cow::Borrowed(data), format)),
+    }
+}
+
+/// The offset of byte `x` of block row `y` in a block linear texture `gobs_x` GOBs wide.
+fn block_linear_offset(x: usize, y: usize, gobs_x: usize, gobs_per_block: usize) -> usize {
+    let block_height = GOB_HEIGHT * gobs_per_block;
+    let block = (y / block_height) * gobs_x + x / GOB_WIDTH;
+    let gob = block * gobs_per_block + (y % block_height) / GOB_HEIGHT;
+    let (x, y) = (x % GOB_WIDTH, y % GOB_HEIGHT);
+    gob * GOB_SIZE + (x / 32) * 256 + (y / 2) * 64 + ((x % 32) / 16) * 32 + (y % 2) * 16 + x % 16
+}
+
+/// Tegra X1 block linear layout, where each block is a column of `gobs_per_block` GOBs.
+fn deswizzle_block_linear(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    (block_width, block_height, block_size): (usize, usize, usize),
+    gobs_per_block: usize,
+) -> Result<Vec<u8>, Error> {
+    let row_size = width.div_ceil(block_width) * block_size;
+    let rows = height.div_ceil(block_height);
+    let gobs_x = row_size.div_ceil(GOB_WIDTH);
+    check_size(data, gobs_x * GOB_WIDTH * rows.next_multiple_of(GOB_HEIGHT * gobs_per_block))?;
+
+    let mut linear = Vec::with_capacity(row_size * rows);
+    for y in 0..rows {
+        linear.extend((0..row_size).map(|x| data[block_linear_offset(x, y, gobs_x, gobs_per_block)]));
+    }
+    Ok(linear)
+}
+
+/// The index of block `x`, `y` within a PS4 tile, in Morton order with `x` in the low bit.
+fn ps4_tile_index(x: usize, y: usize) -> usize {
+    (0..3).fold(0, |index, bit| index | ((x >> bit) & 1) << (bit * 2) | ((y >> bit) & 1) << (bit * 2 + 1))
+}
+
+fn deswizzle_ps4(
+    data: &[u8],
+    width: usize,
+    height: usize,
+    (block_width, block_height, block_size): (usize, usize, usize),
+) -> Result<Vec<u8>, Error> {
+    let (blocks_x, blocks_y) = (width.div_ceil(block_width), height.div_ceil(block_height));
+    let tiles_x = blocks_x.div_ceil(PS4_TILE_SIZE);
+    let tile_blocks = PS4_TILE_SIZE * PS4_TILE_SIZE;
+    check_size(data, tiles_x * blocks_y.div_ceil(PS4_TILE_SIZE) * tile_blocks * block_size)?;
+
+    let mut linear = Vec::with_capacity(blocks_x * blocks_y * block_size);
+    for y in 0..blocks_y {
+        for x in 0..blocks_x {
+            let tile = (y / PS4_TILE_SIZE) * tiles_x + x / PS4_TILE_SIZE;
+            let index = tile * tile_blocks + ps4_tile_index(x % PS4_TILE_SIZE, y % PS4_TILE_SIZE);
+            linear.extend_from_slice(&data[index * block_size..(index + 1) * block_size]);
+        }
+    }
+    Ok(linear)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob(block_height: u32) -> Vec<u8> {
+        let mut blob = vec![0; 8];
+        blob.extend(block_height.to_le_bytes());
+        blob
+    }
+
+    #[test]
+    fn gob_layout() {
+        assert_eq!(block_linear_offset(15, 0, 1, 1), 15);
+        assert_eq!(block_linear_offset(0, 1, 1, 1), 16);
+        assert_eq!(block_linear_offset(16, 0, 1, 1), 32);
+        assert_eq!(block_linear_offset(0, 2, 1, 1), 64);
+        assert_eq!(block_linear_offset(32, 0, 1, 1), 256);
+        // the GOBs of a block are stacked vertically, then blocks go left to right
+        assert_eq!(block_linear_offset(0, 8, 2, 2), 512);
+        assert_eq!(block_linear_offset(64, 0, 2, 2), 1024);
+        assert_eq!(block_linear_offset(0, 16, 2, 2), 2048);
+    }
+
+    #[test]
+    fn switch() {
+        // 16x16 RGBA32 is 64 bytes wide, which is a single column of 2 GOBs
+        let swizzled: Vec<u8> = (0..1024).map(|i| (i / 4) as u8).collect();
+        let (linear, format) =
+            deswizzle(&swizzled, 16, 16, TextureFormat::RGB24, SWITCH, &blob(1)).unwrap();
+        assert_eq!(format, TextureFormat::RGBA32);
+        for (y, row) in linear.chunks_exact(64).enumerate() {
+            for (x, &value) in row.iter().enumerate() {
+                assert_eq!(value as usize, block_linear_offset(x, y, 1, 2) / 4);
+            }
+        }
+
+        assert!(deswizzle(&swizzled, 16, 32, TextureFormat::RGBA32, SWITCH, &blob(1)).is_err());
+        assert!(deswizzle(&swizzled, 16, 16, TextureFormat::RGBA32, SWITCH, &[0; 4]).is_err());
+        // without a blob the data is linear
+        let (linear, _) = deswizzle(&swizzled, 16, 16, TextureFormat::RGBA32, SWITCH, &[]).unwrap();
+        assert!(matches!(linear, Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn ps4() {
+        assert_eq!(ps4_tile_index(1, 0), 1);
+        assert_eq!(ps4_tile_index(0, 1), 2);
+        assert_eq!(ps4_tile_index(7, 7), 63);
+
+        // a 12x4 DXT1 texture is 3x1 blocks padded to a tile of 8x8 blocks
+        let swizzled: Vec<u8> = (0..64 * 8).map(|i| (i / 8) as u8).collect();
+        let (linear, format) = deswizzle(&swizzled, 12, 4, TextureFormat::DXT1, PS4, &[]).unwrap();
+        assert_eq!(format, TextureFormat::DXT1);
+        assert_eq!(linear, [[0; 8], [1; 8], [4; 8]].concat());
+        assert!(deswizzle(&swizzled[..8 * 63], 12, 4, TextureFormat::DXT1, PS4, &[]).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+use super::{check_size, f32_to_u8, half_to_f32, TextureFormat};
+use crate::Error;
+
+fn u16_le(bytes: &[u8]) -> u16 {
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+fn f32_le(bytes: &[u8]) -> f32 {
+    f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn expand4(value: u16) -> u8 {
+    (value as u8 & 0xf) * 0x11
+}
+
+/// Signed normalized channels are shifted so that -128 maps to 0 and 127 to 255.
+fn signed8(value: u8) -> u8 {
+    value ^ 0x80
+}
+
+fn signed16(bytes: &[u8]) -> u8 {
+    signed8(bytes[1])
+}
+
+fn rgb9e5(value: u32) -> [u8; 4] {
+    let scale = 2f32.powi((value >> 27) as i32 - 15 - 9);
+    let channel = |shift: u32| f32_to_u8(((value >> shift) & 0x1ff) as f32 * scale);
+    [channel(0), channel(9), channel(18), 255]
+}
+
+fn yuv_to_rgba(y: u8, u: u8, v: u8) -> [u8; 4] {
+    // BT.601 with studio swing, as YUY2 video data uses
+    let c = 298 * (y as i32 - 16);
+    let d = u as i32 - 128;
+    let e = v as i32 - 128;
+    let clamp = |value: i32| ((value + 128) >> 8).clamp(0, 255) as u8;
+    [clamp(c + 409 * e), clamp(c - 100 * d - 208 * e), clamp(c + 516 * d), 255]
+}
+
+/// Bytes per pixel and the conversion of one pixel to RGBA8.
+type PixelDecoder = (usize, fn(&[u8]) -> [u8; 4]);
+
+fn pixel_decoder(format: TextureFormat) -> Option<PixelDecoder> {
+    use TextureFormat::*;
+    let decoder: PixelDecoder = match format {
+        Alpha8 => (1, |p| [255, 255, 255, p[0]]),
+        R8 => (1, |p| [p[0], 0, 0, 255]),
+        R8_SIGNED => (1, |p| [signed8(p[0]), 0, 0, 255]),
+        RG16 => (2, |p| [p[0], p[1], 0, 255]),
+        RG16_SIGNED => (2, |p| [signed8(p[0]), signed8(p[1]), 0, 255]),
+        ARGB4444 => (2, |p| {
+            let v = u16_le(p);
+            [expand4(v >> 8), expand4(v >> 4), expand4(v), expand4(v >> 12)]
+        }),
+        RGBA4444 => (2, |p| {
+            let v = u16_le(p);
+            [expand4(v >> 12), expand4(v >> 8), expand4(v >> 4), expand4(v)]
+        }),
+        RGB565 => (2, |p| {
+            let v = u16_le(p);
+            let (r, g, b) = ((v >> 11) as u8, (v >> 5) as u8 & 0x3f, v as u8 & 0x1f);
+            [(r << 3) | (r >> 2), (g << 2) | (g >> 4), (b << 3) | (b >> 2), 255]
+        }),
+        R16 => (2, |p| [p[1], 0, 0, 255]),
+        R16_SIGNED => (2, |p| [signed16(p), 0, 0, 255]),
+        RHalf => (2, |p| [f32_to_u8(half_to_f32(u16_le(p))), 0, 0, 255]),
+        RGB24 => (3, |p| [p[0], p[1], p[2], 255]),
+        RGB24_SIGNED => (3, |p| [signed8(p[0]), signed8(p[1]), signed8(p[2]), 255]),
+        BGR24 => (3, |p| [p[2], p[1], p[0], 255]),
+        RGBA32 => (4, |p| [p[0], p[1], p[2], p[3]]),
+        RGBA32_SIGNED => (4, |p| [signed8(p[0]), signed8(p[1]), signed8(p[2]), signed8(p[3])]),
+        ARGB32 => (4, |p| [p[1], p[2], p[3], p[0]]),
+        BGRA32 => (4, |p| [p[2], p[1], p[0], p[3]]),
+        RG32 => (4, |p| [p[1], p[3], 0, 255]),
+        RG32_SIGNED => (4, |p| [signed16(p), signed16(&p[2..]), 0, 255]),
+        RGHalf => (4, |p| {
+            [f32_to_u8(half_to_f32(u16_le(p))), f32_to_u8(half_to_f32(u16_le(&p[2..]))), 0, 255]
+        }),
+        RFloat => (4, |p| [f32_to_u8(f32_le(p)), 0, 0, 255]),
+        RGB9e5Float => (4, |p| rgb9e5(u32::from_le_bytes([p[0], p[1], p[2], p[3]]))),
+        RGB48 => (6, |p| [p[1], p[3], p[5], 255]),
+        RGB48_SIGNED => (6, |p| [signed16(p), signed16(&p[2..]), signed16(&p[4..]), 255]),
+        RGBAHalf => (8, |p| {
+            let channel = |i: usize| f32_to_u8(half_to_f32(u16_le(&p[i * 2..])));
+            [channel(0), channel(1), channel(2), channel(3)]
+        }),
+        RGBA64 => (8, |p| [p[1], p[3], p[5], p[7]]),
+        RGBA64_SIGNED => (8, |p| {
+            [signed16(p), signed16(&p[2..]), signed16(&p[4..]), signed16(&p[6..])]
+        }),
+        RGFloat => (8, |p| [f32_to_u8(f32_le(p)), f32_to_u8(f32_le(&p[4..])), 0, 255]),
+        RGBFloat => (12, |p| {
+            [f32_to_u8(f32_le(p)), f32_to_u8(f32_le(&p[4..])), f32_to_u8(f32_le(&p[8..])), 255]
+        }),
+        RGBAFloat => (16, |p| {
+            let channel = |i: usize| f32_to_u8(f32_le(&p[i * 4..]));
+            [channel(0), channel(1), channel(2), channel(3)]
+        }),
+        ARGBFloat => (16, |p| {
+            let channel = |i: usize| f32_to_u8(f32_le(&p[i * 4..]));
+            [channel(1), channel(2), channel(3), channel(0)]
+        }),
+        _ => return None,
+    };
+    Some(decoder)
+}
+
+/// Bytes per pixel of the formats [`decode`] supports, apart from YUY2.
+pub(super) fn pixel_size(format: TextureFormat) -> Option<usize> {
+    pixel_decoder(format).map(|(size, _)| size)
+}
+
+pub(super) fn decode(data: &[u8], width: usize, height: usize, format: TextureFormat) -> Result<Vec<u8>, Error> {
+    if format == TextureFormat::YUY2 {
+        return decode_yuy2(data, width, height);
+    }
+
+    let (pixel_size, decode_pixel) = pixel_decoder(format)
+        .ok_or_else(|| Error::Message(format!("Texture format {format:?} is not supported")))?;
+    check_size(data, width * height * pixel_size)?;
+    Ok(data
+        .chunks_exact(pixel_size)
+        .take(width * height)
+        .flat_map(decode_pixel)
+        .collect())
+}
+
+/// YUY2 stores pairs of pixels as Y0 U Y1 V.
+fn decode_yuy2(data: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    let pairs = width.div_ceil(2);
+    check_size(data, pairs * height * 4)?;
+
+    let mut image = Vec::with_capacity(width * height * 4);
+    for row in data.chunks_exact(pairs * 4).take(height) {
+        for (index, pair) in row.chunks_exact(4).enumerate() {
+            image.extend(yuv_to_rgba(pair[0], pair[1], pair[3]));
+            if index * 2 + 1 < width {
+                image.extend(yuv_to_rgba(pair[2], pair[1], pair[3]));
+            }
+        }
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use TextureFormat::*;
+
+    fn pixel(data: &[u8], format: TextureFormat) -> Vec<u8> {
+        decode(data, 1, 1, format).unwrap()
+    }
+
+    #[test]
+    fn packed_formats() {
+        assert_eq!(pixel(&[0x1f, 0xf8], RGB565), [255, 0, 255, 255]);
+        assert_eq!(pixel(&[0xe0, 0x07], RGB565), [0, 255, 0, 255]);
+        assert_eq!(pixel(&[0x34, 0x12], ARGB4444), [0x22, 0x33, 0x44, 0x11]);
+        assert_eq!(pixel(&[0x34, 0x12], RGBA4444), [0x11, 0x22, 0x33, 0x44]);
+        assert_eq!(pixel(&[1, 2, 3, 4], ARGB32), [2, 3, 4, 1]);
+        assert_eq!(pixel(&[1, 2, 3, 4], BGRA32), [3, 2, 1, 4]);
+        assert_eq!(pixel(&[9], Alpha8), [255, 255, 255, 9]);
+        assert_eq!(pixel(&[0x80], R8_SIGNED), [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn float_formats() {
+        assert_eq!(pixel(&[0x00, 0x3c], R16), [0x3c, 0, 0, 255]);
+        assert_eq!(pixel(&[0x00, 0x3c, 0x00, 0x38], RGHalf), [255, 128, 0, 255]);
+        let rgba: Vec<u8> = [1.0f32, 0.0, 2.0, 0.5].iter().flat_map(|f| f.to_le_bytes()).collect();
+        assert_eq!(pixel(&rgba, RGBAFloat), [255, 0, 255, 128]);
+        // mantissas of 256 with an exponent of 15 + 1 are 256 * 2^-8 = 1.0
+        assert_eq!(pixel(&((16u32 << 27) | 256).to_le_bytes(), RGB9e5Float), [255, 0, 0, 255]);
+    }
+
+    #[test]
+    fn yuy2() {
+        let image = decode(&[235, 128, 16, 128], 2, 1, YUY2).unwrap();
+        assert_eq!(image, [255, 255, 255, 255, 0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn short_data() {
+        assert!(matches!(decode(&[0; 11], 2, 2, RGB24), Err(Error::InvalidValue(_))));
+        assert!(matches!(decode(&[0; 8], 1, 1, DXT1), Err(Error::Message(_))));
+    }
+}
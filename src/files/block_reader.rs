@@ -0,0 +1,202 @@
+use crate::{
+    files::bundle_file::{decompress_block_data, StorageBlock},
+    read_ext::ReadUrexExt,
+    unitycn::ArchiveStorageDecryptor,
+    Error,
+};
+use std::collections::VecDeque;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+/// A reader that bundles and environments can take ownership of.
+pub trait BlockSource: Read + Seek + Send {}
+impl<T: Read + Seek + Send> BlockSource for T {}
+
+/// The uncompressed data of a bundle, addressed by the offsets of its [`FileEntry`](crate::files::unity_file::FileEntry)s.
+pub enum BlockReader {
+    /// All blocks were decompressed into memory while opening the bundle.
+    Eager(Cursor<Vec<u8>>),
+    /// Blocks are decompressed when they are read.
+    Lazy(LazyBlockReader),
+}
+
+impl BlockReader {
+    /// Total size of the uncompressed data.
+    pub fn len(&self) -> u64 {
+        match self {
+            BlockReader::Eager(cursor) => cursor.get_ref().len() as u64,
+            BlockReader::Lazy(reader) => reader.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for BlockReader {
+    fn default() -> Self {
+        BlockReader::Eager(Cursor::new(Vec::new()))
+    }
+}
+
+impl Read for BlockReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            BlockReader::Eager(cursor) => cursor.read(buf),
+            BlockReader::Lazy(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for BlockReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        match self {
+            BlockReader::Eager(cursor) => cursor.seek(pos),
+            BlockReader::Lazy(reader) => reader.seek(pos),
+        }
+    }
+}
+
+struct LazyBlock {
+    block: StorageBlock,
+    compressed_offset: u64,
+    uncompressed_offset: u64,
+}
+
+/// Reads the blocks of a bundle on demand and keeps the most recently used ones in memory.
+pub struct LazyBlockReader {
+    source: Box<dyn BlockSource>,
+    blocks: Vec<LazyBlock>,
+    decryptor: Option<ArchiveStorageDecryptor>,
+    lzma_header: bool,
+    cache: VecDeque<(usize, Vec<u8>)>,
+    cache_size: usize,
+    position: u64,
+    length: u64,
+}
+
+impl LazyBlockReader {
+    pub(crate) fn new(
+        source: Box<dyn BlockSource>,
+        data_offset: u64,
+        blocks: &[StorageBlock],
+        decryptor: Option<ArchiveStorageDecryptor>,
+        lzma_header: bool,
+        cache_size: usize,
+    ) -> Self {
+        let mut compressed_offset = data_offset;
+        let mut uncompressed_offset = 0;
+        let blocks = blocks
+            .iter()
+            .map(|block| {
+                let lazy_block = LazyBlock {
+                    block: block.clone(),
+                    compressed_offset,
+                    uncompressed_offset,
+                };
+                compressed_offset += block.compressed_size as u64;
+                uncompressed_offset += block.uncompressed_size as u64;
+                lazy_block
+            })
+            .collect();
+
+        LazyBlockReader {
+            source,
+            blocks,
+            decryptor,
+            lzma_header,
+            cache: VecDeque::new(),
+            cache_size: cache_size.max(1),
+            position: 0,
+            length: uncompressed_offset,
+        }
+    }
+
+    /// Total size of the uncompressed data.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Number of blocks that are currently kept decompressed.
+    pub fn cached_blocks(&self) -> usize {
+        self.cache.len()
+    }
+
+    fn block_index(&self, position: u64) -> usize {
+        self.blocks
+            .partition_point(|block| block.uncompressed_offset + block.block.uncompressed_size as u64 <= position)
+    }
+
+    fn load_block(&mut self, index: usize) -> Result<&[u8], Error> {
+        if let Some(cached) = self.cache.iter().position(|(i, _)| *i == index) {
+            // move the hit to the front so that the least recently used block is evicted first
+            let entry = self.cache.remove(cached).unwrap();
+            self.cache.push_front(entry);
+        } else {
+            let lazy_block = &self.blocks[index];
+            self.source.seek(SeekFrom::Start(lazy_block.compressed_offset))?;
+            let compressed = self
+                .source
+                .read_bytes_sized(lazy_block.block.compressed_size as usize)?;
+            let mut data = vec![0u8; lazy_block.block.uncompressed_size as usize];
+            decompress_block_data(
+                compressed,
+                &lazy_block.block,
+                index,
+                &mut data,
+                self.decryptor.as_ref(),
+                self.lzma_header,
+            )?;
+            if self.cache.len() >= self.cache_size {
+                self.cache.pop_back();
+            }
+            self.cache.push_front((index, data));
+        }
+        Ok(&self.cache[0].1)
+    }
+}
+
+impl Read for LazyBlockReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let index = self.block_index(self.position);
+        if index >= self.blocks.len() || buf.is_empty() {
+            return Ok(0);
+        }
+
+        let block_offset = (self.position - self.blocks[index].uncompressed_offset) as usize;
+        let data = self
+            .load_block(index)
+            .map_err(|e| match e {
+                Error::IoError(e) => e,
+                e => std::io::Error::new(std::io::ErrorKind::InvalidData, e),
+            })?;
+        let size = buf.len().min(data.len() - block_offset);
+        buf[..size].copy_from_slice(&data[block_offset..block_offset + size]);
+        self.position += size as u64;
+        Ok(size)
+    }
+}
+
+impl Seek for LazyBlockReader {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.length.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )),
+        }
+    }
+}
@@ -1,13 +1,21 @@
 use crate::{
-    unitycn::ArchiveStorageDecryptor,
-    config::ExtractionConfig,
-    files::{SerializedFile, unity_file::{FileEntry, UnityFile}},
-    read_ext::{ReadSeekUrexExt, ReadUrexExt}, Error,
+    unitycn::{ArchiveStorageDecryptor, ArchiveStorageEncryptor, ArchiveStorageHeader},
+    config::{BlockDecompression, BundleWriteConfig, ExtractionConfig},
+    files::{
+        block_reader::{BlockReader, BlockSource, LazyBlockReader},
+        entry_reader::EntryReader,
+        SerializedFile,
+        unity_file::{FileEntry, UnityFile},
+    },
+    read_ext::{ReadSeekUrexExt, ReadUrexExt},
+    write_ext::{WriteSeekUrexExt, WriteUrexExt},
+    hash::{unity_hash128, SpookyHasher},
+    Error,
 };
 use bitflags::bitflags;
-use byteorder::{BigEndian, ReadBytesExt};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use num_enum::TryFromPrimitive;
-use std::io::{Cursor, Read, Seek, SeekFrom};
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
 bitflags! {
     struct ArchiveFlags: u32 {
@@ -36,7 +44,7 @@ bitflags! {
 //     }
 // }
 
-#[derive(Debug, Eq, PartialEq, TryFromPrimitive)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, TryFromPrimitive)]
 #[repr(u32)]
 pub enum CompressionType {
     None = 0,
@@ -53,9 +61,77 @@ pub struct BundleFileHeader {
     unity_version: String,
     unity_revision: String,
     size: u32,
+    hash: Option<u128>,
+    crc: Option<u32>,
+    minimum_streamed_bytes: Option<u32>,
+    levels_before_streaming: Option<u32>,
+    complete_file_size: Option<u32>,
+    uncompressed_data_hash: Option<u128>,
 }
 
 impl BundleFileHeader {
+    pub fn new(version: u32, unity_version: String, unity_revision: String) -> Self {
+        BundleFileHeader {
+            signature: "UnityFS".to_owned(),
+            version,
+            unity_version,
+            unity_revision,
+            size: 0,
+            hash: None,
+            crc: None,
+            minimum_streamed_bytes: None,
+            levels_before_streaming: None,
+            complete_file_size: None,
+            uncompressed_data_hash: None,
+        }
+    }
+
+    pub fn get_signature(&self) -> &String {
+        &self.signature
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.version
+    }
+
+    pub fn get_unity_version(&self) -> &String {
+        &self.unity_version
+    }
+
+    pub fn get_unity_revision(&self) -> &String {
+        &self.unity_revision
+    }
+
+    /// Hash of the bundle, only stored by UnityRaw and UnityWeb version 4 and newer.
+    /// It isn't known which data it covers, so it can't be verified.
+    pub fn get_hash(&self) -> Option<u128> {
+        self.hash
+    }
+
+    /// CRC of the bundle data, only stored by UnityRaw and UnityWeb version 4 and newer.
+    pub fn get_crc(&self) -> Option<u32> {
+        self.crc
+    }
+
+    /// Bytes that have to be downloaded before a streamed bundle can be opened.
+    pub fn get_minimum_streamed_bytes(&self) -> Option<u32> {
+        self.minimum_streamed_bytes
+    }
+
+    /// Levels that have to be downloaded before a streamed bundle can be opened.
+    pub fn get_levels_before_streaming(&self) -> Option<u32> {
+        self.levels_before_streaming
+    }
+
+    pub fn get_complete_file_size(&self) -> Option<u32> {
+        self.complete_file_size
+    }
+
+    /// Hash128 of the uncompressed block data of UnityFS bundles, zero if it wasn't computed.
+    pub fn get_uncompressed_data_hash(&self) -> Option<u128> {
+        self.uncompressed_data_hash
+    }
+
     fn from_reader<T: Read + Seek>(reader: &mut T) -> Result<Self, Error> {
         Ok(BundleFileHeader {
             signature: reader.read_cstr()?,
@@ -63,9 +139,22 @@ impl BundleFileHeader {
             unity_version: reader.read_cstr()?,
             unity_revision: reader.read_cstr()?,
             size: 0,
+            hash: None,
+            crc: None,
+            minimum_streamed_bytes: None,
+            levels_before_streaming: None,
+            complete_file_size: None,
+            uncompressed_data_hash: None,
         })
     }
 
+    fn uses_new_archive_flags(unity_ver: (u32, u32, u32)) -> bool {
+        !(unity_ver < (2020, 0, 0))
+            | ((unity_ver.0 == 2020) & (unity_ver < (2020, 3, 34)))
+            | ((unity_ver.0 == 2021) & (unity_ver < (2021, 3, 2)))
+            | ((unity_ver.0 == 2022) & (unity_ver < (2022, 1, 1)))
+    }
+
     fn get_revision_tuple(&self, config: &ExtractionConfig) -> Result<(u32, u32, u32), Error> {
         // could be done way better, but this works for now
         let mut revision = self.unity_revision.clone();
@@ -101,85 +190,311 @@ impl BundleFileHeader {
     }
 }
 
-#[derive(Debug)]
+/// End of a level of a streamed UnityRaw or UnityWeb bundle.
+///
+/// The sizes are cumulative, so the last level covers the whole bundle data.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BundleLevel {
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+}
+
+#[derive(Debug, Clone)]
 pub struct StorageBlock {
-    compressed_size: u32,
-    uncompressed_size: u32,
-    flags: u32,
+    pub(crate) compressed_size: u32,
+    pub(crate) uncompressed_size: u32,
+    pub(crate) flags: u32,
 }
 
 pub struct BundleFile {
     pub m_Header: BundleFileHeader,
     pub m_BlocksInfo: Vec<StorageBlock>,
+    pub m_Levels: Vec<BundleLevel>,
     pub m_DirectoryInfo: Vec<FileEntry>,
+    /// Uncompressed data of the bundle, empty if the blocks are decompressed lazily.
+    /// [`BundleFile::block_reader`] reads the data in both cases.
+    #[deprecated(note = "empty with `BlockDecompression::Lazy`, use `block_reader` instead")]
     pub m_BlockReader: Cursor<Vec<u8>>,
+    lazy_block_reader: Option<LazyBlockReader>,
     _decryptor: Option<ArchiveStorageDecryptor>,
+    unitycn_key_name: Option<String>,
 }
 
 impl BundleFile {
+    #[allow(deprecated)]
     pub fn from_reader<T: Read + Seek>(
         reader: &mut T,
         config: &ExtractionConfig,
     ) -> Result<Self, Error> {
-        let mut bundle = Self {
-            m_Header: BundleFileHeader::from_reader(reader)?,
+        let (mut bundle, data_offset) = Self::read_directory(reader, config)?;
+        if let Some(data_offset) = data_offset {
+            match config.block_decompression {
+                BlockDecompression::Eager => {
+                    bundle.m_BlockReader = bundle.decompress_blocks(reader, data_offset)?;
+                }
+                BlockDecompression::Lazy { cache_size } => {
+                    // the reader is only borrowed, so the compressed blocks are copied into memory
+                    let compressed_size = bundle
+                        .m_BlocksInfo
+                        .iter()
+                        .map(|block| block.compressed_size as usize)
+                        .sum();
+                    reader.seek(SeekFrom::Start(data_offset))?;
+                    let compressed = reader.read_bytes_sized(compressed_size)?;
+                    bundle.lazy_block_reader = Some(bundle.new_lazy_block_reader(Box::new(Cursor::new(compressed)), 0, cache_size));
+                }
+            }
+        }
+        if config.verify_integrity {
+            bundle.verify_integrity(config)?;
+        }
+        Ok(bundle)
+    }
+
+    /// Parses a bundle that takes ownership of its reader.
+    ///
+    /// With [`BlockDecompression::Lazy`] the blocks are read from `reader` when
+    /// they are accessed, so only the cached blocks are kept in memory.
+    pub fn from_owned_reader<T: Read + Seek + Send + 'static>(
+        mut reader: T,
+        config: &ExtractionConfig,
+    ) -> Result<Self, Error> {
+        let BlockDecompression::Lazy { cache_size } = config.block_decompression else {
+            return Self::from_reader(&mut reader, config);
+        };
+
+        let (mut bundle, data_offset) = Self::read_directory(&mut reader, config)?;
+        if let Some(data_offset) = data_offset {
+            bundle.lazy_block_reader = Some(bundle.new_lazy_block_reader(Box::new(reader), data_offset, cache_size));
+        }
+        if config.verify_integrity {
+            bundle.verify_integrity(config)?;
+        }
+        Ok(bundle)
+    }
+
+    /// Compares the CRC and hash stored in the bundle against the uncompressed data.
+    ///
+    /// Checksums that are zero weren't computed by Unity and are skipped, as is the
+    /// CRC of a bundle that was opened at a lower level.
+    ///
+    /// The `hash` of UnityRaw and UnityWeb bundles can't be checked, since Unity
+    /// doesn't document which data it covers, so a bundle that has one is rejected.
+    fn verify_integrity(&mut self, config: &ExtractionConfig) -> Result<(), Error> {
+        if self.m_Header.hash.is_some_and(|hash| hash != 0) {
+            return Err(Error::Unimplemented(
+                "The hash of UnityRaw and UnityWeb bundles can't be checked",
+            ));
+        }
+
+        let last_level = self.m_Levels.len().saturating_sub(1);
+        let crc = self
+            .m_Header
+            .crc
+            .filter(|crc| *crc != 0 && config.streamed_level.is_none_or(|level| level == last_level));
+        let hash = self.m_Header.uncompressed_data_hash.filter(|hash| *hash != 0);
+        if crc.is_none() && hash.is_none() {
+            return Ok(());
+        }
+
+        // the data is hashed in chunks, so lazily decompressed bundles aren't loaded as a whole
+        let mut crc_hasher = crc32fast::Hasher::new();
+        let mut spooky_hasher = SpookyHasher::new(0, 0);
+        let mut chunk = vec![0u8; 0x10000];
+        let reader = self.block_reader();
+        reader.seek(SeekFrom::Start(0))?;
+        loop {
+            let read = reader.read(&mut chunk)?;
+            if read == 0 {
+                break;
+            }
+            if crc.is_some() {
+                crc_hasher.update(&chunk[..read]);
+            }
+            if hash.is_some() {
+                spooky_hasher.update(&chunk[..read]);
+            }
+        }
+
+        if let Some(expected) = crc {
+            let actual = crc_hasher.finalize();
+            if actual != expected {
+                return Err(Error::ChecksumMismatch {
+                    name: "CRC",
+                    expected: expected as u128,
+                    actual: actual as u128,
+                });
+            }
+        }
+        if let Some(expected) = hash {
+            let actual = u128::from_be_bytes(spooky_hasher.finish_hash128());
+            if actual != expected {
+                return Err(Error::ChecksumMismatch {
+                    name: "Uncompressed data hash",
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(deprecated)]
+    fn with_header(header: BundleFileHeader) -> Self {
+        Self {
+            m_Header: header,
             m_BlocksInfo: Vec::new(),
+            m_Levels: Vec::new(),
             m_DirectoryInfo: Vec::new(),
             m_BlockReader: Cursor::new(Vec::new()),
+            lazy_block_reader: None,
             _decryptor: None,
-        };
+            unitycn_key_name: None,
+        }
+    }
 
-        (bundle.m_DirectoryInfo, bundle.m_BlockReader) = match bundle.m_Header.signature.as_str() {
+    /// Reads everything up to the block data and returns the offset of the
+    /// block data if it still has to be decompressed.
+    #[allow(deprecated)]
+    fn read_directory<T: Read + Seek>(
+        reader: &mut T,
+        config: &ExtractionConfig,
+    ) -> Result<(Self, Option<u64>), Error> {
+        let mut bundle = Self::with_header(BundleFileHeader::from_reader(reader)?);
+
+        let data_offset = match bundle.m_Header.signature.as_str() {
+            "UnityWeb" | "UnityRaw" if bundle.m_Header.version == 6 => {
+                Some(bundle.read_unityfs(reader, config)?)
+            }
+            // no layout of UnityArchive containers has been verified against real files yet
             "UnityArchive" => {
                 return Err(Error::Unimplemented("UnityArchive is not supported"));
             }
             "UnityWeb" | "UnityRaw" => {
-                if bundle.m_Header.version == 6 {
-                    bundle.read_unityfs(reader, config)?
-                } else {
-                    bundle.read_unity_raw(reader, config)?
-                }
+                bundle.m_BlockReader = bundle.read_unity_raw(reader, config)?;
+                None
             }
-            "UnityFS" => bundle.read_unityfs(reader, config)?,
+            "UnityFS" => Some(bundle.read_unityfs(reader, config)?),
             _ => {
                 return Err(Error::UnknownSignature);
             }
         };
-        Ok(bundle)
+        Ok((bundle, data_offset))
+    }
+
+    /// Opens the entry at `index` of `m_DirectoryInfo` as its own reader.
+    #[allow(deprecated)]
+    pub fn open_entry(&mut self, index: usize) -> Result<EntryReader<'_, dyn BlockSource>, Error> {
+        let entry = self
+            .m_DirectoryInfo
+            .get(index)
+            .ok_or_else(|| Error::EntryNotFound(index.to_string()))?;
+        // streamed bundles opened at a lower level don't contain the entries of the later levels
+        if entry.get_end_offset()? > self.block_data_len() {
+            return Err(Error::EntryNotLoaded(entry.path.clone()));
+        }
+        let reader = match &mut self.lazy_block_reader {
+            Some(lazy_block_reader) => lazy_block_reader as &mut dyn BlockSource,
+            None => &mut self.m_BlockReader,
+        };
+        Ok(EntryReader::new(entry, reader))
+    }
+
+    /// Opens the entry with the given path as its own reader.
+    pub fn open_entry_by_path(&mut self, path: &str) -> Result<EntryReader<'_, dyn BlockSource>, Error> {
+        let index = self
+            .m_DirectoryInfo
+            .iter()
+            .position(|entry| entry.path == path)
+            .ok_or_else(|| Error::EntryNotFound(path.to_owned()))?;
+        self.open_entry(index)
+    }
+
+    /// Reader of the uncompressed data, which is either `m_BlockReader` or the
+    /// [`LazyBlockReader`] of a bundle opened with [`BlockDecompression::Lazy`].
+    #[allow(deprecated)]
+    pub fn block_reader(&mut self) -> &mut dyn BlockSource {
+        match &mut self.lazy_block_reader {
+            Some(lazy_block_reader) => lazy_block_reader,
+            None => &mut self.m_BlockReader,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn block_data_len(&self) -> u64 {
+        match &self.lazy_block_reader {
+            Some(lazy_block_reader) => lazy_block_reader.len(),
+            None => self.m_BlockReader.get_ref().len() as u64,
+        }
+    }
+
+    pub fn get_lazy_block_reader(&self) -> Option<&LazyBlockReader> {
+        self.lazy_block_reader.as_ref()
+    }
+
+    /// Moves the uncompressed data out of the bundle, leaving it empty.
+    #[allow(deprecated)]
+    pub fn take_block_reader(&mut self) -> BlockReader {
+        match self.lazy_block_reader.take() {
+            Some(lazy_block_reader) => BlockReader::Lazy(lazy_block_reader),
+            None => BlockReader::Eager(std::mem::take(&mut self.m_BlockReader)),
+        }
+    }
+
+    fn new_lazy_block_reader(&self, source: Box<dyn BlockSource>, data_offset: u64, cache_size: usize) -> LazyBlockReader {
+        LazyBlockReader::new(
+            source,
+            data_offset,
+            &self.m_BlocksInfo,
+            self._decryptor.clone(),
+            self.lzma_header(),
+            cache_size,
+        )
     }
 
     fn read_unity_raw<T: Read + Seek>(
         &mut self,
         reader: &mut T,
         config: &ExtractionConfig,
-    ) -> Result<(Vec<FileEntry>, Cursor<Vec<u8>>), Error> {
+    ) -> Result<Cursor<Vec<u8>>, Error> {
         if self.m_Header.version >= 4 {
-            let hash = reader.read_u128::<BigEndian>()?;
-            let crc = reader.read_u32::<BigEndian>()?;
+            self.m_Header.hash = Some(reader.read_u128::<BigEndian>()?);
+            self.m_Header.crc = Some(reader.read_u32::<BigEndian>()?);
         }
-        let minimum_streamed_bytes = reader.read_u32::<BigEndian>()?;
+        self.m_Header.minimum_streamed_bytes = Some(reader.read_u32::<BigEndian>()?);
 
         self.m_Header.size = reader.read_u32::<BigEndian>()?;
 
-        let number_of_levels_to_download_before_streaming = reader.read_u32::<BigEndian>()?;
+        self.m_Header.levels_before_streaming = Some(reader.read_u32::<BigEndian>()?);
         let level_count = reader.read_u32::<BigEndian>()?;
+        self.m_Levels = (0..level_count)
+            .map(|_| Ok(BundleLevel {
+                compressed_size: reader.read_u32::<BigEndian>()?,
+                uncompressed_size: reader.read_u32::<BigEndian>()?,
+            }))
+            .collect::<Result<Vec<BundleLevel>, Error>>()?;
 
-        // jump to last level
-        // TODO - keep the levels for use in low-memory block decompressor strategy
-        reader
-            .seek(std::io::SeekFrom::Current(((level_count - 1) * 8) as i64))?;
+        let level_index = config
+            .streamed_level
+            .unwrap_or_else(|| self.m_Levels.len().saturating_sub(1));
+        let level = self.m_Levels.get(level_index).ok_or_else(|| {
+            Error::InvalidValue(format!(
+                "Level {level_index} does not exist, the bundle has {} levels",
+                self.m_Levels.len()
+            ))
+        })?;
 
         let mut m_BlocksInfo = StorageBlock {
-            compressed_size: reader.read_u32::<BigEndian>()?,
-            uncompressed_size: reader.read_u32::<BigEndian>()?,
+            compressed_size: level.compressed_size,
+            uncompressed_size: level.uncompressed_size,
             flags: 0,
         };
 
         if self.m_Header.version >= 2 {
-            let complete_file_size = reader.read_u32::<BigEndian>()?;
+            self.m_Header.complete_file_size = Some(reader.read_u32::<BigEndian>()?);
         }
         if self.m_Header.version >= 3 {
-            let file_info_header_size = reader.read_u128::<BigEndian>()?;
+            let file_info_header_size = reader.read_u32::<BigEndian>()?;
         }
         reader
             .seek(std::io::SeekFrom::Start(self.m_Header.size as u64))?;
@@ -194,7 +509,7 @@ impl BundleFile {
         let mut block_info_reader = Cursor::new(blocks_info_bytes);
 
         let FileEntrys_count = block_info_reader.read_i32::<BigEndian>()?;
-        let m_DirectoryInfo = (0..FileEntrys_count)
+        self.m_DirectoryInfo = (0..FileEntrys_count)
             .map(|_| Ok(FileEntry {
                 path: block_info_reader.read_cstr()?,
                 offset: block_info_reader.read_u32::<BigEndian>()? as i64,
@@ -203,20 +518,19 @@ impl BundleFile {
             }))
             .collect::<Result<Vec<FileEntry>, Error>>()?;
 
-        Ok((m_DirectoryInfo, block_info_reader))
+        Ok(block_info_reader)
     }
 
-    fn read_unityfs<T: Read + Seek>(
+    /// Reads the UnityFS header up to the blocks info, returns the info of the
+    /// blocks info and if the new archive flags are used.
+    fn read_unityfs_header<T: Read + Seek>(
         &mut self,
         reader: &mut T,
         config: &ExtractionConfig,
-    ) -> Result<(Vec<FileEntry>, Cursor<Vec<u8>>), Error> {
+    ) -> Result<(StorageBlock, bool), Error> {
         //ReadHeader
         let unity_ver = self.m_Header.get_revision_tuple(config)?;
-        let use_new_archive_flags = !(unity_ver < (2020, 0, 0))
-            | ((unity_ver.0 == 2020) & (unity_ver < (2020, 3, 34)))
-            | ((unity_ver.0 == 2021) & (unity_ver < (2021, 3, 2)))
-            | ((unity_ver.0 == 2022) & (unity_ver < (2022, 1, 1)));
+        let use_new_archive_flags = BundleFileHeader::uses_new_archive_flags(unity_ver);
         self.m_Header.size = reader.read_i64::<BigEndian>()? as u32;
 
         let block_info = StorageBlock {
@@ -243,6 +557,54 @@ impl BundleFile {
                 reader.seek(SeekFrom::Start(pre_align))?;
             }
         }
+        Ok((block_info, use_new_archive_flags))
+    }
+
+    fn is_encrypted(block_info: &StorageBlock, use_new_archive_flags: bool) -> bool {
+        (use_new_archive_flags
+            & (block_info.flags & ArchiveFlags::USES_ASSET_BUNDLE_ENCRYPTION.bits() > 0))
+            | (!use_new_archive_flags
+                & (block_info.flags & ArchiveFlagsOld::USES_ASSET_BUNDLE_ENCRYPTION.bits() > 0))
+    }
+
+    /// Reads the UnityCN encryption header of a bundle without touching its blocks,
+    /// so that keys can be checked with [`ArchiveStorageHeader::verify_key`].
+    /// Returns `None` if the bundle isn't encrypted.
+    pub fn read_unitycn_header<T: Read + Seek>(
+        reader: &mut T,
+        config: &ExtractionConfig,
+    ) -> Result<Option<ArchiveStorageHeader>, Error> {
+        let mut bundle = Self::with_header(BundleFileHeader::from_reader(reader)?);
+        let is_unityfs = match bundle.m_Header.signature.as_str() {
+            "UnityWeb" | "UnityRaw" => bundle.m_Header.version == 6,
+            "UnityFS" => true,
+            "UnityArchive" => return Err(Error::Unimplemented("UnityArchive is not supported")),
+            _ => return Err(Error::UnknownSignature),
+        };
+        if !is_unityfs {
+            return Ok(None);
+        }
+
+        let (block_info, use_new_archive_flags) = bundle.read_unityfs_header(reader, config)?;
+        if block_info.flags & ArchiveFlags::BLOCKS_INFO_AT_THE_END.bits() != 0
+            || !Self::is_encrypted(&block_info, use_new_archive_flags)
+        {
+            return Ok(None);
+        }
+        Ok(Some(ArchiveStorageHeader::from_reader(reader)?))
+    }
+
+    /// Name of the key of the key ring that decrypted the bundle.
+    pub fn get_unitycn_key_name(&self) -> Option<&str> {
+        self.unitycn_key_name.as_deref()
+    }
+
+    fn read_unityfs<T: Read + Seek>(
+        &mut self,
+        reader: &mut T,
+        config: &ExtractionConfig,
+    ) -> Result<u64, Error> {
+        let (block_info, use_new_archive_flags) = self.read_unityfs_header(reader, config)?;
 
         let blocks_info_bytes: Vec<u8>;
         if block_info.flags & ArchiveFlags::BLOCKS_INFO_AT_THE_END.bits() != 0 {
@@ -250,22 +612,27 @@ impl BundleFile {
             let position = reader.stream_position()?;
             // originally reader.length
             reader
-                .seek(std::io::SeekFrom::End(block_info.compressed_size as i64))?;
+                .seek(std::io::SeekFrom::End(-(block_info.compressed_size as i64)))?;
             blocks_info_bytes = self.decompress_block(reader, &block_info, 0)?;
             reader.seek(std::io::SeekFrom::Start(position))?;
         } else {
             //0x40 BlocksAndDirectoryInfoCombined
-            if (use_new_archive_flags
-                & (block_info.flags & ArchiveFlags::USES_ASSET_BUNDLE_ENCRYPTION.bits() > 0))
-                | (!use_new_archive_flags
-                    & (block_info.flags & ArchiveFlagsOld::USES_ASSET_BUNDLE_ENCRYPTION.bits() > 0))
-            {
+            if Self::is_encrypted(&block_info, use_new_archive_flags) {
                 #[cfg(feature = "unitycn_encryption")]
                 {
-                    self._decryptor = Some(ArchiveStorageDecryptor::from_reader(
-                        reader,
-                        config.unitycn_key.ok_or_else(|| Error::NoUnityCNKey)?,
-                    )?);
+                    let header = ArchiveStorageHeader::from_reader(reader)?;
+                    let key = match config.unitycn_key {
+                        Some(key) if config.unitycn_key_ring.is_empty() || header.verify_key(key) => key,
+                        _ if !config.unitycn_key_ring.is_empty() => {
+                            let (name, key) = header
+                                .find_key(&config.unitycn_key_ring)
+                                .ok_or(Error::NoMatchingUnityCNKey)?;
+                            self.unitycn_key_name = Some(name.clone());
+                            *key
+                        }
+                        _ => return Err(Error::NoUnityCNKey),
+                    };
+                    self._decryptor = Some(header.decryptor(key)?);
                 }
 
                 #[cfg(not(feature = "unitycn_encryption"))]
@@ -276,10 +643,10 @@ impl BundleFile {
 
         let mut block_info_reader = Cursor::new(&blocks_info_bytes);
 
-        let uncompressed_data_hash = block_info_reader.read_u128::<BigEndian>()?;
+        self.m_Header.uncompressed_data_hash = Some(block_info_reader.read_u128::<BigEndian>()?);
 
         let block_info_count = block_info_reader.read_i32::<BigEndian>()?;
-        let m_BlocksInfo = (0..block_info_count)
+        self.m_BlocksInfo = (0..block_info_count)
             .map(|_| Ok(StorageBlock {
                 uncompressed_size: block_info_reader.read_u32::<BigEndian>()?,
                 compressed_size: block_info_reader.read_u32::<BigEndian>()?,
@@ -288,7 +655,7 @@ impl BundleFile {
             .collect::<Result<Vec<StorageBlock>, Error>>()?;
 
         let FileEntrys_count = block_info_reader.read_i32::<BigEndian>()?;
-        let m_DirectoryInfo: Vec<FileEntry> = (0..FileEntrys_count)
+        self.m_DirectoryInfo = (0..FileEntrys_count)
             .map(|_| Ok(FileEntry {
                 offset: block_info_reader.read_i64::<BigEndian>()?,
                 size: block_info_reader.read_i64::<BigEndian>()?,
@@ -303,21 +670,187 @@ impl BundleFile {
             reader.align(16)?;
         }
 
-        let block_data_size: u32 = m_BlocksInfo
+        Ok(reader.stream_position()?)
+    }
+
+    fn decompress_blocks<T: Read + Seek>(
+        &self,
+        reader: &mut T,
+        data_offset: u64,
+    ) -> Result<Cursor<Vec<u8>>, Error> {
+        reader.seek(SeekFrom::Start(data_offset))?;
+
+        let block_data_size: u64 = self
+            .m_BlocksInfo
             .iter()
-            .map(|block| block.uncompressed_size)
+            .map(|block| block.uncompressed_size as u64)
             .sum();
         let mut block_data = vec![0u8; block_data_size as usize];
 
         let mut block_offset = 0usize;
-        for (i, block) in m_BlocksInfo.iter().enumerate() {
+        for (i, block) in self.m_BlocksInfo.iter().enumerate() {
             let end = block_offset + block.uncompressed_size as usize;
             self.decompress_block_into(reader, block, i, &mut block_data[block_offset..end])?;
             block_offset = end;
         }
 
-        let block_reader = Cursor::new(block_data);
-        Ok((m_DirectoryInfo, block_reader))
+        Ok(Cursor::new(block_data))
+    }
+
+    /// Writes the bundle as a UnityFS archive.
+    ///
+    /// The entries are read from [`BundleFile::block_reader`] and packed back to back, so
+    /// UnityRaw and UnityWeb bundles are converted to UnityFS as well.
+    pub fn to_writer<W: Write>(
+        &mut self,
+        writer: &mut W,
+        config: &BundleWriteConfig,
+    ) -> Result<(), Error> {
+        let mut data = Vec::new();
+        let mut entries = Vec::with_capacity(self.m_DirectoryInfo.len());
+        for index in 0..self.m_DirectoryInfo.len() {
+            let mut reader = self.open_entry(index)?;
+            let offset = data.len() as i64;
+            data.extend(reader.read_all()?);
+            let entry = reader.get_entry();
+            entries.push(FileEntry {
+                offset,
+                size: entry.size,
+                flags: entry.flags,
+                path: entry.path.clone(),
+            });
+        }
+
+        let version = if self.m_Header.signature == "UnityFS" {
+            self.m_Header.version
+        } else {
+            6
+        };
+        let header = BundleFileHeader::new(
+            version,
+            self.m_Header.unity_version.clone(),
+            self.m_Header.unity_revision.clone(),
+        );
+        Self::write_unityfs(writer, &header, &entries, &data, config)
+    }
+
+    /// Writes a UnityFS archive from the given directory and the uncompressed
+    /// data that the entry offsets point into.
+    pub fn write_unityfs<W: Write>(
+        writer: &mut W,
+        header: &BundleFileHeader,
+        entries: &[FileEntry],
+        data: &[u8],
+        config: &BundleWriteConfig,
+    ) -> Result<(), Error> {
+        let unity_ver = header.get_revision_tuple(&ExtractionConfig::default())?;
+        let encryptor: Option<ArchiveStorageEncryptor> = match config.unitycn_key {
+            Some(_) if config.blocks_info_at_the_end => {
+                return Err(Error::InvalidValue(
+                    "UnityCN encrypted bundles can't have the blocks info at the end".to_owned(),
+                ));
+            }
+            #[cfg(feature = "unitycn_encryption")]
+            Some(key) => Some(ArchiveStorageEncryptor::new(key)),
+            #[cfg(not(feature = "unitycn_encryption"))]
+            Some(_) => return Err(Error::FeatureDisabled("unitycn_encryption")),
+            None => None,
+        };
+
+        //WriteBlocks
+        let mut blocks_info = Vec::new();
+        let mut block_data = Vec::new();
+        for (i, chunk) in data.chunks(config.block_size.max(1) as usize).enumerate() {
+            let (mut compressed, compression) = compress_block(chunk, config.compression)?;
+            let mut flags = compression as u32;
+            // only lz4 blocks are encrypted
+            if let (Some(encryptor), CompressionType::Lz4 | CompressionType::Lz4hc) = (&encryptor, compression) {
+                let size = compressed.len();
+                encryptor.encrypt_block(&mut compressed, size, i)?;
+                flags |= 0x100;
+            }
+            blocks_info.push(StorageBlock {
+                compressed_size: compressed.len() as u32,
+                uncompressed_size: chunk.len() as u32,
+                flags,
+            });
+            block_data.extend(compressed);
+        }
+
+        //WriteBlocksInfoAndDirectory
+        let mut block_info_writer = Cursor::new(Vec::new());
+        block_info_writer.write_all(&unity_hash128(data))?;
+
+        block_info_writer.write_i32::<BigEndian>(blocks_info.len() as i32)?;
+        for block in &blocks_info {
+            block_info_writer.write_u32::<BigEndian>(block.uncompressed_size)?;
+            block_info_writer.write_u32::<BigEndian>(block.compressed_size)?;
+            block_info_writer.write_u16::<BigEndian>(block.flags as u16)?;
+        }
+
+        block_info_writer.write_i32::<BigEndian>(entries.len() as i32)?;
+        for entry in entries {
+            block_info_writer.write_i64::<BigEndian>(entry.offset)?;
+            block_info_writer.write_i64::<BigEndian>(entry.size)?;
+            block_info_writer.write_u32::<BigEndian>(entry.flags)?;
+            block_info_writer.write_cstr(&entry.path)?;
+        }
+
+        let blocks_info_bytes = block_info_writer.into_inner();
+        let (compressed_blocks_info, block_info_compression) =
+            compress_block(&blocks_info_bytes, config.block_info_compression)?;
+
+        let mut flags = block_info_compression as u32
+            | ArchiveFlags::BLOCKS_AND_DIRECTORY_INFO_COMBINED.bits();
+        if config.blocks_info_at_the_end {
+            flags |= ArchiveFlags::BLOCKS_INFO_AT_THE_END.bits();
+        }
+        if encryptor.is_some() {
+            flags |= if BundleFileHeader::uses_new_archive_flags(unity_ver) {
+                ArchiveFlags::USES_ASSET_BUNDLE_ENCRYPTION.bits()
+            } else {
+                ArchiveFlagsOld::USES_ASSET_BUNDLE_ENCRYPTION.bits()
+            };
+        }
+
+        //WriteHeader
+        let mut header_writer = Cursor::new(Vec::new());
+        header_writer.write_cstr("UnityFS")?;
+        header_writer.write_u32::<BigEndian>(header.version)?;
+        header_writer.write_cstr(&header.unity_version)?;
+        header_writer.write_cstr(&header.unity_revision)?;
+        let size_position = header_writer.position();
+        header_writer.write_i64::<BigEndian>(0)?;
+        header_writer.write_u32::<BigEndian>(compressed_blocks_info.len() as u32)?;
+        header_writer.write_u32::<BigEndian>(blocks_info_bytes.len() as u32)?;
+        header_writer.write_u32::<BigEndian>(flags)?;
+
+        // must mirror the alignment checks done by read_unityfs
+        if header.version >= 7 || (unity_ver.0 >= 2019 && unity_ver.1 >= 4) {
+            header_writer.write_align(16)?;
+        }
+
+        // the encryption header is read right before the blocks info
+        #[cfg(feature = "unitycn_encryption")]
+        if let Some(encryptor) = &encryptor {
+            encryptor.to_writer(&mut header_writer)?;
+        }
+
+        let size = header_writer.position()
+            + compressed_blocks_info.len() as u64
+            + block_data.len() as u64;
+        header_writer.set_position(size_position);
+        header_writer.write_i64::<BigEndian>(size as i64)?;
+
+        writer.write_all(header_writer.get_ref())?;
+        if config.blocks_info_at_the_end {
+            writer.write_all(&block_data)?;
+            writer.write_all(&compressed_blocks_info)?;
+        } else {
+            writer.write_all(&compressed_blocks_info)?;
+            writer.write_all(&block_data)?;
+        }
+        Ok(())
     }
 
     fn read_files<T: Read + Seek>(
@@ -328,72 +861,30 @@ impl BundleFile {
     ) -> Result<Vec<SerializedFile>, Error> {
         file_entries
             .iter()
-            .map(|entry| {
-                reader.seek(std::io::SeekFrom::Start(entry.offset as u64))?;
-                SerializedFile::from_reader(reader, config)
-            })
+            .map(|entry| EntryReader::new(entry, reader).read_serialized_file(config))
             .collect()
     }
 
     fn decompress_block_into<T: Read + Seek>(
-        &mut self,
+        &self,
         reader: &mut T,
         block: &StorageBlock,
         index: usize,
         output: &mut [u8]
     ) -> Result<(), Error> {
-        #[allow(unused_mut)]
-        let mut compressed = reader
+        let compressed = reader
             .read_bytes_sized(block.compressed_size as usize)?;
+        decompress_block_data(compressed, block, index, output, self._decryptor.as_ref(), self.lzma_header())
+    }
 
-        match CompressionType::try_from(block.flags & 0x3F)? {
-            CompressionType::Lzma => {
-                #[cfg(feature = "lzma")]
-                {
-                    let mut compressed_reader = Cursor::new(&compressed);
-                    lzma_rs::lzma_decompress(&mut compressed_reader, &mut Cursor::new(output))?;
-                    Ok(())
-                }
-
-                #[cfg(not(feature = "lzma"))]
-                Err(Error::FeatureDisabled("lzma"))
-            }
-            CompressionType::Lz4 | CompressionType::Lz4hc => {
-                #[cfg(feature = "lz4")]
-                {
-                    if block.flags & 0x100 > 0 {
-                        // UnityCN encryption
-                        #[cfg(feature = "unitycn_encryption")]
-                        if let Some(decryptor) = self._decryptor.as_ref() {
-                            decryptor.decrypt_block(
-                                &mut compressed,
-                                block.compressed_size as usize,
-                                index,
-                            )?;
-                        }
-
-                        #[cfg(not(feature = "unitycn_encryption"))]
-                        return Err(Error::FeatureDisabled("unitycn_encryption"))
-                    }
-                    lz4_flex::block::decompress_into(&compressed, output)?;
-                    Ok(())
-                }
-
-                #[cfg(not(feature = "lz4"))]
-                Err(Error::FeatureDisabled("lz4"))
-            }
-            CompressionType::Lzham => {
-                Err(Error::Unimplemented("LZHAM is not supported"))
-            }
-            CompressionType::None => {
-                output.copy_from_slice(&compressed);
-                Ok(())
-            }
-        }
+    // UnityWeb streams carry the full lzma header, while UnityFS blocks
+    // only store the properties and take the size from the block info
+    fn lzma_header(&self) -> bool {
+        self.m_Header.signature == "UnityWeb" && self.m_Header.version < 6
     }
 
     fn decompress_block<T: Read + Seek>(
-        &mut self,
+        &self,
         reader: &mut T,
         block: &StorageBlock,
         index: usize,
@@ -404,6 +895,117 @@ impl BundleFile {
     }
 }
 
+/// Decompresses (and decrypts if needed) the raw bytes of a block into `output`.
+pub(crate) fn decompress_block_data(
+    #[allow(unused_mut)]
+    mut compressed: Vec<u8>,
+    block: &StorageBlock,
+    index: usize,
+    output: &mut [u8],
+    decryptor: Option<&ArchiveStorageDecryptor>,
+    lzma_header: bool,
+) -> Result<(), Error> {
+    match CompressionType::try_from(block.flags & 0x3F)? {
+        CompressionType::Lzma => {
+            #[cfg(feature = "lzma")]
+            {
+                let size = Some(output.len() as u64);
+                let unpacked_size = if lzma_header {
+                    lzma_rs::decompress::UnpackedSize::ReadHeaderButUseProvided(size)
+                } else {
+                    lzma_rs::decompress::UnpackedSize::UseProvided(size)
+                };
+                let options = lzma_rs::decompress::Options {
+                    unpacked_size,
+                    ..Default::default()
+                };
+                let mut compressed_reader = Cursor::new(&compressed);
+                lzma_rs::lzma_decompress_with_options(&mut compressed_reader, &mut Cursor::new(output), &options)?;
+                Ok(())
+            }
+
+            #[cfg(not(feature = "lzma"))]
+            Err(Error::FeatureDisabled("lzma"))
+        }
+        CompressionType::Lz4 | CompressionType::Lz4hc => {
+            #[cfg(feature = "lz4")]
+            {
+                if block.flags & 0x100 > 0 {
+                    // UnityCN encryption
+                    #[cfg(feature = "unitycn_encryption")]
+                    if let Some(decryptor) = decryptor {
+                        decryptor.decrypt_block(
+                            &mut compressed,
+                            block.compressed_size as usize,
+                            index,
+                        )?;
+                    }
+
+                    #[cfg(not(feature = "unitycn_encryption"))]
+                    return Err(Error::FeatureDisabled("unitycn_encryption"))
+                }
+                lz4_flex::block::decompress_into(&compressed, output)?;
+                Ok(())
+            }
+
+            #[cfg(not(feature = "lz4"))]
+            Err(Error::FeatureDisabled("lz4"))
+        }
+        CompressionType::Lzham => {
+            Err(Error::Unimplemented("LZHAM is not supported"))
+        }
+        CompressionType::None => {
+            output.copy_from_slice(&compressed);
+            Ok(())
+        }
+    }
+}
+
+/// Compresses a block, falling back to storing it uncompressed if that is not any smaller.
+fn compress_block(data: &[u8], compression: CompressionType) -> Result<(Vec<u8>, CompressionType), Error> {
+    let smallest = |compressed: Vec<u8>| {
+        if compressed.len() < data.len() {
+            (compressed, compression)
+        } else {
+            (data.to_vec(), CompressionType::None)
+        }
+    };
+
+    match compression {
+        CompressionType::None => Ok((data.to_vec(), CompressionType::None)),
+        CompressionType::Lzma => {
+            #[cfg(feature = "lzma")]
+            {
+                // unity only stores the properties header, the size is kept in the block info
+                let options = lzma_rs::compress::Options {
+                    unpacked_size: lzma_rs::compress::UnpackedSize::SkipWritingToHeader,
+                };
+                let mut compressed = Vec::new();
+                lzma_rs::lzma_compress_with_options(&mut Cursor::new(data), &mut compressed, &options)?;
+                Ok(smallest(compressed))
+            }
+
+            #[cfg(not(feature = "lzma"))]
+            Err(Error::FeatureDisabled("lzma"))
+        }
+        CompressionType::Lz4 | CompressionType::Lz4hc => {
+            #[cfg(feature = "lz4")]
+            {
+                let compressed = if compression == CompressionType::Lz4hc {
+                    crate::files::lz4hc::compress(data)
+                } else {
+                    lz4_flex::block::compress(data)
+                };
+                Ok(smallest(compressed))
+            }
+
+            #[cfg(not(feature = "lz4"))]
+            Err(Error::FeatureDisabled("lz4"))
+        }
+        CompressionType::Lzham => Err(Error::Unimplemented("LZHAM compression is not supported")),
+    }
+}
+
 impl UnityFile for BundleFile {
     fn from_reader<T: Read + Seek>(reader: &mut T, config: &ExtractionConfig) -> Result<Self, Error>
     where
@@ -412,3 +1014,408 @@ impl UnityFile for BundleFile {
         BundleFile::from_reader(reader, config)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_bundle() -> (BundleFileHeader, Vec<FileEntry>, Vec<u8>) {
+        let header = BundleFileHeader::new(6, "5.x.x".to_owned(), "2018.4.36f1".to_owned());
+        let cab: Vec<u8> = (0..0x50000u32).map(|i| (i % 251) as u8 ^ (i / 4096) as u8).collect();
+        let ress = b"raw resource data".repeat(64);
+
+        let mut data = cab.clone();
+        data.extend(&ress);
+        let entries = vec![
+            FileEntry {
+                offset: 0,
+                size: cab.len() as i64,
+                flags: 4,
+                path: "CAB-0123456789abcdef".to_owned(),
+            },
+            FileEntry {
+                offset: cab.len() as i64,
+                size: ress.len() as i64,
+                flags: 0,
+                path: "CAB-0123456789abcdef.resS".to_owned(),
+            },
+        ];
+        (header, entries, data)
+    }
+
+    fn round_trip(config: &BundleWriteConfig) {
+        let (header, entries, data) = sample_bundle();
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, config).unwrap();
+
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&written), &ExtractionConfig::default()).unwrap();
+        assert_eq!(bundle.m_Header.signature, "UnityFS");
+        assert_eq!(bundle.m_Header.size as usize, written.len());
+        assert_eq!(bundle.m_DirectoryInfo.len(), entries.len());
+        for (read, expected) in bundle.m_DirectoryInfo.iter().zip(&entries) {
+            assert_eq!(read.path, expected.path);
+            assert_eq!(read.offset, expected.offset);
+            assert_eq!(read.size, expected.size);
+            assert_eq!(read.flags, expected.flags);
+        }
+        assert_eq!(read_block_data(&mut bundle), data);
+
+        // writing the parsed bundle again must give the same file
+        let mut rewritten = Vec::new();
+        bundle.to_writer(&mut rewritten, config).unwrap();
+        assert_eq!(rewritten, written);
+    }
+
+    #[test]
+    fn write_uncompressed() {
+        round_trip(&BundleWriteConfig::new(CompressionType::None, false)
+            .with_block_info_compression(CompressionType::None));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn write_lz4() {
+        round_trip(&BundleWriteConfig::new(CompressionType::Lz4, false));
+        round_trip(&BundleWriteConfig::new(CompressionType::Lz4hc, true));
+    }
+
+    #[cfg(feature = "lzma")]
+    #[test]
+    fn write_lzma() {
+        round_trip(&BundleWriteConfig::new(CompressionType::Lzma, false)
+            .with_block_info_compression(CompressionType::Lzma));
+        round_trip(&BundleWriteConfig::new(CompressionType::Lzma, true)
+            .with_block_size(0x8000));
+    }
+
+    #[test]
+    fn write_version_7_aligned() {
+        let (_, entries, data) = sample_bundle();
+        let header = BundleFileHeader::new(7, "5.x.x".to_owned(), "2020.3.48f1".to_owned());
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &BundleWriteConfig::default()).unwrap();
+
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&written), &ExtractionConfig::default()).unwrap();
+        assert_eq!(bundle.m_Header.version, 7);
+        assert_eq!(read_block_data(&mut bundle), data);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    #[allow(deprecated)]
+    fn lazy_block_reader() {
+        let (header, entries, data) = sample_bundle();
+        let mut written = Vec::new();
+        let write_config = BundleWriteConfig::new(CompressionType::Lz4, false).with_block_size(0x8000);
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &write_config).unwrap();
+
+        let config = ExtractionConfig::default()
+            .with_block_decompression(BlockDecompression::Lazy { cache_size: 2 });
+        let borrowed = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+        let owned = BundleFile::from_owned_reader(Cursor::new(written), &config).unwrap();
+
+        for mut bundle in [borrowed, owned] {
+            assert!(bundle.m_BlockReader.get_ref().is_empty());
+            assert_eq!(bundle.get_lazy_block_reader().unwrap().len(), data.len() as u64);
+
+            // a read that crosses a block boundary
+            let (offset, size) = (bundle.m_DirectoryInfo[1].offset as usize, bundle.m_DirectoryInfo[1].size as usize);
+            let reader = bundle.block_reader();
+            reader.seek(SeekFrom::Start(0x7ff0)).unwrap();
+            assert_eq!(reader.read_bytes_sized(0x20).unwrap(), &data[0x7ff0..0x8010]);
+
+            // seeking backwards into a block that has been evicted
+            reader.seek(SeekFrom::Start(offset as u64)).unwrap();
+            let ress = reader.read_bytes_sized(size).unwrap();
+            assert_eq!(ress, &data[offset..]);
+            reader.seek(SeekFrom::Start(10)).unwrap();
+            assert_eq!(reader.read_bytes_sized(10).unwrap(), &data[10..20]);
+
+            assert_eq!(bundle.get_lazy_block_reader().unwrap().cached_blocks(), 2);
+            assert_eq!(read_block_data(&mut bundle), data);
+        }
+    }
+
+    #[test]
+    fn open_entries() {
+        use crate::files::serialized_file::tests::{objects_data, sample_file};
+
+        let mut cab = Cursor::new(Vec::new());
+        sample_file(22, 0).to_writer(&mut cab, &objects_data()).unwrap();
+        let cab = cab.into_inner();
+        let ress = b"resource data placed before the serialized file".to_vec();
+        let entries = vec![
+            FileEntry {
+                offset: 0,
+                size: ress.len() as i64,
+                flags: 0,
+                path: "CAB-0123456789abcdef.resS".to_owned(),
+            },
+            FileEntry {
+                offset: ress.len() as i64,
+                size: cab.len() as i64,
+                flags: 4,
+                path: "CAB-0123456789abcdef".to_owned(),
+            },
+        ];
+        let data = [ress.clone(), cab.clone()].concat();
+
+        let (header, _, _) = sample_bundle();
+        let mut written = Vec::new();
+        let write_config = BundleWriteConfig::new(CompressionType::None, false);
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &write_config).unwrap();
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&written), &ExtractionConfig::default()).unwrap();
+
+        assert_eq!(bundle.open_entry(0).unwrap().read_all().unwrap(), ress);
+
+        let mut entry = bundle.open_entry_by_path("CAB-0123456789abcdef").unwrap();
+        assert_eq!(entry.len(), cab.len() as u64);
+        let serialized = entry.read_serialized_file(&ExtractionConfig::default()).unwrap();
+        assert_eq!(serialized.read_objects_data(&mut entry).unwrap(), objects_data());
+
+        // reads stop at the end of the entry
+        entry.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(entry.read_to_end(&mut Vec::new()).unwrap(), 4);
+
+        assert!(matches!(bundle.open_entry(2), Err(Error::EntryNotFound(_))));
+        assert!(matches!(bundle.open_entry_by_path("missing"), Err(Error::EntryNotFound(_))));
+
+        // malformed directories
+        bundle.m_DirectoryInfo[0].offset = -1;
+        assert!(matches!(bundle.open_entry(0), Err(Error::InvalidValue(_))));
+        bundle.m_DirectoryInfo[0].offset = i64::MAX;
+        assert!(matches!(bundle.open_entry(0), Err(Error::InvalidValue(_))));
+    }
+
+    fn unity_raw_bundle(signature: &str, version: u32) -> Vec<u8> {
+        let files: [(&str, &[u8]); 2] = [("CAB-0123456789abcdef", b"serialized file"), ("level0", b"scene data")];
+        let mut directory = Vec::new();
+        directory.write_i32::<BigEndian>(files.len() as i32).unwrap();
+        let directory_size = 4 + files.iter().map(|(path, _)| path.len() + 1 + 8).sum::<usize>();
+        let mut offset = directory_size;
+        for (path, data) in &files {
+            directory.write_cstr(path).unwrap();
+            directory.write_u32::<BigEndian>(offset as u32).unwrap();
+            directory.write_u32::<BigEndian>(data.len() as u32).unwrap();
+            offset += data.len();
+        }
+        for (_, data) in &files {
+            directory.write_all(data).unwrap();
+        }
+        // the first level ends after the first file
+        let levels = [(directory_size + files[0].1.len()) as u32, directory.len() as u32];
+
+        let mut header = Vec::new();
+        header.write_cstr(signature).unwrap();
+        header.write_u32::<BigEndian>(version).unwrap();
+        header.write_cstr("3.x.x").unwrap();
+        header.write_cstr("3.5.7f6").unwrap();
+        let header_size = header.len() + if version >= 4 { 20 } else { 0 } + (6 + 2 * levels.len()) * 4;
+        if version >= 4 {
+            header.write_u128::<BigEndian>(0x0123456789abcdef).unwrap();
+            header.write_u32::<BigEndian>(crc32fast::hash(&directory)).unwrap();
+        }
+        header.write_u32::<BigEndian>(header_size as u32 + levels[0]).unwrap();
+        header.write_u32::<BigEndian>(header_size as u32).unwrap();
+        header.write_u32::<BigEndian>(1).unwrap();
+        header.write_u32::<BigEndian>(levels.len() as u32).unwrap();
+        for level in levels {
+            header.write_u32::<BigEndian>(level).unwrap();
+            header.write_u32::<BigEndian>(level).unwrap();
+        }
+        header.write_u32::<BigEndian>((header_size + directory.len()) as u32).unwrap();
+        header.write_u32::<BigEndian>(directory_size as u32).unwrap();
+        [header, directory].concat()
+    }
+
+    #[test]
+    fn read_unity_raw() {
+        let data = unity_raw_bundle("UnityRaw", 3);
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&data), &ExtractionConfig::default()).unwrap();
+        assert_eq!(bundle.m_Header.get_signature(), "UnityRaw");
+        assert_eq!(bundle.m_Header.get_version(), 3);
+        assert_eq!(bundle.m_Header.get_hash(), None);
+        assert_eq!(bundle.m_DirectoryInfo.len(), 2);
+        assert_eq!(bundle.open_entry_by_path("level0").unwrap().read_all().unwrap(), b"scene data");
+        assert_eq!(bundle.open_entry(0).unwrap().read_all().unwrap(), b"serialized file");
+
+        let data = unity_raw_bundle("UnityArchive", 3);
+        assert!(matches!(
+            BundleFile::from_reader(&mut Cursor::new(&data), &ExtractionConfig::default()),
+            Err(Error::Unimplemented(_))
+        ));
+    }
+
+    #[test]
+    fn read_streamed_levels() {
+        let data = unity_raw_bundle("UnityRaw", 4);
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&data), &ExtractionConfig::default()).unwrap();
+        assert_eq!(bundle.m_Header.get_hash(), Some(0x0123456789abcdef));
+        assert_eq!(bundle.m_Header.get_crc(), Some(crc32fast::hash(&read_block_data(&mut bundle))));
+        assert_eq!(bundle.m_Header.get_levels_before_streaming(), Some(1));
+        assert_eq!(bundle.m_Header.get_complete_file_size(), Some(data.len() as u32));
+        assert_eq!(bundle.m_Levels.len(), 2);
+        assert_eq!(bundle.m_Levels[1].uncompressed_size as u64, bundle.block_data_len());
+
+        // a partial download that only contains the first level
+        let minimum_streamed_bytes = bundle.m_Header.get_minimum_streamed_bytes().unwrap() as usize;
+        let partial = &data[..minimum_streamed_bytes];
+        assert!(BundleFile::from_reader(&mut Cursor::new(partial), &ExtractionConfig::default()).is_err());
+
+        let config = ExtractionConfig::default().with_streamed_level(0);
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(partial), &config).unwrap();
+        assert_eq!(bundle.m_DirectoryInfo.len(), 2);
+        assert_eq!(bundle.open_entry(0).unwrap().read_all().unwrap(), b"serialized file");
+        assert!(matches!(bundle.open_entry(1), Err(Error::EntryNotLoaded(path)) if path == "level0"));
+        assert!(matches!(bundle.open_entry_by_path("level0"), Err(Error::EntryNotLoaded(_))));
+        assert!(matches!(bundle.to_writer(&mut Vec::new(), &BundleWriteConfig::default()), Err(Error::EntryNotLoaded(_))));
+
+        let config = ExtractionConfig::default().with_streamed_level(2);
+        assert!(matches!(
+            BundleFile::from_reader(&mut Cursor::new(&data), &config),
+            Err(Error::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn verify_unity_raw_crc() {
+        let config = ExtractionConfig::default().with_verify_integrity(true);
+        let mut data = unity_raw_bundle("UnityRaw", 4);
+        // the hash can't be checked, so the bundle is rejected unless it is zero
+        assert!(matches!(BundleFile::from_reader(&mut Cursor::new(&data), &config), Err(Error::Unimplemented(_))));
+        let hash_offset = "UnityRaw\0".len() + 4 + "3.x.x\0".len() + "3.5.7f6\0".len();
+        data[hash_offset..hash_offset + 16].fill(0);
+
+        let bundle = BundleFile::from_reader(&mut Cursor::new(&data), &config).unwrap();
+        // CRC-32 of the bundle data as computed by zlib
+        assert_eq!(bundle.m_Header.get_crc(), Some(0xbb479ab9));
+
+        // the CRC of a partial bundle can't be checked
+        let partial_config = ExtractionConfig::default().with_verify_integrity(true).with_streamed_level(0);
+        BundleFile::from_reader(&mut Cursor::new(&data), &partial_config).unwrap();
+
+        let last = data.len() - 1;
+        data[last] ^= 0xff;
+        let result = BundleFile::from_reader(&mut Cursor::new(&data), &config);
+        assert!(matches!(result, Err(Error::ChecksumMismatch { name: "CRC", expected, actual }) if expected != actual));
+        BundleFile::from_reader(&mut Cursor::new(&data), &ExtractionConfig::default()).unwrap();
+    }
+
+    #[test]
+    fn verify_unityfs_hash() {
+        let (header, entries, data) = sample_bundle();
+        let write_config = BundleWriteConfig::new(CompressionType::None, true)
+            .with_block_info_compression(CompressionType::None);
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &write_config).unwrap();
+
+        let config = ExtractionConfig::default().with_verify_integrity(true);
+        let bundle = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+        assert_eq!(bundle.m_Header.get_uncompressed_data_hash(), Some(u128::from_be_bytes(unity_hash128(&data))));
+
+        // the uncompressed blocks info is at the end and starts with the hash
+        let blocks_info_size = 16 + 4 + bundle.m_BlocksInfo.len() * 10 + 4
+            + entries.iter().map(|entry| 20 + entry.path.len() + 1).sum::<usize>();
+        let hash_offset = written.len() - blocks_info_size;
+
+        // a zero hash wasn't computed and is skipped
+        let mut unhashed = written.clone();
+        unhashed[hash_offset..hash_offset + 16].fill(0);
+        let bundle = BundleFile::from_reader(&mut Cursor::new(&unhashed), &config).unwrap();
+        assert_eq!(bundle.m_Header.get_uncompressed_data_hash(), Some(0));
+
+        written[hash_offset] ^= 0xff;
+        let result = BundleFile::from_reader(&mut Cursor::new(&written), &config);
+        assert!(matches!(result, Err(Error::ChecksumMismatch { name: "Uncompressed data hash", .. })));
+
+        let lazy_config = ExtractionConfig::default()
+            .with_verify_integrity(true)
+            .with_block_decompression(BlockDecompression::Lazy { cache_size: 1 });
+        let result = BundleFile::from_owned_reader(Cursor::new(written), &lazy_config);
+        assert!(matches!(result, Err(Error::ChecksumMismatch { .. })));
+    }
+
+    #[cfg(all(feature = "lz4", feature = "unitycn_encryption"))]
+    #[test]
+    fn write_unitycn_encrypted() {
+        let key = *b"0123456789abcdef";
+        for revision in ["2019.4.40f1", "2021.3.5f1"] {
+            let (_, entries, data) = sample_bundle();
+            let header = BundleFileHeader::new(7, "5.x.x".to_owned(), revision.to_owned());
+            let write_config = BundleWriteConfig::new(CompressionType::Lz4hc, false)
+                .with_block_size(0x8000)
+                .with_unitycn_key(key);
+            let mut written = Vec::new();
+            BundleFile::write_unityfs(&mut written, &header, &entries, &data, &write_config).unwrap();
+
+            assert!(matches!(
+                BundleFile::from_reader(&mut Cursor::new(&written), &ExtractionConfig::default()),
+                Err(Error::NoUnityCNKey)
+            ));
+
+            let config = ExtractionConfig::default().with_unitycn_key(key);
+            let mut bundle = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+            assert!(bundle.m_BlocksInfo.iter().all(|block| block.flags & 0x100 != 0));
+            assert_eq!(read_block_data(&mut bundle), data);
+        }
+
+        let at_the_end = BundleWriteConfig::new(CompressionType::Lz4, true).with_unitycn_key(key);
+        let (header, entries, data) = sample_bundle();
+        let result = BundleFile::write_unityfs(&mut Vec::new(), &header, &entries, &data, &at_the_end);
+        assert!(matches!(result, Err(Error::InvalidValue(_))));
+    }
+
+    #[cfg(all(feature = "lz4", feature = "unitycn_encryption"))]
+    #[test]
+    fn unitycn_key_ring() {
+        let key = *b"0123456789abcdef";
+        let wrong_key = *b"fedcba9876543210";
+        let (_, entries, data) = sample_bundle();
+        let header = BundleFileHeader::new(7, "5.x.x".to_owned(), "2021.3.5f1".to_owned());
+        let write_config = BundleWriteConfig::new(CompressionType::Lz4, false).with_unitycn_key(key);
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &write_config).unwrap();
+
+        let config = ExtractionConfig::default();
+        let unitycn_header = BundleFile::read_unitycn_header(&mut Cursor::new(&written), &config)
+            .unwrap()
+            .unwrap();
+        assert!(unitycn_header.verify_key(key));
+        assert!(!unitycn_header.verify_key(wrong_key));
+
+        let mut plain = Vec::new();
+        BundleFile::write_unityfs(&mut plain, &header, &entries, &data, &BundleWriteConfig::default()).unwrap();
+        assert!(BundleFile::read_unitycn_header(&mut Cursor::new(&plain), &config).unwrap().is_none());
+
+        let key_ring = vec![("other game".to_owned(), wrong_key), ("this game".to_owned(), key)];
+        let config = ExtractionConfig::default().with_unitycn_key_ring(key_ring.clone());
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+        assert_eq!(bundle.get_unitycn_key_name(), Some("this game"));
+        assert_eq!(read_block_data(&mut bundle), data);
+
+        // the key ring is also used when the given key is wrong
+        let config = ExtractionConfig::default()
+            .with_unitycn_key(wrong_key)
+            .with_unitycn_key_ring(key_ring);
+        let bundle = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+        assert_eq!(bundle.get_unitycn_key_name(), Some("this game"));
+
+        let config = ExtractionConfig::default().with_unitycn_key(key);
+        let bundle = BundleFile::from_reader(&mut Cursor::new(&written), &config).unwrap();
+        assert_eq!(bundle.get_unitycn_key_name(), None);
+
+        let config = ExtractionConfig::default().with_unitycn_key_ring(vec![("other game".to_owned(), wrong_key)]);
+        assert!(matches!(
+            BundleFile::from_reader(&mut Cursor::new(&written), &config),
+            Err(Error::NoMatchingUnityCNKey)
+        ));
+    }
+
+    fn read_block_data(bundle: &mut BundleFile) -> Vec<u8> {
+        let mut data = Vec::new();
+        let reader = bundle.block_reader();
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        reader.read_to_end(&mut data).unwrap();
+        data
+    }
+}
@@ -0,0 +1,88 @@
+use crate::{
+    config::ExtractionConfig,
+    files::{unity_file::FileEntry, SerializedFile},
+    Error,
+};
+use std::io::{Read, Seek, SeekFrom};
+
+/// A view of a single [`FileEntry`] of an archive.
+///
+/// Positions are relative to the start of the entry and reads stop at the end of it,
+/// so parsers can't run into the data of a neighbouring entry.
+pub struct EntryReader<'a, R: Read + Seek + ?Sized> {
+    entry: &'a FileEntry,
+    reader: &'a mut R,
+    position: u64,
+}
+
+impl<'a, R: Read + Seek + ?Sized> EntryReader<'a, R> {
+    pub fn new(entry: &'a FileEntry, reader: &'a mut R) -> Self {
+        EntryReader {
+            entry,
+            reader,
+            position: 0,
+        }
+    }
+
+    pub fn get_entry(&self) -> &FileEntry {
+        self.entry
+    }
+
+    pub fn len(&self) -> u64 {
+        self.entry.size as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Reads the whole entry.
+    pub fn read_all(&mut self) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::with_capacity(self.len() as usize);
+        self.seek(SeekFrom::Start(0))?;
+        self.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Parses the entry as a [`SerializedFile`].
+    pub fn read_serialized_file(&mut self, config: &ExtractionConfig) -> Result<SerializedFile, Error> {
+        self.seek(SeekFrom::Start(0))?;
+        SerializedFile::from_reader(self, config)
+    }
+}
+
+impl<R: Read + Seek + ?Sized> Read for EntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let remaining = self.len().saturating_sub(self.position);
+        let size = (buf.len() as u64).min(remaining) as usize;
+        if size == 0 {
+            return Ok(0);
+        }
+
+        self.reader
+            .seek(SeekFrom::Start(self.entry.offset as u64 + self.position))?;
+        let read = self.reader.read(&mut buf[..size])?;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl<R: Read + Seek + ?Sized> Seek for EntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        match position {
+            Some(position) => {
+                self.position = position;
+                Ok(position)
+            }
+            None => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )),
+        }
+    }
+}
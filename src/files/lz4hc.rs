@@ -0,0 +1,189 @@
+// High compression LZ4 block encoder, based on the hash chain match finder of the reference LZ4HC.
+// reference: https://github.com/lz4/lz4/blob/dev/lib/lz4hc.c
+//
+// The output is a regular LZ4 block, so it is decompressed like any other,
+// but every position searches the whole window for its longest match
+// instead of taking the first one that the fast encoder finds.
+
+const MIN_MATCH: usize = 4;
+// the last 5 bytes are always literals and the last match has to start 12 bytes before the end
+const LAST_LITERALS: usize = 5;
+const MF_LIMIT: usize = 12;
+const MAX_DISTANCE: usize = 0xffff;
+const HASH_LOG: u32 = 15;
+const MAX_ATTEMPTS: usize = 256;
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let sequence = u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+struct MatchFinder {
+    /// Last position + 1 of every hash, 0 if there is none.
+    head: Vec<u32>,
+    /// Distance to the previous position with the same hash, 0 at the end of the chain.
+    chain: Vec<u16>,
+    next_to_insert: usize,
+}
+
+impl MatchFinder {
+    fn new() -> Self {
+        MatchFinder {
+            head: vec![0; 1 << HASH_LOG],
+            chain: vec![0; MAX_DISTANCE + 1],
+            next_to_insert: 0,
+        }
+    }
+
+    fn insert_up_to(&mut self, data: &[u8], pos: usize) {
+        while self.next_to_insert < pos {
+            let current = self.next_to_insert;
+            let hash = hash(data, current);
+            let distance = match self.head[hash] {
+                0 => 0,
+                previous => current + 1 - previous as usize,
+            };
+            self.chain[current & MAX_DISTANCE] = if distance > MAX_DISTANCE { 0 } else { distance as u16 };
+            self.head[hash] = current as u32 + 1;
+            self.next_to_insert += 1;
+        }
+    }
+
+    /// Returns the offset and length of the longest match at `pos` that ends before `match_limit`.
+    fn find(&mut self, data: &[u8], pos: usize, match_limit: usize) -> Option<(usize, usize)> {
+        self.insert_up_to(data, pos);
+
+        let mut best: Option<(usize, usize)> = None;
+        let mut best_length = MIN_MATCH - 1;
+        let mut candidate = match self.head[hash(data, pos)] {
+            0 => return None,
+            head => head as usize - 1,
+        };
+        for _ in 0..MAX_ATTEMPTS {
+            if pos - candidate > MAX_DISTANCE {
+                break;
+            }
+            // a longer match has to differ from the best one at its last byte
+            if data[candidate + best_length] == data[pos + best_length] {
+                let length = data[candidate..match_limit]
+                    .iter()
+                    .zip(&data[pos..match_limit])
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best = Some((pos - candidate, length));
+                    if pos + length == match_limit {
+                        break;
+                    }
+                }
+            }
+
+            match self.chain[candidate & MAX_DISTANCE] as usize {
+                0 => break,
+                distance if distance > candidate => break,
+                distance => candidate -= distance,
+            }
+        }
+        best
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, length: usize) {
+    if length >= 15 {
+        let mut rest = length - 15;
+        while rest >= 255 {
+            output.push(255);
+            rest -= 255;
+        }
+        output.push(rest as u8);
+    }
+}
+
+fn write_sequence(output: &mut Vec<u8>, literals: &[u8], offset: usize, match_length: usize) {
+    let match_length = match_length - MIN_MATCH;
+    output.push(((literals.len().min(15) << 4) | match_length.min(15)) as u8);
+    write_length(output, literals.len());
+    output.extend_from_slice(literals);
+    output.extend_from_slice(&(offset as u16).to_le_bytes());
+    write_length(output, match_length);
+}
+
+/// Compresses `data` into a single LZ4 block.
+pub(crate) fn compress(data: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(data.len() / 2 + 16);
+    let mut anchor = 0;
+
+    if data.len() > MF_LIMIT {
+        let mut finder = MatchFinder::new();
+        let limit = data.len() - MF_LIMIT;
+        let match_limit = data.len() - LAST_LITERALS;
+        let mut pos = 0;
+        while pos < limit {
+            let Some((offset, length)) = finder.find(data, pos, match_limit) else {
+                pos += 1;
+                continue;
+            };
+            // lazy matching, the literal is emitted if the next position has a longer match
+            if pos + 1 < limit {
+                if let Some((_, next_length)) = finder.find(data, pos + 1, match_limit) {
+                    if next_length > length {
+                        pos += 1;
+                        continue;
+                    }
+                }
+            }
+            write_sequence(&mut output, &data[anchor..pos], offset, length);
+            pos += length;
+            anchor = pos;
+        }
+    }
+
+    let literals = &data[anchor..];
+    output.push((literals.len().min(15) << 4) as u8);
+    write_length(&mut output, literals.len());
+    output.extend_from_slice(literals);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert_eq!(lz4_flex::block::decompress(&compressed, data.len()).unwrap(), data);
+        compressed
+    }
+
+    #[test]
+    fn compress_round_trip() {
+        round_trip(b"");
+        round_trip(b"short");
+        round_trip(&[7u8; 13]);
+        round_trip(&b"long run of one literal".repeat(1000));
+        round_trip(&(0..0x30000u32).map(|i| (i * 7 % 251) as u8).collect::<Vec<u8>>());
+
+        // pseudo random literals with matches further back than the window
+        let mut state = 0x12345678u32;
+        let noise: Vec<u8> = (0..0x20000)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                (state >> 24) as u8
+            })
+            .collect();
+        round_trip(&[&noise[..], &noise[..0x100], &noise[0x10000..0x11000]].concat());
+    }
+
+    #[test]
+    fn compresses_better_than_the_fast_encoder() {
+        let data: Vec<u8> = (0..0x20000u32)
+            .flat_map(|i| format!("entry {} of {}, ", i % 997, i % 13).into_bytes())
+            .take(0x20000)
+            .collect();
+        let compressed = round_trip(&data);
+        assert!(compressed.len() < lz4_flex::block::compress(&data).len());
+    }
+}
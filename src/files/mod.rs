@@ -1,9 +1,15 @@
+pub(crate) mod block_reader;
 pub(crate) mod bundle_file;
+pub(crate) mod entry_reader;
+#[cfg(feature = "lz4")]
+pub(crate) mod lz4hc;
 pub(crate) mod serialized_file;
-mod unity_file;
-mod web_file;
+pub(crate) mod unity_file;
+pub(crate) mod web_file;
 
-pub use bundle_file::BundleFile;
+pub use block_reader::{BlockReader, BlockSource, LazyBlockReader};
+pub use bundle_file::{BundleFile, BundleLevel, CompressionType};
+pub use entry_reader::EntryReader;
 pub use serialized_file::{SerializedFile, ObjectReader};
-// pub use web_file::WebFile;
+pub use web_file::{WebFile, WebCompressionType};
 pub use unity_file::UnityFile;
@@ -1,14 +1,15 @@
-use std::io::SeekFrom;
+use std::io::{Cursor, SeekFrom, Write};
 
 use super::UnityFile;
 use crate::{
     config::ExtractionConfig,
     read_ext::{ReadSeekUrexExt, ReadUrexExt},
+    write_ext::{WriteSeekUrexExt, WriteUrexExt},
     Error,
     TypeTreeNode, TypeTreeValue
 };
 use bitflags::bitflags;
-use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt};
+use byteorder::{BigEndian, ByteOrder, LittleEndian, ReadBytesExt, WriteBytesExt};
 
 #[derive(Debug, Copy, Clone)]
 pub struct SerializedFileHeader {
@@ -65,6 +66,52 @@ impl SerializedFileHeader {
         self.unknown = reader.read_i64::<B>()?; // unknown
         Ok(())
     }
+
+    fn to_writer<T: std::io::Write, B: ByteOrder>(self, writer: &mut T) -> Result<(), Error> {
+        if self.m_Version >= SerializedFileFormatVersion::LARGE_FILES_SUPPORT.bits() {
+            // the small header fields are superseded by the large file header
+            writer.write_u32::<B>(0)?;
+            writer.write_u32::<B>(0)?;
+            writer.write_u32::<B>(self.m_Version)?;
+            writer.write_u32::<B>(0)?;
+        } else {
+            writer.write_u32::<B>(self.m_MetadataSize)?;
+            writer.write_u32::<B>(self.m_FileSize as u32)?;
+            writer.write_u32::<B>(self.m_Version)?;
+            writer.write_u32::<B>(self.m_DataOffset as u32)?;
+        }
+
+        if self.m_Version >= SerializedFileFormatVersion::UNKNOWN_9.bits() {
+            writer.write_u8(self.m_Endianness)?;
+            writer.write_all(&self.m_Reserved)?;
+
+            if self.m_Version >= SerializedFileFormatVersion::LARGE_FILES_SUPPORT.bits() {
+                writer.write_u32::<B>(self.m_MetadataSize)?;
+                writer.write_i64::<B>(self.m_FileSize)?;
+                writer.write_i64::<B>(self.m_DataOffset)?;
+                writer.write_i64::<B>(self.unknown)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> u64 {
+        if self.m_Version >= SerializedFileFormatVersion::LARGE_FILES_SUPPORT.bits() {
+            48
+        } else if self.m_Version >= SerializedFileFormatVersion::UNKNOWN_9.bits() {
+            20
+        } else {
+            16
+        }
+    }
+
+    pub fn get_version(&self) -> u32 {
+        self.m_Version
+    }
+
+    pub fn get_endianness(&self) -> u8 {
+        self.m_Endianness
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -146,6 +193,59 @@ impl SerializedType {
 
         Ok(typ)
     }
+
+    pub fn to_writer<T: std::io::Write + std::io::Seek, B: ByteOrder>(
+        &self,
+        writer: &mut T,
+        header: &SerializedFileHeader,
+        m_EnableTypeTree: bool,
+        isRefType: bool,
+    ) -> Result<(), Error> {
+        writer.write_i32::<B>(self.m_ClassID)?;
+
+        if header.m_Version >= SerializedFileFormatVersion::REFACTORED_CLASS_ID.bits() {
+            writer.write_bool(self.m_IsStrippedType)?;
+        }
+
+        if header.m_Version >= SerializedFileFormatVersion::REFACTOR_TYPE_DATA.bits() {
+            writer.write_i16::<B>(self.m_ScriptTypeIndex)?;
+        }
+
+        if header.m_Version >= SerializedFileFormatVersion::HAS_TYPE_TREE_HASHES.bits() {
+            if (isRefType && self.m_ScriptTypeIndex >= 0)
+                || ((header.m_Version < SerializedFileFormatVersion::REFACTORED_CLASS_ID.bits()
+                    && self.m_ClassID < 0)
+                    || (header.m_Version
+                        >= SerializedFileFormatVersion::REFACTORED_CLASS_ID.bits()
+                        && self.m_ClassID == 114))
+            {
+                writer.write_all(&self.m_ScriptID)?;
+            }
+            writer.write_all(&self.m_OldTypeHash)?;
+        }
+
+        if m_EnableTypeTree {
+            let node = self.m_Type.as_ref().ok_or(Error::TypeTreeNotFound)?;
+            if header.m_Version >= SerializedFileFormatVersion::UNKNOWN_12.bits()
+                || header.m_Version == SerializedFileFormatVersion::UNKNOWN_10.bits()
+            {
+                node.blob_to_writer::<T, B>(writer, header.m_Version)?;
+            } else {
+                node.to_writer::<T, B>(writer, header.m_Version)?;
+            }
+            if header.m_Version >= SerializedFileFormatVersion::STORES_TYPE_DEPENDENCIES.bits() {
+                if isRefType {
+                    writer.write_cstr(self.m_ClassName.as_deref().unwrap_or_default())?;
+                    writer.write_cstr(self.m_NameSpace.as_deref().unwrap_or_default())?;
+                    writer.write_cstr(self.m_AsmName.as_deref().unwrap_or_default())?;
+                } else {
+                    writer.write_i32_array::<B>(&self.m_TypeDependencies, true)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -240,6 +340,49 @@ impl ObjectInfo {
 
         Ok(objectInfo)
     }
+
+    pub fn to_writer<T: std::io::Write + std::io::Seek, B: ByteOrder>(
+        &self,
+        writer: &mut T,
+        header: &SerializedFileHeader,
+        bigIDEnabled: Option<i32>,
+    ) -> Result<(), Error> {
+        if bigIDEnabled.is_some_and(|v| v > 0) {
+            writer.write_i64::<B>(self.m_PathID)?;
+        } else if header.m_Version < 14 {
+            writer.write_i32::<B>(self.m_PathID as i32)?;
+        } else {
+            writer.write_align(4)?;
+            writer.write_i64::<B>(self.m_PathID)?;
+        }
+
+        let offset = self.m_Offset - header.m_DataOffset;
+        if header.m_Version >= SerializedFileFormatVersion::LARGE_FILES_SUPPORT.bits() {
+            writer.write_i64::<B>(offset)?;
+        } else {
+            writer.write_u32::<B>(offset as u32)?;
+        }
+        writer.write_u32::<B>(self.m_Size)?;
+        writer.write_i32::<B>(self.m_TypeID)?;
+        if header.m_Version < SerializedFileFormatVersion::REFACTORED_CLASS_ID.bits() {
+            writer.write_u16::<B>(self.m_ClassID as u16)?;
+        }
+        if header.m_Version < SerializedFileFormatVersion::HAS_SCRIPT_TYPE_INDEX.bits() {
+            writer.write_u16::<B>(self.m_IsDestroyed.unwrap_or(0))?;
+        }
+        if header.m_Version >= SerializedFileFormatVersion::HAS_SCRIPT_TYPE_INDEX.bits()
+            && header.m_Version < SerializedFileFormatVersion::REFACTOR_TYPE_DATA.bits()
+        {
+            writer.write_i16::<B>(self.m_ScriptTypeIndex.unwrap_or(-1))?;
+        }
+        if header.m_Version == SerializedFileFormatVersion::SUPPORTS_STRIPPED_OBJECT.bits()
+            || header.m_Version == SerializedFileFormatVersion::REFACTORED_CLASS_ID.bits()
+        {
+            writer.write_u8(self.m_Stripped.unwrap_or(0))?;
+        }
+
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -260,10 +403,26 @@ impl ScriptType {
             {
                 reader.read_i32::<B>()? as i64
             } else {
+                reader.align4()?;
                 reader.read_i64::<B>()?
             },
         })
     }
+
+    pub fn to_writer<T: std::io::Write + std::io::Seek, B: ByteOrder>(
+        &self,
+        writer: &mut T,
+        header: &SerializedFileHeader,
+    ) -> Result<(), Error> {
+        writer.write_i32::<B>(self.localSerializedFileIndex)?;
+        if header.m_Version < SerializedFileFormatVersion::UNKNOWN_14.bits() {
+            writer.write_i32::<B>(self.localIdentifierInFile as i32)?;
+        } else {
+            writer.write_align4()?;
+            writer.write_i64::<B>(self.localIdentifierInFile)?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -298,6 +457,31 @@ impl FileIdentifier {
             pathName: reader.read_cstr()?,
         })
     }
+
+    /// Path of the referenced file, e.g. `archive:/CAB-.../CAB-...` for files inside bundles.
+    pub fn get_path_name(&self) -> &str {
+        &self.pathName
+    }
+
+    pub fn to_writer<T: std::io::Write, B: ByteOrder>(
+        &self,
+        writer: &mut T,
+        header: &SerializedFileHeader,
+    ) -> Result<(), Error> {
+        if header.m_Version >= SerializedFileFormatVersion::UNKNOWN_6.bits() {
+            writer.write_cstr(self.tempEmpty.as_deref().unwrap_or_default())?;
+        }
+        if header.m_Version >= SerializedFileFormatVersion::UNKNOWN_5.bits() {
+            let mut guid = [0u8; 16];
+            if let Some(value) = self.guid.as_ref() {
+                guid.copy_from_slice(value);
+            }
+            writer.write_all(&guid)?;
+            writer.write_i32::<B>(self.typeId.unwrap_or(0))?;
+        }
+        writer.write_cstr(&self.pathName)?;
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -348,6 +532,27 @@ impl<'a, R: std::io::Read + std::io::Seek> ObjectReader<'a, R> {
             _ => Err(Error::InvalidEndianness),
         }
     }
+
+    /// Encodes a value with the object's type tree, giving raw data that can
+    /// replace the object's data when writing the file.
+    pub fn encode(&self, value: &TypeTreeValue) -> Result<Vec<u8>, Error> {
+        let node = self.get_type_tree().ok_or(Error::TypeTreeNotFound)?;
+
+        let mut writer = Cursor::new(Vec::new());
+        match self.file.m_Header.m_Endianness {
+            0 => node.write::<_, LittleEndian>(&mut writer, value)?,
+            1 => node.write::<_, BigEndian>(&mut writer, value)?,
+            _ => return Err(Error::InvalidEndianness),
+        }
+        Ok(writer.into_inner())
+    }
+
+    /// Encodes a typed object, such as one of the `objects` classes, with the object's type tree.
+    #[cfg(feature = "serde")]
+    pub fn encode_object<T: serde::Serialize>(&self, object: &T) -> Result<Vec<u8>, Error> {
+        let node = self.get_type_tree().ok_or(Error::TypeTreeNotFound)?;
+        self.encode(&TypeTreeValue::from_serializable(object, node)?)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -355,6 +560,7 @@ pub struct SerializedFile {
     pub m_Header: SerializedFileHeader,
     pub m_UnityVersion: Option<String>,
     pub m_TargetPlatform: Option<i32>,
+    pub m_EnableTypeTree: bool,
     pub m_bigIDEnabled: Option<i32>,
     pub m_Types: Vec<SerializedType>,
     pub m_Objects: Vec<ObjectInfo>,
@@ -378,6 +584,26 @@ impl SerializedFile {
         }
     }
 
+    /// Checks whether the header at the reader's position describes a serialized file of `length` bytes.
+    ///
+    /// Only the header is read, so this is cheap to run on large resource files.
+    pub(crate) fn is_serialized_file<T: std::io::Read + std::io::Seek>(
+        reader: &mut T,
+        length: u64,
+        config: &crate::config::ExtractionConfig,
+    ) -> bool {
+        match SerializedFileHeader::from_reader::<T, BigEndian>(reader, config) {
+            Ok(header) => {
+                header.m_Version > 0
+                    && header.m_FileSize as u64 == length
+                    && (0..=length as i64).contains(&header.m_DataOffset)
+                    && (header.m_MetadataSize as u64) < length
+                    && header.m_Endianness <= 1
+            }
+            Err(_) => false,
+        }
+    }
+
     fn from_reader_endianed<T, B>(
         reader: &mut T,
         header: SerializedFileHeader,
@@ -398,22 +624,22 @@ impl SerializedFile {
             m_TargetPlatform = Some(reader.read_i32::<B>()?);
         }
 
-        let mut m_EnabledTypeTree = false;
+        let mut m_EnableTypeTree = true;
         if header.m_Version >= SerializedFileFormatVersion::HAS_TYPE_TREE_HASHES.bits() {
-            m_EnabledTypeTree = reader.read_bool()?;
+            m_EnableTypeTree = reader.read_bool()?;
         }
 
         // Read Types
         let typeCount = reader.read_i32::<B>()?;
         let m_Types: Vec<SerializedType> = (0..typeCount)
-            .map(|_| SerializedType::from_reader::<T, B>(reader, &header, m_EnabledTypeTree, false))
+            .map(|_| SerializedType::from_reader::<T, B>(reader, &header, m_EnableTypeTree, false))
             .collect::<Result<Vec<SerializedType>, Error>>()?;
 
-        let m_bigIDEnabled = None;
+        let mut m_bigIDEnabled = None;
         if header.m_Version >= SerializedFileFormatVersion::UNKNOWN_7.bits()
             && header.m_Version < SerializedFileFormatVersion::UNKNOWN_14.bits()
         {
-            let bigIDEnabled = Some(reader.read_i32::<B>()?);
+            m_bigIDEnabled = Some(reader.read_i32::<B>()?);
         }
 
         // Read Objects
@@ -422,13 +648,13 @@ impl SerializedFile {
             .map(|_| ObjectInfo::from_reader::<T, B>(reader, &header, m_bigIDEnabled, &m_Types))
             .collect::<Result<Vec<ObjectInfo>, Error>>()?;
 
-        let m_ScriptTypes = None;
+        let mut m_ScriptTypes = None;
         if header.m_Version >= SerializedFileFormatVersion::HAS_SCRIPT_TYPE_INDEX.bits() {
             let scriptCount = reader.read_i32::<B>()?;
-            let m_ScriptTypes = Some(
+            m_ScriptTypes = Some(
                 (0..scriptCount)
-                    .map(|_| LocalSerializedObjectIdentifier::from_reader::<T, B>(reader, &header))
-                    .collect::<Result<Vec<LocalSerializedObjectIdentifier>, Error>>()?
+                    .map(|_| ScriptType::from_reader::<T, B>(reader, &header))
+                    .collect::<Result<Vec<ScriptType>, Error>>()?
             );
         }
 
@@ -437,16 +663,16 @@ impl SerializedFile {
             .map(|_| FileIdentifier::from_reader::<T, B>(reader, &header))
             .collect::<Result<Vec<FileIdentifier>, Error>>()?;
 
-        let m_RefTypes = None;
+        let mut m_RefTypes = None;
         if header.m_Version >= SerializedFileFormatVersion::SUPPORTS_REF_OBJECT.bits() {
             let refTypesCount = reader.read_i32::<B>()?;
-            let m_RefTypes = Some(
+            m_RefTypes = Some(
                 (0..refTypesCount)
                     .map(|_|
                         SerializedType::from_reader::<T, B>(
                             reader,
                             &header,
-                            m_EnabledTypeTree,
+                            m_EnableTypeTree,
                             true,
                         )
                     )
@@ -454,9 +680,9 @@ impl SerializedFile {
             );
         }
 
-        let m_UserInformation = None;
+        let mut m_UserInformation = None;
         if header.m_Version >= SerializedFileFormatVersion::UNKNOWN_5.bits() {
-            let m_UserInformation = Some(reader.read_cstr()?);
+            m_UserInformation = Some(reader.read_cstr()?);
         }
 
         //reader.AlignStream(16);
@@ -464,6 +690,7 @@ impl SerializedFile {
             m_Header: header,
             m_UnityVersion,
             m_TargetPlatform,
+            m_EnableTypeTree,
             m_bigIDEnabled,
             m_Types,
             m_Objects,
@@ -474,6 +701,144 @@ impl SerializedFile {
         })
     }
 
+    /// Reads the raw data of every object, in the order of `m_Objects`.
+    pub fn read_objects_data<R: std::io::Read + std::io::Seek>(
+        &self,
+        reader: &mut R,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        self.m_Objects
+            .iter()
+            .map(|object_info| self.get_object_reader(object_info, reader).get_raw_data())
+            .collect()
+    }
+
+    /// Writes the file with the given object data, which has to be in the
+    /// order of `m_Objects`. Offsets and sizes are recalculated.
+    pub fn to_writer<W: std::io::Write + std::io::Seek>(
+        &self,
+        writer: &mut W,
+        objects_data: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        if objects_data.len() != self.m_Objects.len() {
+            return Err(Error::InvalidValue(format!(
+                "Expected data for {} objects, got {}",
+                self.m_Objects.len(),
+                objects_data.len()
+            )));
+        }
+
+        match self.m_Header.m_Endianness {
+            0 => self.to_writer_endianed::<W, LittleEndian>(writer, objects_data),
+            1 => self.to_writer_endianed::<W, BigEndian>(writer, objects_data),
+            _ => Err(Error::InvalidEndianness),
+        }
+    }
+
+    fn to_writer_endianed<W, B>(
+        &self,
+        writer: &mut W,
+        objects_data: &[Vec<u8>],
+    ) -> Result<(), Error>
+    where
+        W: std::io::Write + std::io::Seek,
+        B: ByteOrder,
+    {
+        let mut header = self.m_Header;
+        let version = header.m_Version;
+        let is_old_layout = version < SerializedFileFormatVersion::UNKNOWN_9.bits();
+
+        // Layout Objects
+        // old files keep the data right after the header and the metadata at the end
+        let data_base = if is_old_layout { header.size() } else { 0 };
+        let mut data = Cursor::new(Vec::new());
+        let mut objects = self.m_Objects.clone();
+        for (object, object_data) in objects.iter_mut().zip(objects_data) {
+            data.write_align(8)?;
+            object.m_Offset = (data_base + data.position()) as i64;
+            object.m_Size = object_data.len() as u32;
+            data.write_all(object_data)?;
+        }
+        let data = data.into_inner();
+        header.m_DataOffset = 0;
+
+        // Write Metadata
+        let mut metadata = Cursor::new(Vec::new());
+        if version >= SerializedFileFormatVersion::UNKNOWN_7.bits() {
+            metadata.write_cstr(self.m_UnityVersion.as_deref().unwrap_or_default())?;
+        }
+
+        if version >= SerializedFileFormatVersion::UNKNOWN_8.bits() {
+            metadata.write_i32::<B>(self.m_TargetPlatform.unwrap_or(0))?;
+        }
+
+        if version >= SerializedFileFormatVersion::HAS_TYPE_TREE_HASHES.bits() {
+            metadata.write_bool(self.m_EnableTypeTree)?;
+        }
+
+        metadata.write_i32::<B>(self.m_Types.len() as i32)?;
+        for typ in self.m_Types.iter() {
+            typ.to_writer::<_, B>(&mut metadata, &header, self.m_EnableTypeTree, false)?;
+        }
+
+        if version >= SerializedFileFormatVersion::UNKNOWN_7.bits()
+            && version < SerializedFileFormatVersion::UNKNOWN_14.bits()
+        {
+            metadata.write_i32::<B>(self.m_bigIDEnabled.unwrap_or(0))?;
+        }
+
+        metadata.write_i32::<B>(objects.len() as i32)?;
+        for object in objects.iter() {
+            object.to_writer::<_, B>(&mut metadata, &header, self.m_bigIDEnabled)?;
+        }
+
+        if version >= SerializedFileFormatVersion::HAS_SCRIPT_TYPE_INDEX.bits() {
+            let script_types = self.m_ScriptTypes.as_deref().unwrap_or_default();
+            metadata.write_i32::<B>(script_types.len() as i32)?;
+            for script_type in script_types {
+                script_type.to_writer::<_, B>(&mut metadata, &header)?;
+            }
+        }
+
+        metadata.write_i32::<B>(self.m_Externals.len() as i32)?;
+        for external in self.m_Externals.iter() {
+            external.to_writer::<_, B>(&mut metadata, &header)?;
+        }
+
+        if version >= SerializedFileFormatVersion::SUPPORTS_REF_OBJECT.bits() {
+            let ref_types = self.m_RefTypes.as_deref().unwrap_or_default();
+            metadata.write_i32::<B>(ref_types.len() as i32)?;
+            for ref_type in ref_types {
+                ref_type.to_writer::<_, B>(&mut metadata, &header, self.m_EnableTypeTree, true)?;
+            }
+        }
+
+        if version >= SerializedFileFormatVersion::UNKNOWN_5.bits() {
+            metadata.write_cstr(self.m_UserInformation.as_deref().unwrap_or_default())?;
+        }
+        let metadata = metadata.into_inner();
+
+        // Write Header
+        if is_old_layout {
+            // the endianness byte is counted as part of the metadata
+            header.m_MetadataSize = metadata.len() as u32 + 1;
+            header.m_FileSize = (data_base + data.len() as u64) as i64 + header.m_MetadataSize as i64;
+            header.to_writer::<W, BigEndian>(writer)?;
+            writer.write_all(&data)?;
+            writer.write_u8(header.m_Endianness)?;
+            writer.write_all(&metadata)?;
+        } else {
+            let metadata_end = header.size() + metadata.len() as u64;
+            header.m_MetadataSize = metadata.len() as u32;
+            header.m_DataOffset = ((metadata_end + 15) & !15) as i64;
+            header.m_FileSize = header.m_DataOffset + data.len() as i64;
+            header.to_writer::<W, BigEndian>(writer)?;
+            writer.write_all(&metadata)?;
+            writer.write_all(&vec![0u8; (header.m_DataOffset as u64 - metadata_end) as usize])?;
+            writer.write_all(&data)?;
+        }
+        Ok(())
+    }
+
     pub fn get_object_reader<'a, R: std::io::Read + std::io::Seek>(
         &'a self,
         object_info: &'a ObjectInfo,
@@ -543,3 +908,127 @@ bitflags! {
         const LARGE_FILES_SUPPORT = 22;
     }
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn string_node(name: &str) -> TypeTreeNode {
+        TypeTreeNode::new("string".to_owned(), name.to_owned(), -1, 0x8000, vec![
+            TypeTreeNode::new("Array".to_owned(), "Array".to_owned(), -1, 0x4001, vec![
+                TypeTreeNode::new("int".to_owned(), "size".to_owned(), 4, 1, vec![]),
+                TypeTreeNode::new("char".to_owned(), "data".to_owned(), 1, 1, vec![]),
+            ]),
+        ])
+    }
+
+    fn text_asset_type() -> TypeTreeNode {
+        TypeTreeNode::new("TextAsset".to_owned(), "Base".to_owned(), -1, 0x8000, vec![
+            string_node("m_Name"),
+            string_node("m_Script"),
+            TypeTreeNode::new("CustomType".to_owned(), "m_Custom".to_owned(), 4, 0, vec![]),
+        ])
+    }
+
+    pub(crate) fn sample_file(version: u32, endianness: u8) -> SerializedFile {
+        let serialized_type = |m_ClassID: i32, m_ScriptTypeIndex: i16| SerializedType {
+            m_ClassID,
+            m_IsStrippedType: false,
+            m_ScriptTypeIndex,
+            m_ScriptID: [7; 16],
+            m_OldTypeHash: [9; 16],
+            m_Type: Some(text_asset_type()),
+            m_ClassName: Some("Class".to_owned()),
+            m_NameSpace: Some("Namespace".to_owned()),
+            m_AsmName: Some("Assembly-CSharp".to_owned()),
+            m_TypeDependencies: vec![1, 2],
+        };
+        let object_info = |m_PathID: i64, m_TypeID: i32, m_ClassID: i32| ObjectInfo {
+            m_PathID,
+            m_Offset: 0,
+            m_Size: 0,
+            m_TypeID,
+            m_ClassID,
+            m_IsDestroyed: Some(0),
+            m_ScriptTypeIndex: Some(-1),
+            m_Stripped: Some(0),
+        };
+
+        SerializedFile {
+            m_Header: SerializedFileHeader {
+                m_MetadataSize: 0,
+                m_FileSize: 0,
+                m_Version: version,
+                m_DataOffset: 0,
+                m_Endianness: endianness,
+                m_Reserved: [0; 3],
+                unknown: 0,
+            },
+            m_UnityVersion: Some("2019.4.40f1".to_owned()),
+            m_TargetPlatform: Some(13),
+            m_EnableTypeTree: true,
+            m_bigIDEnabled: Some(0),
+            m_Types: vec![serialized_type(49, -1), serialized_type(114, 0)],
+            m_Objects: vec![
+                object_info(1, 0, 49),
+                object_info(-5981516478463471, 1, 114),
+                object_info(3, 0, 49),
+            ],
+            m_ScriptTypes: Some(vec![ScriptType {
+                localSerializedFileIndex: 1,
+                localIdentifierInFile: 11500000,
+            }]),
+            m_Externals: vec![FileIdentifier {
+                tempEmpty: Some(String::new()),
+                guid: Some(vec![1; 16]),
+                typeId: Some(0),
+                pathName: "archive:/CAB-00000000000000000000000000000000/CAB-00000000000000000000000000000000".to_owned(),
+            }],
+            m_RefTypes: Some(vec![serialized_type(0, 0)]),
+            m_UserInformation: Some(String::new()),
+        }
+    }
+
+    pub(crate) fn objects_data() -> Vec<Vec<u8>> {
+        vec![vec![1, 2, 3], b"monobehaviour data".to_vec(), vec![0xAB; 37]]
+    }
+
+    #[test]
+    fn round_trip_all_versions() {
+        let versions = [2, 3, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22];
+        for version in versions {
+            for endianness in [0, 1] {
+                let file = sample_file(version, endianness);
+                let mut written = Cursor::new(Vec::new());
+                file.to_writer(&mut written, &objects_data()).unwrap();
+
+                written.set_position(0);
+                let read = SerializedFile::from_reader(&mut written, &ExtractionConfig::default())
+                    .unwrap_or_else(|e| panic!("version {version}, endianness {endianness}: {e}"));
+                assert_eq!(read.m_Header.m_Version, version);
+                assert_eq!(read.m_Header.m_Endianness, endianness);
+                assert_eq!(read.m_Header.m_FileSize as usize, written.get_ref().len());
+                assert_eq!(read.m_Types.len(), 2);
+                assert_eq!(read.m_Objects.len(), 3);
+                assert_eq!(read.m_Objects[1].m_ClassID, 114);
+                assert_eq!(read.m_Externals[0].pathName, file.m_Externals[0].pathName);
+                let node = read.m_Types[0].m_Type.as_ref().unwrap();
+                assert_eq!(node.get_type(), "TextAsset");
+                assert_eq!(node.get_children()[2].get_type(), "CustomType");
+                assert_eq!(read.read_objects_data(&mut written).unwrap(), objects_data());
+
+                // writing the parsed file again must give the same bytes
+                let mut rewritten = Cursor::new(Vec::new());
+                read.to_writer(&mut rewritten, &read.read_objects_data(&mut written).unwrap()).unwrap();
+                assert_eq!(rewritten.get_ref(), written.get_ref(), "version {version}, endianness {endianness}");
+            }
+        }
+    }
+
+    #[test]
+    fn mismatched_object_count() {
+        let file = sample_file(22, 0);
+        let result = file.to_writer(&mut Cursor::new(Vec::new()), &[]);
+        assert!(matches!(result, Err(Error::InvalidValue(_))));
+    }
+}
@@ -45,4 +45,16 @@ impl FileEntry {
     pub fn get_path(&self) -> &String {
         &self.path
     }
+
+    /// Offset of the end of the entry, rejecting offsets and sizes read from
+    /// a malformed file that are negative or overflow.
+    pub fn get_end_offset(&self) -> Result<u64, Error> {
+        if self.offset < 0 || self.size < 0 {
+            return Err(Error::InvalidValue(format!("{} has a negative offset or size", self.path)));
+        }
+        self.offset
+            .checked_add(self.size)
+            .map(|end| end as u64)
+            .ok_or_else(|| Error::InvalidValue(format!("{} ends beyond the largest offset", self.path)))
+    }
 }
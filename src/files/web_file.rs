@@ -1,45 +1,261 @@
-use crate::{config::ExtractionConfig, files::unity_file::UnityFile, read_ext::ReadUrexExt, Error};
-use byteorder::{BigEndian, ReadBytesExt};
-use std::io::{Read, Seek};
-enum WebCompressionType {
+use crate::{
+    config::ExtractionConfig,
+    files::unity_file::{FileEntry, UnityFile},
+    read_ext::ReadUrexExt,
+    Error,
+};
+use byteorder::{LittleEndian, ReadBytesExt};
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum WebCompressionType {
     None,
     GZip,
     Brotli,
 }
 
-struct WebFile {
-    compression: WebCompressionType,
-    signature: String,
+pub struct WebFile {
+    pub compression: WebCompressionType,
+    pub signature: String,
+    pub m_DirectoryInfo: Vec<FileEntry>,
+    pub m_DataReader: Cursor<Vec<u8>>,
 }
 
-const GZIP_MAGIC: u16 = 0x1f8b;
-const BROTLI_MAGIC: &str = "brotli";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const BROTLI_MAGIC: &[u8] = b"brotli";
+const BROTLI_MAGIC_OFFSET: usize = 0x20;
+const WEB_DATA_SIGNATURE: &str = "UnityWebData1.0";
 
-impl UnityFile for WebFile {
-    fn from_reader<T: Read + Seek>(reader: &mut T, config: &ExtractionConfig) -> Result<Self, Error>
-    where
-        Self: Sized,
-    {
-        let start_pos = reader.stream_position()?;
-        let mut compression: WebCompressionType = WebCompressionType::None;
-        // GZIP
-        if reader.read_u16::<BigEndian>()? == GZIP_MAGIC {
-            reader.seek(std::io::SeekFrom::Current(-2))?;
-            compression = WebCompressionType::GZip;
+impl WebCompressionType {
+    /// Detects the compression from the first bytes of a file, `None` if they don't start a web file.
+    fn detect(header: &[u8]) -> Option<Self> {
+        if header.starts_with(&GZIP_MAGIC) {
+            Some(WebCompressionType::GZip)
+        } else if header.get(BROTLI_MAGIC_OFFSET..BROTLI_MAGIC_OFFSET + BROTLI_MAGIC.len())
+            == Some(BROTLI_MAGIC)
+        {
+            Some(WebCompressionType::Brotli)
+        } else if header.starts_with(WEB_DATA_SIGNATURE.as_bytes())
+            && header.get(WEB_DATA_SIGNATURE.len()) == Some(&0)
+        {
+            Some(WebCompressionType::None)
         } else {
-            // Brotli
-            reader.seek(std::io::SeekFrom::Current(0x20 - 2))?;
-            let magic = reader.read_string_sized(6)?;
-            reader.seek(std::io::SeekFrom::Current(-0x26))?;
-            if magic == BROTLI_MAGIC {
-                compression = WebCompressionType::Brotli;
-            }
+            None
+        }
+    }
+}
+
+impl WebFile {
+    /// Checks whether the data at the reader's position starts an uncompressed,
+    /// gzip or brotli compressed web file. The reader is moved back afterwards.
+    pub fn is_web_file<T: Read + Seek>(reader: &mut T) -> Result<bool, Error> {
+        let start = reader.stream_position()?;
+        let mut header = Vec::new();
+        reader
+            .by_ref()
+            .take((BROTLI_MAGIC_OFFSET + BROTLI_MAGIC.len()) as u64)
+            .read_to_end(&mut header)?;
+        reader.seek(SeekFrom::Start(start))?;
+        Ok(WebCompressionType::detect(&header).is_some())
+    }
+
+    pub fn from_reader<T: Read + Seek>(
+        reader: &mut T,
+        config: &ExtractionConfig,
+    ) -> Result<Self, Error> {
+        let mut raw = Vec::new();
+        reader.read_to_end(&mut raw)?;
+
+        let compression = WebCompressionType::detect(&raw).ok_or(Error::UnknownSignature)?;
+
+        let data = match compression {
+            WebCompressionType::None => raw,
+            WebCompressionType::GZip => Self::decompress_gzip(&raw)?,
+            WebCompressionType::Brotli => Self::decompress_brotli(&raw)?,
+        };
+
+        let mut data_reader = Cursor::new(data);
+        let signature = data_reader.read_cstr()?;
+        if signature != WEB_DATA_SIGNATURE {
+            return Err(Error::UnknownSignature);
         }
-        let signature = reader.read_cstr()?;
+
+        // the header size doubles as the offset of the first file
+        let head_size = data_reader.read_u32::<LittleEndian>()? as u64;
+        let mut m_DirectoryInfo = Vec::new();
+        while data_reader.position() < head_size {
+            let offset = data_reader.read_u32::<LittleEndian>()? as i64;
+            let size = data_reader.read_u32::<LittleEndian>()? as i64;
+            let path_length = data_reader.read_u32::<LittleEndian>()? as usize;
+            let path = data_reader.read_string_sized(path_length)?;
+            m_DirectoryInfo.push(FileEntry {
+                offset,
+                size,
+                flags: 0,
+                path,
+            });
+        }
+        data_reader.seek(SeekFrom::Start(0))?;
 
         Ok(WebFile {
             compression,
             signature,
+            m_DirectoryInfo,
+            m_DataReader: data_reader,
         })
     }
+
+    fn decompress_gzip(raw: &[u8]) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "gzip")]
+        {
+            let mut data = Vec::new();
+            flate2::read::GzDecoder::new(raw).read_to_end(&mut data)?;
+            Ok(data)
+        }
+
+        #[cfg(not(feature = "gzip"))]
+        Err(Error::FeatureDisabled("gzip"))
+    }
+
+    fn decompress_brotli(raw: &[u8]) -> Result<Vec<u8>, Error> {
+        #[cfg(feature = "brotli")]
+        {
+            let mut data = Vec::new();
+            brotli::Decompressor::new(raw, 4096).read_to_end(&mut data)?;
+            Ok(data)
+        }
+
+        #[cfg(not(feature = "brotli"))]
+        Err(Error::FeatureDisabled("brotli"))
+    }
+}
+
+impl UnityFile for WebFile {
+    fn from_reader<T: Read + Seek>(reader: &mut T, config: &ExtractionConfig) -> Result<Self, Error>
+    where
+        Self: Sized,
+    {
+        WebFile::from_reader(reader, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use byteorder::WriteBytesExt;
+    use std::io::Write;
+
+    fn build_web_data(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let head_size = WEB_DATA_SIGNATURE.len() + 1 + 4
+            + files.iter().map(|(path, _)| 12 + path.len()).sum::<usize>();
+
+        let mut out = Vec::new();
+        out.write_all(WEB_DATA_SIGNATURE.as_bytes()).unwrap();
+        out.write_u8(0).unwrap();
+        out.write_u32::<LittleEndian>(head_size as u32).unwrap();
+
+        let mut offset = head_size;
+        for (path, data) in files {
+            out.write_u32::<LittleEndian>(offset as u32).unwrap();
+            out.write_u32::<LittleEndian>(data.len() as u32).unwrap();
+            out.write_u32::<LittleEndian>(path.len() as u32).unwrap();
+            out.write_all(path.as_bytes()).unwrap();
+            offset += data.len();
+        }
+        for (_, data) in files {
+            out.write_all(data).unwrap();
+        }
+        out
+    }
+
+    fn check_web_file(mut web_file: WebFile) {
+        assert_eq!(web_file.signature, WEB_DATA_SIGNATURE);
+        assert_eq!(web_file.m_DirectoryInfo.len(), 2);
+        assert_eq!(web_file.m_DirectoryInfo[0].path, "data.unity3d");
+        assert_eq!(web_file.m_DirectoryInfo[1].path, "Il2CppData/Metadata/global-metadata.dat");
+
+        let entry = &web_file.m_DirectoryInfo[1];
+        web_file.m_DataReader.seek(SeekFrom::Start(entry.offset as u64)).unwrap();
+        let data = web_file.m_DataReader.read_bytes_sized(entry.size as usize).unwrap();
+        assert_eq!(data, b"metadata");
+    }
+
+    fn sample() -> Vec<u8> {
+        build_web_data(&[
+            ("data.unity3d", b"UnityFS"),
+            ("Il2CppData/Metadata/global-metadata.dat", b"metadata"),
+        ])
+    }
+
+    #[test]
+    fn uncompressed_web_data() {
+        let web_file = WebFile::from_reader(&mut Cursor::new(sample()), &ExtractionConfig::default()).unwrap();
+        assert_eq!(web_file.compression, WebCompressionType::None);
+        check_web_file(web_file);
+    }
+
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn gzip_web_data() {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&sample()).unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        let web_file = WebFile::from_reader(&mut Cursor::new(compressed), &ExtractionConfig::default()).unwrap();
+        assert_eq!(web_file.compression, WebCompressionType::GZip);
+        check_web_file(web_file);
+    }
+
+    /// Stores `data` in a brotli stream that starts with the metadata comment Unity
+    /// writes, which puts "brotli" at 0x20. The data is kept in an uncompressed
+    /// meta-block, so the stream can be built without an encoder.
+    fn brotli_web_data(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::new();
+        let (mut bits, mut bit_count) = (0u64, 0);
+        let mut write_bits = |out: &mut Vec<u8>, value: u64, count: u32, align: bool| {
+            bits |= value << bit_count;
+            bit_count += count;
+            if align {
+                bit_count = bit_count.next_multiple_of(8);
+            }
+            while bit_count >= 8 {
+                out.push(bits as u8);
+                bits >>= 8;
+                bit_count -= 8;
+            }
+        };
+
+        let comment = b"UnityWeb Compressed Content (brotli)";
+        // window bits 22, then a metadata meta-block holding the comment
+        write_bits(&mut out, 0b1011, 4, false);
+        write_bits(&mut out, 0b010110, 6, false);
+        write_bits(&mut out, (comment.len() - 1) as u64, 8, true);
+        out.extend(comment);
+        // an uncompressed meta-block, followed by the empty last one
+        write_bits(&mut out, 0b000, 3, false);
+        write_bits(&mut out, (data.len() - 1) as u64, 16, false);
+        write_bits(&mut out, 1, 1, true);
+        out.extend(data);
+        write_bits(&mut out, 0b11, 2, true);
+        out
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_web_data_round_trip() {
+        let compressed = brotli_web_data(&sample());
+        assert_eq!(&compressed[BROTLI_MAGIC_OFFSET..BROTLI_MAGIC_OFFSET + BROTLI_MAGIC.len()], BROTLI_MAGIC);
+        assert!(WebFile::is_web_file(&mut Cursor::new(&compressed)).unwrap());
+
+        let web_file = WebFile::from_reader(&mut Cursor::new(compressed), &ExtractionConfig::default()).unwrap();
+        assert_eq!(web_file.compression, WebCompressionType::Brotli);
+        check_web_file(web_file);
+    }
+
+    #[test]
+    fn unknown_signature() {
+        assert!(!WebFile::is_web_file(&mut Cursor::new(b"UnityFS\0")).unwrap());
+        let result = WebFile::from_reader(&mut Cursor::new(b"UnityFS\0".to_vec()), &ExtractionConfig::default());
+        assert!(matches!(result, Err(Error::UnknownSignature)));
+    }
 }
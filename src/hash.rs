@@ -0,0 +1,283 @@
+// SpookyHash V2 by Bob Jenkins, which Unity uses for its Hash128 values
+// reference: https://burtleburtle.net/bob/hash/spooky.html
+
+const SC_NUM_VARS: usize = 12;
+const SC_BLOCK_SIZE: usize = SC_NUM_VARS * 8;
+const SC_BUF_SIZE: usize = 2 * SC_BLOCK_SIZE;
+const SC_CONST: u64 = 0xdeadbeefdeadbeef;
+
+fn read_u64(data: &[u8], index: usize) -> u64 {
+    u64::from_le_bytes(data[index * 8..index * 8 + 8].try_into().unwrap())
+}
+
+fn mix(data: &[u8], s: &mut [u64; SC_NUM_VARS]) {
+    const ROTATIONS: [u32; SC_NUM_VARS] = [11, 32, 43, 31, 17, 28, 39, 57, 55, 54, 22, 46];
+    for i in 0..SC_NUM_VARS {
+        s[i] = s[i].wrapping_add(read_u64(data, i));
+        s[(i + 2) % SC_NUM_VARS] ^= s[(i + 10) % SC_NUM_VARS];
+        s[(i + 11) % SC_NUM_VARS] ^= s[i];
+        s[i] = s[i].rotate_left(ROTATIONS[i]);
+        s[(i + 11) % SC_NUM_VARS] = s[(i + 11) % SC_NUM_VARS].wrapping_add(s[(i + 1) % SC_NUM_VARS]);
+    }
+}
+
+fn end_partial(h: &mut [u64; SC_NUM_VARS]) {
+    const ROTATIONS: [u32; SC_NUM_VARS] = [44, 15, 34, 21, 38, 33, 10, 13, 38, 53, 42, 54];
+    for (i, rotation) in ROTATIONS.iter().enumerate() {
+        let a = (i + 11) % SC_NUM_VARS;
+        let b = (i + 1) % SC_NUM_VARS;
+        let c = (i + 2) % SC_NUM_VARS;
+        h[a] = h[a].wrapping_add(h[b]);
+        h[c] ^= h[a];
+        h[b] = h[b].rotate_left(*rotation);
+    }
+}
+
+fn end(data: &[u8], h: &mut [u64; SC_NUM_VARS]) {
+    for (i, value) in h.iter_mut().enumerate() {
+        *value = value.wrapping_add(read_u64(data, i));
+    }
+    end_partial(h);
+    end_partial(h);
+    end_partial(h);
+}
+
+fn short_mix(h: &mut [u64; 4]) {
+    const ROTATIONS: [u32; 12] = [50, 52, 30, 41, 54, 48, 38, 37, 62, 34, 5, 36];
+    for (i, rotation) in ROTATIONS.iter().enumerate() {
+        let a = (i + 2) % 4;
+        let b = (i + 3) % 4;
+        let c = i % 4;
+        h[a] = h[a].rotate_left(*rotation);
+        h[a] = h[a].wrapping_add(h[b]);
+        h[c] ^= h[a];
+    }
+}
+
+fn short_end(h: &mut [u64; 4]) {
+    const ROTATIONS: [u32; 11] = [15, 52, 26, 51, 28, 9, 47, 54, 32, 25, 63];
+    for (i, rotation) in ROTATIONS.iter().enumerate() {
+        let a = (i + 3) % 4;
+        let b = (i + 2) % 4;
+        h[a] ^= h[b];
+        h[b] = h[b].rotate_left(*rotation);
+        h[a] = h[a].wrapping_add(h[b]);
+    }
+}
+
+fn short(message: &[u8], seed1: u64, seed2: u64) -> (u64, u64) {
+    let length = message.len();
+    let mut h = [seed1, seed2, SC_CONST, SC_CONST];
+
+    let mut data = message;
+    if length > 15 {
+        while data.len() >= 32 {
+            h[2] = h[2].wrapping_add(read_u64(data, 0));
+            h[3] = h[3].wrapping_add(read_u64(data, 1));
+            short_mix(&mut h);
+            h[0] = h[0].wrapping_add(read_u64(data, 2));
+            h[1] = h[1].wrapping_add(read_u64(data, 3));
+            data = &data[32..];
+        }
+        if data.len() >= 16 {
+            h[2] = h[2].wrapping_add(read_u64(data, 0));
+            h[3] = h[3].wrapping_add(read_u64(data, 1));
+            short_mix(&mut h);
+            data = &data[16..];
+        }
+    }
+
+    // the remaining bytes are added as little endian words, the upper half to d
+    h[3] = h[3].wrapping_add((length as u64) << 56);
+    if data.is_empty() {
+        h[2] = h[2].wrapping_add(SC_CONST);
+        h[3] = h[3].wrapping_add(SC_CONST);
+    } else {
+        let mut buf = [0u8; 16];
+        buf[..data.len()].copy_from_slice(data);
+        h[2] = h[2].wrapping_add(read_u64(&buf, 0));
+        h[3] = h[3].wrapping_add(read_u64(&buf, 1));
+    }
+    short_end(&mut h);
+    (h[0], h[1])
+}
+
+/// Computes the 128-bit SpookyHash V2 of `message`.
+pub fn spooky_hash128(message: &[u8], seed1: u64, seed2: u64) -> (u64, u64) {
+    if message.len() < SC_BUF_SIZE {
+        return short(message, seed1, seed2);
+    }
+
+    let mut h = [0u64; SC_NUM_VARS];
+    for i in 0..SC_NUM_VARS / 3 {
+        h[i * 3] = seed1;
+        h[i * 3 + 1] = seed2;
+        h[i * 3 + 2] = SC_CONST;
+    }
+
+    let mut blocks = message.chunks_exact(SC_BLOCK_SIZE);
+    for block in &mut blocks {
+        mix(block, &mut h);
+    }
+
+    let remainder = blocks.remainder();
+    let mut buf = [0u8; SC_BLOCK_SIZE];
+    buf[..remainder.len()].copy_from_slice(remainder);
+    buf[SC_BLOCK_SIZE - 1] = remainder.len() as u8;
+    end(&buf, &mut h);
+    (h[0], h[1])
+}
+
+/// Computes the [`spooky_hash128`] of a message that is passed in pieces,
+/// so it doesn't have to be kept in memory as a whole.
+pub struct SpookyHasher {
+    state: [u64; SC_NUM_VARS],
+    data: [u8; SC_BUF_SIZE],
+    length: usize,
+    remainder: usize,
+}
+
+impl SpookyHasher {
+    pub fn new(seed1: u64, seed2: u64) -> Self {
+        let mut state = [0u64; SC_NUM_VARS];
+        state[0] = seed1;
+        state[1] = seed2;
+        SpookyHasher {
+            state,
+            data: [0u8; SC_BUF_SIZE],
+            length: 0,
+            remainder: 0,
+        }
+    }
+
+    /// Adds the next piece of the message.
+    pub fn update(&mut self, mut message: &[u8]) {
+        // short messages are kept until they are long enough to be mixed
+        if self.remainder + message.len() < SC_BUF_SIZE {
+            self.data[self.remainder..self.remainder + message.len()].copy_from_slice(message);
+            self.length += message.len();
+            self.remainder += message.len();
+            return;
+        }
+
+        let mut h = self.state;
+        if self.length < SC_BUF_SIZE {
+            for i in 0..SC_NUM_VARS / 3 {
+                h[i * 3] = self.state[0];
+                h[i * 3 + 1] = self.state[1];
+                h[i * 3 + 2] = SC_CONST;
+            }
+        }
+        self.length += message.len();
+
+        if self.remainder > 0 {
+            let prefix = SC_BUF_SIZE - self.remainder;
+            self.data[self.remainder..].copy_from_slice(&message[..prefix]);
+            mix(&self.data[..SC_BLOCK_SIZE], &mut h);
+            mix(&self.data[SC_BLOCK_SIZE..], &mut h);
+            message = &message[prefix..];
+        }
+
+        let mut blocks = message.chunks_exact(SC_BLOCK_SIZE);
+        for block in &mut blocks {
+            mix(block, &mut h);
+        }
+        let remainder = blocks.remainder();
+        self.data[..remainder.len()].copy_from_slice(remainder);
+        self.remainder = remainder.len();
+        self.state = h;
+    }
+
+    /// Returns the hash of everything passed to [`SpookyHasher::update`].
+    pub fn finish(&self) -> (u64, u64) {
+        if self.length < SC_BUF_SIZE {
+            return short(&self.data[..self.length], self.state[0], self.state[1]);
+        }
+
+        let mut h = self.state;
+        let mut data = &self.data[..self.remainder];
+        if data.len() >= SC_BLOCK_SIZE {
+            mix(&data[..SC_BLOCK_SIZE], &mut h);
+            data = &data[SC_BLOCK_SIZE..];
+        }
+
+        let mut buf = [0u8; SC_BLOCK_SIZE];
+        buf[..data.len()].copy_from_slice(data);
+        buf[SC_BLOCK_SIZE - 1] = data.len() as u8;
+        end(&buf, &mut h);
+        (h[0], h[1])
+    }
+
+    /// Returns the hash as the bytes of a Unity Hash128.
+    pub fn finish_hash128(&self) -> [u8; 16] {
+        let (hash1, hash2) = self.finish();
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&hash1.to_le_bytes());
+        bytes[8..].copy_from_slice(&hash2.to_le_bytes());
+        bytes
+    }
+}
+
+/// Computes the [`spooky_hash128`] of `message` as the bytes of a Unity Hash128.
+pub fn unity_hash128(message: &[u8]) -> [u8; 16] {
+    let mut hasher = SpookyHasher::new(0, 0);
+    hasher.update(message);
+    hasher.finish_hash128()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spooky_hash_reference_values() {
+        // first results of the reference TestResults, the message is (i + 128) for every byte i
+        let expected: [u32; 64] = [
+            0x6bf50919, 0x70de1d26, 0xa2b37298, 0x35bc5fbf, 0x8223b279, 0x5bcb315e, 0x53fe88a1, 0xf9f1a233,
+            0xee193982, 0x54f86f29, 0xc8772d36, 0x9ed60886, 0x5f23d1da, 0x1ed9f474, 0xf2ef0c89, 0x83ec01f9,
+            0xf274736c, 0x7e9ac0df, 0xc7aed250, 0xb1015811, 0xe23470f5, 0x48ac20c4, 0xe2ab3cd5, 0x608f8363,
+            0xd0639e68, 0xc4e8e7ab, 0x863c7c5b, 0x4ea63579, 0x99ae8622, 0x170c658b, 0x149ba493, 0x027bca7c,
+            0xe5cfc8b6, 0xce01d9d7, 0x11103330, 0x5d1f5ed4, 0xca720ecb, 0xef408aec, 0x733b90ec, 0x855737a6,
+            0x9856c65f, 0x647411f7, 0x50777c74, 0xf0f1a8b7, 0x9d7e55a5, 0xc68dd371, 0xfc1af2cc, 0x75728d0a,
+            0x390e5fdc, 0xf389b84c, 0xfb0ccf23, 0xc95bad0e, 0x5b1cb85a, 0x6bdae14f, 0x6deb4626, 0x93047034,
+            0x6f3266c6, 0xf529c3bd, 0x396322e7, 0x3777d042, 0x1cd6a5a2, 0x197b402e, 0xc28d0d2b, 0x09c1afb4,
+        ];
+        let buf: Vec<u8> = (0..expected.len()).map(|i| (i + 128) as u8).collect();
+        for (length, expected) in expected.iter().enumerate() {
+            assert_eq!(spooky_hash128(&buf[..length], 0, 0).0 as u32, *expected, "length {length}");
+        }
+    }
+
+    #[test]
+    fn spooky_hash_verification_codes() {
+        // SMHasher VerificationTest: the keys 0..i are hashed with the seed 256 - i, and the
+        // concatenated hashes (4096 bytes for 128 bits) are hashed again with the seed 0
+        let key: Vec<u8> = (0..=255).collect();
+        for (hash_bytes, expected) in [(4, 0xa48be265u32), (8, 0x972c4bdc), (16, 0x893cfcbe)] {
+            let mut hashes = Vec::with_capacity(hash_bytes * 256);
+            for length in 0..256 {
+                let seed = 256 - length as u64;
+                let (hash1, hash2) = spooky_hash128(&key[..length], seed, seed);
+                let bytes = [hash1.to_le_bytes(), hash2.to_le_bytes()].concat();
+                hashes.extend_from_slice(&bytes[..hash_bytes]);
+            }
+            assert_eq!(spooky_hash128(&hashes, 0, 0).0 as u32, expected, "{} bits", hash_bytes * 8);
+        }
+    }
+
+    #[test]
+    fn spooky_hasher_pieces() {
+        let message: Vec<u8> = (0..1000u32).map(|i| (i * 31 % 251) as u8).collect();
+        for length in [0, 15, 16, 95, 96, 191, 192, 193, 300, 1000] {
+            let message = &message[..length];
+            let expected = spooky_hash128(message, 7, 11);
+            for piece_size in [1, 13, 96, 200, 1000] {
+                let mut hasher = SpookyHasher::new(7, 11);
+                for piece in message.chunks(piece_size) {
+                    hasher.update(piece);
+                }
+                assert_eq!(hasher.finish(), expected, "length {length}, pieces of {piece_size}");
+            }
+        }
+    }
+}
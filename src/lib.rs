@@ -2,10 +2,15 @@
 #![allow(unused_variables)]
 #![allow(dead_code)]
 pub mod config;
+pub mod environment;
+pub mod export;
+pub mod resource;
 pub mod files;
 pub mod read_ext;
+pub mod write_ext;
+pub(crate) mod hash;
 
-mod unitycn;
+pub mod unitycn;
 
 mod error;
 pub use error::Error;
@@ -1,13 +1,24 @@
 pub use runirip_objects::*;
 
-use crate::files::{ObjectReader, SerializedFile};
+use crate::environment::Environment;
+use crate::files::{BlockSource, ObjectReader, SerializedFile};
+use crate::Error;
 
 pub trait PPtrExt {
+    /// Looks the object up in `asset`, which only works for pointers into the same file.
+    /// Use [`PPtrExt::resolve`] for pointers into other files.
     fn get_object_reader<'a, R: std::io::Read + std::io::Seek>(
         &'a self,
         asset: &'a SerializedFile,
         reader: &'a mut R,
     ) -> Option<ObjectReader<'a, R>>;
+
+    /// Follows the pointer from the serialized file `from` to an object in any file loaded in `env`.
+    fn resolve<'a>(
+        &self,
+        env: &'a mut Environment,
+        from: &str,
+    ) -> Result<ObjectReader<'a, Box<dyn BlockSource>>, Error>;
 }
 
 impl PPtrExt for PPtr {
@@ -16,9 +27,20 @@ impl PPtrExt for PPtr {
         asset: &'a SerializedFile,
         reader: &'a mut R,
     ) -> Option<ObjectReader<'a, R>> {
+        if self.m_FileID != 0 {
+            return None;
+        }
         asset.m_Objects
             .iter()
             .find(|x| x.m_PathID == self.m_PathID)
             .map(|object_info| asset.get_object_reader(object_info, reader))
     }
-}
\ No newline at end of file
+
+    fn resolve<'a>(
+        &self,
+        env: &'a mut Environment,
+        from: &str,
+    ) -> Result<ObjectReader<'a, Box<dyn BlockSource>>, Error> {
+        env.get_object_reader(from, self.m_FileID, self.m_PathID)
+    }
+}
@@ -0,0 +1,222 @@
+use crate::{environment::Environment, files::BundleFile, read_ext::ReadUrexExt, Error};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+
+const ARCHIVE_PREFIX: &str = "archive:/";
+
+/// A range of bytes in a `.resS` or `.resource` file, as referenced by
+/// `StreamingInfo` and `StreamedResource` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceRange {
+    /// Either a path inside an archive like `archive:/CAB-x/CAB-x.resS` or a path on disk.
+    pub path: String,
+    pub offset: u64,
+    pub size: u64,
+}
+
+impl ResourceRange {
+    pub fn new(path: String, offset: u64, size: u64) -> Self {
+        ResourceRange { path, offset, size }
+    }
+
+    /// Whether the data lives in an entry of the bundle that holds the referencing object.
+    pub fn is_archive_path(&self) -> bool {
+        self.path.starts_with(ARCHIVE_PREFIX)
+    }
+
+    /// Last component of the path, which is the entry name inside a bundle.
+    pub fn get_file_name(&self) -> &str {
+        self.path.rsplit(['/', '\\']).next().unwrap_or(&self.path)
+    }
+
+    /// Reads the range from a reader over the whole resource file.
+    pub fn read_from<R: Read + Seek>(&self, reader: &mut R) -> Result<Vec<u8>, Error> {
+        let length = reader.seek(SeekFrom::End(0))?;
+        if self.offset.checked_add(self.size).is_none_or(|end| end > length) {
+            return Err(Error::InvalidValue(format!(
+                "{} bytes at {} are out of bounds of {} ({length} bytes)",
+                self.size, self.offset, self.path
+            )));
+        }
+        reader.seek(SeekFrom::Start(self.offset))?;
+        reader.read_bytes_sized(self.size as usize)
+    }
+
+    /// Reads the range from the entry of `bundle` that the path names.
+    pub fn read_from_bundle(&self, bundle: &mut BundleFile) -> Result<Vec<u8>, Error> {
+        self.check_path()?;
+        let file_name = self.get_file_name();
+        let index = bundle
+            .m_DirectoryInfo
+            .iter()
+            .position(|entry| entry.path.eq_ignore_ascii_case(file_name))
+            .ok_or_else(|| Error::EntryNotFound(self.path.clone()))?;
+        self.read_from(&mut bundle.open_entry(index)?)
+    }
+
+    /// Reads the range from a file in `directory`.
+    ///
+    /// Archive paths are looked up by their file name, plain paths relative to the
+    /// directory first and by their file name if that doesn't exist.
+    pub fn read_from_directory<P: AsRef<Path>>(&self, directory: P) -> Result<Vec<u8>, Error> {
+        let path = self.find_in_directory(directory.as_ref())?;
+        let mut file = std::fs::File::open(path)?;
+        self.read_from(&mut file)
+    }
+
+    /// Reads the range from a resource file loaded into `env`, falling back to the
+    /// file system for plain relative paths that weren't loaded.
+    pub fn read_from_environment(&self, env: &mut Environment) -> Result<Vec<u8>, Error> {
+        self.check_path()?;
+        if let Some(resource) = env.get_resource_file_mut(&self.path) {
+            return self.read_from(resource.get_reader());
+        }
+        if !self.is_archive_path() {
+            check_relative(&self.path)?;
+            if Path::new(&self.path).is_file() {
+                let mut file = std::fs::File::open(&self.path)?;
+                return self.read_from(&mut file);
+            }
+        }
+        Err(Error::EntryNotFound(self.path.clone()))
+    }
+
+    fn find_in_directory(&self, directory: &Path) -> Result<PathBuf, Error> {
+        self.check_path()?;
+        if !self.is_archive_path() {
+            check_relative(&self.path)?;
+            let path = directory.join(&self.path);
+            if path.is_file() {
+                return Ok(path);
+            }
+        }
+        check_relative(self.get_file_name())?;
+        let path = directory.join(self.get_file_name());
+        if path.is_file() {
+            Ok(path)
+        } else {
+            Err(Error::EntryNotFound(self.path.clone()))
+        }
+    }
+
+    fn check_path(&self) -> Result<(), Error> {
+        // objects whose data is stored inline have an empty path
+        if self.path.is_empty() {
+            Err(Error::InvalidValue("the resource path is empty".to_owned()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Checks that `path` stays inside the folder it is joined to, so paths read
+/// from assets can't point to an absolute path or climb out with `..`.
+fn check_relative(path: &str) -> Result<(), Error> {
+    let is_relative = Path::new(path)
+        .components()
+        .all(|component| matches!(component, Component::Normal(_) | Component::CurDir));
+    if is_relative {
+        Ok(())
+    } else {
+        Err(Error::InvalidValue(format!("the resource path {path} leaves the folder")))
+    }
+}
+
+#[cfg(feature = "objects")]
+impl From<&crate::objects::classes::StreamingInfo> for ResourceRange {
+    fn from(info: &crate::objects::classes::StreamingInfo) -> Self {
+        ResourceRange::new(info.path.clone(), info.offset, info.size as u64)
+    }
+}
+
+#[cfg(feature = "objects")]
+impl From<&crate::objects::classes::StreamedResource> for ResourceRange {
+    fn from(resource: &crate::objects::classes::StreamedResource) -> Self {
+        ResourceRange::new(resource.m_Source.clone(), resource.m_Offset as u64, resource.m_Size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{BundleWriteConfig, ExtractionConfig},
+        files::{bundle_file::BundleFileHeader, unity_file::FileEntry, CompressionType},
+    };
+    use std::io::Cursor;
+
+    const RESS: &str = "CAB-0123456789abcdef.resS";
+
+    fn bundle_data() -> Vec<u8> {
+        let data: Vec<u8> = (0..200u8).collect();
+        let entries = vec![
+            FileEntry {
+                offset: 0,
+                size: 100,
+                flags: 4,
+                path: "CAB-0123456789abcdef".to_owned(),
+            },
+            FileEntry {
+                offset: 100,
+                size: 100,
+                flags: 0,
+                path: RESS.to_owned(),
+            },
+        ];
+        let header = BundleFileHeader::new(6, "5.x.x".to_owned(), "2019.4.40f1".to_owned());
+        let config = BundleWriteConfig::new(CompressionType::None, false)
+            .with_block_info_compression(CompressionType::None);
+        let mut written = Vec::new();
+        BundleFile::write_unityfs(&mut written, &header, &entries, &data, &config).unwrap();
+        written
+    }
+
+    #[test]
+    fn read_from_bundle() {
+        let mut bundle = BundleFile::from_reader(&mut Cursor::new(bundle_data()), &ExtractionConfig::default()).unwrap();
+        let range = ResourceRange::new(format!("archive:/CAB-0123456789abcdef/{RESS}"), 10, 5);
+        assert!(range.is_archive_path());
+        assert_eq!(range.read_from_bundle(&mut bundle).unwrap(), vec![110, 111, 112, 113, 114]);
+
+        let out_of_bounds = ResourceRange::new(range.path.clone(), 98, 5);
+        assert!(matches!(out_of_bounds.read_from_bundle(&mut bundle), Err(Error::InvalidValue(_))));
+        let missing = ResourceRange::new("archive:/CAB-1/CAB-1.resS".to_owned(), 0, 1);
+        assert!(matches!(missing.read_from_bundle(&mut bundle), Err(Error::EntryNotFound(_))));
+        let inline = ResourceRange::new(String::new(), 0, 0);
+        assert!(matches!(inline.read_from_bundle(&mut bundle), Err(Error::InvalidValue(_))));
+    }
+
+    #[test]
+    fn read_from_environment() {
+        let mut env = Environment::default();
+        env.load_reader("bundle.unity3d", Cursor::new(bundle_data())).unwrap();
+        let range = ResourceRange::new(format!("archive:/CAB-0123456789abcdef/{RESS}"), 0, 3);
+        assert_eq!(range.read_from_environment(&mut env).unwrap(), vec![100, 101, 102]);
+    }
+
+    #[test]
+    fn read_from_directory() {
+        let directory = std::env::temp_dir().join(format!("runirip-resource-{}", std::process::id()));
+        std::fs::create_dir_all(directory.join("Data")).unwrap();
+        std::fs::write(directory.join("Data/sharedassets0.resource"), b"0123456789").unwrap();
+
+        let plain = ResourceRange::new("Data/sharedassets0.resource".to_owned(), 2, 3);
+        assert_eq!(plain.read_from_directory(&directory).unwrap(), b"234");
+        // plain paths fall back to the file name when the folder layout differs
+        assert_eq!(plain.read_from_directory(directory.join("Data")).unwrap(), b"234");
+        let archive = ResourceRange::new("archive:/CAB-1/sharedassets0.resource".to_owned(), 7, 3);
+        assert_eq!(archive.read_from_directory(directory.join("Data")).unwrap(), b"789");
+
+        // paths that leave the folder are rejected before they are joined
+        let data = directory.join("Data");
+        for path in ["../resource.txt", "Data/../../resource.txt", "/etc/passwd", "archive:/CAB-1/.."] {
+            let range = ResourceRange::new(path.to_owned(), 0, 1);
+            assert!(matches!(range.read_from_directory(&data), Err(Error::InvalidValue(_))), "{path}");
+        }
+        let mut env = Environment::default();
+        let absolute = ResourceRange::new(data.join("sharedassets0.resource").to_string_lossy().into_owned(), 0, 1);
+        assert!(matches!(absolute.read_from_environment(&mut env), Err(Error::InvalidValue(_))));
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
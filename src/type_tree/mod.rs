@@ -7,4 +7,9 @@ pub use value::Value;
 #[cfg(feature = "serde")]
 mod de;
 #[cfg(feature = "serde")]
-pub use de::Deserializer;
\ No newline at end of file
+pub use de::Deserializer;
+
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(feature = "serde")]
+pub use ser::Serializer;
@@ -1,11 +1,12 @@
 #![allow(clippy::redundant_closure_call)]
 use std::collections::HashMap;
 
-use crate::{common_strings::COMMON_STRINGS, TypeTreeValue};
+use crate::{common_strings::{COMMON_STRINGS, COMMON_STRING_OFFSETS}, TypeTreeValue};
 use crate::read_ext::ReadUrexExt;
+use crate::write_ext::WriteUrexExt;
 use crate::Error;
 use bitflags::bitflags;
-use byteorder::{ByteOrder, ReadBytesExt};
+use byteorder::{ByteOrder, ReadBytesExt, WriteBytesExt};
 
 bitflags! {
     struct TransferMetaFlags: i32 {
@@ -219,6 +220,138 @@ impl Node {
         Ok(root_node)
     }
 
+    /// Creates a node, assigning the levels and indices of the whole subtree.
+    pub fn new(
+        m_Type: String,
+        m_Name: String,
+        m_ByteSize: i32,
+        m_MetaFlag: i32,
+        children: Vec<Node>,
+    ) -> Node {
+        let mut node = Node {
+            m_Version: 1,
+            m_Level: 0,
+            m_TypeFlags: (m_Type == "Array") as i32,
+            m_ByteSize,
+            m_Index: Some(0),
+            m_MetaFlag: Some(m_MetaFlag),
+            m_Type,
+            m_Name,
+            m_RefTypeHash: None,
+            m_VariableCount: None,
+            children,
+        };
+
+        fn renumber(node: &mut Node, level: u8, index: &mut i32) {
+            node.m_Level = level;
+            node.m_Index = Some(*index);
+            *index += 1;
+            for child in node.children.iter_mut() {
+                renumber(child, level + 1, index);
+            }
+        }
+        renumber(&mut node, 0, &mut 0);
+        node
+    }
+
+    pub fn get_type(&self) -> &String {
+        &self.m_Type
+    }
+
+    pub fn get_name(&self) -> &String {
+        &self.m_Name
+    }
+
+    pub fn get_byte_size(&self) -> i32 {
+        self.m_ByteSize
+    }
+
+    pub fn get_meta_flag(&self) -> Option<i32> {
+        self.m_MetaFlag
+    }
+
+    pub fn get_children(&self) -> &Vec<Node> {
+        &self.children
+    }
+
+    pub fn to_writer<W: std::io::Write, B: ByteOrder>(
+        &self,
+        writer: &mut W,
+        version: u32,
+    ) -> Result<(), Error> {
+        writer.write_cstr(&self.m_Type)?;
+        writer.write_cstr(&self.m_Name)?;
+        writer.write_i32::<B>(self.m_ByteSize)?;
+        if version == 2 {
+            writer.write_i32::<B>(self.m_VariableCount.unwrap_or(0))?;
+        }
+        if version != 3 {
+            writer.write_i32::<B>(self.m_Index.unwrap_or(0))?;
+        }
+        writer.write_i32::<B>(self.m_TypeFlags)?;
+        writer.write_i32::<B>(self.m_Version)?;
+        if version != 3 {
+            writer.write_i32::<B>(self.m_MetaFlag.unwrap_or(0))?;
+        }
+        writer.write_i32::<B>(self.children.len() as i32)?;
+        for child in self.children.iter() {
+            child.to_writer::<W, B>(writer, version)?;
+        }
+        Ok(())
+    }
+
+    pub fn blob_to_writer<W: std::io::Write, B: ByteOrder>(
+        &self,
+        writer: &mut W,
+        version: u32,
+    ) -> Result<(), Error> {
+        // the blob stores the tree as a flat list in depth-first order
+        fn flatten<'a>(node: &'a Node, nodes: &mut Vec<&'a Node>) {
+            nodes.push(node);
+            for child in node.children.iter() {
+                flatten(child, nodes);
+            }
+        }
+        let mut nodes = Vec::new();
+        flatten(self, &mut nodes);
+
+        let mut string_buffer = Vec::new();
+        let mut string_offsets: HashMap<String, u32> = HashMap::new();
+        let mut string_offset = |value: &str| -> Result<u32, Error> {
+            if let Some(offset) = COMMON_STRING_OFFSETS.get(value) {
+                return Ok(offset | 0x80000000);
+            }
+            if let Some(offset) = string_offsets.get(value) {
+                return Ok(*offset);
+            }
+            let offset = string_buffer.len() as u32;
+            string_buffer.write_cstr(value)?;
+            string_offsets.insert(value.to_owned(), offset);
+            Ok(offset)
+        };
+
+        let mut node_buffer = Vec::new();
+        for node in nodes.iter() {
+            node_buffer.write_u16::<B>(node.m_Version as u16)?;
+            node_buffer.write_u8(node.m_Level)?;
+            node_buffer.write_u8(node.m_TypeFlags as u8)?;
+            node_buffer.write_u32::<B>(string_offset(&node.m_Type)?)?;
+            node_buffer.write_u32::<B>(string_offset(&node.m_Name)?)?;
+            node_buffer.write_i32::<B>(node.m_ByteSize)?;
+            node_buffer.write_i32::<B>(node.m_Index.unwrap_or(0))?;
+            node_buffer.write_i32::<B>(node.m_MetaFlag.unwrap_or(0))?;
+            if version >= 19 {
+                node_buffer.write_u64::<B>(node.m_RefTypeHash.unwrap_or(0))?;
+            }
+        }
+
+        writer.write_i32::<B>(nodes.len() as i32)?;
+        writer.write_i32::<B>(string_buffer.len() as i32)?;
+        writer.write_all(&node_buffer)?;
+        writer.write_all(&string_buffer)?;
+        Ok(())
+    }
+
     fn requires_align(&self) -> bool {
         (self.m_MetaFlag.unwrap_or(0) & TransferMetaFlags::ALIGN_BYTES_FLAG.bits()) != 0
     }
@@ -341,10 +474,12 @@ impl Node {
                     // class
                     let mut map = HashMap::new();
                     for child in self.children.iter() {
-                        map.insert(
-                            child.m_Name.clone(),
+                        let value = if self.m_Type == "TextAsset" && child.m_Name == "m_Script" {
+                            child.read_script::<R, B>(reader)?
+                        } else {
                             child.read::<R, B>(reader)?
-                        );
+                        };
+                        map.insert(child.m_Name.clone(), value);
                     }
                     TypeTreeValue::Class(map)
                 }
@@ -355,4 +490,292 @@ impl Node {
         }
         Ok(value)
     }
+
+    /// Reads the script of a TextAsset, which can hold binary data that is kept as bytes.
+    fn read_script<R: std::io::Read + std::io::Seek, B: ByteOrder>(&self, reader: &mut R) -> Result<TypeTreeValue, Error> {
+        use crate::read_ext::ReadSeekUrexExt;
+
+        if self.m_Type != "string" {
+            return self.read::<R, B>(reader);
+        }
+        let value = match String::from_utf8(reader.read_bytes::<B>()?) {
+            Ok(s) => TypeTreeValue::String(s),
+            Err(e) => TypeTreeValue::TypelessData(e.into_bytes()),
+        };
+        if self.requires_align() || self.children[0].requires_align() {
+            reader.align4()?;
+        }
+        Ok(value)
+    }
+
+    fn mismatch(&self, value: &TypeTreeValue) -> Error {
+        Error::InvalidValue(format!(
+            "{} {} cannot be written from a {} value",
+            self.m_Type,
+            self.m_Name,
+            value.kind()
+        ))
+    }
+
+    /// Writes a value using the same layout that [`Node::read`] expects.
+    pub fn write<W: std::io::Write + std::io::Seek, B: ByteOrder>(
+        &self,
+        writer: &mut W,
+        value: &TypeTreeValue,
+    ) -> Result<(), Error> {
+        use crate::write_ext::WriteSeekUrexExt;
+
+        let mut align = self.requires_align();
+        match self.m_Type.as_str() {
+            "SInt8" => {
+                writer.write_i8(value.i8().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "UInt8" => {
+                writer.write_u8(value.u8().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "char" => {
+                let c = value.char().ok_or_else(|| self.mismatch(value))?;
+                let byte = u8::try_from(c).map_err(|_| Error::InvalidValue(format!(
+                    "char {} does not fit in a byte: {c:?}",
+                    self.m_Name
+                )))?;
+                writer.write_u8(byte)?;
+            }
+            "SInt16" | "short" => {
+                writer.write_i16::<B>(value.i16().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "UInt16" | "unsigned short" => {
+                writer.write_u16::<B>(value.u16().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "SInt32" | "int" => {
+                writer.write_i32::<B>(value.i32().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "UInt32" | "unsigned int" | "Type*" => {
+                writer.write_u32::<B>(value.u32().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "SInt64" | "long long" => {
+                writer.write_i64::<B>(value.i64().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "UInt64" | "unsigned long long" | "FileSize" => {
+                writer.write_u64::<B>(value.u64().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "float" => {
+                writer.write_f32::<B>(value.f32().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "double" => {
+                writer.write_f64::<B>(value.f64().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "bool" => {
+                writer.write_bool(value.bool().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "string" => {
+                align |= self.children.first().is_some_and(|array| array.requires_align());
+                match value {
+                    TypeTreeValue::TypelessData(bytes) => writer.write_bytes::<B>(bytes)?,
+                    _ => writer.write_string::<B>(value.string().ok_or_else(|| self.mismatch(value))?)?,
+                }
+            }
+            "TypelessData" => {
+                writer.write_bytes::<B>(value.typeless_data().ok_or_else(|| self.mismatch(value))?)?;
+            }
+            "map" => {
+                if self.children.len() != 1 || self.children[0].children.len() != 2 {
+                    return Err(Error::InvalidValue("Malformed map node".to_owned()));
+                }
+
+                let pair = &self.children[0].children[1];
+                align |= pair.requires_align();
+
+                if pair.children.len() != 2 {
+                    return Err(Error::InvalidValue("Malformed map node".to_owned()));
+                }
+
+                let first = &pair.children[0];
+                let second = &pair.children[1];
+
+                let entries = value.map().ok_or_else(|| self.mismatch(value))?;
+                writer.write_array_len::<B>(entries.len())?;
+                for (key, entry) in entries {
+                    first.write::<W, B>(writer, key)?;
+                    second.write::<W, B>(writer, entry)?;
+                }
+            }
+            _ => {
+                if self.children.len() == 1 && self.children[0].m_Type == "Array" {
+                    let array = &self.children[0];
+                    if array.children.len() != 2 {
+                        return Err(Error::InvalidValue("Malformed array node".to_owned()));
+                    }
+
+                    align |= array.requires_align();
+
+                    let items = value.array().ok_or_else(|| self.mismatch(value))?;
+                    let data = &array.children[1];
+                    writer.write_array_len::<B>(items.len())?;
+                    for item in items {
+                        data.write::<W, B>(writer, item)?;
+                    }
+                } else {
+                    let fields = value.class().ok_or_else(|| self.mismatch(value))?;
+                    for child in self.children.iter() {
+                        let field = fields.get(&child.m_Name).ok_or_else(|| Error::InvalidValue(format!(
+                            "{} {} is missing the field {}",
+                            self.m_Type,
+                            self.m_Name,
+                            child.m_Name
+                        )))?;
+                        child.write::<W, B>(writer, field)?;
+                    }
+                }
+            }
+        }
+        if align {
+            writer.write_align4()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::{BigEndian, LittleEndian};
+
+    use super::*;
+
+    const ALIGN: i32 = TransferMetaFlags::ALIGN_BYTES_FLAG.bits();
+
+    fn node(typ: &str, name: &str, meta_flag: i32, children: Vec<Node>) -> Node {
+        Node::new(typ.to_owned(), name.to_owned(), -1, meta_flag, children)
+    }
+
+    fn array_node(typ: &str, name: &str, meta_flag: i32, data: Node) -> Node {
+        node(typ, name, meta_flag, vec![
+            node("Array", "Array", meta_flag, vec![node("int", "size", 0, vec![]), data]),
+        ])
+    }
+
+    fn round_trip<B: ByteOrder>(node: &Node, value: &TypeTreeValue) -> Vec<u8> {
+        let mut writer = Cursor::new(Vec::new());
+        node.write::<_, B>(&mut writer, value).unwrap();
+
+        writer.set_position(0);
+        let read = node.read::<_, B>(&mut writer).unwrap();
+        assert_eq!(&read, value);
+        assert_eq!(writer.position() as usize, writer.get_ref().len());
+        writer.into_inner()
+    }
+
+    #[test]
+    fn primitives_round_trip() {
+        let primitives = vec![
+            ("SInt8", TypeTreeValue::SInt8(-5)),
+            ("UInt8", TypeTreeValue::UInt8(250)),
+            ("char", TypeTreeValue::Char('x')),
+            ("SInt16", TypeTreeValue::SInt16(-1234)),
+            ("short", TypeTreeValue::SInt16(4321)),
+            ("UInt16", TypeTreeValue::UInt16(60000)),
+            ("unsigned short", TypeTreeValue::UInt16(1)),
+            ("SInt32", TypeTreeValue::SInt32(-100000)),
+            ("int", TypeTreeValue::SInt32(100000)),
+            ("UInt32", TypeTreeValue::UInt32(4000000000)),
+            ("unsigned int", TypeTreeValue::UInt32(7)),
+            ("Type*", TypeTreeValue::Type(42)),
+            ("SInt64", TypeTreeValue::SInt64(-1 << 40)),
+            ("long long", TypeTreeValue::SInt64(1 << 40)),
+            ("UInt64", TypeTreeValue::UInt64(u64::MAX)),
+            ("unsigned long long", TypeTreeValue::UInt64(3)),
+            ("FileSize", TypeTreeValue::FileSize(1 << 33)),
+            ("float", TypeTreeValue::Float(1.5)),
+            ("double", TypeTreeValue::Double(-2.25)),
+            ("bool", TypeTreeValue::Bool(true)),
+            ("TypelessData", TypeTreeValue::TypelessData(vec![1, 2, 3, 4, 5])),
+        ];
+
+        let mut children = Vec::new();
+        let mut fields = HashMap::new();
+        for (i, (typ, value)) in primitives.into_iter().enumerate() {
+            let name = format!("field{i}");
+            children.push(node(typ, &name, 0, vec![]));
+            fields.insert(name, value);
+        }
+        children.push(array_node("string", "m_Name", 0, node("char", "data", 0, vec![])));
+        fields.insert("m_Name".to_owned(), TypeTreeValue::String("name".to_owned()));
+
+        let class = node("Primitives", "Base", 0, children);
+        let value = TypeTreeValue::Class(fields);
+        round_trip::<LittleEndian>(&class, &value);
+        round_trip::<BigEndian>(&class, &value);
+    }
+
+    #[test]
+    fn containers_round_trip() {
+        let pair = node("pair", "data", 0, vec![
+            array_node("string", "first", ALIGN, node("char", "data", 0, vec![])),
+            node("SInt32", "second", 0, vec![]),
+        ]);
+        let class = node("Container", "Base", 0, vec![
+            array_node("vector", "m_Bytes", ALIGN, node("UInt8", "data", 0, vec![])),
+            array_node("map", "m_Map", 0, pair),
+            node("bool", "m_Flag", ALIGN, vec![]),
+            node("float", "m_Value", 0, vec![]),
+        ]);
+
+        let value = TypeTreeValue::Class(HashMap::from([
+            ("m_Bytes".to_owned(), TypeTreeValue::Array(vec![TypeTreeValue::UInt8(1), TypeTreeValue::UInt8(2)])),
+            ("m_Map".to_owned(), TypeTreeValue::Map(vec![
+                (TypeTreeValue::String("a".to_owned()), TypeTreeValue::SInt32(1)),
+                (TypeTreeValue::String("bcdef".to_owned()), TypeTreeValue::SInt32(2)),
+            ])),
+            ("m_Flag".to_owned(), TypeTreeValue::Bool(true)),
+            ("m_Value".to_owned(), TypeTreeValue::Float(0.5)),
+        ]));
+        let bytes = round_trip::<LittleEndian>(&class, &value);
+        // vector: 6 -> 8, map: 4 + (5 -> 8) + 4 + (9 -> 12) + 4, bool: 1 -> 4, float: 4
+        assert_eq!(bytes.len(), 8 + 32 + 4 + 4);
+    }
+
+    #[test]
+    fn binary_text_asset_script() {
+        let string = |name: &str| array_node("string", name, ALIGN, node("char", "data", 0, vec![]));
+        let text_asset = node("TextAsset", "Base", 0, vec![string("m_Name"), string("m_Script")]);
+        let script = vec![0xff, 0xfe, 0x00];
+
+        let value = TypeTreeValue::Class(HashMap::from([
+            ("m_Name".to_owned(), TypeTreeValue::String("data".to_owned())),
+            ("m_Script".to_owned(), TypeTreeValue::TypelessData(script.clone())),
+        ]));
+        round_trip::<LittleEndian>(&text_asset, &value);
+
+        // every other string still has to be valid UTF-8
+        let mut writer = Cursor::new(Vec::new());
+        writer.write_bytes::<LittleEndian>(&script).unwrap();
+        writer.write_bytes::<LittleEndian>(b"text").unwrap();
+        writer.set_position(0);
+        let error = text_asset.read::<_, LittleEndian>(&mut writer).unwrap_err();
+        assert!(matches!(error, Error::IoError(ref e) if e.kind() == std::io::ErrorKind::InvalidData));
+
+        let other = node("MonoScript", "Base", 0, vec![string("m_Script")]);
+        writer.set_position(0);
+        assert!(other.read::<_, LittleEndian>(&mut writer).is_err());
+    }
+
+    #[test]
+    fn mismatched_values() {
+        let class = node("Class", "Base", 0, vec![node("int", "m_Int", 0, vec![])]);
+
+        let wrong_type = TypeTreeValue::Class(HashMap::from([
+            ("m_Int".to_owned(), TypeTreeValue::Float(1.0)),
+        ]));
+        let error = class.write::<_, LittleEndian>(&mut Cursor::new(Vec::new()), &wrong_type).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value: int m_Int cannot be written from a Float value");
+
+        let missing_field = TypeTreeValue::Class(HashMap::new());
+        let error = class.write::<_, LittleEndian>(&mut Cursor::new(Vec::new()), &missing_field).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value: Class Base is missing the field m_Int");
+
+        let not_a_class = TypeTreeValue::Array(vec![]);
+        assert!(class.write::<_, LittleEndian>(&mut Cursor::new(Vec::new()), &not_a_class).is_err());
+    }
 }
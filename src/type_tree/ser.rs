@@ -0,0 +1,695 @@
+use std::collections::HashMap;
+
+use crate::{Error, TypeTreeNode, TypeTreeValue};
+
+use serde::{ser::Impossible, Serialize};
+
+/// Serializes values into [`TypeTreeValue`]s shaped by a type tree node.
+///
+/// Numbers are converted to the type the node declares, fields that are not
+/// part of the node (such as `Option` fields of other Unity versions) are skipped
+/// and `(K, V)` tuples are turned into map pairs.
+#[derive(Debug)]
+pub struct Serializer<'a>(&'a TypeTreeNode);
+
+impl<'a> Serializer<'a> {
+    pub fn new(node: &'a TypeTreeNode) -> Self {
+        Self(node)
+    }
+
+    fn mismatch(&self, what: &str) -> Error {
+        Error::InvalidValue(format!(
+            "{} {} cannot be serialized from {what}",
+            self.0.get_type(),
+            self.0.get_name()
+        ))
+    }
+
+    fn integer(&self, v: i128) -> Result<TypeTreeValue, Error> {
+        let out_of_range = || Error::InvalidValue(format!(
+            "{v} is out of range for {} {}",
+            self.0.get_type(),
+            self.0.get_name()
+        ));
+
+        Ok(match self.0.get_type().as_str() {
+            "SInt8" => TypeTreeValue::SInt8(v.try_into().map_err(|_| out_of_range())?),
+            "UInt8" => TypeTreeValue::UInt8(v.try_into().map_err(|_| out_of_range())?),
+            "char" => TypeTreeValue::Char(u8::try_from(v).map_err(|_| out_of_range())? as char),
+            "SInt16" | "short" => TypeTreeValue::SInt16(v.try_into().map_err(|_| out_of_range())?),
+            "UInt16" | "unsigned short" => TypeTreeValue::UInt16(v.try_into().map_err(|_| out_of_range())?),
+            "SInt32" | "int" => TypeTreeValue::SInt32(v.try_into().map_err(|_| out_of_range())?),
+            "UInt32" | "unsigned int" => TypeTreeValue::UInt32(v.try_into().map_err(|_| out_of_range())?),
+            "Type*" => TypeTreeValue::Type(v.try_into().map_err(|_| out_of_range())?),
+            "SInt64" | "long long" => TypeTreeValue::SInt64(v.try_into().map_err(|_| out_of_range())?),
+            "UInt64" | "unsigned long long" => TypeTreeValue::UInt64(v.try_into().map_err(|_| out_of_range())?),
+            "FileSize" => TypeTreeValue::FileSize(v.try_into().map_err(|_| out_of_range())?),
+            "float" => TypeTreeValue::Float(v as f32),
+            "double" => TypeTreeValue::Double(v as f64),
+            "bool" => TypeTreeValue::Bool(v != 0),
+            _ => return Err(self.mismatch("an integer")),
+        })
+    }
+
+    fn float(&self, v: f64) -> Result<TypeTreeValue, Error> {
+        match self.0.get_type().as_str() {
+            "float" => Ok(TypeTreeValue::Float(v as f32)),
+            "double" => Ok(TypeTreeValue::Double(v)),
+            _ => Err(self.mismatch("a float")),
+        }
+    }
+
+    /// Returns the element node if the node is an array.
+    fn array_data(&self) -> Option<&'a TypeTreeNode> {
+        let children = self.0.get_children();
+        if children.len() == 1 && children[0].get_type() == "Array" {
+            children[0].get_children().get(1)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the `first` and `second` nodes if the node is a map.
+    fn map_pair(&self) -> Result<(&'a TypeTreeNode, &'a TypeTreeNode), Error> {
+        match self.array_data().map(|pair| pair.get_children().as_slice()) {
+            Some([first, second]) if self.0.get_type() == "map" => Ok((first, second)),
+            _ => Err(Error::InvalidValue("Malformed map node".to_owned())),
+        }
+    }
+}
+
+/// Matches a type tree field name with the name of the generated struct field.
+fn field_name_matches(node_name: &str, field: &str) -> bool {
+    if node_name == field {
+        return true;
+    }
+
+    // mirrors generate_rust_name of the class generator
+    let mut name: String = node_name
+        .chars()
+        .map(|c| if matches!(c, ':' | ' ' | '[' | ']' | '?' | '.' | '-') { '_' } else { c })
+        .collect();
+    if let Some(stripped) = name.strip_prefix("(int&)") {
+        name = stripped.to_owned();
+    }
+    if name.starts_with(|c: char| c.is_ascii_digit()) || name == "type" || name == "loop" {
+        name.insert(0, '_');
+    }
+    name == field
+}
+
+impl<'a> serde::Serializer for Serializer<'a> {
+    type Ok = TypeTreeValue;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = TupleSerializer<'a>;
+    type SerializeTupleStruct = Impossible<TypeTreeValue, Error>;
+    type SerializeTupleVariant = Impossible<TypeTreeValue, Error>;
+    type SerializeMap = MapSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = Impossible<TypeTreeValue, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        match self.0.get_type().as_str() {
+            "bool" => Ok(TypeTreeValue::Bool(v)),
+            _ => self.integer(v as i128),
+        }
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
+        self.integer(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        self.integer(v as i128)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
+        self.integer(i128::try_from(v).map_err(|_| self.mismatch("a u128"))?)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        self.float(v as f64)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        self.float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        match self.0.get_type().as_str() {
+            "char" => Ok(TypeTreeValue::Char(v)),
+            _ => self.integer(v as i128),
+        }
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        if self.0.get_type() == "string" {
+            return Ok(TypeTreeValue::String(v.to_owned()));
+        }
+
+        // the generator replaces classes that only hold a name (NamedObject) with a string
+        match self.0.get_children().as_slice() {
+            [name] if name.get_type() == "string" => Ok(TypeTreeValue::Class(HashMap::from([
+                (name.get_name().clone(), TypeTreeValue::String(v.to_owned())),
+            ]))),
+            _ => Err(self.mismatch("a string")),
+        }
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        // strings that aren't UTF-8 are kept as bytes, like when they are read
+        if matches!(self.0.get_type().as_str(), "TypelessData" | "string") {
+            return Ok(TypeTreeValue::TypelessData(v.to_vec()));
+        }
+
+        match self.array_data() {
+            Some(data) if data.get_type() == "UInt8" => Ok(TypeTreeValue::Array(
+                v.iter().map(|b| TypeTreeValue::UInt8(*b)).collect()
+            )),
+            _ => Err(self.mismatch("bytes")),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Err(self.mismatch("None"))
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Err(self.mismatch("()"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.integer(variant_index as i128)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        let elements = if self.0.get_type() == "map" {
+            self.map_pair()?;
+            SeqElements::Pairs(self.array_data().ok_or_else(|| self.mismatch("a sequence"))?)
+        } else if self.0.get_type() == "TypelessData" {
+            SeqElements::Bytes(TypeTreeNode::new("UInt8".to_owned(), "data".to_owned(), 1, 0, Vec::new()))
+        } else if let Some(data) = self.array_data() {
+            SeqElements::Node(data)
+        } else {
+            return Err(self.mismatch("a sequence"));
+        };
+
+        Ok(SeqSerializer {
+            elements,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        match self.0.get_children().as_slice() {
+            [first, second] if len == 2 && self.0.get_type() == "pair" => Ok(TupleSerializer {
+                nodes: [first, second],
+                values: Vec::with_capacity(2),
+            }),
+            _ => Err(self.mismatch("a tuple")),
+        }
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Err(self.mismatch(name))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        let (first, second) = self.map_pair()?;
+        Ok(MapSerializer {
+            first,
+            second,
+            key: None,
+            values: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        if self.array_data().is_some() || self.0.get_children().is_empty() {
+            return Err(self.mismatch(name));
+        }
+
+        Ok(StructSerializer {
+            node: self.0,
+            fields: HashMap::with_capacity(len),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Err(self.mismatch(name))
+    }
+}
+
+#[derive(Debug)]
+enum SeqElements<'a> {
+    Node(&'a TypeTreeNode),
+    Pairs(&'a TypeTreeNode),
+    Bytes(TypeTreeNode),
+}
+
+#[derive(Debug)]
+pub struct SeqSerializer<'a> {
+    elements: SeqElements<'a>,
+    values: Vec<TypeTreeValue>,
+}
+
+impl serde::ser::SerializeSeq for SeqSerializer<'_> {
+    type Ok = TypeTreeValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        let node = match &self.elements {
+            SeqElements::Node(node) | SeqElements::Pairs(node) => *node,
+            SeqElements::Bytes(node) => node,
+        };
+        self.values.push(value.serialize(Serializer::new(node))?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        match self.elements {
+            SeqElements::Node(_) => Ok(TypeTreeValue::Array(self.values)),
+            SeqElements::Bytes(_) => Ok(TypeTreeValue::TypelessData(
+                self.values.iter().filter_map(|v| v.u8()).collect()
+            )),
+            SeqElements::Pairs(pair) => {
+                // the pairs come back as classes from TupleSerializer
+                let [first, second] = pair.get_children().as_slice() else {
+                    return Err(Error::InvalidValue("Malformed map node".to_owned()));
+                };
+                Ok(TypeTreeValue::Map(
+                    self.values
+                        .into_iter()
+                        .map(|value| match value {
+                            TypeTreeValue::Class(mut fields) => Ok((
+                                fields.remove(first.get_name()).ok_or_else(|| Error::InvalidValue("Malformed map pair".to_owned()))?,
+                                fields.remove(second.get_name()).ok_or_else(|| Error::InvalidValue("Malformed map pair".to_owned()))?,
+                            )),
+                            _ => Err(Error::InvalidValue("Map entries must be (key, value) tuples".to_owned())),
+                        })
+                        .collect::<Result<Vec<(TypeTreeValue, TypeTreeValue)>, Error>>()?
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TupleSerializer<'a> {
+    nodes: [&'a TypeTreeNode; 2],
+    values: Vec<TypeTreeValue>,
+}
+
+impl serde::ser::SerializeTuple for TupleSerializer<'_> {
+    type Ok = TypeTreeValue;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        let node = self.nodes
+            .get(self.values.len())
+            .ok_or_else(|| Error::InvalidValue("Pairs must have two elements".to_owned()))?;
+        self.values.push(value.serialize(Serializer::new(node))?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        // pairs outside of maps are read as classes
+        Ok(TypeTreeValue::Class(
+            self.nodes
+                .iter()
+                .map(|node| node.get_name().clone())
+                .zip(self.values)
+                .collect()
+        ))
+    }
+}
+
+#[derive(Debug)]
+pub struct MapSerializer<'a> {
+    first: &'a TypeTreeNode,
+    second: &'a TypeTreeNode,
+    key: Option<TypeTreeValue>,
+    values: Vec<(TypeTreeValue, TypeTreeValue)>,
+}
+
+impl serde::ser::SerializeMap for MapSerializer<'_> {
+    type Ok = TypeTreeValue;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        self.key = Some(key.serialize(Serializer::new(self.first))?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        let key = self.key
+            .take()
+            .ok_or_else(|| Error::InvalidValue("Map value serialized before its key".to_owned()))?;
+        self.values.push((key, value.serialize(Serializer::new(self.second))?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        Ok(TypeTreeValue::Map(self.values))
+    }
+}
+
+#[derive(Debug)]
+pub struct StructSerializer<'a> {
+    node: &'a TypeTreeNode,
+    fields: HashMap<String, TypeTreeValue>,
+}
+
+impl serde::ser::SerializeStruct for StructSerializer<'_> {
+    type Ok = TypeTreeValue;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
+    where
+        T: ?Sized + Serialize
+    {
+        // fields of other unity versions are not part of the node and are skipped
+        if let Some(child) = self.node
+            .get_children()
+            .iter()
+            .find(|child| field_name_matches(child.get_name(), key))
+        {
+            self.fields.insert(child.get_name().clone(), value.serialize(Serializer::new(child))?);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        if let Some(missing) = self.node
+            .get_children()
+            .iter()
+            .find(|child| !self.fields.contains_key(child.get_name()))
+        {
+            return Err(Error::InvalidValue(format!(
+                "{} {} is missing the field {}",
+                self.node.get_type(),
+                self.node.get_name(),
+                missing.get_name()
+            )));
+        }
+        Ok(TypeTreeValue::Class(self.fields))
+    }
+}
+
+#[cfg(all(test, feature = "objects"))]
+mod tests {
+    use std::io::Cursor;
+
+    use byteorder::LittleEndian;
+
+    use super::super::*;
+    use crate::objects::{classes::*, PPtr};
+
+    fn node(typ: &str, name: &str, children: Vec<Node>) -> Node {
+        Node::new(typ.to_owned(), name.to_owned(), -1, 0, children)
+    }
+
+    fn array(typ: &str, name: &str, data: Node) -> Node {
+        node(typ, name, vec![
+            node("Array", "Array", vec![node("int", "size", vec![]), data]),
+        ])
+    }
+
+    fn string(name: &str) -> Node {
+        array("string", name, node("char", "data", vec![]))
+    }
+
+    fn pptr(name: &str) -> Node {
+        node("PPtr<Object>", name, vec![
+            node("int", "m_FileID", vec![]),
+            node("SInt64", "m_PathID", vec![]),
+        ])
+    }
+
+    fn asset_bundle_node() -> Node {
+        let asset_info = |name: &str| node("AssetInfo", name, vec![
+            node("int", "preloadIndex", vec![]),
+            node("int", "preloadSize", vec![]),
+            pptr("asset"),
+        ]);
+        node("AssetBundle", "Base", vec![
+            string("m_Name"),
+            array("vector", "m_PreloadTable", pptr("data")),
+            array("map", "m_Container", node("pair", "data", vec![string("first"), asset_info("second")])),
+            asset_info("m_MainAsset"),
+            node("unsigned int", "m_RuntimeCompatibility", vec![]),
+            string("m_AssetBundleName"),
+            array("vector", "m_Dependencies", string("data")),
+            node("bool", "m_IsStreamedSceneAssetBundle", vec![]),
+            node("int", "m_ExplicitDataLayout", vec![]),
+            node("int", "m_PathFlags", vec![]),
+            array("map", "m_SceneHashes", node("pair", "data", vec![string("first"), string("second")])),
+        ])
+    }
+
+    fn asset_info(path_id: i64) -> AssetInfo {
+        AssetInfo {
+            asset: PPtr { m_FileID: 0, m_PathID: path_id },
+            preloadIndex: 1,
+            preloadSize: 2,
+        }
+    }
+
+    #[test]
+    fn text_asset_serialization() {
+        let node = node("TextAsset", "Base", vec![string("m_Name"), string("m_Script")]);
+        let text_asset = TextAsset {
+            m_Name: "strings".to_owned(),
+            m_Script: "localised text".to_owned().into(),
+            m_PathName: None,
+        };
+
+        let value = Value::from_serializable(&text_asset, &node).unwrap();
+        let class = value.class().unwrap();
+        assert_eq!(class.len(), 2);
+        assert_eq!(class["m_Name"], Value::String("strings".to_owned()));
+        assert_eq!(class["m_Script"], Value::String("localised text".to_owned()));
+    }
+
+    #[test]
+    fn binary_text_asset_round_trip() {
+        let node = node("TextAsset", "Base", vec![string("m_Name"), string("m_Script")]);
+        let text_asset = TextAsset {
+            m_Name: "table".to_owned(),
+            m_Script: vec![0x1b, b'L', b'u', b'a', 0xff, 0x00].into(),
+            m_PathName: None,
+        };
+
+        let value = Value::from_serializable(&text_asset, &node).unwrap();
+        assert_eq!(value.class().unwrap()["m_Script"], Value::TypelessData(text_asset.m_Script.0.clone()));
+
+        let mut writer = Cursor::new(Vec::new());
+        node.write::<_, LittleEndian>(&mut writer, &value).unwrap();
+        writer.set_position(0);
+        let read = node.read::<_, LittleEndian>(&mut writer).unwrap();
+        assert_eq!(read, value);
+
+        let parsed: TextAsset = read.parse().unwrap();
+        assert_eq!(parsed.m_Script, text_asset.m_Script);
+        assert_eq!(parsed.m_Script.to_str(), None);
+    }
+
+    #[test]
+    fn asset_bundle_round_trip() {
+        let asset_bundle = AssetBundle {
+            m_Container: vec![
+                ("assets/a.png".to_owned(), asset_info(10)),
+                ("assets/b.prefab".to_owned(), asset_info(-20)),
+            ],
+            m_MainAsset: asset_info(0),
+            m_Name: "bundle".to_owned(),
+            m_PreloadTable: vec![PPtr { m_FileID: 1, m_PathID: 123456789012 }],
+            m_AssetBundleName: Some("bundle".to_owned()),
+            m_ClassCompatibility: None,
+            m_ClassVersionMap: None,
+            m_Dependencies: Some(vec!["shared".to_owned()]),
+            m_ExplicitDataLayout: Some(0),
+            m_IsStreamedSceneAssetBundle: Some(false),
+            m_PathFlags: Some(7),
+            m_RuntimeCompatibility: Some(1),
+            m_SceneHashes: Some(vec![]),
+            m_ScriptCompatibility: None,
+        };
+
+        let node = asset_bundle_node();
+        let value = Value::from_serializable(&asset_bundle, &node).unwrap();
+        let class = value.class().unwrap();
+        assert_eq!(class["m_RuntimeCompatibility"], Value::UInt32(1));
+        assert_eq!(
+            class["m_PreloadTable"].array().unwrap()[0].class().unwrap()["m_FileID"],
+            Value::SInt32(1)
+        );
+        let container = class["m_Container"].map().unwrap();
+        assert_eq!(container[1].0, Value::String("assets/b.prefab".to_owned()));
+
+        let mut writer = Cursor::new(Vec::new());
+        node.write::<_, LittleEndian>(&mut writer, &value).unwrap();
+        writer.set_position(0);
+        let read = node.read::<_, LittleEndian>(&mut writer).unwrap();
+        assert_eq!(read, value);
+
+        let parsed: AssetBundle = read.parse().unwrap();
+        assert_eq!(parsed.m_Container[1].1.asset.m_PathID, -20);
+        assert_eq!(parsed.m_PreloadTable[0].m_PathID, 123456789012);
+        assert_eq!(parsed.m_Dependencies, Some(vec!["shared".to_owned()]));
+        assert_eq!(parsed.m_PathFlags, Some(7));
+    }
+
+    #[test]
+    fn human_template_map() {
+        let node = node("HumanTemplate", "Base", vec![
+            string("m_Name"),
+            array("map", "m_BoneTemplate", node("pair", "data", vec![string("first"), string("second")])),
+        ]);
+        let human_template = HumanTemplate {
+            m_Name: "HumanModel".to_owned(),
+            m_BoneTemplate: vec![("Hips".to_owned(), "mixamorig:Hips".to_owned())],
+        };
+
+        let value = Value::from_serializable(&human_template, &node).unwrap();
+        assert_eq!(
+            value.class().unwrap()["m_BoneTemplate"],
+            Value::Map(vec![(
+                Value::String("Hips".to_owned()),
+                Value::String("mixamorig:Hips".to_owned())
+            )])
+        );
+    }
+
+    #[test]
+    fn shape_mismatches() {
+        let text_asset = TextAsset {
+            m_Name: "strings".to_owned(),
+            m_Script: Default::default(),
+            m_PathName: None,
+        };
+
+        let missing = node("TextAsset", "Base", vec![string("m_Name"), string("m_Script"), node("int", "m_Extra", vec![])]);
+        let error = Value::from_serializable(&text_asset, &missing).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value: TextAsset Base is missing the field m_Extra");
+
+        let none = node("TextAsset", "Base", vec![string("m_Name"), string("m_Script"), string("m_PathName")]);
+        let error = Value::from_serializable(&text_asset, &none).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value: string m_PathName cannot be serialized from None");
+
+        let out_of_range = node("Class", "Base", vec![node("UInt8", "insertIndex", vec![]), pptr("addedObject"), pptr("targetCorrespondingSourceObject")]);
+        let added_game_object = AddedGameObject {
+            addedObject: PPtr { m_FileID: 0, m_PathID: 1 },
+            insertIndex: 300,
+            targetCorrespondingSourceObject: PPtr { m_FileID: 0, m_PathID: 2 },
+        };
+        let error = Value::from_serializable(&added_game_object, &out_of_range).unwrap_err();
+        assert_eq!(error.to_string(), "Invalid value: 300 is out of range for UInt8 insertIndex");
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::HashMap;
 #[cfg(feature = "serde")]
 use serde::{de::IntoDeserializer, Deserialize, Serialize};
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize), serde(untagged))]
 pub enum Value {
     SInt8(i8),
@@ -156,8 +156,39 @@ impl Value {
         }
     }
 
+    /// Name of the variant, used in error messages.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::SInt8(_) => "SInt8",
+            Self::UInt8(_) => "UInt8",
+            Self::Char(_) => "Char",
+            Self::SInt16(_) => "SInt16",
+            Self::UInt16(_) => "UInt16",
+            Self::SInt32(_) => "SInt32",
+            Self::UInt32(_) => "UInt32",
+            Self::Type(_) => "Type",
+            Self::SInt64(_) => "SInt64",
+            Self::UInt64(_) => "UInt64",
+            Self::FileSize(_) => "FileSize",
+            Self::Float(_) => "Float",
+            Self::Double(_) => "Double",
+            Self::Bool(_) => "Bool",
+            Self::String(_) => "String",
+            Self::TypelessData(_) => "TypelessData",
+            Self::Map(_) => "Map",
+            Self::Array(_) => "Array",
+            Self::Class(_) => "Class",
+        }
+    }
+
     #[cfg(feature = "serde")]
     pub fn parse<'de, T: serde::Deserialize<'de>>(&'de self) -> Result<T, crate::Error> {
         T::deserialize(self.into_deserializer())
     }
+
+    /// Builds a value shaped by the given type tree node, the inverse of [`Value::parse`].
+    #[cfg(feature = "serde")]
+    pub fn from_serializable<T: Serialize>(value: &T, node: &super::Node) -> Result<Value, crate::Error> {
+        value.serialize(super::Serializer::new(node))
+    }
 }
\ No newline at end of file
@@ -31,14 +31,27 @@ fn to_uint4_array(source: &[u8; 16], offset: usize) -> [u8; 32] {
     result
 }
 
-pub struct ArchiveStorageDecryptor {
-    index: [u8; 16],
-    sub: [u8; 16],
+fn key_byte(sub: &[u8; 16], index: usize) -> u8 {
+    let b: [u8; 4] = [
+        sub[((index >> 2) & 3) + 4],
+        sub[index & 3],
+        sub[((index >> 4) & 3) + 8],
+        sub[((index % 256) >> 6) + 12],
+    ];
+    b.iter().fold(0u8, |acc: u8, x| acc.overflowing_add(*x).0)
 }
 
-impl ArchiveStorageDecryptor {
-    #[cfg(feature = "unitycn_encryption")]
-    pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: &mut T, archive_key: [u8; 16]) -> Result<Self, Error> {
+/// The encryption header that precedes the blocks info of UnityCN bundles.
+#[derive(Debug, Clone)]
+pub struct ArchiveStorageHeader {
+    info_bytes: [u8; 16],
+    info_key: [u8; 16],
+    signature_bytes: [u8; 16],
+    signature_key: [u8; 16],
+}
+
+impl ArchiveStorageHeader {
+    pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: &mut T) -> Result<Self, Error> {
         use crate::read_ext::ReadUrexExt;
         use byteorder::{BigEndian, ReadBytesExt};
         use std::io::SeekFrom;
@@ -53,12 +66,33 @@ impl ArchiveStorageDecryptor {
         let signature_key: [u8; 16] = reader.read_bytes_sized(16)?.try_into().unwrap();
         reader.seek(SeekFrom::Current(1))?;
 
-        let signature: [u8; 16] = decrypt_key(signature_key, signature_bytes, archive_key);
-        if signature != UNITY3D_SIGNATURE {
+        Ok(ArchiveStorageHeader {
+            info_bytes,
+            info_key,
+            signature_bytes,
+            signature_key,
+        })
+    }
+
+    /// Checks if `archive_key` decrypts the signature of the header.
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn verify_key(&self, archive_key: [u8; 16]) -> bool {
+        decrypt_key(self.signature_key, self.signature_bytes, archive_key) == UNITY3D_SIGNATURE
+    }
+
+    /// Returns the first of the named keys that decrypts the signature of the header.
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn find_key<'a>(&self, keys: &'a [(String, [u8; 16])]) -> Option<&'a (String, [u8; 16])> {
+        keys.iter().find(|(_, key)| self.verify_key(*key))
+    }
+
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn decryptor(&self, archive_key: [u8; 16]) -> Result<ArchiveStorageDecryptor, Error> {
+        if !self.verify_key(archive_key) {
             return Err(Error::UnknownSignature);
         }
 
-        let data = decrypt_key(info_key, info_bytes, archive_key);
+        let data = decrypt_key(self.info_key, self.info_bytes, archive_key);
         let data = to_uint4_array(&data, 0);
         let index: [u8; 16] = data[0..16].try_into()?;
         let mut sub: [u8; 16] = [0; 16];
@@ -69,6 +103,19 @@ impl ArchiveStorageDecryptor {
         }
         Ok(ArchiveStorageDecryptor { index, sub })
     }
+}
+
+#[derive(Clone)]
+pub struct ArchiveStorageDecryptor {
+    index: [u8; 16],
+    sub: [u8; 16],
+}
+
+impl ArchiveStorageDecryptor {
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn from_reader<T: std::io::Read + std::io::Seek>(reader: &mut T, archive_key: [u8; 16]) -> Result<Self, Error> {
+        ArchiveStorageHeader::from_reader(reader)?.decryptor(archive_key)
+    }
 
     // Decrypts a StorageBlock in place
     pub fn decrypt_block(&self, bytes: &mut [u8], size: usize, index: usize) -> Result<(), Error> {
@@ -85,13 +132,7 @@ impl ArchiveStorageDecryptor {
     }
 
     fn decrypt_byte(&self, bytes: &mut [u8], offset: usize, index: usize) -> (u8, usize, usize) {
-        let b: [u8; 4] = [
-            self.sub[((index >> 2) & 3) + 4],
-            self.sub[index & 3],
-            self.sub[((index >> 4) & 3) + 8],
-            self.sub[((index % 256) >> 6) + 12],
-        ];
-        let b: u8 = b.iter().fold(0u8, |acc: u8, x| acc.overflowing_add(*x).0);
+        let b = key_byte(&self.sub, index);
 
         let byte_low = self.index[(bytes[offset] & 0xF) as usize]
             .overflowing_sub(b)
@@ -105,19 +146,18 @@ impl ArchiveStorageDecryptor {
 
     fn decrypt(&self, bytes: &mut [u8], offset: usize, index: usize, end: usize) -> Result<usize, Error> {
         let (cur_byte, mut offset, mut index) = self.decrypt_byte(bytes, offset, index);
-        // u24, split into u16 and u8 here for easier handling
-        let mut byte_high: u16 = (cur_byte >> 4) as u16;
+        let mut byte_high = (cur_byte >> 4) as usize;
         let byte_low = cur_byte & 0xF;
 
         if byte_high == 0xF {
             let mut b: u8 = 0xFF;
             while b == 0xFF {
                 (b, offset, index) = self.decrypt_byte(bytes, offset, index);
-                byte_high += b as u16;
+                byte_high += b as usize;
             }
         }
 
-        offset += byte_high as usize;
+        offset += byte_high;
 
         if offset < end {
             (_, offset, index) = self.decrypt_byte(bytes, offset, index);
@@ -133,3 +173,204 @@ impl ArchiveStorageDecryptor {
         Ok(offset)
     }
 }
+
+/// Encrypts LZ4 blocks the way [`ArchiveStorageDecryptor`] expects them.
+#[derive(Clone)]
+pub struct ArchiveStorageEncryptor {
+    archive_key: [u8; 16],
+    index: [u8; 16],
+    sub: [u8; 16],
+    /// inverse of the index table
+    reverse_index: [u8; 16],
+}
+
+impl ArchiveStorageEncryptor {
+    /// Creates an encryptor with tables derived from the archive key,
+    /// so that encrypting the same bundle twice gives the same output.
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn new(archive_key: [u8; 16]) -> Self {
+        let random = decrypt_key([0; 16], [0; 16], archive_key);
+
+        // Fisher-Yates shuffle of the nibbles, the index table has to be a permutation
+        let mut index: [u8; 16] = std::array::from_fn(|i| i as u8);
+        for i in (1..16).rev() {
+            index.swap(i, random[i] as usize % (i + 1));
+        }
+        let sub = decrypt_key([1; 16], [0; 16], archive_key).map(|x| x & 0xF);
+
+        Self::with_tables(archive_key, index, sub).unwrap()
+    }
+
+    /// Creates an encryptor with the given tables.
+    /// `index` has to be a permutation of 0..16 and the values of `sub` have to be nibbles.
+    pub fn with_tables(archive_key: [u8; 16], index: [u8; 16], sub: [u8; 16]) -> Result<Self, Error> {
+        let mut reverse_index = [0xFF; 16];
+        for (i, value) in index.iter().enumerate() {
+            if *value > 0xF || reverse_index[*value as usize] != 0xFF {
+                return Err(Error::InvalidValue(
+                    "UnityCN index table has to be a permutation of 0..16".to_owned(),
+                ));
+            }
+            reverse_index[*value as usize] = i as u8;
+        }
+        if sub.iter().any(|x| *x > 0xF) {
+            return Err(Error::InvalidValue("UnityCN sub table may only contain nibbles".to_owned()));
+        }
+
+        Ok(ArchiveStorageEncryptor {
+            archive_key,
+            index,
+            sub,
+            reverse_index,
+        })
+    }
+
+    /// Writes the 70 byte header that [`ArchiveStorageDecryptor::from_reader`] reads.
+    #[cfg(feature = "unitycn_encryption")]
+    pub fn to_writer<T: std::io::Write>(&self, writer: &mut T) -> Result<(), Error> {
+        use byteorder::{BigEndian, WriteBytesExt};
+
+        // packs the tables the way from_reader unpacks them with to_uint4_array
+        let mut nibbles = [0u8; 32];
+        nibbles[..16].copy_from_slice(&self.index);
+        for j in 0..4 {
+            for i in 0..4 {
+                nibbles[16 + i * 4 + j] = self.sub[i + j * 4];
+            }
+        }
+        let data: [u8; 16] = std::array::from_fn(|i| (nibbles[i * 2] << 4) | nibbles[i * 2 + 1]);
+
+        let info_key = decrypt_key([2; 16], [0; 16], self.archive_key);
+        let signature_key = decrypt_key([3; 16], [0; 16], self.archive_key);
+
+        writer.write_u32::<BigEndian>(0)?;
+        writer.write_all(&decrypt_key(info_key, data, self.archive_key))?;
+        writer.write_all(&info_key)?;
+        writer.write_u8(0)?;
+        writer.write_all(&decrypt_key(signature_key, UNITY3D_SIGNATURE, self.archive_key))?;
+        writer.write_all(&signature_key)?;
+        writer.write_u8(0)?;
+        Ok(())
+    }
+
+    /// Encrypts an LZ4 compressed StorageBlock in place
+    pub fn encrypt_block(&self, bytes: &mut [u8], size: usize, index: usize) -> Result<(), Error> {
+        let mut index = index;
+        let mut offset = 0;
+
+        while offset < size {
+            offset = self.encrypt(bytes, offset, index, size)?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn encrypt_byte(&self, bytes: &mut [u8], offset: usize, index: usize) -> (u8, usize, usize) {
+        let b = key_byte(&self.sub, index);
+
+        let byte = bytes[offset];
+        let byte_low = self.reverse_index[((byte & 0xF).wrapping_add(b) & 0xF) as usize];
+        let byte_high = self.reverse_index[((byte >> 4).wrapping_add(b) & 0xF) as usize];
+        bytes[offset] = byte_low | (byte_high << 4);
+        (byte, offset + 1, index + 1)
+    }
+
+    // mirrors ArchiveStorageDecryptor::decrypt, but follows the LZ4 sequence using the plain bytes
+    fn encrypt(&self, bytes: &mut [u8], offset: usize, index: usize, end: usize) -> Result<usize, Error> {
+        let (cur_byte, mut offset, mut index) = self.encrypt_byte(bytes, offset, index);
+        let mut byte_high = (cur_byte >> 4) as usize;
+        let byte_low = cur_byte & 0xF;
+
+        if byte_high == 0xF {
+            let mut b: u8 = 0xFF;
+            while b == 0xFF {
+                (b, offset, index) = self.encrypt_byte(bytes, offset, index);
+                byte_high += b as usize;
+            }
+        }
+
+        offset += byte_high;
+
+        if offset < end {
+            (_, offset, index) = self.encrypt_byte(bytes, offset, index);
+            (_, offset, index) = self.encrypt_byte(bytes, offset, index);
+            if byte_low == 0xF {
+                let mut b: u8 = 0xFF;
+                while b == 0xFF {
+                    (b, offset, index) = self.encrypt_byte(bytes, offset, index);
+                }
+            }
+        }
+
+        Ok(offset)
+    }
+}
+
+#[cfg(all(test, feature = "unitycn_encryption", feature = "lz4"))]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const KEY: [u8; 16] = *b"0123456789abcdef";
+
+    fn synthetic_blocks() -> Vec<Vec<u8>> {
+        let repetitive = b"m_Name m_Script m_PathID ".repeat(200);
+        let mixed: Vec<u8> = (0..4000u32).map(|i| (i * 7 % 13) as u8 ^ (i / 300) as u8).collect();
+        // long literal runs need the extended length bytes
+        let noise: Vec<u8> = (0..3000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+        [repetitive, mixed, noise, b"short".to_vec()]
+            .iter()
+            .map(|data| lz4_flex::block::compress(data))
+            .collect()
+    }
+
+    fn decryptor_for(encryptor: &ArchiveStorageEncryptor, key: [u8; 16]) -> Result<ArchiveStorageDecryptor, Error> {
+        let mut header = Vec::new();
+        encryptor.to_writer(&mut header).unwrap();
+        assert_eq!(header.len(), 70);
+        ArchiveStorageDecryptor::from_reader(&mut Cursor::new(header), key)
+    }
+
+    #[test]
+    fn decrypt_encrypted_blocks() {
+        let encryptor = ArchiveStorageEncryptor::new(KEY);
+        let decryptor = decryptor_for(&encryptor, KEY).unwrap();
+        assert_eq!(decryptor.index, encryptor.index);
+        assert_eq!(decryptor.sub, encryptor.sub);
+
+        for (i, block) in synthetic_blocks().into_iter().enumerate() {
+            let mut bytes = block.clone();
+            encryptor.encrypt_block(&mut bytes, block.len(), i).unwrap();
+            assert_ne!(bytes, block);
+
+            decryptor.decrypt_block(&mut bytes, block.len(), i).unwrap();
+            assert_eq!(bytes, block);
+        }
+    }
+
+    #[test]
+    fn custom_tables() {
+        let index = [3, 0, 15, 7, 1, 2, 14, 4, 5, 13, 6, 8, 12, 9, 11, 10];
+        let sub = [9, 1, 0, 15, 4, 4, 2, 8, 11, 3, 6, 12, 0, 5, 7, 1];
+        let encryptor = ArchiveStorageEncryptor::with_tables(KEY, index, sub).unwrap();
+        let decryptor = decryptor_for(&encryptor, KEY).unwrap();
+
+        let block = &synthetic_blocks()[0];
+        let mut bytes = block.clone();
+        encryptor.encrypt_block(&mut bytes, block.len(), 5).unwrap();
+        decryptor.decrypt_block(&mut bytes, block.len(), 5).unwrap();
+        assert_eq!(&bytes, block);
+
+        let mut duplicate = index;
+        duplicate[0] = 0;
+        assert!(ArchiveStorageEncryptor::with_tables(KEY, duplicate, sub).is_err());
+        assert!(ArchiveStorageEncryptor::with_tables(KEY, index, [16; 16]).is_err());
+    }
+
+    #[test]
+    fn wrong_key() {
+        let encryptor = ArchiveStorageEncryptor::new(KEY);
+        let result = decryptor_for(&encryptor, *b"fedcba9876543210");
+        assert!(matches!(result, Err(Error::UnknownSignature)));
+    }
+}
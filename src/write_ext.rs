@@ -0,0 +1,77 @@
+use byteorder::{ByteOrder, WriteBytesExt};
+use std::io::Seek;
+
+use crate::Error;
+
+macro_rules! generate_write_array_method{
+    ($typ:ty) => {
+        paste::item! {
+            #[doc = "Writes an array of [`" $typ "`]s. If with_len is true the length is written before the elements."]
+            fn [< write_ $typ _array >]<T: ByteOrder> (&mut self, values: &[$typ], with_len: bool) -> Result<(), Error>{
+                if with_len {
+                    self.write_array_len::<T>(values.len())?;
+                }
+                for value in values {
+                    self.[< write_ $typ >]::<T>(*value)?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+pub trait WriteUrexExt: WriteBytesExt {
+    fn write_array_len<T: ByteOrder>(&mut self, len: usize) -> Result<(), Error> {
+        let len = u32::try_from(len)
+            .map_err(|_| Error::InvalidValue(format!("Array length {len} does not fit in u32")))?;
+        self.write_u32::<T>(len)?;
+        Ok(())
+    }
+
+    fn write_cstr(&mut self, value: &str) -> Result<(), Error> {
+        self.write_all(value.as_bytes())?;
+        self.write_u8(0)?;
+        Ok(())
+    }
+
+    fn write_string<T: ByteOrder>(&mut self, value: &str) -> Result<(), Error> {
+        self.write_bytes::<T>(value.as_bytes())
+    }
+
+    fn write_bytes<T: ByteOrder>(&mut self, value: &[u8]) -> Result<(), Error> {
+        self.write_array_len::<T>(value.len())?;
+        self.write_all(value)?;
+        Ok(())
+    }
+
+    fn write_bool(&mut self, value: bool) -> Result<(), Error> {
+        self.write_u8(value as u8)?;
+        Ok(())
+    }
+
+    generate_write_array_method!(i16);
+    generate_write_array_method!(i32);
+    generate_write_array_method!(i64);
+    generate_write_array_method!(u16);
+    generate_write_array_method!(u32);
+    generate_write_array_method!(u64);
+}
+
+pub trait WriteSeekUrexExt: WriteUrexExt + Seek {
+    fn write_align(&mut self, align: usize) -> Result<(), Error> {
+        let pos = self.stream_position()?;
+        let new_pos = (pos + align as u64 - 1) & !(align as u64 - 1);
+        let diff = new_pos - pos;
+        if diff > 0 {
+            self.write_all(&vec![0u8; diff as usize])?;
+        }
+        Ok(())
+    }
+
+    fn write_align4(&mut self) -> Result<(), Error> {
+        self.write_align(4)
+    }
+}
+
+impl<W: std::io::Write + ?Sized> WriteUrexExt for W {}
+impl<W: std::io::Write + Seek + ?Sized> WriteSeekUrexExt for W {}
@@ -102,7 +102,7 @@ class AllClassHandler:
         f.writelines(
             [
                 "#![allow(warnings)]\n",
-                "use crate::objects::PPtr;\n",
+                "use crate::objects::{ByteString, PPtr};\n",
                 "use serde::{Deserialize, Serialize};\n",
                 "\n",
             ]
@@ -175,6 +175,14 @@ class AllClassHandler:
                 typ.value = "Box<OffsetPtr>"
                 break
 
+        # TextAsset scripts are often binary data instead of text
+        # Hotfix: TextAsset.m_Script: String -> ByteString
+        text_asset_m_script = self.classes["TextAsset"].fields["m_Script"]
+        for typ, versions in text_asset_m_script.types.items():
+            if isinstance(typ, Typ) and typ.value == "String":
+                typ.value = "ByteString"
+                break
+
 
 @dataclass
 class Class: